@@ -384,6 +384,16 @@ pub trait FftField: Field {
         }
         Some(omega)
     }
+
+    /// Replace `selfs` with their FFT over the coset `offset * H`, where `H` is
+    /// the subgroup of order `selfs.len()`, or with their inverse FFT from that
+    /// coset if `inverse` is set.
+    ///
+    /// Returns `false`, leaving `selfs` alone, unless the field has its own way
+    /// to do this. Radix-2 domains try it before their usual FFT.
+    fn radix2_fft_in_place(_selfs: &mut [Self], _offset: Self, _inverse: bool) -> bool {
+        false
+    }
 }
 
 /// The interface for a prime field.
@@ -234,7 +234,8 @@ pub trait EvaluationDomain<F: FftField>:
 
 /// Types that can be FFT-ed must implement this trait.
 pub trait DomainCoeff<F: FftField>:
-    Copy
+    'static
+    + Copy
     + Send
     + Sync
     + core::ops::Add<Output = Self>
@@ -249,7 +250,8 @@ pub trait DomainCoeff<F: FftField>:
 impl<T, F> DomainCoeff<F> for T
 where
     F: FftField,
-    T: Copy
+    T: 'static
+        + Copy
         + Send
         + Sync
         + core::ops::Add<Output = Self>
@@ -8,6 +8,7 @@ use crate::domain::{DomainCoeff, EvaluationDomain};
 use ark_ff::{FftField, FftParameters};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::{
+    any::Any,
     convert::TryFrom,
     fmt,
     io::{Read, Write},
@@ -37,6 +38,18 @@ pub struct Radix2EvaluationDomain<F: FftField> {
     pub generator_inv: F,
 }
 
+/// Hand `x_s` to [FftField::radix2_fft_in_place] if its entries are field
+/// elements, returning whether that did the FFT.
+fn field_fft_in_place<F: FftField, T: DomainCoeff<F>>(
+    x_s: &mut Vec<T>,
+    offset: F,
+    inverse: bool,
+) -> bool {
+    (x_s as &mut dyn Any)
+        .downcast_mut::<Vec<F>>()
+        .map_or(false, |x_s| F::radix2_fft_in_place(x_s, offset, inverse))
+}
+
 impl<F: FftField> fmt::Debug for Radix2EvaluationDomain<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Radix-2 multiplicative subgroup of size {}", self.size)
@@ -99,21 +112,37 @@ impl<F: FftField> EvaluationDomain<F> for Radix2EvaluationDomain<F> {
     fn fft_in_place<T: DomainCoeff<F>>(&self, coeffs: &mut Vec<T>) {
         assert!(coeffs.len() <= self.size());
         coeffs.resize(self.size(), T::zero());
-        self.in_order_fft_in_place(&mut *coeffs)
+        if !field_fft_in_place(coeffs, F::one(), false) {
+            self.in_order_fft_in_place(&mut *coeffs)
+        }
     }
 
     #[inline]
     fn ifft_in_place<T: DomainCoeff<F>>(&self, evals: &mut Vec<T>) {
         assert!(evals.len() <= self.size());
         evals.resize(self.size(), T::zero());
-        self.in_order_ifft_in_place(&mut *evals);
+        if !field_fft_in_place(evals, F::one(), true) {
+            self.in_order_ifft_in_place(&mut *evals);
+        }
+    }
+
+    #[inline]
+    fn coset_fft_in_place<T: DomainCoeff<F>>(&self, coeffs: &mut Vec<T>) {
+        assert!(coeffs.len() <= self.size());
+        coeffs.resize(self.size(), T::zero());
+        if !field_fft_in_place(coeffs, F::multiplicative_generator(), false) {
+            Self::distribute_powers(coeffs, F::multiplicative_generator());
+            self.in_order_fft_in_place(&mut *coeffs)
+        }
     }
 
     #[inline]
     fn coset_ifft_in_place<T: DomainCoeff<F>>(&self, evals: &mut Vec<T>) {
         assert!(evals.len() <= self.size());
         evals.resize(self.size(), T::zero());
-        self.in_order_coset_ifft_in_place(&mut *evals);
+        if !field_fft_in_place(evals, F::multiplicative_generator(), true) {
+            self.in_order_coset_ifft_in_place(&mut *evals);
+        }
     }
 
     fn evaluate_all_lagrange_coefficients(&self, tau: F) -> Vec<F> {
@@ -476,7 +476,11 @@ impl<'a, 'b, F: FftField> Mul<&'a DensePolynomial<F>> for &'b DensePolynomial<F>
             let mut self_evals = self.evaluate_over_domain_by_ref(domain);
             let other_evals = other.evaluate_over_domain_by_ref(domain);
             self_evals *= &other_evals;
-            self_evals.interpolate()
+            let mut result = self_evals.interpolate();
+            // Shared coefficients can't be checked for zero, so drop the (zero) coefficients
+            // above the product's degree bound explicitly.
+            result.coeffs.truncate(self.coeffs.len() + other.coeffs.len() - 1);
+            result
         }
     }
 }
@@ -9,6 +9,7 @@ use ark_poly_commit::marlin_pc::MarlinKZG10;
 use ark_poly_commit::reveal as pc_reveal;
 use blake2::Blake2s;
 use mpc_algebra::*;
+use mpc_net::{ActiveNet as Net, MpcNet};
 use Marlin;
 
 use super::*;
@@ -26,6 +27,15 @@ impl<F: PrimeField, S: FieldShare<F>> Reveal for ProverMsg<MpcField<F, S>> {
         }
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        match self {
+            ProverMsg::EmptyMessage => {
+                (Net::party_id() == party).then_some(ProverMsg::EmptyMessage)
+            }
+            ProverMsg::FieldElements(d) => d.reveal_to(party).map(ProverMsg::FieldElements),
+        }
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         match b {
             ProverMsg::EmptyMessage => ProverMsg::EmptyMessage,
@@ -39,6 +49,22 @@ impl<F: PrimeField, S: FieldShare<F>> Reveal for ProverMsg<MpcField<F, S>> {
             ProverMsg::FieldElements(d) => ProverMsg::FieldElements(Reveal::from_public(d)),
         }
     }
+
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let ds = bs
+            .into_iter()
+            .map(|b| {
+                b.map(|b| match b {
+                    ProverMsg::EmptyMessage => None,
+                    ProverMsg::FieldElements(d) => Some(d),
+                })
+            })
+            .collect();
+        Option::<Vec<MpcField<F, S>>>::input_from_batch(owner, ds)
+            .into_iter()
+            .map(|d| d.map_or(ProverMsg::EmptyMessage, ProverMsg::FieldElements))
+            .collect()
+    }
 }
 
 impl<F: Field> MpcWire for ProverMsg<F> {
@@ -126,7 +126,7 @@ fn test_group<G: Group>() {
 
     let s1_pub = G::ScalarField::rand(rng);
     let s2_pub = G::ScalarField::rand(rng);
-    let s2 = GszFieldShare::from_public(s1_pub);
+    let s2 = GszFieldShare::from_public(s2_pub);
     let mut a = a;
     <GszGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::scale_pub_scalar(&mut a, &s1_pub);
     let as1s2 = <GszGroupShare<G, NaiveMsm<G>> as GroupShare<G>>::scale(
@@ -209,7 +209,7 @@ fn main() {
     debug!("Start");
     let opt = Opt::from_args();
     println!("{:?}", opt);
-    Net::init_from_file(opt.input.to_str().unwrap(), opt.id);
+    Net::init_from_file(opt.input.to_str().unwrap(), opt.id).unwrap();
 
     test::<ark_bls12_377::Fr>();
     test_ip::<ark_bls12_377::Fr>();
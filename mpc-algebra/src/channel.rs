@@ -1,30 +1,172 @@
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use digest::Digest;
-use rand::RngCore;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 use sha2::Sha256;
 use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
 
 use mpc_net::two as net_two;
 
-use mpc_net::MpcNet;
+use mpc_net::{session, MpcNet, MpcNetError};
+
+/// Protocols have no way to recover from a network failure. By the time we see the error, the
+/// network layer has already told the other parties that we are aborting.
+#[inline]
+pub(crate) fn or_abort<T>(r: Result<T, MpcNetError>) -> T {
+    r.unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// The parties disagree about what was broadcast.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Equivocation {
+    /// The parties whose messages did not reach everyone the same. Either each of them sent
+    /// different parties different messages, or some party lied about what it got from them.
+    pub senders: Vec<usize>,
+}
+
+impl Display for Equivocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "parties {:?} broadcast inconsistently", self.senders)
+    }
+}
+
+impl std::error::Error for Equivocation {}
+
+/// Whether [MpcSerNet::consistent_broadcast] checks its messages, for the current session.
+#[derive(Default)]
+struct EchoBroadcast(bool);
+
+/// Turn echo broadcast on or off for the rest of the session.
+///
+/// When it is on, [MpcSerNet::consistent_broadcast] (which the malicious share types use to open
+/// values, and [MpcSerNet::atomic_broadcast] uses for its commitments) runs
+/// [MpcSerNet::echo_broadcast], and aborts if any party equivocated. That costs an extra round,
+/// in which each party sends a hash of every message it got.
+pub fn set_echo_broadcast(on: bool) {
+    session::with_state(|m: &mut EchoBroadcast| m.0 = on);
+}
+
+pub fn echo_broadcast() -> bool {
+    session::with_state(|m: &mut EchoBroadcast| m.0)
+}
 
 pub trait MpcSerNet: MpcNet {
     #[inline]
     fn broadcast<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Vec<T> {
         let mut bytes_out = Vec::new();
         out.serialize(&mut bytes_out).unwrap();
-        let bytes_in = Self::broadcast_bytes(&bytes_out);
+        let bytes_in = or_abort(Self::broadcast_bytes(&bytes_out));
         bytes_in
             .into_iter()
             .map(|b| T::deserialize(&b[..]).unwrap())
             .collect()
     }
 
+    /// Broadcast `bytes`, then check that every party got the same messages.
+    ///
+    /// Each party echoes a hash of every message it got to all the others. If the parties that
+    /// succeed are honest, they got the same messages; a party that equivocates (or lies in its
+    /// echo) can only make some of them fail.
+    fn echo_broadcast_bytes(bytes: &[u8]) -> Result<Vec<Vec<u8>>, Equivocation> {
+        let all = or_abort(Self::broadcast_bytes(bytes));
+        let digests: Vec<u8> = all
+            .iter()
+            .flat_map(|m| CommitHash::digest(m).to_vec())
+            .collect();
+        let echoes = or_abort(Self::broadcast_bytes(&digests));
+        let len = CommitHash::output_size();
+        let senders: Vec<usize> = (0..all.len())
+            .filter(|j| {
+                let ours = &digests[j * len..(j + 1) * len];
+                echoes
+                    .iter()
+                    .any(|e| e.get(j * len..(j + 1) * len) != Some(ours))
+            })
+            .collect();
+        if senders.is_empty() {
+            Ok(all)
+        } else {
+            Err(Equivocation { senders })
+        }
+    }
+
+    /// Like [MpcSerNet::broadcast], checked as in [MpcSerNet::echo_broadcast_bytes].
+    #[inline]
+    fn echo_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(
+        out: &T,
+    ) -> Result<Vec<T>, Equivocation> {
+        let mut bytes_out = Vec::new();
+        out.serialize(&mut bytes_out).unwrap();
+        Ok(Self::echo_broadcast_bytes(&bytes_out)?
+            .into_iter()
+            .map(|b| T::deserialize(&b[..]).unwrap())
+            .collect())
+    }
+
+    /// Broadcast `bytes`, checking that every party got the same messages if echo broadcast is on
+    /// (see [set_echo_broadcast]). Aborts if they did not.
+    fn consistent_broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
+        if !echo_broadcast() {
+            return or_abort(Self::broadcast_bytes(bytes));
+        }
+        Self::echo_broadcast_bytes(bytes).unwrap_or_else(|e| {
+            Self::abort();
+            panic!("{}", e)
+        })
+    }
+
+    /// Like [MpcSerNet::broadcast], checked as in [MpcSerNet::consistent_broadcast_bytes].
+    #[inline]
+    fn consistent_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Vec<T> {
+        let mut bytes_out = Vec::new();
+        out.serialize(&mut bytes_out).unwrap();
+        Self::consistent_broadcast_bytes(&bytes_out)
+            .into_iter()
+            .map(|b| T::deserialize(&b[..]).unwrap())
+            .collect()
+    }
+
+    /// Send `outs[j]` to party `j`, returning what each party sent us.
+    #[inline]
+    fn all_to_all<T: CanonicalDeserialize + CanonicalSerialize>(outs: &[T]) -> Vec<T> {
+        let bytes_out: Vec<Vec<u8>> = outs
+            .iter()
+            .map(|out| {
+                let mut bytes_out = Vec::new();
+                out.serialize(&mut bytes_out).unwrap();
+                bytes_out
+            })
+            .collect();
+        or_abort(Self::all_to_all_bytes(&bytes_out))
+            .into_iter()
+            .map(|b| T::deserialize(&b[..]).unwrap())
+            .collect()
+    }
+
+    /// Send `out` to party `to` only, which gets what every party sent it. Everyone else gets
+    /// `None`.
+    #[inline]
+    fn send_to<T: CanonicalDeserialize + CanonicalSerialize>(to: usize, out: &T) -> Option<Vec<T>> {
+        let mut bytes_out = Vec::new();
+        out.serialize(&mut bytes_out).unwrap();
+        let outs: Vec<Vec<u8>> = (0..Self::n_parties())
+            .map(|j| if j == to { bytes_out.clone() } else { Vec::new() })
+            .collect();
+        let bytes_in = or_abort(Self::all_to_all_bytes(&outs));
+        (Self::party_id() == to).then(|| {
+            bytes_in
+                .into_iter()
+                .map(|b| T::deserialize(&b[..]).unwrap())
+                .collect()
+        })
+    }
+
     #[inline]
     fn send_to_king<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Option<Vec<T>> {
         let mut bytes_out = Vec::new();
         out.serialize(&mut bytes_out).unwrap();
-        Self::send_bytes_to_king(&bytes_out).map(|bytes_in| {
+        or_abort(Self::send_bytes_to_king(&bytes_out)).map(|bytes_in| {
             bytes_in
                 .into_iter()
                 .map(|b| T::deserialize(&b[..]).unwrap())
@@ -34,7 +176,7 @@ pub trait MpcSerNet: MpcNet {
 
     #[inline]
     fn recv_from_king<T: CanonicalDeserialize + CanonicalSerialize>(out: Option<Vec<T>>) -> T {
-        let bytes_in = Self::recv_bytes_from_king(out.map(|outs| {
+        let bytes_in = or_abort(Self::recv_bytes_from_king(out.map(|outs| {
             outs.iter()
                 .map(|out| {
                     let mut bytes_out = Vec::new();
@@ -42,7 +184,7 @@ pub trait MpcSerNet: MpcNet {
                     bytes_out
                 })
                 .collect()
-        }));
+        })));
         T::deserialize(&bytes_in[..]).unwrap()
     }
 
@@ -50,28 +192,35 @@ pub trait MpcSerNet: MpcNet {
     fn atomic_broadcast<T: CanonicalDeserialize + CanonicalSerialize>(out: &T) -> Vec<T> {
         let mut bytes_out = Vec::new();
         out.serialize(&mut bytes_out).unwrap();
-        let ser_len = bytes_out.len();
-        bytes_out.resize(ser_len + COMMIT_RAND_BYTES, 0);
-        rand::thread_rng().fill_bytes(&mut bytes_out[ser_len..]);
-        let commitment = CommitHash::new().chain(&bytes_out).finalize();
+        let (commitment, opening) = commit(&bytes_out);
         // exchange commitments
-        let all_commits = Self::broadcast_bytes(&commitment[..]);
+        let all_commits = Self::consistent_broadcast_bytes(&commitment);
         // exchange (data || randomness)
-        let all_data = Self::broadcast_bytes(&bytes_out);
+        let all_data = or_abort(Self::broadcast_bytes(&opening));
         let self_id = Self::party_id();
-        for i in 0..all_commits.len() {
-            if i != self_id {
-                // check other commitment
-                assert_eq!(
-                    &all_commits[i][..],
-                    &CommitHash::new().chain(&all_data[i]).finalize()[..]
-                );
+        all_commits
+            .iter()
+            .zip(&all_data)
+            .enumerate()
+            .map(|(i, (c, d))| {
+                let data = if i == self_id { &bytes_out[..] } else { open_commitment(c, d) };
+                T::deserialize(data).unwrap()
+            })
+            .collect()
+    }
+
+    /// A generator of public coins, seeded by all parties together: no party can predict them
+    /// before every party has committed to its part of the seed.
+    fn coin_rng() -> StdRng {
+        let mut seed = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut seed);
+        let mut joint = [0u8; 32];
+        for part in Self::atomic_broadcast(&seed) {
+            for (j, p) in joint.iter_mut().zip(part) {
+                *j ^= p;
             }
         }
-        all_data
-            .into_iter()
-            .map(|d| T::deserialize(&d[..ser_len]).unwrap())
-            .collect()
+        StdRng::from_seed(joint)
     }
 
     #[inline]
@@ -91,6 +240,27 @@ const COMMIT_RAND_BYTES: usize = 32;
 /// The hash function to use for the commitment
 type CommitHash = Sha256;
 
+/// Commit to `bytes`. Returns the commitment, and its opening: `bytes` followed by the
+/// commitment randomness.
+pub(crate) fn commit(bytes: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut opening = bytes.to_vec();
+    opening.resize(bytes.len() + COMMIT_RAND_BYTES, 0);
+    rand::thread_rng().fill_bytes(&mut opening[bytes.len()..]);
+    (CommitHash::new().chain(&opening).finalize().to_vec(), opening)
+}
+
+/// The bytes committed to by `commitment`, if `opening` opens it.
+pub(crate) fn try_open_commitment<'a>(commitment: &[u8], opening: &'a [u8]) -> Option<&'a [u8]> {
+    (opening.len() >= COMMIT_RAND_BYTES
+        && CommitHash::new().chain(opening).finalize()[..] == commitment[..])
+        .then(|| &opening[..opening.len() - COMMIT_RAND_BYTES])
+}
+
+/// The bytes committed to by `commitment`. Panics if `opening` does not open it.
+fn open_commitment<'a>(commitment: &[u8], opening: &'a [u8]) -> &'a [u8] {
+    try_open_commitment(commitment, opening).expect("A party broke its commitment")
+}
+
 #[inline]
 pub fn exchange<F: CanonicalSerialize + CanonicalDeserialize>(f: &F) -> F {
     let mut bytes_out = Vec::new();
@@ -5,6 +5,7 @@ pub mod reveal;
 pub use reveal::*;
 pub mod channel;
 pub mod com;
+pub mod ot;
 pub mod group;
 pub mod share;
 pub use share::*;
@@ -48,16 +49,731 @@ pub mod malicious_majority {
 pub mod honest_majority {
     use super::{
         share::msm::NaiveMsm,
-        share::gsz20::{field::GszFieldShare, group::GszGroupShare},
-        wire::{field, group},
+        share::gsz20::{field::GszFieldShare, group::GszGroupShare, GszPairingShare},
+        wire::{field, group, pairing},
     };
     pub type MpcField<F> = field::MpcField<F, GszFieldShare<F>>;
     pub type MpcGroup<G> = group::MpcGroup<G, GszGroupShare<G, NaiveMsm<G>>>;
-    // pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, GszPairingShare<E>>;
-    // pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, GszPairingShare<E>>;
-    // pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, GszPairingShare<E>>;
-    // pub type MpcG2Projective<E> = pairing::MpcG2Projective<E, GszPairingShare<E>>;
-    // pub type MpcG1Prep<E> = pairing::MpcG1Prep<E, GszPairingShare<E>>;
-    // pub type MpcG2Prep<E> = pairing::MpcG2Prep<E, GszPairingShare<E>>;
-    // pub type MpcPairingEngine<E> = pairing::MpcPairingEngine<E, GszPairingShare<E>>;
+    pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, GszPairingShare<E>>;
+    pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, GszPairingShare<E>>;
+    pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, GszPairingShare<E>>;
+    pub type MpcG2Projective<E> = pairing::MpcG2Projective<E, GszPairingShare<E>>;
+    pub type MpcG1Prep<E> = pairing::MpcG1Prep<E, GszPairingShare<E>>;
+    pub type MpcG2Prep<E> = pairing::MpcG2Prep<E, GszPairingShare<E>>;
+    pub type MpcPairingEngine<E> = pairing::MpcPairingEngine<E, GszPairingShare<E>>;
+}
+
+pub mod three_party {
+    use super::{
+        share::msm::NaiveMsm,
+        share::rss::{RssFieldShare, RssGroupShare, RssPairingShare},
+        wire::{field, group, pairing},
+    };
+    pub type MpcField<F> = field::MpcField<F, RssFieldShare<F>>;
+    pub type MpcGroup<G> = group::MpcGroup<G, RssGroupShare<G, NaiveMsm<G>>>;
+    pub type MpcG1Affine<E> = pairing::MpcG1Affine<E, RssPairingShare<E>>;
+    pub type MpcG2Affine<E> = pairing::MpcG2Affine<E, RssPairingShare<E>>;
+    pub type MpcG1Projective<E> = pairing::MpcG1Projective<E, RssPairingShare<E>>;
+    pub type MpcG2Projective<E> = pairing::MpcG2Projective<E, RssPairingShare<E>>;
+    pub type MpcG1Prep<E> = pairing::MpcG1Prep<E, RssPairingShare<E>>;
+    pub type MpcG2Prep<E> = pairing::MpcG2Prep<E, RssPairingShare<E>>;
+    pub type MpcPairingEngine<E> = pairing::MpcPairingEngine<E, RssPairingShare<E>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::share::{
+        add::AdditivePairingShare,
+        gsz20::{field::GszFieldShare, packed::PackedPairingShare, GszPairingShare},
+        msm::NaiveMsm,
+        rss::{RssFieldShare, RssPairingShare},
+        spdz::{SpdzFieldShare, SpdzPairingShare},
+    };
+    use super::*;
+    use ark_bls12_377::{Bls12_377, Fr, G1Projective, G2Projective};
+    use ark_ec::{PairingEngine, ProjectiveCurve};
+    use ark_ff::{FftField, Field, One, PrimeField, SquareRootField, UniformRand, Zero};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::rand::rngs::StdRng;
+    use mpc_net::{run_on, run_parties, MemTransport};
+
+    /// Run `f(party_id, rng)` as each of `n` in-process parties, with dealt triples (they are
+    /// much faster than OT ones; `ot_triples` tests those). Every party gets the same `rng`, so
+    /// they all draw the same values, though only the king's are shared.
+    fn run_dealt<T: Send>(n: usize, f: impl Fn(usize, &mut StdRng) -> T + Sync) -> Vec<T> {
+        run_dealt_on(MemTransport::network(n), f)
+    }
+
+    /// Like [run_dealt], but over the given transports, which may have been configured first.
+    fn run_dealt_on<T: Send>(
+        transports: Vec<MemTransport>,
+        f: impl Fn(usize, &mut StdRng) -> T + Sync,
+    ) -> Vec<T> {
+        run_on(transports, |id| {
+            share::beaver::trust_dealer(true);
+            f(id, &mut ark_std::test_rng())
+        })
+    }
+
+    /// Tests run for every scheme, each in a module named after the scheme, over that scheme's
+    /// pairing share and 3 in-process parties.
+    macro_rules! scheme_tests {
+        ($($scheme:ident: $share:ty,)*) => {$(
+            mod $scheme {
+                use super::*;
+
+                #[test]
+                fn arith() {
+                    check_arith::<$share>(3);
+                }
+
+                #[test]
+                fn ext_arith() {
+                    check_ext_arith::<$share>(3);
+                }
+
+                #[test]
+                fn serialization() {
+                    check_serialization::<$share>(3);
+                }
+
+                #[test]
+                fn bls() {
+                    check_bls::<$share>(3);
+                }
+
+                #[test]
+                fn bits() {
+                    check_bits::<$share>(3);
+                }
+
+                #[test]
+                fn sqrt() {
+                    check_sqrt::<$share>(3);
+                }
+
+                #[test]
+                fn input() {
+                    check_input::<$share>(3);
+                }
+
+                #[test]
+                fn reveal_to() {
+                    check_reveal_to::<$share>(3);
+                }
+            }
+        )*};
+    }
+
+    scheme_tests! {
+        hbc: AdditivePairingShare<Bls12_377>,
+        spdz: SpdzPairingShare<Bls12_377>,
+        gsz: GszPairingShare<Bls12_377>,
+        rss: RssPairingShare<Bls12_377>,
+    }
+
+    /// Multiply, invert and divide shared values, as each of `n` in-process parties.
+    fn check_arith<S: PairingShare<Bls12_377>>(n: usize) {
+        type F<S> = MpcField<Fr, <S as PairingShare<Bls12_377>>::FrShare>;
+        run_dealt(n, |_, rng| {
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            let x = F::<S>::king_share(a, rng);
+            let y = F::<S>::king_share(b, rng);
+            assert_eq!((x * y).reveal(), a * b);
+            assert_eq!(x.inverse().unwrap().reveal(), a.inverse().unwrap());
+            assert_eq!((x / y).reveal(), a / b);
+            let mut prods = vec![x, y, x];
+            F::<S>::partial_products_in_place(&mut prods);
+            let prods: Vec<Fr> = prods.into_iter().map(|p| p.reveal()).collect();
+            assert_eq!(prods, vec![a, a * b, a * b * a]);
+            // Shared points times shared scalars use group triples.
+            let p = G1Projective::rand(rng);
+            let q = MpcG1Projective::<Bls12_377, S>::king_share(p, rng);
+            assert_eq!((q * x).reveal(), p.mul(a.into_repr()));
+        });
+    }
+
+    /// By default, triples come from OT, over prime and extension fields alike.
+    fn ot_triples<S: PairingShare<Bls12_377>>(n: usize) {
+        use share::field::ExtFieldShare;
+        type Fqe = <Bls12_377 as PairingEngine>::Fqe;
+        run_parties(n, |_| {
+            share::beaver::set_batch_size::<Fr, S::FrShare>(2);
+            share::beaver::set_batch_size::<Fqe, <S::FqeShare as ExtFieldShare<Fqe>>::Ext>(1);
+            let rng = &mut ark_std::test_rng();
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            let x = MpcField::<Fr, S::FrShare>::king_share(a, rng);
+            let y = MpcField::<Fr, S::FrShare>::king_share(b, rng);
+            assert_eq!((x * y).reveal(), a * b);
+            assert_eq!((x / y).reveal(), a / b);
+            let (c, d) = (Fqe::rand(rng), Fqe::rand(rng));
+            let z = MpcExtField::<Fqe, S::FqeShare>::king_share(c, rng);
+            let w = MpcExtField::<Fqe, S::FqeShare>::king_share(d, rng);
+            assert_eq!((z * w).reveal(), c * d);
+        });
+    }
+
+    #[test]
+    fn hbc_ot_triples() {
+        ot_triples::<AdditivePairingShare<Bls12_377>>(3);
+    }
+
+    #[test]
+    fn spdz_ot_triples() {
+        ot_triples::<SpdzPairingShare<Bls12_377>>(3);
+    }
+
+    /// Decompose and compare shared values.
+    fn check_bits<S: PairingShare<Bls12_377>>(n: usize) {
+        type F<S> = MpcField<Fr, <S as PairingShare<Bls12_377>>::FrShare>;
+        run_dealt(n, |_, rng| {
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            share::bits::preprocess_bits::<Fr, S::FrShare>(100);
+            let x = F::<S>::king_share(a, rng);
+            let y = F::<S>::king_share(b, rng);
+            let bits: Vec<Fr> = x.bits_le().into_iter().map(|b| b.reveal()).collect();
+            let expected: Vec<Fr> = F::<S>::from_public(a)
+                .bits_le()
+                .into_iter()
+                .map(|b| b.reveal())
+                .collect();
+            assert_eq!(bits, expected);
+            let lt = Fr::from(a.into_repr() < b.into_repr());
+            assert_eq!(x.lt_bit(&y).reveal(), lt);
+            assert_eq!(y.lt_bit(&x).reveal(), Fr::one() - lt);
+            assert_eq!(x.eq_bit(&y).reveal(), Fr::zero());
+            let zero = x - F::<S>::from_public(a);
+            assert_eq!(zero.is_zero_bit().reveal(), Fr::one());
+        });
+    }
+
+    /// Take square roots of shared values, and test shared values for squareness.
+    fn check_sqrt<S: PairingShare<Bls12_377>>(n: usize) {
+        type F<S> = MpcField<Fr, <S as PairingShare<Bls12_377>>::FrShare>;
+        run_dealt(n, |_, rng| {
+            let a = Fr::rand(rng).square();
+            let b = a * Fr::multiplicative_generator();
+            let x = F::<S>::king_share(a, rng);
+            let y = F::<S>::king_share(b, rng);
+            assert!(x.legendre().is_qr());
+            assert!(y.legendre().is_qnr());
+            assert_eq!(x.sqrt().unwrap().square().reveal(), a);
+            assert!(y.sqrt().is_none());
+            assert_eq!(x.is_square_bit().reveal(), Fr::one());
+            assert_eq!(y.is_square_bit().reveal(), Fr::zero());
+        });
+    }
+
+    /// Have parties other than the king input field and group elements.
+    fn check_input<S: PairingShare<Bls12_377>>(n: usize) {
+        run_dealt(n, |id, rng| {
+            let a = Fr::rand(rng);
+            let bs = [Fr::rand(rng), Fr::rand(rng)];
+            let p = G1Projective::rand(rng);
+            let x = MpcField::<Fr, S::FrShare>::input_from(1, (id == 1).then_some(a));
+            let ys = MpcField::<Fr, S::FrShare>::input_from_batch(
+                2,
+                bs.iter().map(|b| (id == 2).then_some(*b)).collect(),
+            );
+            let q = MpcG1Projective::<Bls12_377, S>::input_from(2, (id == 2).then_some(p));
+            let e = Bls12_377::pairing(p, G2Projective::rand(rng));
+            let f = <MpcPairingEngine<Bls12_377, S> as PairingEngine>::Fqk::input_from(
+                1,
+                (id == 1).then_some(e),
+            );
+            assert_eq!((x * ys[0]).reveal(), a * bs[0]);
+            assert_eq!(ys[1].reveal(), bs[1]);
+            assert_eq!((q * x).reveal(), p.mul(a.into_repr()));
+            assert_eq!(f.reveal(), e);
+        });
+    }
+
+    /// Reveal shared and public values to one party only.
+    fn check_reveal_to<S: PairingShare<Bls12_377>>(n: usize) {
+        run_dealt(n, |id, rng| {
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            let p = G1Projective::rand(rng);
+            let x = MpcField::<Fr, S::FrShare>::king_share(a, rng);
+            let y = MpcField::<Fr, S::FrShare>::king_share(b, rng);
+            let q = MpcG1Projective::<Bls12_377, S>::king_share(p, rng);
+            let fs = vec![x * y, MpcField::from_public(b)].reveal_to(1);
+            let g = (q * x).reveal_to(1);
+            let h = G2Projective::rand(rng);
+            let e = MpcPairingEngine::<Bls12_377, S>::pairing(q, MpcG2Projective::from_public(h))
+                .reveal_to(1);
+            assert_eq!(fs, (id == 1).then_some(vec![a * b, b]));
+            assert_eq!(g, (id == 1).then_some(p.mul(a.into_repr())));
+            assert_eq!(e, (id == 1).then(|| Bls12_377::pairing(p, h)));
+        });
+    }
+
+    /// Any party can be the king, and stats count bytes per link.
+    #[test]
+    fn other_king() {
+        use mpc_net::{ActiveNet as Net, MpcNet};
+        let mut transports = MemTransport::network(3);
+        for t in &mut transports {
+            t.set_king(2);
+        }
+        run_dealt_on(transports, |id, rng| {
+            assert_eq!(Net::am_king(), id == 2);
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            // Only the king's inputs count: everyone else passes garbage.
+            let (a_in, b_in) = if id == 2 { (a, b) } else { (Fr::from(id as u64), Fr::zero()) };
+            let x = MpcField::<Fr, SpdzFieldShare<Fr>>::king_share(a_in, rng);
+            let y = MpcField::<Fr, SpdzFieldShare<Fr>>::king_share(b_in, rng);
+            assert_eq!((x * y).reveal(), a * b);
+            let x = MpcField::<Fr, GszFieldShare<Fr>>::king_share(a_in, rng);
+            let y = MpcField::<Fr, GszFieldShare<Fr>>::king_share(b_in, rng);
+            assert_eq!((x / y).reveal(), a / b);
+            let x = MpcField::<Fr, RssFieldShare<Fr>>::king_share(a_in, rng);
+            let y = MpcField::<Fr, RssFieldShare<Fr>>::king_share(b_in, rng);
+            assert_eq!((x * y + MpcField::from_public(a)).reveal(), a * b + a);
+            let p = G1Projective::rand(rng);
+            let p_in = if id == 2 { p } else { G1Projective::zero() };
+            type G<S> = MpcG1Projective<Bls12_377, S>;
+            let q = G::<SpdzPairingShare<Bls12_377>>::king_share(p_in, rng);
+            assert_eq!(q.reveal(), p);
+            let stats = Net::stats();
+            assert_eq!(stats.bytes_sent_to[id], 0);
+            assert_eq!(stats.bytes_sent, stats.bytes_sent_to.iter().sum::<usize>());
+        });
+    }
+
+    /// Echo broadcast catches parties that disagree about what was sent, and the malicious share
+    /// types still open correctly with it on.
+    #[test]
+    fn echo_broadcast() {
+        use channel::{set_echo_broadcast, Equivocation, MpcSerNet};
+        use mpc_net::{ActiveNet as Net, MpcNet};
+        let results = run_parties(3, |id| {
+            if id == 2 {
+                Net::broadcast_bytes(&[2]).unwrap();
+                // Claim that every message hashed to zero.
+                Net::broadcast_bytes(&[0; 96]).unwrap();
+                None
+            } else {
+                Some(Net::echo_broadcast(&(id as u64)))
+            }
+        });
+        for r in &results[..2] {
+            let senders = vec![0, 1, 2];
+            assert_eq!(*r, Some(Err(Equivocation { senders })));
+        }
+        run_dealt(3, |_, rng| {
+            set_echo_broadcast(true);
+            let a = Fr::rand(rng);
+            let x = MpcField::<Fr, SpdzFieldShare<Fr>>::king_share(a, rng);
+            assert_eq!((x * x).reveal(), a * a);
+            let y = MpcField::<Fr, GszFieldShare<Fr>>::king_share(a, rng);
+            assert_eq!((y * y).reveal(), a * a);
+        });
+    }
+
+    /// King shares dealt with Pedersen VSS work as usual.
+    #[test]
+    fn gsz_vss() {
+        run_dealt(3, |_, rng| {
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            share::gsz20::vss::enable::<G1Projective, NaiveMsm<G1Projective>>();
+            let x = MpcField::<Fr, GszFieldShare<Fr>>::king_share(a, rng);
+            let ys = MpcField::<Fr, GszFieldShare<Fr>>::king_share_batch(vec![b, a], rng);
+            assert_eq!((x * ys[0]).reveal(), a * b);
+            assert_eq!((ys[1] - x).reveal(), Fr::zero());
+        });
+    }
+
+    /// A party that lies in the MAC check makes it fail, and in identifiable-abort mode the
+    /// others name it.
+    #[test]
+    fn spdz_mac_check_cheater() {
+        use share::spdz::{self, MacCheckFailed};
+        for identify in [false, true] {
+            let results = run_dealt(3, |id, rng| {
+                spdz::set_identifiable_abort(identify);
+                let k = spdz::mac_share::<Fr>();
+                let x = SpdzFieldShare::<Fr>::king_share(Fr::one(), rng);
+                let honest = x.reveal();
+                // Party 2 sends a wrong MAC check share.
+                let lie = if id == 2 { Fr::one() } else { Fr::zero() };
+                let r = spdz::mac_checked_open(vec![x.sh.val], vec![x.mac.val], k, |x, k| {
+                    *k * x + lie
+                });
+                (honest, r)
+            });
+            for (id, (honest, r)) in results.into_iter().enumerate().take(2) {
+                assert_eq!(honest, Fr::one(), "party {}", id);
+                let culprits = if identify { vec![2] } else { vec![] };
+                assert_eq!(r, Err(MacCheckFailed { culprits }));
+            }
+        }
+    }
+
+    /// A bad multiplicative share fails the MAC check when opened, which `try_reveal` reports.
+    #[test]
+    fn spdz_mul_mac_check() {
+        use share::spdz::{MacCheckFailed, SpdzMulFieldShare};
+        let results = run_dealt(3, |id, rng| {
+            let fs: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+            let mut x = SpdzMulFieldShare::<Fr, Fr>::from_add_shared(fs[id]);
+            let honest = x.try_reveal();
+            if id == 2 {
+                x.sh.val.double_in_place();
+            }
+            (honest, fs.iter().product::<Fr>(), x.try_reveal())
+        });
+        for (honest, f, r) in results {
+            assert_eq!(honest, Ok(f));
+            assert_eq!(r, Err(MacCheckFailed::default()));
+        }
+    }
+
+    /// With n >= 3t + 1, a bad share is corrected when opening, and its sender named.
+    #[test]
+    fn gsz_robust_open() {
+        use share::gsz20::{decode, field, group};
+        run_parties(4, |id| {
+            let mut x = field::rand::<Fr>();
+            let mut p = group::rand::<G1Projective, NaiveMsm<G1Projective>>();
+            let (a, q) = (x.reveal(), p.reveal());
+            assert!(decode::cheaters().is_empty());
+            if id == 3 {
+                x.val += Fr::one();
+                p.val += q;
+            }
+            assert_eq!(x.reveal(), a);
+            assert_eq!(p.reveal(), q);
+            assert_eq!(decode::cheaters(), vec![3]);
+        });
+    }
+
+    /// A bad group share is corrected, and its sender named, even if that party sent no bad
+    /// field share.
+    #[test]
+    fn gsz_group_open_corrects() {
+        use share::gsz20::{decode, group};
+        run_parties(4, |id| {
+            let mut p = group::rand::<G1Projective, NaiveMsm<G1Projective>>();
+            let q = p.reveal();
+            if id == 1 {
+                p.val += q;
+            }
+            assert_eq!(p.reveal(), q);
+            assert_eq!(decode::cheaters(), vec![1]);
+        });
+    }
+
+    /// More bad group shares than can be corrected abort the opening.
+    #[test]
+    fn gsz_group_open_aborts() {
+        use share::gsz20::group;
+        let results = run_parties(4, |id| {
+            let mut p = group::rand::<G1Projective, NaiveMsm<G1Projective>>();
+            let q = p.reveal();
+            if id >= 2 {
+                p.val += q;
+            }
+            std::panic::catch_unwind(move || p.reveal()).is_err()
+        });
+        assert_eq!(results, vec![true; 4]);
+    }
+
+    /// Add to, and shift, RSS multiplicative shares, which go through additive shares.
+    #[test]
+    fn rss_mul_share_add() {
+        use share::rss::RssMulFieldShare;
+        run_dealt(3, |id, rng| {
+            let fs: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+            let gs: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+            let c = Fr::rand(rng);
+            let mut x = RssMulFieldShare::from_add_shared(fs[id]);
+            let y = RssMulFieldShare::from_add_shared(gs[id]);
+            let (f, g): (Fr, Fr) = (fs.iter().product(), gs.iter().product());
+            x.add(&y);
+            assert_eq!(x.reveal(), f + g);
+            x.shift(&c);
+            assert_eq!(x.reveal(), f + g + c);
+            let z: RssFieldShare<Fr> = y.map_homo(|v| v);
+            assert_eq!(z.reveal(), g);
+        });
+    }
+
+    /// A king that deals a bad share, and cannot justify it, is caught by everyone.
+    #[test]
+    fn gsz_vss_bad_dealer() {
+        use share::gsz20::vss::{self, Dealing, VssError};
+        let results = run_dealt(3, |id, rng| {
+            let dealing = (id == 0).then(|| {
+                let mut d = Dealing::<G1Projective>::new(&[Fr::rand(rng)], rng);
+                d.shares[1][0] += Fr::one();
+                d
+            });
+            vss::receive::<G1Projective, NaiveMsm<G1Projective>>(1, dealing).map(|_| ())
+        });
+        assert_eq!(results, vec![Err(VssError::BadAnswer { party: 1 }); 3]);
+    }
+
+    /// GSZ with five parties, which have no FFT domain in `Fr`, and a lower threshold than usual.
+    /// With it, a bad share can be corrected.
+    #[test]
+    fn gsz_threshold() {
+        use share::gsz20::{self, decode};
+        run_dealt(5, |id, rng| {
+            gsz20::set_threshold(1);
+            let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+            let x = MpcField::<Fr, GszFieldShare<Fr>>::king_share(a, rng);
+            let y = MpcField::<Fr, GszFieldShare<Fr>>::input_from(4, (id == 4).then_some(b));
+            let mut z = x * y;
+            if let (4, MpcField::Shared(s)) = (id, &mut z) {
+                s.val += Fr::one();
+            }
+            assert_eq!(z.reveal(), a * b);
+            assert_eq!(decode::cheaters(), vec![4]);
+        });
+    }
+
+    /// Convert additive shares of field, group and extension field elements into GSZ shares.
+    #[test]
+    fn gsz_from_add_shared() {
+        use share::gsz20::{ext_field::ExtShare, group::GszGroupShare};
+        type Fqe = <Bls12_377 as PairingEngine>::Fqe;
+        run_dealt(3, |id, rng| {
+            let fs: Vec<Fr> = (0..3).map(|_| Fr::rand(rng)).collect();
+            let gs: Vec<G1Projective> = (0..3).map(|_| G1Projective::rand(rng)).collect();
+            let es: Vec<Fqe> = (0..3).map(|_| Fqe::rand(rng)).collect();
+            let x = GszFieldShare::from_add_shared(fs[id]);
+            let p = GszGroupShare::<G1Projective, NaiveMsm<_>>::from_add_shared(gs[id]);
+            let e = ExtShare::from_add_shared_batch(vec![es[id]]).pop().unwrap();
+            assert_eq!(x.reveal(), fs.iter().sum());
+            assert_eq!(p.reveal(), gs.iter().sum::<G1Projective>());
+            assert_eq!(e.reveal(), es.iter().sum());
+        });
+    }
+
+    /// GSZ shares of pairing outputs, in the exponent: random ones, conversions, and additions
+    /// whose results are pairing outputs too.
+    #[test]
+    fn gsz_mul_share_ops() {
+        use share::gsz20::{ext_field::ExtShare, mul_field::MulFieldShare};
+        type Fqk = <Bls12_377 as PairingEngine>::Fqk;
+        run_dealt(3, |id, rng| {
+            let mut e = || Bls12_377::pairing(G1Projective::rand(rng), G2Projective::rand(rng));
+            let (f, g) = (e(), e());
+            let mut fs: Vec<Fqk> = (0..3).map(|_| e()).collect();
+            let sum: Fqk = fs.iter().sum();
+            fs[0] += f - sum;
+            let r = MulFieldShare::<Fqk, Fr>::rand(rng).reveal();
+            assert!(!r.is_one() && r.pow(Fr::characteristic()).is_one());
+            let mut x = MulFieldShare::<Fqk, Fr>::from_add_shared(fs[id]);
+            assert_eq!(x.reveal(), f);
+            x.add(&MulFieldShare::from_add_shared(Fqk::zero()));
+            assert_eq!(x.reveal(), f);
+            x.shift(&(g - f));
+            assert_eq!(x.reveal(), g);
+            let z: ExtShare<Fqk> = x.map_homo(|v| v);
+            assert_eq!(z.reveal(), g);
+        });
+    }
+
+    /// GSZ extension field products are checked coordinate by coordinate, over the base field.
+    #[test]
+    fn gsz_ext_product_check() {
+        use share::gsz20::ext_field::{self, ExtShare, GszExtTriple};
+        type Fqe = <Bls12_377 as PairingEngine>::Fqe;
+        let results = run_dealt(3, |_, rng| {
+            let (a, b) = (Fqe::rand(rng), Fqe::rand(rng));
+            let x = ExtShare::king_share(a, rng);
+            let y = ExtShare::king_share(b, rng);
+            let mut z = x.mul(y, &mut PanicBeaverSource::default());
+            assert_eq!(z.reveal(), a * b);
+            z.shift(&Fqe::one());
+            let bad = vec![GszExtTriple(x, y, z)];
+            std::panic::catch_unwind(move || ext_field::check_ext_products(bad)).is_err()
+        });
+        assert_eq!(results, vec![true; 3]);
+    }
+
+    /// Arithmetic on shares of an extension field.
+    fn check_ext_arith<S: PairingShare<Bls12_377>>(n: usize) {
+        use share::field::ExtFieldShare;
+        type Fqe = <Bls12_377 as PairingEngine>::Fqe;
+        run_dealt(n, |_, rng| {
+            // Only a few triples are needed, and SPDZ ones over Fqe are slow to deal.
+            share::beaver::set_batch_size::<Fqe, <S::FqeShare as ExtFieldShare<Fqe>>::Ext>(4);
+            let (a, b) = (Fqe::rand(rng), Fqe::rand(rng));
+            let x = MpcExtField::<Fqe, S::FqeShare>::king_share(a, rng);
+            let y = MpcExtField::<Fqe, S::FqeShare>::king_share(b, rng);
+            assert_eq!((x + y).reveal(), a + b);
+            assert_eq!((x * y).reveal(), a * b);
+            assert_eq!((x / y).reveal(), a / b);
+            let mut prods = vec![x, y, x];
+            MpcExtField::partial_products_in_place(&mut prods);
+            let prods: Vec<Fqe> = prods.into_iter().map(|p| p.reveal()).collect();
+            assert_eq!(prods, vec![a, a * b, a * b * a]);
+        });
+    }
+
+    /// Pack, multiply, permute and unpack shares, three secrets to a share.
+    #[test]
+    fn packed_ops() {
+        use share::gsz20::{self, packed};
+        run_dealt(8, |_, rng| {
+            gsz20::set_threshold(1);
+            packed::set_pack_size(3);
+            let mut a: Vec<Fr> = (0..7).map(|_| Fr::rand(rng)).collect();
+            let xs = packed::pack(&GszFieldShare::king_share_batch(a.clone(), rng));
+            a.resize(9, Fr::zero());
+            assert_eq!(xs.len(), 3);
+            assert_eq!(packed::open(&xs), a);
+            let squares: Vec<Fr> = a.iter().map(|x| x.square()).collect();
+            assert_eq!(packed::open(&packed::mul(&xs, &xs)), squares);
+            let perm: Vec<usize> = (0..9).rev().collect();
+            let reversed: Vec<Fr> = a.iter().rev().cloned().collect();
+            assert_eq!(packed::open(&packed::permute(&xs, &perm)), reversed);
+            let unpacked: Vec<Fr> = packed::unpack(&xs).into_iter().map(|x| x.reveal()).collect();
+            assert_eq!(unpacked, a);
+        });
+    }
+
+    /// A packed FFT matches the plain one, and its inverse undoes it.
+    #[test]
+    fn packed_fft() {
+        use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+        use share::gsz20::{self, packed};
+        run_dealt(8, |_, rng| {
+            gsz20::set_threshold(1);
+            packed::set_pack_size(2);
+            let a: Vec<Fr> = (0..16).map(|_| Fr::rand(rng)).collect();
+            let xs = packed::pack(&GszFieldShare::king_share_batch(a.clone(), rng));
+            let ys = packed::fft(&xs);
+            assert_eq!(
+                packed::open(&ys),
+                Radix2EvaluationDomain::<Fr>::new(16).unwrap().fft(&a)
+            );
+            assert_eq!(packed::open(&packed::ifft(&ys)), a);
+            let g = Fr::multiplicative_generator();
+            let zs = packed::coset_fft(&xs, g);
+            assert_eq!(
+                packed::open(&zs),
+                Radix2EvaluationDomain::<Fr>::new(16).unwrap().coset_fft(&a)
+            );
+            assert_eq!(packed::open(&packed::coset_ifft(&zs, g)), a);
+        });
+    }
+
+    /// With packed FFTs on, a domain's FFTs over GSZ shares are done packed, and the packing of
+    /// their output is kept.
+    #[test]
+    fn packed_domain_fft() {
+        use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+        use mpc_net::{ActiveNet as Net, MpcNet};
+        use share::gsz20::{self, packed};
+        type F = MpcField<Fr, GszFieldShare<Fr>>;
+        run_dealt(8, |_, rng| {
+            gsz20::set_threshold(1);
+            packed::set_pack_size(2);
+            packed::set_packed_ffts(true);
+            let a: Vec<Fr> = (0..16).map(|_| Fr::rand(rng)).collect();
+            let plain = Radix2EvaluationDomain::<Fr>::new(16).unwrap();
+            let domain = Radix2EvaluationDomain::<F>::new(16).unwrap();
+            let xs = F::king_share_batch(a.clone(), rng);
+            let to_king = Net::stats().to_king;
+            let ys = domain.coset_fft(&xs);
+            assert!(Net::stats().to_king > to_king);
+            assert_eq!(ys.clone().reveal(), plain.coset_fft(&a));
+            let zs = domain.ifft(&ys);
+            assert_eq!(zs.clone().reveal(), plain.ifft(&plain.coset_fft(&a)));
+            let shares: Vec<GszFieldShare<Fr>> = zs
+                .iter()
+                .map(|z| match z {
+                    MpcField::Shared(s) => *s,
+                    MpcField::Public(_) => unreachable!(),
+                })
+                .collect();
+            // Finding the kept packing takes only the broadcast of the digests.
+            let broadcasts = Net::stats().broadcasts;
+            packed::pack_kept(&shares);
+            assert_eq!(Net::stats().broadcasts, broadcasts + 1);
+        });
+    }
+
+    /// A packed MSM, three scalars to a share, through the curve's `multi_scalar_mul`.
+    #[test]
+    fn packed_msm() {
+        use ark_ec::AffineCurve;
+        use share::gsz20::{self, packed};
+        type G1 = <Bls12_377 as PairingEngine>::G1Affine;
+        run_dealt(8, |_, rng| {
+            gsz20::set_threshold(1);
+            packed::set_pack_size(3);
+            let bases: Vec<G1> = (0..10).map(|_| G1Projective::rand(rng).into_affine()).collect();
+            let a: Vec<Fr> = (0..10).map(|_| Fr::rand(rng)).collect();
+            let expected = G1::multi_scalar_mul(&bases, &a);
+            let bases: Vec<MpcG1Affine<Bls12_377, PackedPairingShare<Bls12_377>>> =
+                bases.into_iter().map(MpcG1Affine::from_public).collect();
+            let xs = MpcField::<Fr, GszFieldShare<Fr>>::king_share_batch(a, rng);
+            assert_eq!(AffineCurve::multi_scalar_mul(&bases, &xs).reveal(), expected);
+        });
+    }
+
+    /// Write out shared and public values, read them back, and check that they still open to the
+    /// same thing.
+    fn check_serialization<S: PairingShare<Bls12_377>>(n: usize) {
+        run_dealt(n, |_, rng| {
+            let a = Fr::rand(rng);
+            let p = G1Projective::rand(rng).into_affine();
+            let xs = vec![
+                MpcField::<Fr, S::FrShare>::king_share(a, rng),
+                MpcField::from_public(a),
+            ];
+            let q = MpcG1Affine::<Bls12_377, S>::king_share(p, rng);
+            let mut bytes = Vec::new();
+            xs.serialize(&mut bytes).unwrap();
+            q.serialize(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), xs.serialized_size() + q.serialized_size());
+            let mut reader = &bytes[..];
+            let xs2 = Vec::<MpcField<Fr, S::FrShare>>::deserialize(&mut reader).unwrap();
+            let q2 = MpcG1Affine::<Bls12_377, S>::deserialize(&mut reader).unwrap();
+            assert!(reader.is_empty());
+            assert_eq!(xs, xs2);
+            assert_eq!(q, q2);
+            assert_eq!(xs2[0].reveal(), a);
+            assert_eq!(xs2[1].reveal(), a);
+            assert_eq!(q2.reveal(), p);
+        });
+    }
+
+    /// Sign a message hash with a shared BLS key, and verify the shared signature in the MPC.
+    fn check_bls<S: PairingShare<Bls12_377>>(n: usize) {
+        type E<S> = MpcPairingEngine<Bls12_377, S>;
+        run_dealt(n, |_, rng| {
+            let sk = Fr::rand(rng);
+            let h = G1Projective::rand(rng).into_affine();
+            let g2 = G2Projective::prime_subgroup_generator().into_affine();
+            // Multiplying re-randomizes the sharing.
+            let sk_sh = MpcField::<Fr, S::FrShare>::king_share(sk, rng)
+                * MpcField::king_share(Fr::one(), rng);
+            let h = MpcG1Affine::<Bls12_377, S>::from_public(h);
+            let g2 = MpcG2Affine::<Bls12_377, S>::from_public(g2);
+            let sig = h * sk_sh;
+            let pk = g2 * sk_sh;
+            let expected = Bls12_377::pairing(h.reveal(), g2.reveal()).pow(sk.into_repr());
+            assert_eq!(E::<S>::pairing(sig, g2).reveal(), expected);
+            assert_eq!(E::<S>::pairing(h, pk).reveal(), expected);
+            // Both points shared.
+            assert_eq!(E::<S>::pairing(sig, pk).reveal(), expected.pow(sk.into_repr()));
+            let ml = E::<S>::miller_loop(&[(sig.into(), (-g2).into()), (h.into(), pk.into())]);
+            let one = E::<S>::final_exponentiation(&ml).unwrap();
+            assert!(one.reveal().is_one());
+            assert_eq!(
+                Bls12_377::pairing(sig.reveal(), g2.reveal()),
+                Bls12_377::pairing(h.reveal(), pk.reveal())
+            );
+        });
+    }
+
+    #[test]
+    fn packed_bls() {
+        check_bls::<PackedPairingShare<Bls12_377>>(3);
+    }
 }
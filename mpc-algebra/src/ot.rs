@@ -0,0 +1,424 @@
+//! Oblivious transfer (OT) and oblivious linear evaluation (OLE) between every pair of parties.
+//!
+//! This is the machinery behind dishonest-majority preprocessing (see
+//! [crate::share::beaver::OtTripleGenerator]):
+//!
+//! * base OTs are Chou-Orlandi ["simplest OT"](https://ia.cr/2015/267) over BLS12-377 G1,
+//! * they are extended with [IKNP](https://www.iacr.org/archive/crypto2003/27290145/27290145.pdf),
+//!   so the number of curve operations does not grow with the number of OTs, with the consistency
+//!   check of [KOS15](https://ia.cr/2015/546), so a receiver that does not stick to its choice
+//!   bits is caught before it learns anything, and
+//! * OLE is Gilboa's protocol: one OT per bit of the receiver's scalar.
+//!
+//! Gilboa's protocol itself is only secure against semi-honest adversaries: a sender can put
+//! different multiples of its input in different OTs. Its output must be checked by the caller,
+//! as SPDZ does with its input masks and Beaver triples.
+//!
+//! Messages between two parties travel over `all_to_all`; the base-OT public keys are broadcast.
+use ark_bls12_377::{Fr as OtScalar, G1Projective as OtGroup};
+use ark_ec::ProjectiveCurve;
+use ark_ff::prelude::*;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use digest::Digest;
+use rand::Rng;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::Sha256;
+
+use std::convert::TryInto;
+use std::ops::{Add, Sub};
+
+use crate::channel::MpcSerNet;
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+
+/// Number of base OTs, and the computational security of the extension.
+const KAPPA: usize = 128;
+const KAPPA_BYTES: usize = KAPPA / 8;
+/// Statistical security of the consistency check.
+const SIGMA: usize = 64;
+/// Extra random OTs per extension, which hide the real choice bits in the consistency check.
+const CHECK_OTS: usize = KAPPA + SIGMA;
+
+type Seed = [u8; 32];
+
+/// A module over `F`, i.e. something an OLE can multiply by the receiver's scalar.
+pub trait OleModule<F: PrimeField>:
+    Copy + Zero + Add<Output = Self> + Sub<Output = Self> + UniformRand + CanonicalSerialize + CanonicalDeserialize
+{
+    /// `2 * self`
+    fn ole_double(&self) -> Self;
+    /// `s * self`
+    fn ole_scale(&self, s: &F) -> Self;
+}
+
+/// A field is a module over its base prime field.
+impl<F: Field> OleModule<F::BasePrimeField> for F {
+    fn ole_double(&self) -> Self {
+        Field::double(self)
+    }
+    fn ole_scale(&self, s: &F::BasePrimeField) -> Self {
+        *self * embed::<F>(*s)
+    }
+}
+
+/// `s` as an element of `F`.
+pub fn embed<F: Field>(s: F::BasePrimeField) -> F {
+    let mut elems = vec![F::BasePrimeField::zero(); F::extension_degree() as usize];
+    elems[0] = s;
+    F::from_base_prime_field_elems(&elems).unwrap()
+}
+
+/// The multiplicative group of `F`, written additively, as a module over the exponents `S`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct MulElem<F: Field>(pub F);
+
+impl<F: Field> Add for MulElem<F> {
+    type Output = Self;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, other: Self) -> Self {
+        MulElem(self.0 * other.0)
+    }
+}
+
+impl<F: Field> Sub for MulElem<F> {
+    type Output = Self;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn sub(self, other: Self) -> Self {
+        MulElem(self.0 / other.0)
+    }
+}
+
+impl<F: Field> Zero for MulElem<F> {
+    fn zero() -> Self {
+        MulElem(F::one())
+    }
+    fn is_zero(&self) -> bool {
+        self.0.is_one()
+    }
+}
+
+impl<F: Field> UniformRand for MulElem<F> {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        loop {
+            let f = F::rand(rng);
+            if !f.is_zero() {
+                break MulElem(f);
+            }
+        }
+    }
+}
+
+impl<F: Field, S: PrimeField> OleModule<S> for MulElem<F> {
+    fn ole_double(&self) -> Self {
+        MulElem(self.0.square())
+    }
+    fn ole_scale(&self, s: &S) -> Self {
+        MulElem(self.0.pow(s.into_repr()))
+    }
+}
+
+/// Base OT results with every other party, reused by all extensions in this session.
+struct OtState {
+    /// Bumped on every extension, so that base seeds are never expanded into the same stream twice.
+    counter: u64,
+    /// Per peer: the seed pairs for extensions where we receive.
+    recv_seeds: Vec<Vec<[Seed; 2]>>,
+    /// Per peer: our secret choice string for extensions where we send.
+    send_delta: Vec<[u8; KAPPA_BYTES]>,
+    /// Per peer: the seeds chosen by `send_delta`.
+    send_seeds: Vec<Vec<Seed>>,
+}
+
+#[inline]
+fn get_bit(bytes: &[u8], i: usize) -> bool {
+    (bytes[i / 8] >> (i % 8)) & 1 == 1
+}
+
+#[inline]
+fn xor_into(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+fn hash_point(p: &OtGroup, from: usize, to: usize, k: usize) -> Seed {
+    let mut bytes = Vec::new();
+    p.serialize(&mut bytes).unwrap();
+    let mut out = [0u8; 32];
+    out.copy_from_slice(
+        &Sha256::new()
+            .chain(&bytes)
+            .chain((from as u64).to_le_bytes())
+            .chain((to as u64).to_le_bytes())
+            .chain((k as u64).to_le_bytes())
+            .finalize(),
+    );
+    out
+}
+
+/// Multiply in GF(2^128), modulo x^128 + x^7 + x^2 + x + 1.
+fn gf_mul(mut a: u128, mut b: u128) -> u128 {
+    let mut acc = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            acc ^= a;
+        }
+        b >>= 1;
+        let carry = a >> 127;
+        a <<= 1;
+        if carry == 1 {
+            a ^= 0x87;
+        }
+    }
+    acc
+}
+
+/// `sum_l chi_l * rows_l` in GF(2^128).
+///
+/// Computed as `sum_k x^k (sum of the chi_l whose row has bit k set)`, which takes one
+/// multiplication per bit position rather than one per row.
+fn combine_rows(rows: &[[u8; KAPPA_BYTES]], chis: &[u128]) -> u128 {
+    let mut by_bit = [0u128; KAPPA];
+    for (row, chi) in rows.iter().zip(chis) {
+        let mut bits = u128::from_le_bytes(*row);
+        while bits != 0 {
+            by_bit[bits.trailing_zeros() as usize] ^= chi;
+            bits &= bits - 1;
+        }
+    }
+    by_bit.iter().rev().fold(0, |acc, s| gf_mul(acc, 2) ^ s)
+}
+
+/// Expand `seed` into `len` pseudorandom bytes, using stream `stream`.
+fn prg(seed: &Seed, stream: u64, len: usize) -> Vec<u8> {
+    let mut rng = ChaCha20Rng::from_seed(*seed);
+    rng.set_stream(stream);
+    let mut out = vec![0u8; len];
+    rng.fill_bytes(&mut out);
+    out
+}
+
+/// The one-time pad for OT number `l` of extension `counter` from `from` to `to`.
+fn pad(row: &[u8], from: usize, to: usize, counter: u64, l: usize, len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 32);
+    let mut block = 0u64;
+    while out.len() < len {
+        out.extend_from_slice(
+            &Sha256::new()
+                .chain(row)
+                .chain((from as u64).to_le_bytes())
+                .chain((to as u64).to_le_bytes())
+                .chain(counter.to_le_bytes())
+                .chain((l as u64).to_le_bytes())
+                .chain(block.to_le_bytes())
+                .finalize(),
+        );
+        block += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// Send `out[j]` to each party `j`, returning what each party sent us.
+fn exchange(mut out: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    out[Net::party_id()].clear();
+    crate::channel::or_abort(Net::all_to_all_bytes(&out))
+}
+
+/// Abort, blaming `party` for a malformed or inconsistent OT message.
+fn cheated(party: usize) -> ! {
+    Net::abort();
+    panic!("party {} cheated in OT extension", party)
+}
+
+/// Run the base OTs with every other party.
+fn base_ots() -> OtState {
+    let n = Net::n_parties();
+    let me = Net::party_id();
+    let rng = &mut rand::thread_rng();
+    let g = OtGroup::prime_subgroup_generator();
+
+    // We are the base-OT sender towards every peer, with a single key.
+    let a = OtScalar::rand(rng);
+    let big_a = g.mul(a.into_repr());
+    let all_a = Net::broadcast(&big_a);
+
+    // We are the base-OT receiver towards every peer, choosing by our (per-peer) delta.
+    let mut send_delta = vec![[0u8; KAPPA_BYTES]; n];
+    let mut send_seeds = vec![Vec::new(); n];
+    let mut out = vec![Vec::new(); n];
+    for j in (0..n).filter(|j| *j != me) {
+        rng.fill(&mut send_delta[j][..]);
+        for k in 0..KAPPA {
+            let b = OtScalar::rand(rng);
+            let mut big_b = g.mul(b.into_repr());
+            if get_bit(&send_delta[j], k) {
+                big_b += &all_a[j];
+            }
+            big_b.serialize(&mut out[j]).unwrap();
+            send_seeds[j].push(hash_point(&all_a[j].mul(b.into_repr()), j, me, k));
+        }
+    }
+    let ins = exchange(out);
+
+    let mut recv_seeds = vec![Vec::new(); n];
+    for i in (0..n).filter(|i| *i != me) {
+        let mut reader = &ins[i][..];
+        for k in 0..KAPPA {
+            let big_b = OtGroup::deserialize(&mut reader).unwrap();
+            let k0 = hash_point(&big_b.mul(a.into_repr()), me, i, k);
+            let k1 = hash_point(&(big_b - big_a).mul(a.into_repr()), me, i, k);
+            recv_seeds[i].push([k0, k1]);
+        }
+    }
+    OtState {
+        counter: 0,
+        recv_seeds,
+        send_delta,
+        send_seeds,
+    }
+}
+
+/// Given each party's `xs` and `ys`, returns this party's additive share of
+/// `(sum_i xs_i[l]) * (sum_j ys_j[l])` for each `l`.
+///
+/// The cross terms `xs_i * ys_j` are computed by an OLE in which party `i` sends and party `j`
+/// receives, so no party learns anything about another's inputs.
+pub fn mul_add_shared<F: PrimeField, M: OleModule<F>>(xs: &[M], ys: &[F]) -> Vec<M> {
+    assert_eq!(xs.len(), ys.len());
+    let n = Net::n_parties();
+    let me = Net::party_id();
+    let mut state = session::with_state(|s: &mut Option<OtState>| s.take()).unwrap_or_else(base_ots);
+    state.counter += 1;
+    let counter = state.counter;
+
+    let rng = &mut rand::thread_rng();
+    let bits = F::size_in_bits();
+    let m_used = ys.len() * bits;
+    let m = m_used + CHECK_OTS;
+    let col_len = m.div_ceil(8);
+    let msg_len = M::zero().serialized_size();
+
+    // Our choice bits, as receiver: the bits of each y, least significant first, then random
+    // ones for the consistency check.
+    let mut choices = vec![0u8; col_len];
+    rng.fill(&mut choices[m_used / 8..]);
+    choices[m_used / 8] &= !((1 << (m_used % 8)) - 1);
+    for (i, y) in ys.iter().enumerate() {
+        for (k, bit) in y.into_repr().to_bits_le().into_iter().take(bits).enumerate() {
+            if bit {
+                choices[(i * bits + k) / 8] |= 1 << ((i * bits + k) % 8);
+            }
+        }
+    }
+
+    // Extension, as receiver: send u^k = G(k0) ^ G(k1) ^ r, keep t^k = G(k0).
+    let mut t_rows: Vec<Vec<[u8; KAPPA_BYTES]>> = vec![Vec::new(); n];
+    let mut out = vec![Vec::new(); n];
+    for i in (0..n).filter(|i| *i != me) {
+        let mut rows = vec![[0u8; KAPPA_BYTES]; m];
+        for k in 0..KAPPA {
+            let [k0, k1] = &state.recv_seeds[i][k];
+            let t = prg(k0, counter, col_len);
+            let mut u = prg(k1, counter, col_len);
+            xor_into(&mut u, &t);
+            xor_into(&mut u, &choices);
+            out[i].extend_from_slice(&u);
+            for (l, row) in rows.iter_mut().enumerate() {
+                if get_bit(&t, l) {
+                    row[k / 8] |= 1 << (k % 8);
+                }
+            }
+        }
+        t_rows[i] = rows;
+    }
+    let us = exchange(out);
+
+    // Extension, as sender: q^k = G(k_delta) ^ delta_k * u^k, so that row q_l = t_l ^ r_l * delta.
+    let mut q_rows: Vec<Vec<[u8; KAPPA_BYTES]>> = vec![Vec::new(); n];
+    for j in (0..n).filter(|j| *j != me) {
+        let delta = &state.send_delta[j];
+        let mut rows = vec![[0u8; KAPPA_BYTES]; m];
+        for k in 0..KAPPA {
+            let mut q = prg(&state.send_seeds[j][k], counter, col_len);
+            if get_bit(delta, k) {
+                let u = us[j].get(k * col_len..(k + 1) * col_len);
+                xor_into(&mut q, u.unwrap_or_else(|| cheated(j)));
+            }
+            for (l, row) in rows.iter_mut().enumerate() {
+                if get_bit(&q, l) {
+                    row[k / 8] |= 1 << (k % 8);
+                }
+            }
+        }
+        q_rows[j] = rows;
+    }
+
+    // The consistency check: for random chi, the receiver shows that
+    // sum chi_l q_l = sum chi_l t_l + (sum chi_l r_l) delta, which only holds (except with
+    // probability 2^-SIGMA) if it used the same choice bits r for every column.
+    let mut coins = Net::coin_rng();
+    let chis: Vec<u128> = (0..m).map(|_| coins.gen()).collect();
+    let mut out = vec![Vec::new(); n];
+    for i in (0..n).filter(|i| *i != me) {
+        let r_sum: u128 = (0..m)
+            .filter(|l| get_bit(&choices, *l))
+            .fold(0, |acc, l| acc ^ chis[l]);
+        out[i].extend_from_slice(&r_sum.to_le_bytes());
+        out[i].extend_from_slice(&combine_rows(&t_rows[i], &chis).to_le_bytes());
+    }
+    let sums = exchange(out);
+    for j in (0..n).filter(|j| *j != me) {
+        let sum = |k: usize| {
+            let bytes = sums[j].get(k * KAPPA_BYTES..(k + 1) * KAPPA_BYTES);
+            u128::from_le_bytes(bytes.unwrap_or_else(|| cheated(j)).try_into().unwrap())
+        };
+        let delta = u128::from_le_bytes(state.send_delta[j]);
+        if combine_rows(&q_rows[j], &chis) != sum(1) ^ gf_mul(sum(0), delta) {
+            cheated(j);
+        }
+    }
+
+    // OT l carries Gilboa's messages (rho, rho + 2^k x).
+    let mut shares: Vec<M> = xs.iter().zip(ys).map(|(x, y)| x.ole_scale(y)).collect();
+    let mut out = vec![Vec::new(); n];
+    for j in (0..n).filter(|j| *j != me) {
+        let delta = &state.send_delta[j];
+        let rows = &q_rows[j];
+        for (i, x) in xs.iter().enumerate() {
+            let mut x_pow = *x;
+            for k in 0..bits {
+                let l = i * bits + k;
+                let rho = M::rand(rng);
+                shares[i] = shares[i] - rho;
+                let mut c0 = Vec::new();
+                rho.serialize(&mut c0).unwrap();
+                let mut c1 = Vec::new();
+                (rho + x_pow).serialize(&mut c1).unwrap();
+                xor_into(&mut c0, &pad(&rows[l], me, j, counter, l, msg_len));
+                let mut row1 = rows[l];
+                xor_into(&mut row1, delta);
+                xor_into(&mut c1, &pad(&row1, me, j, counter, l, msg_len));
+                out[j].extend_from_slice(&c0);
+                out[j].extend_from_slice(&c1);
+                x_pow = x_pow.ole_double();
+            }
+        }
+    }
+    let cts = exchange(out);
+
+    // Decrypt the messages we chose.
+    for i in (0..n).filter(|i| *i != me) {
+        for l in 0..m_used {
+            let b = get_bit(&choices, l) as usize;
+            let start = (2 * l + b) * msg_len;
+            let ct = cts[i].get(start..start + msg_len);
+            let mut msg = ct.unwrap_or_else(|| cheated(i)).to_vec();
+            xor_into(&mut msg, &pad(&t_rows[i][l], i, me, counter, l, msg_len));
+            let msg = M::deserialize(&msg[..]).unwrap_or_else(|_| cheated(i));
+            shares[l / bits] = shares[l / bits] + msg;
+        }
+    }
+    session::with_state(|s: &mut Option<OtState>| *s = Some(state));
+    shares
+}
@@ -1,7 +1,11 @@
 #![macro_use]
 use ark_std::{collections::BTreeMap, marker::PhantomData, rc::Rc};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use rand::Rng;
 
+use crate::channel::MpcSerNet;
+use mpc_net::{ActiveNet as Net, MpcNet};
+
 /// A type should implement [Reveal] if it represents the MPC abstraction of some base type.
 ///
 /// It is typically implemented for shared (or possibly shared) data.
@@ -17,8 +21,15 @@ pub trait Reveal: Sized {
 
     /// Reveal shared data, yielding plain data.
     fn reveal(self) -> Self::Base;
+    /// Reveal shared data to `party` only, which gets `Some` of the plain data. Every other party
+    /// gets `None`, and learns nothing.
+    fn reveal_to(self, party: usize) -> Option<Self::Base>;
     /// Construct a share of the sum of the `b` over all machines in the protocol.
     fn from_add_shared(b: Self::Base) -> Self;
+    /// Construct shares of the sums of the `bs` over all machines in the protocol.
+    fn from_add_shared_batch(bs: Vec<Self::Base>) -> Vec<Self> {
+        bs.into_iter().map(Self::from_add_shared).collect()
+    }
     /// Lift public data (same in all machines) into shared data.
     fn from_public(b: Self::Base) -> Self;
     /// If this share type has some underlying value of the base type, grabs it.
@@ -35,6 +46,16 @@ pub trait Reveal: Sized {
     fn king_share_batch<R: Rng>(bs: Vec<Self::Base>, rng: &mut R) -> Vec<Self> {
         bs.into_iter().map(|b| Self::king_share(b, rng)).collect()
     }
+    /// Have party `owner` share its `b`, without any other party learning it. Every other party
+    /// passes `None`.
+    fn input_from(owner: usize, b: Option<Self::Base>) -> Self {
+        Self::input_from_batch(owner, vec![b]).pop().unwrap()
+    }
+    /// Have party `owner` share its `bs`. Every other party passes as many `None`s.
+    ///
+    /// Only the values are hidden: lengths, and other parts of the shape of `bs`, are sent to
+    /// everyone.
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self>;
     /// Initialize the network protocol associated with this sharing system, if it is not
     /// initialized.
     fn init_protocol() {}
@@ -42,6 +63,32 @@ pub trait Reveal: Sized {
     fn deinit_protocol() {}
 }
 
+/// `owner`'s `x`, which every other party passes as `None`, sent to everyone.
+///
+/// This is for public parts of inputs, such as their lengths.
+pub fn from_owner<T: CanonicalSerialize + CanonicalDeserialize>(owner: usize, x: Option<T>) -> T {
+    Net::consistent_broadcast(&x)
+        .swap_remove(owner)
+        .expect("The input owner must know its inputs")
+}
+
+/// The `Some` values of the owner's `bs`, panicking on `None`, or `None` for anyone else.
+fn owner_values<T>(owner: usize, bs: Vec<Option<T>>) -> Option<Vec<T>> {
+    (Net::party_id() == owner).then(|| {
+        bs.into_iter()
+            .map(|b| b.expect("The input owner must know its inputs"))
+            .collect()
+    })
+}
+
+/// The owner's `xs`, each as `Some`, or `n` `None`s for anyone else.
+fn owner_inputs<T>(xs: Option<Vec<T>>, n: usize) -> Vec<Option<T>> {
+    match xs {
+        Some(xs) => xs.into_iter().map(Some).collect(),
+        None => (0..n).map(|_| None).collect(),
+    }
+}
+
 impl Reveal for usize {
     type Base = usize;
 
@@ -49,6 +96,10 @@ impl Reveal for usize {
         self
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        (Net::party_id() == party).then_some(self)
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         b
     }
@@ -64,6 +115,10 @@ impl Reveal for usize {
     fn king_share<R: Rng>(b: Self::Base, _rng: &mut R) -> Self {
         b
     }
+
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        from_owner(owner, owner_values(owner, bs))
+    }
 }
 
 impl<T: Reveal> Reveal for PhantomData<T> {
@@ -73,6 +128,10 @@ impl<T: Reveal> Reveal for PhantomData<T> {
         PhantomData::default()
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        (Net::party_id() == party).then(PhantomData::default)
+    }
+
     fn from_add_shared(_b: Self::Base) -> Self {
         PhantomData::default()
     }
@@ -86,6 +145,9 @@ impl<T: Reveal> Reveal for PhantomData<T> {
     fn king_share<R: Rng>(_b: Self::Base, _rng: &mut R) -> Self {
         PhantomData::default()
     }
+    fn input_from_batch(_owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        bs.iter().map(|_| PhantomData::default()).collect()
+    }
 
     fn init_protocol() {
         T::init_protocol()
@@ -101,6 +163,11 @@ impl<T: Reveal> Reveal for Vec<T> {
     fn reveal(self) -> Self::Base {
         self.into_iter().map(|x| x.reveal()).collect()
     }
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        // Reveal every element before checking whether we got any of them.
+        let xs: Vec<Option<T::Base>> = self.into_iter().map(|x| x.reveal_to(party)).collect();
+        (Net::party_id() == party).then(|| xs.into_iter().collect()).flatten()
+    }
     fn from_public(other: Self::Base) -> Self {
         other
             .into_iter()
@@ -122,6 +189,19 @@ impl<T: Reveal> Reveal for Vec<T> {
     fn king_share<R: Rng>(b: Self::Base, rng: &mut R) -> Self {
         T::king_share_batch(b, rng)
     }
+    /// The lengths of the vectors are sent to everyone, and their entries input in one batch.
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let bs = owner_values(owner, bs);
+        let lens: Vec<usize> = from_owner(
+            owner,
+            bs.as_ref().map(|bs| bs.iter().map(|b| b.len()).collect()),
+        );
+        let flat = owner_inputs(bs.map(|bs| bs.into_iter().flatten().collect()), lens.iter().sum());
+        let mut xs = T::input_from_batch(owner, flat).into_iter();
+        lens.into_iter()
+            .map(|len| xs.by_ref().take(len).collect())
+            .collect()
+    }
 
     fn init_protocol() {
         T::init_protocol()
@@ -140,6 +220,15 @@ where
     fn reveal(self) -> Self::Base {
         self.into_iter().map(|x| x.reveal()).collect()
     }
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        let kvs: Vec<_> = self
+            .into_iter()
+            .map(|(k, v)| (k.reveal_to(party), v.reveal_to(party)))
+            .collect();
+        (Net::party_id() == party)
+            .then(|| kvs.into_iter().map(|(k, v)| Some((k?, v?))).collect())
+            .flatten()
+    }
     fn from_public(other: Self::Base) -> Self {
         other.into_iter().map(|x| Reveal::from_public(x)).collect()
     }
@@ -155,6 +244,16 @@ where
             .map(|x| Reveal::unwrap_as_public(x))
             .collect()
     }
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let kvs = bs
+            .into_iter()
+            .map(|b| b.map(|m| m.into_iter().collect()))
+            .collect();
+        Vec::<(K, V)>::input_from_batch(owner, kvs)
+            .into_iter()
+            .map(|kvs| kvs.into_iter().collect())
+            .collect()
+    }
 
     fn init_protocol() {
         K::init_protocol();
@@ -172,6 +271,12 @@ impl<T: Reveal> Reveal for Option<T> {
     fn reveal(self) -> Self::Base {
         self.map(|x| x.reveal())
     }
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        match self {
+            Some(x) => x.reveal_to(party).map(Some),
+            None => (Net::party_id() == party).then_some(None),
+        }
+    }
     fn from_public(other: Self::Base) -> Self {
         other.map(|x| <T as Reveal>::from_public(x))
     }
@@ -182,6 +287,20 @@ impl<T: Reveal> Reveal for Option<T> {
         self
             .map(|x| Reveal::unwrap_as_public(x))
     }
+    /// Which of the values are `Some` is sent to everyone.
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let bs = owner_values(owner, bs);
+        let some: Vec<bool> = from_owner(
+            owner,
+            bs.as_ref().map(|bs| bs.iter().map(|b| b.is_some()).collect()),
+        );
+        let n = some.iter().filter(|s| **s).count();
+        let inner = owner_inputs(bs.map(|bs| bs.into_iter().flatten().collect()), n);
+        let mut xs = T::input_from_batch(owner, inner).into_iter();
+        some.into_iter()
+            .map(|s| if s { xs.next() } else { None })
+            .collect()
+    }
     fn init_protocol() {
         T::init_protocol()
     }
@@ -198,6 +317,9 @@ where
     fn reveal(self) -> Self::Base {
         Rc::new((*self).clone().reveal())
     }
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        (*self).clone().reveal_to(party).map(Rc::new)
+    }
     fn from_public(other: Self::Base) -> Self {
         Rc::new(Reveal::from_public((*other).clone()))
     }
@@ -207,6 +329,10 @@ where
     fn unwrap_as_public(self) -> Self::Base {
         Rc::new((*self).clone().unwrap_as_public())
     }
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let bs = bs.into_iter().map(|b| b.map(|b| (*b).clone())).collect();
+        T::input_from_batch(owner, bs).into_iter().map(Rc::new).collect()
+    }
     fn init_protocol() {
         T::init_protocol()
     }
@@ -220,6 +346,10 @@ impl<A: Reveal, B: Reveal> Reveal for (A, B) {
     fn reveal(self) -> Self::Base {
         (self.0.reveal(), self.1.reveal())
     }
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        let (a, b) = (self.0.reveal_to(party), self.1.reveal_to(party));
+        Some((a?, b?))
+    }
     fn from_public(other: Self::Base) -> Self {
         (
             <A as Reveal>::from_public(other.0),
@@ -235,6 +365,19 @@ impl<A: Reveal, B: Reveal> Reveal for (A, B) {
     fn unwrap_as_public(self) -> Self::Base {
         (self.0.unwrap_as_public(), self.1.unwrap_as_public())
     }
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let (a, b): (Vec<_>, Vec<_>) = bs
+            .into_iter()
+            .map(|b| match b {
+                Some((a, b)) => (Some(a), Some(b)),
+                None => (None, None),
+            })
+            .unzip();
+        A::input_from_batch(owner, a)
+            .into_iter()
+            .zip(B::input_from_batch(owner, b))
+            .collect()
+    }
     fn init_protocol() {
         A::init_protocol();
         B::init_protocol();
@@ -256,6 +399,19 @@ macro_rules! struct_reveal_impl {
                 )*
             }
         }
+        fn reveal_to(self, party: usize) -> Option<Self::Base> {
+            // Every field must be revealed, even by parties that get `None` for the first one.
+            $(
+                let $x = self.$x.reveal_to(party);
+            )*
+            Some({
+                $con {
+                    $(
+                        $x: $x?,
+                    )*
+                }
+            })
+        }
         fn from_public(other: Self::Base) -> Self {
             $con {
                 $(
@@ -277,6 +433,39 @@ macro_rules! struct_reveal_impl {
                 )*
             }
         }
+        fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+            // Each field is input in a batch of its own.
+            let n = bs.len();
+            $(
+                let mut $x = Vec::with_capacity(n);
+            )*
+            for b in bs {
+                match b {
+                    Some(b) => {
+                        $(
+                            $x.push(Some(b.$x));
+                        )*
+                    }
+                    None => {
+                        $(
+                            $x.push(None);
+                        )*
+                    }
+                }
+            }
+            $(
+                let mut $x = Reveal::input_from_batch(owner, $x).into_iter();
+            )*
+            (0..n)
+                .map(|_| {
+                    $con {
+                        $(
+                            $x: $x.next().unwrap(),
+                        )*
+                    }
+                })
+                .collect()
+        }
     }
 }
 
@@ -290,6 +479,19 @@ macro_rules! struct_reveal_simp_impl {
                 )*
             }
         }
+        fn reveal_to(self, party: usize) -> Option<Self::Base> {
+            // Every field must be revealed, even by parties that get `None` for the first one.
+            $(
+                let $x = self.$x.reveal_to(party);
+            )*
+            Some({
+                $con {
+                    $(
+                        $x: $x?,
+                    )*
+                }
+            })
+        }
         fn from_public(other: Self::Base) -> Self {
             $con {
                 $(
@@ -311,6 +513,39 @@ macro_rules! struct_reveal_simp_impl {
                 )*
             }
         }
+        fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+            // Each field is input in a batch of its own.
+            let n = bs.len();
+            $(
+                let mut $x = Vec::with_capacity(n);
+            )*
+            for b in bs {
+                match b {
+                    Some(b) => {
+                        $(
+                            $x.push(Some(b.$x));
+                        )*
+                    }
+                    None => {
+                        $(
+                            $x.push(None);
+                        )*
+                    }
+                }
+            }
+            $(
+                let mut $x = Reveal::input_from_batch(owner, $x).into_iter();
+            )*
+            (0..n)
+                .map(|_| {
+                    $con {
+                        $(
+                            $x: $x.next().unwrap(),
+                        )*
+                    }
+                })
+                .collect()
+        }
     }
 }
 
@@ -19,13 +19,13 @@ use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{ActiveNet as Net, MpcNet};
 use crate::channel::MpcSerNet;
 
 use super::field::{
     DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare, SparsePolynomial,
 };
-use super::group::GroupShare;
+use super::group::{no_flags, GroupShare};
 use super::pairing::{AffProjShare, PairingShare};
 use super::BeaverSource;
 use crate::msm::*;
@@ -85,6 +85,9 @@ impl<F: Field> Reveal for AdditiveFieldShare<F> {
     fn reveal(self) -> F {
         Net::broadcast(&self.val).into_iter().sum()
     }
+    fn reveal_to(self, party: usize) -> Option<F> {
+        Net::send_to(party, &self.val).map(|vals| vals.into_iter().sum())
+    }
     fn from_public(f: F) -> Self {
         Self {
             val: if Net::am_king() { f } else { F::zero() },
@@ -115,6 +118,20 @@ impl<F: Field> Reveal for AdditiveFieldShare<F> {
         rs.push(final_shares);
         Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
     }
+    /// The owner's share is its input, and everyone else's is zero: that hides the input from the
+    /// other parties, and needs no communication.
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        let am_owner = Net::party_id() == owner;
+        fs.into_iter()
+            .map(|f| {
+                Self::from_add_shared(if am_owner {
+                    f.expect("The input owner must know its inputs")
+                } else {
+                    F::zero()
+                })
+            })
+            .collect()
+    }
 }
 
 impl<F: Field> FieldShare<F> for AdditiveFieldShare<F> {
@@ -178,6 +195,9 @@ impl<G: Group, M> Reveal for AdditiveGroupShare<G, M> {
     fn reveal(self) -> G {
         Net::broadcast(&self.val).into_iter().sum()
     }
+    fn reveal_to(self, party: usize) -> Option<G> {
+        Net::send_to(party, &self.val).map(|vals| vals.into_iter().sum())
+    }
     fn from_public(f: G) -> Self {
         Self {
             val: if Net::am_king() { f } else { G::zero() },
@@ -212,6 +232,19 @@ impl<G: Group, M> Reveal for AdditiveGroupShare<G, M> {
         rs.push(final_shares);
         Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
     }
+    /// As for field shares, the owner's share is its input.
+    fn input_from_batch(owner: usize, fs: Vec<Option<G>>) -> Vec<Self> {
+        let am_owner = Net::party_id() == owner;
+        fs.into_iter()
+            .map(|f| {
+                Self::from_add_shared(if am_owner {
+                    f.expect("The input owner must know its inputs")
+                } else {
+                    G::zero()
+                })
+            })
+            .collect()
+    }
 }
 
 impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for AdditiveGroupShare<G, M> {
@@ -272,46 +305,49 @@ macro_rules! impl_basics {
             }
         }
         impl<T: $bound> ToBytes for $share<T> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.val.write(writer)
             }
         }
         impl<T: $bound> FromBytes for $share<T> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Ok(Self { val: T::read(reader)? })
             }
         }
         impl<T: $bound> CanonicalSerialize for $share<T> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.val.serialize(writer)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.val.serialized_size()
             }
         }
         impl<T: $bound> CanonicalSerializeWithFlags for $share<T> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                self.val.serialize_with_flags(writer, flags)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                self.val.serialized_size_with_flags::<F>()
             }
         }
         impl<T: $bound> CanonicalDeserialize for $share<T> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    val: T::deserialize(reader)?,
+                })
             }
         }
         impl<T: $bound> CanonicalDeserializeWithFlags for $share<T> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let (val, flags) = T::deserialize_with_flags(reader)?;
+                Ok((Self { val }, flags))
             }
         }
         impl<T: $bound> UniformRand for $share<T> {
@@ -334,46 +370,54 @@ macro_rules! impl_basics_2_param {
             }
         }
         impl<T: $bound, M> ToBytes for $share<T, M> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                self.val.write(writer)
             }
         }
         impl<T: $bound, M> FromBytes for $share<T, M> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    val: T::read(reader)?,
+                    _phants: PhantomData,
+                })
             }
         }
         impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+                self.val.serialize(writer)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.val.serialized_size()
             }
         }
         impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
+                writer: W,
                 _flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                no_flags::<F>()?;
+                self.serialize(writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                self.serialized_size()
             }
         }
         impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    val: T::deserialize(reader)?,
+                    _phants: PhantomData,
+                })
             }
         }
         impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                no_flags::<F>()?;
+                Ok((Self::deserialize(reader)?, F::default()))
             }
         }
         impl<T: $bound, M> UniformRand for $share<T, M> {
@@ -414,6 +458,9 @@ impl<F: Field> Reveal for MulFieldShare<F> {
     fn reveal(self) -> F {
         Net::broadcast(&self.val).into_iter().product()
     }
+    fn reveal_to(self, party: usize) -> Option<F> {
+        Net::send_to(party, &self.val).map(|vals| vals.into_iter().product())
+    }
     fn from_public(f: F) -> Self {
         Self {
             val: if Net::am_king() { f } else { F::one() },
@@ -425,6 +472,19 @@ impl<F: Field> Reveal for MulFieldShare<F> {
     fn unwrap_as_public(self) -> F {
         self.val
     }
+    /// The owner's factor is its input, and everyone else's is one.
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        let am_owner = Net::party_id() == owner;
+        fs.into_iter()
+            .map(|f| {
+                Self::from_add_shared(if am_owner {
+                    f.expect("The input owner must know its inputs")
+                } else {
+                    F::one()
+                })
+            })
+            .collect()
+    }
 }
 
 impl<F: Field> FieldShare<F> for MulFieldShare<F> {
@@ -531,8 +591,10 @@ macro_rules! groups_share {
                 }
                 a
             }
-            fn add_pub_proj_sh_aff(_a: &E::$proj, _o: Self::AffineShare) -> Self::ProjectiveShare {
-                unimplemented!()
+            fn add_pub_proj_sh_aff(a: &E::$proj, o: Self::AffineShare) -> Self::ProjectiveShare {
+                let mut o = Self::sh_aff_to_proj(o);
+                o.shift(a);
+                o
             }
         }
     };
@@ -566,4 +628,20 @@ impl<E: PairingEngine> PairingShare<E> for AdditivePairingShare<E> {
         AdditiveGroupShare<E::G2Projective, crate::msm::ProjectiveMsm<E::G2Projective>>;
     type G1 = AdditiveG1Share<E>;
     type G2 = AdditiveG2Share<E>;
+
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> MulFieldShare<E::Fqk> {
+        MulFieldShare { val: f(s.val) }
+    }
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> MulFieldShare<E::Fqk> {
+        MulFieldShare { val: f(s.val) }
+    }
+    fn fqk_map(s: MulFieldShare<E::Fqk>, f: impl Fn(E::Fqk) -> E::Fqk) -> MulFieldShare<E::Fqk> {
+        MulFieldShare { val: f(s.val) }
+    }
 }
@@ -0,0 +1,352 @@
+//! Preprocessing for shared multiplication: Beaver triples and inverse pairs.
+//!
+//! Each (field, sharing scheme) pair has a pool of triples and inverse pairs. The pool is
+//! filled by a [TripleGenerator], either ahead of time with [preprocess] (the offline phase) or
+//! on demand, when online multiplications have used everything up.
+//!
+//! Unless told otherwise, pools use an [OtTripleGenerator]. Letting the king deal triples is much
+//! faster, but has to be asked for, with [trust_dealer] or [set_triple_generator].
+use ark_ec::group::Group;
+use ark_ff::prelude::*;
+
+use std::marker::PhantomData;
+
+use derivative::Derivative;
+
+use super::field::FieldShare;
+use super::group::GroupShare;
+use super::BeaverSource;
+use crate::channel::MpcSerNet;
+use crate::ot;
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+
+/// Default number of triples (and inverse pairs) generated when the pool runs dry.
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Produces fresh Beaver triples and inverse pairs for field shares of type `S`.
+///
+/// All parties must call into their generators in the same order, with the same arguments.
+pub trait TripleGenerator<F: Field, S: FieldShare<F>>: Send {
+    /// `n` triples `(a, b, c)` with `c = a * b`.
+    fn triples(&mut self, n: usize) -> Vec<(S, S, S)>;
+    /// `n` pairs `(r, s)` with `r * s = 1`.
+    ///
+    /// By default, this consumes a triple `(a, b, c)` per pair and opens `c`. Since `a` and `b`
+    /// are independent and uniform, `c` reveals nothing about `a`, and `(a, b / c)` is an inverse
+    /// pair.
+    fn inv_pairs(&mut self, n: usize) -> Vec<(S, S)> {
+        let triples = self.triples(n);
+        let cs = S::batch_open(triples.iter().map(|t| t.2));
+        triples
+            .into_iter()
+            .zip(cs)
+            .map(|((a, mut b, _), c)| {
+                let c_inv = c.inverse().expect("zero product in inverse pair generation");
+                b.scale(&c_inv);
+                (a, b)
+            })
+            .collect()
+    }
+}
+
+/// The king samples triples and deals them out.
+///
+/// Secure only if the king is trusted: it knows every triple. See [trust_dealer].
+#[derive(Default, Clone, Copy, Debug)]
+pub struct DealerTripleGenerator;
+
+impl<F: Field, S: FieldShare<F>> TripleGenerator<F, S> for DealerTripleGenerator {
+    fn triples(&mut self, n: usize) -> Vec<(S, S, S)> {
+        let rng = &mut rand::thread_rng();
+        let (a, b): (Vec<F>, Vec<F>) = if Net::am_king() {
+            (0..n).map(|_| (F::rand(rng), F::rand(rng))).unzip()
+        } else {
+            (vec![F::zero(); n], vec![F::zero(); n])
+        };
+        let c: Vec<F> = a.iter().zip(&b).map(|(a, b)| *a * b).collect();
+        let a = S::king_share_batch(a, rng);
+        let b = S::king_share_batch(b, rng);
+        let c = S::king_share_batch(c, rng);
+        a.into_iter()
+            .zip(b)
+            .zip(c)
+            .map(|((a, b), c)| (a, b, c))
+            .collect()
+    }
+
+    fn inv_pairs(&mut self, n: usize) -> Vec<(S, S)> {
+        let rng = &mut rand::thread_rng();
+        let (r, s): (Vec<F>, Vec<F>) = if Net::am_king() {
+            (0..n)
+                .map(|_| loop {
+                    let r = F::rand(rng);
+                    if let Some(s) = r.inverse() {
+                        break (r, s);
+                    }
+                })
+                .unzip()
+        } else {
+            (vec![F::zero(); n], vec![F::zero(); n])
+        };
+        let r = S::king_share_batch(r, rng);
+        let s = S::king_share_batch(s, rng);
+        r.into_iter().zip(s).collect()
+    }
+}
+
+/// Dishonest-majority triple generation from oblivious transfer.
+///
+/// Each party samples its own additive shares of `a` and `b`; shares of `c = a * b` are computed
+/// with pairwise OLEs (see [crate::ot]). No party (or coalition short of all parties) learns the
+/// triples.
+///
+/// The OLEs let a cheating party add errors to `c`, so every triple is checked by sacrificing
+/// another with the same `b`, as in [MASCOT](https://ia.cr/2016/505): for a random public `t`,
+/// the parties open `rho = t * a - a'` and check that `t * c - c' - rho * b` opens to zero. With
+/// share types that check their openings (e.g. SPDZ), this is secure against malicious parties
+/// (up to abort).
+///
+/// In an extension field, `b` is multiplied in one coordinate (over the prime field) at a time,
+/// so a triple costs as many OLEs as the extension degree.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct OtTripleGenerator;
+
+impl<F: Field, S: FieldShare<F>> TripleGenerator<F, S> for OtTripleGenerator {
+    fn triples(&mut self, n: usize) -> Vec<(S, S, S)> {
+        let rng = &mut rand::thread_rng();
+        let d = F::extension_degree() as usize;
+        let basis: Vec<F> = (0..d)
+            .map(|i| {
+                let mut e = vec![F::BasePrimeField::zero(); d];
+                e[i] = F::BasePrimeField::one();
+                F::from_base_prime_field_elems(&e).unwrap()
+            })
+            .collect();
+        // The triples, then the ones sacrificed for them.
+        let a: Vec<F> = (0..2 * n).map(|_| F::rand(rng)).collect();
+        let b_coords: Vec<F::BasePrimeField> =
+            (0..n * d).map(|_| F::BasePrimeField::rand(rng)).collect();
+        let b: Vec<F> = b_coords
+            .chunks(d)
+            .map(|c| F::from_base_prime_field_elems(c).unwrap())
+            .collect();
+        // a * b is the sum over coordinates i of (a * e_i) * b_i.
+        let a_basis: Vec<F> = a
+            .iter()
+            .flat_map(|a| basis.iter().map(move |e| *a * e))
+            .collect();
+        let b_twice: Vec<F::BasePrimeField> = b_coords.iter().chain(&b_coords).cloned().collect();
+        let c: Vec<F> = ot::mul_add_shared(&a_basis, &b_twice)
+            .chunks(d)
+            .map(|c| c.iter().sum())
+            .collect();
+        let mut shares = S::from_add_shared_batch(a.into_iter().chain(b).chain(c).collect());
+        let c = shares.split_off(3 * n);
+        let b = shares.split_off(2 * n);
+        let a = shares;
+
+        let mut coins = Net::coin_rng();
+        let ts: Vec<F> = (0..n).map(|_| F::rand(&mut coins)).collect();
+        let rhos = S::batch_open((0..n).map(|i| {
+            let mut rho = a[i];
+            rho.scale(&ts[i]).sub(&a[n + i]);
+            rho
+        }));
+        let sigmas = S::batch_open((0..n).map(|i| {
+            let mut sigma = c[i];
+            let mut rho_b = b[i];
+            rho_b.scale(&rhos[i]);
+            sigma.scale(&ts[i]).sub(&c[n + i]).sub(&rho_b);
+            sigma
+        }));
+        if !sigmas.iter().all(|s| s.is_zero()) {
+            Net::abort();
+            panic!("a triple failed its sacrifice check");
+        }
+        a.into_iter()
+            .zip(b)
+            .zip(c)
+            .take(n)
+            .map(|((a, b), c)| (a, b, c))
+            .collect()
+    }
+}
+
+struct Pool<F: Field, S: FieldShare<F>> {
+    triples: Vec<(S, S, S)>,
+    inv_pairs: Vec<(S, S)>,
+    /// `None` until the pool is first filled, or a generator is set.
+    generator: Option<Box<dyn TripleGenerator<F, S>>>,
+    batch_size: usize,
+}
+
+impl<F: Field, S: FieldShare<F>> Default for Pool<F, S> {
+    fn default() -> Self {
+        Self {
+            triples: Vec::new(),
+            inv_pairs: Vec::new(),
+            generator: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+impl<F: Field, S: FieldShare<F>> Pool<F, S> {
+    fn generator(&mut self) -> &mut dyn TripleGenerator<F, S> {
+        let generator = self.generator.get_or_insert_with(|| {
+            if session::with_state(|t: &mut TrustDealer| t.0) {
+                Box::new(DealerTripleGenerator)
+            } else {
+                Box::new(OtTripleGenerator)
+            }
+        });
+        &mut **generator
+    }
+}
+
+/// Whether pools without a generator of their own use a [DealerTripleGenerator].
+#[derive(Default)]
+struct TrustDealer(bool);
+
+/// Let the king deal the preprocessing for every `(F, S)` whose pool has not been given a
+/// generator with [set_triple_generator], or (with `false`) stop doing so.
+///
+/// This is only secure if the king is trusted, and only affects pools that have not been filled
+/// yet.
+pub fn trust_dealer(trust: bool) {
+    session::with_state(|t: &mut TrustDealer| t.0 = trust)
+}
+
+/// Run `f` on the current session's pool for `(F, S)`.
+///
+/// Generators talk to the network, and may themselves need preprocessing for other types; both
+/// are fine while `f` runs.
+fn with_pool<F: Field, S: FieldShare<F>, O>(f: impl FnOnce(&mut Pool<F, S>) -> O) -> O {
+    session::with_state(f)
+}
+
+/// Use `generator` for all future preprocessing for `(F, S)`.
+pub fn set_triple_generator<F: Field, S: FieldShare<F>, G: TripleGenerator<F, S> + 'static>(
+    generator: G,
+) {
+    with_pool::<F, S, _>(|p| p.generator = Some(Box::new(generator)))
+}
+
+/// Generate at least `n` triples or pairs at a time when the pool for `(F, S)` runs dry.
+pub fn set_batch_size<F: Field, S: FieldShare<F>>(n: usize) {
+    assert!(n > 0);
+    with_pool::<F, S, _>(|p| p.batch_size = n)
+}
+
+/// The offline phase: add `n_triples` triples and `n_inv_pairs` inverse pairs to the pool for
+/// `(F, S)`.
+pub fn preprocess<F: Field, S: FieldShare<F>>(n_triples: usize, n_inv_pairs: usize) {
+    with_pool::<F, S, _>(|p| {
+        let ts = p.generator().triples(n_triples);
+        p.triples.extend(ts);
+        let ps = p.generator().inv_pairs(n_inv_pairs);
+        p.inv_pairs.extend(ps);
+    })
+}
+
+/// Drop all unused preprocessing for `(F, S)`.
+pub fn clear<F: Field, S: FieldShare<F>>() {
+    with_pool::<F, S, _>(|p| {
+        p.triples.clear();
+        p.inv_pairs.clear();
+    })
+}
+
+/// The number of triples and inverse pairs left in the pool for `(F, S)`.
+pub fn pool_size<F: Field, S: FieldShare<F>>() -> (usize, usize) {
+    with_pool::<F, S, _>(|p| (p.triples.len(), p.inv_pairs.len()))
+}
+
+fn take_triples<F: Field, S: FieldShare<F>>(n: usize) -> Vec<(S, S, S)> {
+    with_pool::<F, S, _>(|p| {
+        if p.triples.len() < n {
+            let m = std::cmp::max(p.batch_size, n - p.triples.len());
+            let ts = p.generator().triples(m);
+            p.triples.extend(ts);
+        }
+        let rest = p.triples.len() - n;
+        p.triples.split_off(rest)
+    })
+}
+
+fn take_inv_pairs<F: Field, S: FieldShare<F>>(n: usize) -> Vec<(S, S)> {
+    with_pool::<F, S, _>(|p| {
+        if p.inv_pairs.len() < n {
+            let m = std::cmp::max(p.batch_size, n - p.inv_pairs.len());
+            let ps = p.generator().inv_pairs(m);
+            p.inv_pairs.extend(ps);
+        }
+        let rest = p.inv_pairs.len() - n;
+        p.inv_pairs.split_off(rest)
+    })
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+/// Draws triples and inverse pairs from the pool for `(F, S)`.
+pub struct PooledFieldTripleSource<F, S> {
+    _scalar: PhantomData<F>,
+    _share: PhantomData<S>,
+}
+
+impl<F: Field, S: FieldShare<F>> BeaverSource<S, S, S> for PooledFieldTripleSource<F, S> {
+    #[inline]
+    fn triple(&mut self) -> (S, S, S) {
+        take_triples::<F, S>(1).pop().unwrap()
+    }
+    fn triples(&mut self, n: usize) -> (Vec<S>, Vec<S>, Vec<S>) {
+        let mut xs = Vec::with_capacity(n);
+        let mut ys = Vec::with_capacity(n);
+        let mut zs = Vec::with_capacity(n);
+        for (x, y, z) in take_triples::<F, S>(n) {
+            xs.push(x);
+            ys.push(y);
+            zs.push(z);
+        }
+        (xs, ys, zs)
+    }
+    #[inline]
+    fn inv_pair(&mut self) -> (S, S) {
+        take_inv_pairs::<F, S>(1).pop().unwrap()
+    }
+    fn inv_pairs(&mut self, n: usize) -> (Vec<S>, Vec<S>) {
+        take_inv_pairs::<F, S>(n).into_iter().unzip()
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+/// Draws triples `(a * H, b, c * H)` for group shares `S`, from triples `(a, b, c)` in the pool
+/// for their field shares, where `H` is a public base.
+///
+/// Since `a` is uniform, so is `a * H`, and nobody learns more about the group triple than about
+/// the field one.
+pub struct PooledGroupTripleSource<G, S> {
+    _group: PhantomData<G>,
+    _share: PhantomData<S>,
+}
+
+/// The public base of [PooledGroupTripleSource].
+fn group_triple_base<G: Group>() -> G {
+    super::group::hash_to_group(b"beaver group triple base")
+}
+
+impl<G: Group, S: GroupShare<G>> BeaverSource<S, S::FieldShare, S>
+    for PooledGroupTripleSource<G, S>
+{
+    #[inline]
+    fn triple(&mut self) -> (S, S::FieldShare, S) {
+        let (a, b, c) = take_triples::<G::ScalarField, S::FieldShare>(1).pop().unwrap();
+        let base = group_triple_base::<G>();
+        (S::scale_pub_group(base, &a), b, S::scale_pub_group(base, &c))
+    }
+    #[inline]
+    fn inv_pair(&mut self) -> (S::FieldShare, S::FieldShare) {
+        take_inv_pairs::<G::ScalarField, S::FieldShare>(1).pop().unwrap()
+    }
+}
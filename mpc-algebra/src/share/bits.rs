@@ -0,0 +1,343 @@
+//! Bit decomposition, comparison, zero-testing and square-testing of shared prime field elements.
+//!
+//! The outputs are shared field elements that are 0 or 1. r1cs-std `Boolean`s hold plain `bool`s,
+//! so they cannot carry these: a circuit that needs the bits of a shared witness allocates them as
+//! field variables, and constrains them to be bits itself.
+//!
+//! These work for any [FieldShare]: they only add, multiply and open shares. Shared random bits
+//! come from squaring: if `r` is a uniform non-zero shared value, opening `r^2` reveals nothing
+//! about the sign of `r / sqrt(r^2)`, so `(r / sqrt(r^2) + 1) / 2` is a uniform shared bit. Bits
+//! can be made ahead of time with [preprocess_bits].
+use ark_ff::{BitIteratorLE, Field, FpParameters, PrimeField, SquareRootField};
+
+use std::marker::PhantomData;
+
+use mpc_net::session;
+
+use super::beaver::PooledFieldTripleSource;
+use super::field::FieldShare;
+
+/// Unused random shared bits.
+struct BitPool<F, S>(Vec<S>, PhantomData<F>);
+
+impl<F, S> Default for BitPool<F, S> {
+    fn default() -> Self {
+        Self(Vec::new(), PhantomData)
+    }
+}
+
+fn batch_mul<F: Field, S: FieldShare<F>>(xs: Vec<S>, ys: Vec<S>) -> Vec<S> {
+    if xs.is_empty() {
+        return xs;
+    }
+    S::batch_mul(xs, ys, &mut PooledFieldTripleSource::<F, S>::default())
+}
+
+/// `x`, or `1 - x` if `flip` is set.
+fn flip<F: Field, S: FieldShare<F>>(mut x: S, flip: bool) -> S {
+    if flip {
+        x.neg().shift(&F::one());
+    }
+    x
+}
+
+/// The low `m` bits of `x`, least significant first.
+fn public_bits<F: PrimeField>(x: F, m: usize) -> Vec<bool> {
+    BitIteratorLE::new(x.into_repr()).take(m).collect()
+}
+
+/// `2^m - p`, in `m` bits.
+fn neg_modulus_bits<F: PrimeField>(m: usize) -> Vec<bool> {
+    // The two's complement of p: flip every bit, then add one.
+    let flipped: Vec<bool> = BitIteratorLE::new(F::Params::MODULUS)
+        .take(m)
+        .map(|b| !b)
+        .collect();
+    let mut one = vec![false; m];
+    one[0] = true;
+    add_bits(&flipped, &one)
+}
+
+/// `a + b mod 2^m`, for `m`-bit `a` and `b`.
+fn add_bits(a: &[bool], b: &[bool]) -> Vec<bool> {
+    let mut carry = false;
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let sum = a ^ b ^ carry;
+            carry = (a & b) | (carry & (a ^ b));
+            sum
+        })
+        .collect()
+}
+
+fn gen_bits<F: PrimeField + SquareRootField, S: FieldShare<F>>(n: usize) -> Vec<S> {
+    let rng = &mut rand::thread_rng();
+    let two_inv = F::from(2u64).inverse().unwrap();
+    let mut bits = Vec::with_capacity(n);
+    while bits.len() < n {
+        let rs = S::batch_rand(n - bits.len(), rng);
+        let squares = S::batch_open(batch_mul(rs.clone(), rs.clone()));
+        for (mut r, square) in rs.into_iter().zip(squares) {
+            // r is zero with probability 1/p, in which case we draw another.
+            if let Some(root) = square.sqrt().filter(|root| !root.is_zero()) {
+                r.scale(&(root.inverse().unwrap() * two_inv)).shift(&two_inv);
+                bits.push(r);
+            }
+        }
+    }
+    bits
+}
+
+/// Set aside `n` random shared bits for later decompositions and comparisons.
+pub fn preprocess_bits<F: PrimeField + SquareRootField, S: FieldShare<F>>(n: usize) {
+    let bits = gen_bits::<F, S>(n);
+    session::with_state(|p: &mut BitPool<F, S>| p.0.extend(bits));
+}
+
+/// `n` random shared bits, taken from the preprocessed ones if there are enough.
+pub fn rand_bits<F: PrimeField + SquareRootField, S: FieldShare<F>>(n: usize) -> Vec<S> {
+    let mut bits = session::with_state(|p: &mut BitPool<F, S>| {
+        let rest = p.0.len().saturating_sub(n);
+        p.0.split_off(rest)
+    });
+    if bits.len() < n {
+        bits.extend(gen_bits::<F, S>(n - bits.len()));
+    }
+    bits
+}
+
+/// Add public `m`-bit integers `ys` to shared ones `xs`, bit by bit.
+///
+/// Returns the low `m` bits of each sum, and its carry out. Takes `m - 1` rounds of
+/// multiplication.
+fn add_public<F: Field, S: FieldShare<F>>(xs: &[&[S]], ys: &[Vec<bool>]) -> Vec<(Vec<S>, S)> {
+    let k = xs.len();
+    let m = ys.first().map_or(0, |y| y.len());
+    let mut sums = vec![Vec::with_capacity(m); k];
+    let mut carries = vec![S::from_public(F::zero()); k];
+    for i in 0..m {
+        let x_carries = if i == 0 {
+            carries.clone()
+        } else {
+            batch_mul(xs.iter().map(|x| x[i]).collect(), carries.clone())
+        };
+        for j in 0..k {
+            let (x, c, xc) = (xs[j][i], carries[j], x_carries[j]);
+            // x xor c = x + c - 2xc
+            let mut x_xor_c = x;
+            x_xor_c.add(&c).sub(&xc).sub(&xc);
+            sums[j].push(flip(x_xor_c, ys[j][i]));
+            // The carry is x and c if the public bit is 0, and x or c (= x + c - xc) if it is 1.
+            carries[j] = if ys[j][i] {
+                let mut x_or_c = x;
+                x_or_c.add(&c).sub(&xc);
+                x_or_c
+            } else {
+                xc
+            };
+        }
+    }
+    sums.into_iter().zip(carries).collect()
+}
+
+/// `n` uniformly random shared values below `p`, each with its shared bits.
+fn rand_solved<F: PrimeField + SquareRootField, S: FieldShare<F>>(n: usize) -> Vec<(Vec<S>, S)> {
+    let m = F::size_in_bits();
+    let neg_p = neg_modulus_bits::<F>(m);
+    let mut out = Vec::with_capacity(n);
+    while out.len() < n {
+        let missing = n - out.len();
+        let bits = rand_bits::<F, S>(missing * m);
+        let candidates: Vec<&[S]> = bits.chunks(m).collect();
+        // r < p exactly when r + (2^m - p) does not carry out. Opening that is safe, since we
+        // only keep the candidates below p.
+        let carries: Vec<S> = add_public(&candidates, &vec![neg_p.clone(); missing])
+            .into_iter()
+            .map(|(_, carry)| carry)
+            .collect();
+        for (r_bits, too_big) in candidates.into_iter().zip(S::batch_open(carries)) {
+            if too_big.is_zero() {
+                let mut r = S::from_public(F::zero());
+                for b in r_bits.iter().rev() {
+                    r.scale(&F::from(2u64)).add(b);
+                }
+                out.push((r_bits.to_vec(), r));
+            }
+        }
+    }
+    out
+}
+
+/// The bits of shared values, least significant first.
+///
+/// Each decomposition masks its input with a random value of known bits, opens it, and adds the
+/// bits back, reducing mod `p`. This takes about `log2(p)` rounds, but they are shared by the
+/// whole batch.
+pub fn batch_bits<F: PrimeField + SquareRootField, S: FieldShare<F>>(xs: Vec<S>) -> Vec<Vec<S>> {
+    let m = F::size_in_bits();
+    let neg_p = neg_modulus_bits::<F>(m);
+    let rs = rand_solved::<F, S>(xs.len());
+    // r is uniform, so x - r reveals nothing.
+    let cs = S::batch_open(xs.into_iter().zip(&rs).map(|(mut x, (_, r))| {
+        x.sub(r);
+        x
+    }));
+    // x = r + c (as integers) if that is below p, and otherwise r + c - p, which is
+    // r + c + (2^m - p) mod 2^m. The second sum carries out exactly when r + c >= p.
+    let mut lanes: Vec<&[S]> = Vec::with_capacity(2 * rs.len());
+    let mut addends = Vec::with_capacity(2 * rs.len());
+    for ((r_bits, _), c) in rs.iter().zip(cs) {
+        let c = public_bits(c, m);
+        lanes.push(r_bits);
+        lanes.push(r_bits);
+        addends.push(add_bits(&c, &neg_p));
+        addends.push(c);
+    }
+    let sums = add_public(&lanes, &addends);
+    let (mut lows, mut diffs, mut wraps) = (Vec::new(), Vec::new(), Vec::new());
+    for pair in sums.chunks(2) {
+        let ((wrapped, wrap), (low, _)) = (&pair[0], &pair[1]);
+        for (w, l) in wrapped.iter().zip(low) {
+            let mut d = *w;
+            d.sub(l);
+            diffs.push(d);
+            wraps.push(*wrap);
+        }
+        lows.extend(low.iter().cloned());
+    }
+    // bit = low + wrap * (wrapped - low)
+    batch_mul(diffs, wraps)
+        .into_iter()
+        .zip(lows)
+        .map(|(mut d, l)| {
+            d.add(&l);
+            d
+        })
+        .collect::<Vec<_>>()
+        .chunks(m)
+        .map(|c| c.to_vec())
+        .collect()
+}
+
+/// `[x < y]` for shared `x` and `y`, comparing them as integers in `[0, p)`.
+pub fn batch_less_than<F: PrimeField + SquareRootField, S: FieldShare<F>>(
+    xs: Vec<S>,
+    ys: Vec<S>,
+) -> Vec<S> {
+    let k = xs.len();
+    let m = F::size_in_bits();
+    let mut bits = batch_bits::<F, S>(xs.into_iter().chain(ys).collect());
+    let y_bits: Vec<S> = bits.split_off(k).concat();
+    let x_bits: Vec<S> = bits.concat();
+    // x_i xor y_i
+    let diffs: Vec<S> = batch_mul(x_bits.clone(), y_bits.clone())
+        .into_iter()
+        .zip(x_bits.iter().zip(&y_bits))
+        .map(|(xy, (x, y))| {
+            let mut d = *x;
+            d.add(y).sub(&xy).sub(&xy);
+            d
+        })
+        .collect();
+    // From the least significant bit up: where the bits differ, x < y so far exactly when y's
+    // bit is set. lt <- lt + diff * (y - lt)
+    let mut lts = vec![S::from_public(F::zero()); k];
+    for i in 0..m {
+        let steps: Vec<S> = (0..k)
+            .map(|j| {
+                let mut s = y_bits[j * m + i];
+                s.sub(&lts[j]);
+                s
+            })
+            .collect();
+        let ds = (0..k).map(|j| diffs[j * m + i]).collect();
+        for (lt, step) in lts.iter_mut().zip(batch_mul(ds, steps)) {
+            lt.add(&step);
+        }
+    }
+    lts
+}
+
+/// `[x = 0]` for shared `x`.
+///
+/// `x` is masked by a random `r` of known bits, and `c = x + r` is opened. Then `x` is zero
+/// exactly when `r` and `c` have the same bits, which is the product of `m = log2(p)` shared bits.
+/// That takes `log2(m)` rounds, after the `r` are drawn (which does not depend on `x`).
+pub fn batch_is_zero<F: PrimeField + SquareRootField, S: FieldShare<F>>(xs: Vec<S>) -> Vec<S> {
+    let m = F::size_in_bits();
+    let rs = rand_solved::<F, S>(xs.len());
+    // r is uniform, so x + r reveals nothing.
+    let cs = S::batch_open(xs.into_iter().zip(&rs).map(|(mut x, (_, r))| {
+        x.add(r);
+        x
+    }));
+    // [r_i = c_i] is r_i if c_i is set, and 1 - r_i otherwise.
+    let mut eqs: Vec<Vec<S>> = rs
+        .iter()
+        .zip(cs)
+        .map(|((r_bits, _), c)| {
+            r_bits
+                .iter()
+                .zip(public_bits(c, m))
+                .map(|(r, c)| flip(*r, !c))
+                .collect()
+        })
+        .collect();
+    // Multiply each lane's bits together, halving them each round.
+    while eqs.first().map_or(false, |e| e.len() > 1) {
+        let (mut ls, mut rs) = (Vec::new(), Vec::new());
+        for e in &eqs {
+            for pair in e.chunks_exact(2) {
+                ls.push(pair[0]);
+                rs.push(pair[1]);
+            }
+        }
+        let mut prods = batch_mul(ls, rs).into_iter();
+        for e in &mut eqs {
+            let odd = e.len() % 2 == 1;
+            let last = e.last().cloned();
+            *e = prods.by_ref().take(e.len() / 2).collect();
+            if odd {
+                e.extend(last);
+            }
+        }
+    }
+    eqs.into_iter().map(|mut e| e.pop().unwrap()).collect()
+}
+
+/// `[x is a square]` for shared `x`, where zero counts as a square.
+///
+/// `x` is masked by a random `s` that is a square exactly when a random shared bit `b` is set, and
+/// then `x * s` is opened; whether that is a square, together with `b`, gives whether `x` is. Zero
+/// is first replaced by one, so that the opened value does not reveal it.
+pub fn batch_is_square<F: PrimeField + SquareRootField, S: FieldShare<F>>(xs: Vec<S>) -> Vec<S> {
+    let k = xs.len();
+    let ys: Vec<S> = xs
+        .clone()
+        .into_iter()
+        .zip(batch_is_zero::<F, S>(xs))
+        .map(|(mut x, z)| {
+            x.add(&z);
+            x
+        })
+        .collect();
+    let bs = rand_bits::<F, S>(k);
+    let rs = S::batch_rand(k, &mut rand::thread_rng());
+    // s = r^2 if b is set, and r^2 * g otherwise, where the generator g is not a square.
+    let g = F::multiplicative_generator();
+    let factors = bs
+        .iter()
+        .map(|b| {
+            let mut f = *b;
+            f.scale(&(F::one() - g)).shift(&g);
+            f
+        })
+        .collect();
+    let ss = batch_mul(batch_mul(rs.clone(), rs), factors);
+    let masked = S::batch_open(batch_mul(ys, ss));
+    bs.into_iter()
+        .zip(masked)
+        .map(|(b, m)| flip(b, m.legendre().is_qnr()))
+        .collect()
+}
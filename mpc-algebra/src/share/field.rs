@@ -1,11 +1,13 @@
 use ark_ff::bytes::{FromBytes, ToBytes};
 use ark_ff::prelude::*;
+use ark_ff::FftField;
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
     CanonicalSerializeWithFlags,
 };
 //use ark_poly::univariate::{DensePolynomial,DenseOrSparsePolynomial};
 use core::ops::*;
+use rand::Rng;
 use std::cmp::Ord;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
@@ -45,6 +47,11 @@ pub trait FieldShare<F: Field>:
         selfs.into_iter().map(|s| s.open()).collect()
     }
 
+    /// Shares of `n` uniformly random values, which no party knows.
+    fn batch_rand<R: Rng>(n: usize, rng: &mut R) -> Vec<Self> {
+        (0..n).map(|_| Self::rand(rng)).collect()
+    }
+
     fn add(&mut self, other: &Self) -> &mut Self;
 
     fn sub(&mut self, other: &Self) -> &mut Self {
@@ -127,14 +134,15 @@ pub trait FieldShare<F: Field>:
     }
 
     fn inv<S: BeaverSource<Self, Self, Self>>(self, source: &mut S) -> Self {
-        let (x, mut y) = source.inv_pair();
+        let (mut x, _) = source.inv_pair();
         let xa = x.mul(self, source).open().inverse().unwrap();
-        *y.scale(&xa)
+        *x.scale(&xa)
     }
 
     fn batch_inv<S: BeaverSource<Self, Self, Self>>(xs: Vec<Self>, source: &mut S) -> Vec<Self> {
-        let (bs, cs) = source.inv_pairs(xs.len());
-        cs.into_iter()
+        let (bs, _) = source.inv_pairs(xs.len());
+        bs.clone()
+            .into_iter()
             .zip(
                 Self::batch_open(Self::batch_mul(xs, bs, source))
                     .into_iter()
@@ -181,6 +189,16 @@ pub trait FieldShare<F: Field>:
         mms_inv
     }
 
+    /// The FFT of `shares` over the coset `offset * H`, where `H` is the subgroup of order
+    /// `shares.len()`, or their inverse FFT from that coset if `inverse` is set. `None` unless the
+    /// sharing has a better way to do this than share by share.
+    fn coset_fft(_shares: &[Self], _offset: F, _inverse: bool) -> Option<Vec<Self>>
+    where
+        F: FftField,
+    {
+        None
+    }
+
     fn univariate_div_qr<'a>(
         _num: DenseOrSparsePolynomial<Self>,
         _den: DenseOrSparsePolynomial<F>,
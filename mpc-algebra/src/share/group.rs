@@ -3,10 +3,14 @@ use ark_ff::bytes::{FromBytes, ToBytes};
 use ark_ff::prelude::*;
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
-    CanonicalSerializeWithFlags,
+    CanonicalSerializeWithFlags, Flags, SerializationError,
 };
 use ark_std::{end_timer, start_timer};
 use core::ops::*;
+use digest::Digest;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sha2::Sha256;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
@@ -14,6 +18,32 @@ use super::field::FieldShare;
 use super::BeaverSource;
 use crate::Reveal;
 
+/// A public group element that nobody knows the discrete logarithm of, derived from `label`.
+///
+/// The label is hashed into the seed of the group's own sampler, which, for the curves here,
+/// tries random x-coordinates until one is on the curve. That is hashing to the curve by
+/// try-and-increment: the point is fixed by the label, but it is not a known multiple of the
+/// generator.
+pub fn hash_to_group<G: Group>(label: &[u8]) -> G {
+    let seed: [u8; 32] = Sha256::new()
+        .chain(b"mpc-algebra hash to group ")
+        .chain(std::any::type_name::<G>())
+        .chain(label)
+        .finalize()
+        .into();
+    G::rand(&mut StdRng::from_seed(seed))
+}
+
+/// Group elements have no spare bits to carry flags in, so group shares can only be
+/// (de)serialized with empty flags.
+pub fn no_flags<F: Flags>() -> Result<(), SerializationError> {
+    if F::BIT_SIZE == 0 {
+        Ok(())
+    } else {
+        Err(SerializationError::UnexpectedFlags)
+    }
+}
+
 /// Secret sharing scheme which support affine functions of secrets.
 pub trait GroupShare<G: Group>:
     Clone
@@ -0,0 +1,246 @@
+//! Robust reconstruction of GSZ shares.
+//!
+//! The shares of a degree-`d` polynomial form a Reed-Solomon codeword. With `n >= d + 2t + 1`
+//! parties, up to `t` bad shares can be corrected, and whoever sent them named. Field shares are
+//! decoded with Berlekamp-Welch. That needs linear algebra on the shares themselves, which we
+//! cannot do on group shares without discrete logs. Instead, group shares are decoded by finding
+//! the bad parties: we drop the shares of a few parties at a time, those already caught first,
+//! until the rest agree.
+//!
+//! Bad shares that cannot be corrected abort the computation.
+use ark_ec::group::Group;
+use ark_ff::{Field, Zero};
+use ark_poly::univariate::{DenseOrSparsePolynomial, DensePolynomial};
+use ark_poly::{Polynomial, UVPolynomial};
+use log::warn;
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+
+use super::{interpolate_at_zero, lagrange, points, t};
+
+/// Why shares could not be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The shares do not lie on one polynomial, and there are too few parties to correct them.
+    Inconsistent { degree: usize },
+    /// There are more bad shares than can be corrected.
+    TooManyErrors { degree: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Inconsistent { degree } => {
+                write!(f, "Shares do not lie on a degree-{} polynomial", degree)
+            }
+            DecodeError::TooManyErrors { degree } => write!(
+                f,
+                "Too many bad shares of a degree-{} polynomial to correct",
+                degree
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Abort the computation, and return `e`.
+fn fail<T>(e: DecodeError) -> Result<T, DecodeError> {
+    Net::abort();
+    Err(e)
+}
+
+/// Parties caught sending bad shares.
+#[derive(Default)]
+struct Cheaters(BTreeSet<usize>);
+
+/// Every party caught sending a bad share so far in this session.
+pub fn cheaters() -> Vec<usize> {
+    session::with_state(|c: &mut Cheaters| c.0.iter().cloned().collect())
+}
+
+fn report(cheaters: Vec<usize>) {
+    if !cheaters.is_empty() {
+        warn!("Parties {:?} sent bad shares", cheaters);
+        session::with_state(|c: &mut Cheaters| c.0.extend(cheaters));
+    }
+}
+
+/// Whether we can correct `t` errors in shares of a degree-`d` polynomial.
+fn can_correct(n: usize, d: usize) -> bool {
+    t() > 0 && n > d + 2 * t()
+}
+
+/// Solve `a x = b`, if it has any solution.
+fn solve<F: Field>(mut a: Vec<Vec<F>>, mut b: Vec<F>) -> Option<Vec<F>> {
+    let (rows, cols) = (a.len(), a.first().map_or(0, |r| r.len()));
+    let mut pivots = Vec::new();
+    let mut row = 0;
+    for col in 0..cols {
+        let p = match (row..rows).find(|r| !a[*r][col].is_zero()) {
+            Some(p) => p,
+            None => continue,
+        };
+        a.swap(row, p);
+        b.swap(row, p);
+        let inv = a[row][col].inverse().unwrap();
+        for x in &mut a[row][col..] {
+            *x *= inv;
+        }
+        b[row] *= inv;
+        let (pivot, pivot_b) = (a[row].clone(), b[row]);
+        for r in (0..rows).filter(|r| *r != row) {
+            let factor = a[r][col];
+            if !factor.is_zero() {
+                for (x, p) in a[r][col..].iter_mut().zip(&pivot[col..]) {
+                    *x -= *p * factor;
+                }
+                b[r] -= pivot_b * factor;
+            }
+        }
+        pivots.push(col);
+        row += 1;
+    }
+    if b[row..].iter().any(|x| !x.is_zero()) {
+        return None;
+    }
+    // Free variables are zero.
+    let mut x = vec![F::zero(); cols];
+    for (r, c) in pivots.into_iter().enumerate() {
+        x[c] = b[r];
+    }
+    Some(x)
+}
+
+/// Berlekamp-Welch: the degree-`d` polynomial through all but at most `e` of the points
+/// `(xs[i], ys[i])`, if there is one.
+fn berlekamp_welch<F: Field>(
+    xs: &[F],
+    ys: &[F],
+    d: usize,
+    e: usize,
+) -> Option<DensePolynomial<F>> {
+    // Find a monic E of degree e and a Q of degree e + d with Q(x_i) = y_i E(x_i) for all i.
+    // E vanishes on the errors, and Q = PE.
+    let (a, b): (Vec<Vec<F>>, Vec<F>) = xs
+        .iter()
+        .zip(ys)
+        .map(|(x, y)| {
+            let powers: Vec<F> = std::iter::successors(Some(F::one()), |p| Some(*p * x))
+                .take(e + d + 1)
+                .collect();
+            let row: Vec<F> = powers
+                .iter()
+                .cloned()
+                .chain(powers[..e].iter().map(|p| -*p * y))
+                .collect();
+            (row, *y * powers[e])
+        })
+        .unzip();
+    let sol = solve(a, b)?;
+    let q = DensePolynomial::from_coefficients_slice(&sol[..e + d + 1]);
+    let mut e_coeffs = sol[e + d + 1..].to_vec();
+    e_coeffs.push(F::one());
+    let e_poly = DensePolynomial::from_coefficients_vec(e_coeffs);
+    let (p, r) = DenseOrSparsePolynomial::from(&q).divide_with_q_and_r(&(&e_poly).into())?;
+    let errors = xs.iter().zip(ys).filter(|(x, y)| p.evaluate(x) != **y).count();
+    (r.is_zero() && p.degree() <= d && errors <= e).then_some(p)
+}
+
+/// The value at zero of the degree-`d` polynomial whose evaluations at the share points are
+/// `shares`, correcting bad shares if there are enough parties. Aborts if it cannot.
+pub fn decode_field<F: Field>(shares: Vec<F>, d: usize) -> Result<F, DecodeError> {
+    let dot = |ls: &[F], ss: &[F]| ls.iter().zip(ss).map(|(l, s)| *l * s).sum();
+    if let Some(x) = interpolate_at_zero(&shares, d, dot) {
+        return Ok(x);
+    }
+    if !can_correct(shares.len(), d) {
+        return fail(DecodeError::Inconsistent { degree: d });
+    }
+    let xs: Vec<F> = points();
+    let p = match berlekamp_welch(&xs, &shares, d, t()) {
+        Some(p) => p,
+        None => return fail(DecodeError::TooManyErrors { degree: d }),
+    };
+    report(
+        (0..shares.len())
+            .filter(|i| p.evaluate(&xs[*i]) != shares[*i])
+            .collect(),
+    );
+    Ok(p.evaluate(&F::zero()))
+}
+
+fn interpolate_with<G: Group>(ls: &[G::ScalarField], ys: &[G]) -> G {
+    ls.iter().zip(ys).map(|(l, y)| y.mul(l)).sum()
+}
+
+fn interpolate_at<G: Group>(xs: &[G::ScalarField], ys: &[G], z: G::ScalarField) -> G {
+    interpolate_with(&lagrange(xs, z), ys)
+}
+
+/// Every set of `k` of the parties `0..n`.
+fn subsets(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    (k - 1..n)
+        .flat_map(|last| {
+            subsets(last, k - 1).into_iter().map(move |mut s| {
+                s.push(last);
+                s
+            })
+        })
+        .collect()
+}
+
+/// If the shares of everyone but `dropped` lie on one degree-`d` polynomial, `d + 1` points on it.
+fn agree_without<G: Group>(
+    shares: &[G],
+    d: usize,
+    xs: &[G::ScalarField],
+    dropped: &[usize],
+) -> Option<(Vec<G::ScalarField>, Vec<G>)> {
+    let good: Vec<usize> = (0..shares.len()).filter(|i| !dropped.contains(i)).collect();
+    let (basis, rest) = good.split_at(d + 1);
+    let bx: Vec<G::ScalarField> = basis.iter().map(|i| xs[*i]).collect();
+    let by: Vec<G> = basis.iter().map(|i| shares[*i]).collect();
+    rest.iter()
+        .all(|i| interpolate_at(&bx, &by, xs[*i]) == shares[*i])
+        .then_some((bx, by))
+}
+
+/// The value at zero of the degree-`d` polynomial (over `G`) whose evaluations at the share points
+/// are `shares`, correcting bad shares if there are enough parties. Aborts if it cannot.
+///
+/// We look for at most `t` parties whose shares, once dropped, leave shares that agree: first
+/// those already caught, then every set of parties, smallest first. More than `d + t` shares
+/// remain, so if they agree, at least `d + 1` of them are honest, and they give the right
+/// polynomial. That takes up to `C(n, t)` tries, but only when there are bad shares.
+pub fn decode_group<G: Group>(shares: &[G], d: usize) -> Result<G, DecodeError> {
+    if let Some(g) = interpolate_at_zero(shares, d, interpolate_with) {
+        return Ok(g);
+    }
+    let n = shares.len();
+    if !can_correct(n, d) {
+        return fail(DecodeError::Inconsistent { degree: d });
+    }
+    let xs: Vec<G::ScalarField> = points();
+    let known = cheaters();
+    let candidates = std::iter::once(known)
+        .filter(|k| !k.is_empty() && k.len() <= t())
+        .chain((1..=t()).flat_map(|k| subsets(n, k)));
+    for dropped in candidates {
+        if let Some((bx, by)) = agree_without(shares, d, &xs, &dropped) {
+            let bad = dropped
+                .into_iter()
+                .filter(|i| interpolate_at(&bx, &by, xs[*i]) != shares[*i])
+                .collect();
+            report(bad);
+            return Ok(interpolate_at(&bx, &by, G::ScalarField::zero()));
+        }
+    }
+    fail(DecodeError::TooManyErrors { degree: d })
+}
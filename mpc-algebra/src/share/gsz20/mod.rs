@@ -24,19 +24,15 @@ use ark_ff::{
     prelude::*,
     FftField,
 };
-use ark_poly::{
-    domain::{EvaluationDomain, MixedRadixEvaluationDomain},
-    Polynomial, UVPolynomial,
-};
+use ark_poly::UVPolynomial;
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
     CanonicalSerializeWithFlags, Flags, SerializationError,
 };
 use ark_std::{end_timer, start_timer};
-use mpc_net::{MpcMultiNet as Net, MpcNet};
+use mpc_net::{session, ActiveNet as Net, MpcNet};
 
-use once_cell::sync::OnceCell;
-use std::any::{Any, TypeId};
+use std::any::Any;
 use std::borrow::Cow;
 use std::cmp::Ord;
 use std::collections::HashMap;
@@ -44,10 +40,8 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
-use std::sync::Mutex;
 
 use derivative::Derivative;
-use lazy_static::lazy_static;
 use log::debug;
 use rand::Rng;
 
@@ -59,58 +53,121 @@ use crate::msm::Msm;
 use crate::share::pairing::{AffProjShare, PairingShare};
 use crate::Reveal;
 
-lazy_static! {
-    static ref TYPE_LISTS: Mutex<HashMap<TypeId, Vec<Box<dyn Any + Send>>>> =
-        Mutex::new(HashMap::new());
-    static ref SHARE_DOMAIN: OnceCell<Box<dyn Any + Send + Sync>> = OnceCell::new();
+/// Values of type `T` set aside in the current session.
+struct TypeList<T>(Vec<T>);
+
+impl<T> Default for TypeList<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
 }
 
 fn take_types<T: Any + Send>() -> Vec<T> {
-    let mut lists = TYPE_LISTS.lock().unwrap();
-    let type_id = TypeId::of::<T>();
-    let list = lists.remove(&type_id).unwrap_or_else(|| Vec::new());
-    list.into_iter()
-        .map(|x| *x.downcast::<T>().unwrap())
-        .collect()
+    session::with_state(|l: &mut TypeList<T>| std::mem::take(&mut l.0))
 }
 fn add_type<T: Any + Send>(t: T) {
-    let mut lists = TYPE_LISTS.lock().unwrap();
-    let type_id = TypeId::of::<T>();
-    use std::collections::hash_map::Entry;
-    match lists.entry(type_id) {
-        Entry::Occupied(o) => o.into_mut().push(Box::new(t)),
-        Entry::Vacant(v) => {
-            v.insert(vec![Box::new(t)]);
-        }
-    };
+    session::with_state(|l: &mut TypeList<T>| l.0.push(t))
 }
 fn add_types<T: Any + Send>(ts: Vec<T>) {
-    for t in ts {
-        add_type(t);
-    }
+    session::with_state(|l: &mut TypeList<T>| l.0.extend(ts))
 }
 
-/// Malicious degree
+/// The threshold set for the current session, if any.
+#[derive(Default)]
+struct Threshold(Option<usize>);
+
+/// Malicious degree: the most parties that may be corrupted. Unless set with [set_threshold], it
+/// is `(n - 1) / 2`.
 pub fn t() -> usize {
-    (Net::n_parties() - 1) / 2
+    session::with_state(|t: &mut Threshold| t.0).unwrap_or((Net::n_parties() - 1) / 2)
+}
+
+/// Share with threshold `t` for the rest of the session. Call it before making any shares.
+///
+/// Products of t-shares are 2t-shares, which the `n` parties can only open if `t < n / 2`.
+pub fn set_threshold(t: usize) {
+    let n = Net::n_parties();
+    assert!(2 * t < n, "Threshold {} is too high for {} parties", t, n);
+    session::with_state(|s: &mut Threshold| s.0 = Some(t));
+}
+
+/// The evaluation point of party `j`'s shares: `j + 1`.
+pub fn point<F: Field>(j: usize) -> F {
+    F::from((j + 1) as u64)
+}
+
+/// The evaluation points of the parties' shares, in party order.
+pub fn points<F: Field>() -> Vec<F> {
+    (0..Net::n_parties()).map(point).collect()
 }
 
-pub fn domain<F: FftField>() -> &'static MixedRadixEvaluationDomain<F> {
-    SHARE_DOMAIN.get_or_init(|| {
-        let d = MixedRadixEvaluationDomain::<F>::new(Net::n_parties()).unwrap();
-        assert_eq!(d.size(), Net::n_parties(),
-            "Attempted to build an evaluation domain of size {}, but could only get one of size {}.\nThis domain is needed in order to support Shamir shares for this many parties", Net::n_parties(), d.size(), );
-        Box::new(d)
-    }).downcast_ref().unwrap()
+/// The polynomial with coefficients `coeffs` (constant first), at `x`.
+fn evaluate<F: Field>(coeffs: &[F], x: F) -> F {
+    coeffs.iter().rev().fold(F::zero(), |acc, c| acc * x + c)
 }
 
+/// The Lagrange coefficients for evaluating a polynomial at `z` from its values at `xs`.
+fn lagrange<F: Field>(xs: &[F], z: F) -> Vec<F> {
+    xs.iter()
+        .enumerate()
+        .map(|(k, xk)| {
+            xs.iter()
+                .enumerate()
+                .filter(|(m, _)| *m != k)
+                .fold(F::one(), |acc, (_, xm)| {
+                    acc * (z - xm) * (*xk - xm).inverse().unwrap()
+                })
+        })
+        .collect()
+}
+
+/// Lagrange coefficients over the first `d + 1` points, by `d`: for the value at zero, and at
+/// each later point.
+struct Interpolators<F>(HashMap<usize, (Vec<F>, Vec<Vec<F>>)>);
+
+impl<F> Default for Interpolators<F> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// The value at zero of the degree-`d` polynomial through the parties' `shares`, or `None` if they
+/// do not all lie on one.
+///
+/// Shares may live in any `F`-module: `combine(ls, ss)` is the linear combination of the `ss` with
+/// coefficients `ls`.
+fn interpolate_at_zero<F: Field, T: PartialEq>(
+    shares: &[T],
+    d: usize,
+    combine: impl Fn(&[F], &[T]) -> T,
+) -> Option<T> {
+    let d = d.min(shares.len() - 1);
+    let (at_zero, at_rest) = session::with_state(|i: &mut Interpolators<F>| {
+        i.0.entry(d)
+            .or_insert_with(|| {
+                let xs = points::<F>();
+                let at_rest = xs[d + 1..].iter().map(|x| lagrange(&xs[..=d], *x)).collect();
+                (lagrange(&xs[..=d], F::zero()), at_rest)
+            })
+            .clone()
+    });
+    let basis = &shares[..=d];
+    at_rest
+        .iter()
+        .zip(&shares[d + 1..])
+        .all(|(ls, s)| combine(ls, basis) == *s)
+        .then(|| combine(&at_zero, basis))
+}
+
+pub mod decode;
+pub mod vss;
+
 pub mod field {
     use super::*;
 
     /// A Goyal-Song '20 share.
     ///
-    /// This is an evaluation of a polynomial at the party-number's position in the multiplicative
-    /// subgroup of order equal to the number of parties.
+    /// This is an evaluation of a polynomial at the party's [point].
     #[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
     pub struct GszFieldShare<F: Field> {
         pub val: F,
@@ -128,46 +185,59 @@ pub mod field {
         }
     }
     impl<T: FftField> ToBytes for GszFieldShare<T> {
-        fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-            unimplemented!("write")
+        fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            (self.degree as u64).write(&mut writer)?;
+            self.val.write(writer)
         }
     }
     impl<T: FftField> FromBytes for GszFieldShare<T> {
-        fn read<R: Read>(_reader: R) -> io::Result<Self> {
-            unimplemented!("read")
+        fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+            let degree = u64::read(&mut reader)? as usize;
+            Ok(Self {
+                val: T::read(reader)?,
+                degree,
+            })
         }
     }
     impl<T: FftField> CanonicalSerialize for GszFieldShare<T> {
-        fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-            unimplemented!("serialize")
+        fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+            self.degree.serialize(&mut writer)?;
+            self.val.serialize(writer)
         }
         fn serialized_size(&self) -> usize {
-            unimplemented!("serialized_size")
+            self.degree.serialized_size() + self.val.serialized_size()
         }
     }
     impl<T: FftField> CanonicalSerializeWithFlags for GszFieldShare<T> {
         fn serialize_with_flags<W: Write, F: Flags>(
             &self,
-            _writer: W,
-            _flags: F,
+            mut writer: W,
+            flags: F,
         ) -> Result<(), SerializationError> {
-            unimplemented!("serialize_with_flags")
+            self.degree.serialize(&mut writer)?;
+            self.val.serialize_with_flags(writer, flags)
         }
 
         fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-            unimplemented!("serialized_size_with_flags")
+            self.degree.serialized_size() + self.val.serialized_size_with_flags::<F>()
         }
     }
     impl<T: FftField> CanonicalDeserialize for GszFieldShare<T> {
-        fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-            unimplemented!("deserialize")
+        fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+            let degree = usize::deserialize(&mut reader)?;
+            Ok(Self {
+                val: T::deserialize(reader)?,
+                degree,
+            })
         }
     }
     impl<T: FftField> CanonicalDeserializeWithFlags for GszFieldShare<T> {
         fn deserialize_with_flags<R: Read, F: Flags>(
-            _reader: R,
+            mut reader: R,
         ) -> Result<(Self, F), SerializationError> {
-            unimplemented!("deserialize_with_flags")
+            let degree = usize::deserialize(&mut reader)?;
+            let (val, flags) = T::deserialize_with_flags(reader)?;
+            Ok((Self { val, degree }, flags))
         }
     }
     impl<T: FftField> UniformRand for GszFieldShare<T> {
@@ -182,33 +252,36 @@ pub mod field {
         fn reveal(self) -> F {
             open(&self)
         }
+        fn reveal_to(self, party: usize) -> Option<F> {
+            open_to(party, &self)
+        }
         fn from_public(f: F) -> Self {
             Self { val: f, degree: 0 }
         }
-        fn from_add_shared(_f: F) -> Self {
-            unimplemented!()
+        fn from_add_shared(f: F) -> Self {
+            from_add_shared(vec![f]).pop().unwrap()
+        }
+        fn from_add_shared_batch(fs: Vec<F>) -> Vec<Self> {
+            from_add_shared(fs)
         }
         fn unwrap_as_public(self) -> F {
             self.val
         }
-        fn king_share<R: Rng>(f: Self::Base, _rng: &mut R) -> Self {
-            let fs = vec![f; Net::n_parties()];
-            let king_f = Net::recv_from_king(if Net::am_king() { Some(fs) } else { None });
-            Self {
-                val: king_f,
-                degree: t(),
+        fn king_share<R: Rng>(f: Self::Base, rng: &mut R) -> Self {
+            if super::vss::dealer::<F>().is_some() {
+                return Self::king_share_batch(vec![f], rng).pop().unwrap();
             }
+            Self::input_from(Net::king(), Net::am_king().then_some(f))
         }
         fn king_share_batch<R: Rng>(f: Vec<Self::Base>, _rng: &mut R) -> Vec<Self> {
-            let fs = vec![f; Net::n_parties()];
-            let king_fs = Net::recv_from_king(if Net::am_king() { Some(fs) } else { None });
-            king_fs
-                .into_iter()
-                .map(|king_f| Self {
-                    val: king_f,
-                    degree: t(),
-                })
-                .collect()
+            if let Some(deal) = super::vss::dealer::<F>() {
+                return deal(f).unwrap_or_else(|e| panic!("{}", e));
+            }
+            let am_king = Net::am_king();
+            input_from(Net::king(), f.into_iter().map(|f| am_king.then_some(f)).collect())
+        }
+        fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+            input_from(owner, fs)
         }
     }
     impl<F: FftField> GszFieldShare<F> {
@@ -260,6 +333,7 @@ pub mod field {
     impl<F: FftField> FieldShare<F> for GszFieldShare<F> {
         fn add(&mut self, other: &Self) -> &mut Self {
             self.val += other.val;
+            self.degree = self.degree.max(other.degree);
             self
         }
 
@@ -275,6 +349,7 @@ pub mod field {
 
         fn sub(&mut self, other: &Self) -> &mut Self {
             self.val -= other.val;
+            self.degree = self.degree.max(other.degree);
             self
         }
 
@@ -283,11 +358,15 @@ pub mod field {
             self
         }
 
+        fn batch_rand<R: Rng>(n: usize, _rng: &mut R) -> Vec<Self> {
+            batch_rand(n)
+        }
+
         fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
             let (self_vec, mut deg_vec): (Vec<F>, Vec<usize>) =
                 selfs.into_iter().map(|s| (s.val, s.degree)).unzip();
             let timer = start_timer!(|| format!("Batch open: {}", self_vec.len()));
-            let mut all_vals = Net::broadcast(&self_vec);
+            let mut all_vals = Net::consistent_broadcast(&self_vec);
             let mut out = Vec::new();
             while all_vals[0].len() > 0 {
                 let vals: Vec<F> = all_vals.iter_mut().map(|v| v.pop().unwrap()).collect();
@@ -326,7 +405,7 @@ pub mod field {
             xs: Vec<Self>,
             source: &mut S,
         ) -> Vec<Self> {
-            let rs: Vec<_> = (0..xs.len()).map(|_| rand::<F>()).collect();
+            let rs = batch_rand::<F>(xs.len());
             let self_rs = Self::batch_mul(xs, rs.clone(), source);
             let mut self_rs = Self::batch_open(self_rs);
             for x in &mut self_rs {
@@ -345,7 +424,7 @@ pub mod field {
             src: &mut S,
         ) -> Vec<Self> {
             let n = x.len();
-            let m: Vec<Self> = (0..(n + 1)).map(|_| rand::<F>()).collect();
+            let m = batch_rand::<F>(n + 1);
             let m_inv = Self::batch_inv(m.clone(), src);
             let mx = Self::batch_mul(m[..n].iter().cloned().collect(), x, src);
             let mxm = Self::batch_mul(mx, m_inv[1..].iter().cloned().collect(), src);
@@ -373,40 +452,179 @@ pub mod field {
             num.divide_with_q_and_r(&den)
                 .map(|(q, r)| (Self::d_poly_unshare(q, t()), Self::d_poly_unshare(r, t())))
         }
+
+        fn coset_fft(shares: &[Self], offset: F, inverse: bool) -> Option<Vec<Self>> {
+            super::packed::fft_shares(shares, offset, inverse)
+        }
     }
 
-    /// Yields a t-share of a random r.
+    /// Number of random sharings generated at a time when a pool runs dry.
+    pub const RAND_BATCH_SIZE: usize = 1024;
+
+    /// Unused t-shares of random values.
+    struct RandPool<F: Field>(Vec<GszFieldShare<F>>);
+    /// Unused (t, 2t)-share pairs of random values.
+    struct DoubleRandPool<F: Field>(Vec<(GszFieldShare<F>, GszFieldShare<F>)>);
+
+    /// Shamir-share `secret` with a random polynomial of degree `degree`.
+    ///
+    /// Returns the share of each party.
+    pub(super) fn share_poly<F: FftField>(secret: F, degree: usize) -> Vec<F> {
+        let rng = &mut rand::thread_rng();
+        let mut coeffs = vec![secret];
+        coeffs.extend((0..degree).map(|_| F::rand(rng)));
+        points().into_iter().map(|x| evaluate(&coeffs, x)).collect()
+    }
+
+    /// The public Vandermonde matrix used to extract randomness: `n - t` rows, `n` columns, with
+    /// entry `(k, i)` equal to `(i + 1)^k`.
+    ///
+    /// Every `(n - t) x (n - t)` submatrix is invertible, so the outputs are uniform as long as
+    /// the `n - t` honest parties' inputs are.
+    fn vandermonde<F: Field>() -> Vec<Vec<F>> {
+        let n = Net::n_parties();
+        let mut row = vec![F::one(); n];
+        (0..n - t())
+            .map(|_| {
+                let this_row = row.clone();
+                for (i, r) in row.iter_mut().enumerate() {
+                    *r *= F::from((i + 1) as u64);
+                }
+                this_row
+            })
+            .collect()
+    }
+
+    /// Generate `count` random values, each shared at every degree in `degrees`.
+    ///
+    /// Every party deals sharings of fresh random values; the Vandermonde matrix turns each
+    /// group of `n` dealt sharings into `n - t` random ones. So this takes one round, in which
+    /// each party sends `ceil(count / (n - t)) * degrees.len()` field elements to each other party.
+    ///
+    /// `out[k][d]` is the share of the `k`th value at degree `degrees[d]`.
+    fn extract_rand<F: FftField>(count: usize, degrees: &[usize]) -> Vec<Vec<GszFieldShare<F>>> {
+        let rng = &mut rand::thread_rng();
+        extract_rand_with(count, degrees, || {
+            let secret = F::rand(rng);
+            degrees.iter().map(|d| share_poly(secret, *d)).collect()
+        })
+    }
+
+    /// Like [extract_rand], but each party deals with `deal`, which returns every party's share
+    /// of each of its sharings, one sharing per entry of `degrees`.
     ///
-    /// Stubbed b/c it can be pre-processed.
+    /// The extraction is linear, so any linear relation between the dealt sharings (the same
+    /// secret at two degrees, one secret the sum of others, ...) holds between the outputs too.
+    pub(super) fn extract_rand_with<F: FftField>(
+        count: usize,
+        degrees: &[usize],
+        mut deal: impl FnMut() -> Vec<Vec<F>>,
+    ) -> Vec<Vec<GszFieldShare<F>>> {
+        let n = Net::n_parties();
+        let per_round = n - t();
+        let rounds = count.div_ceil(per_round);
+        let mut to_send: Vec<Vec<F>> = vec![Vec::with_capacity(rounds * degrees.len()); n];
+        for _ in 0..rounds {
+            let dealt = deal();
+            debug_assert_eq!(dealt.len(), degrees.len());
+            for shares in dealt {
+                for (out, share) in to_send.iter_mut().zip(shares) {
+                    out.push(share);
+                }
+            }
+        }
+        let received = Net::all_to_all(&to_send);
+        let matrix = vandermonde::<F>();
+        let mut out = Vec::with_capacity(rounds * per_round);
+        for r in 0..rounds {
+            for row in &matrix {
+                out.push(
+                    degrees
+                        .iter()
+                        .enumerate()
+                        .map(|(j, d)| {
+                            let val = received
+                                .iter()
+                                .zip(row)
+                                .map(|(dealt, m)| dealt[r * degrees.len() + j] * m)
+                                .sum();
+                            GszFieldShare { val, degree: *d }
+                        })
+                        .collect(),
+                );
+            }
+        }
+        out.truncate(count);
+        out
+    }
+
+    /// The offline phase: add `n_rands` random t-shares and `n_double_rands` random
+    /// (t, 2t)-share pairs to their pools.
+    pub fn preprocess<F: FftField>(n_rands: usize, n_double_rands: usize) {
+        let mut rands = take_types::<RandPool<F>>().pop().map(|p| p.0).unwrap_or_default();
+        let mut doubles = take_types::<DoubleRandPool<F>>()
+            .pop()
+            .map(|p| p.0)
+            .unwrap_or_default();
+        if n_rands > 0 {
+            rands.extend(extract_rand::<F>(n_rands, &[t()]).into_iter().map(|s| s[0]));
+        }
+        if n_double_rands > 0 {
+            doubles.extend(
+                extract_rand::<F>(n_double_rands, &[t(), 2 * t()])
+                    .into_iter()
+                    .map(|s| (s[0], s[1])),
+            );
+        }
+        add_type(RandPool(rands));
+        add_type(DoubleRandPool(doubles));
+    }
+
+    /// Yields a t-share of a random r.
     ///
     /// Protocol 3.
-    pub fn rand<F: Field>() -> GszFieldShare<F> {
-        GszFieldShare {
-            val: F::one(),
-            degree: t(),
+    pub fn rand<F: FftField>() -> GszFieldShare<F> {
+        batch_rand(1).pop().unwrap()
+    }
+
+    /// Yields t-shares of `n` random values.
+    pub fn batch_rand<F: FftField>(n: usize) -> Vec<GszFieldShare<F>> {
+        let mut pool = take_types::<RandPool<F>>().pop().map(|p| p.0).unwrap_or_default();
+        if pool.len() < n {
+            let m = std::cmp::max(RAND_BATCH_SIZE, n - pool.len());
+            pool.extend(extract_rand::<F>(m, &[t()]).into_iter().map(|s| s[0]));
         }
+        let out = pool.split_off(pool.len() - n);
+        add_type(RandPool(pool));
+        out
     }
 
     /// Yields two shares of a random `r`, one of degree t, one of degree 2t
     ///
-    /// Stubbed b/c it can be pre-processed.
-    ///
     /// Protocol 4.
-    pub fn double_rand<F: Field>() -> (GszFieldShare<F>, GszFieldShare<F>) {
-        (
-            GszFieldShare {
-                val: F::one(),
-                degree: t(),
-            },
-            GszFieldShare {
-                val: F::one(),
-                degree: 2 * t(),
-            },
-        )
-    }
-
-    pub fn batch_double_rand<F: Field>(n: usize) -> (Vec<GszFieldShare<F>>, Vec<GszFieldShare<F>>) {
-        (0..n).map(|_| double_rand()).unzip()
+    pub fn double_rand<F: FftField>() -> (GszFieldShare<F>, GszFieldShare<F>) {
+        let (mut r, mut r2) = batch_double_rand(1);
+        (r.pop().unwrap(), r2.pop().unwrap())
+    }
+
+    pub fn batch_double_rand<F: FftField>(
+        n: usize,
+    ) -> (Vec<GszFieldShare<F>>, Vec<GszFieldShare<F>>) {
+        let mut pool = take_types::<DoubleRandPool<F>>()
+            .pop()
+            .map(|p| p.0)
+            .unwrap_or_default();
+        if pool.len() < n {
+            let m = std::cmp::max(RAND_BATCH_SIZE, n - pool.len());
+            pool.extend(
+                extract_rand::<F>(m, &[t(), 2 * t()])
+                    .into_iter()
+                    .map(|s| (s[0], s[1])),
+            );
+        }
+        let out = pool.split_off(pool.len() - n);
+        add_type(DoubleRandPool(pool));
+        out.into_iter().unzip()
     }
 
     pub fn check_accumulated_field_products<F: FftField>() {
@@ -430,32 +648,81 @@ pub mod field {
         }
     }
 
+    /// Open shares to `owner` only, which gets `Some` of the values. Everyone else gets `None`.
+    fn batch_open_to<F: FftField>(owner: usize, shares: &[GszFieldShare<F>]) -> Option<Vec<F>> {
+        let vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+        let received = Net::send_to(owner, &vals)?;
+        Some(
+            shares
+                .iter()
+                .enumerate()
+                .map(|(i, s)| open_degree_vec(received.iter().map(|r| r[i]).collect(), s.degree))
+                .collect(),
+        )
+    }
+
+    /// Input `owner`'s `fs`; everyone else passes `None`s.
+    ///
+    /// Each input is masked by a random t-share `r`, which is opened to the owner only. The owner
+    /// broadcasts `f - r`, and everyone adds that to their share of `r`.
+    ///
+    /// The outputs are t-shares because the `r` are, whatever the owner sends. Nothing else is
+    /// checked: only with echo broadcast on (see [crate::channel::set_echo_broadcast]) is a
+    /// corrupt owner kept from giving parties shares of different inputs.
+    pub fn input_from<F: FftField>(owner: usize, fs: Vec<Option<F>>) -> Vec<GszFieldShare<F>> {
+        let rs = batch_rand::<F>(fs.len());
+        let ds: Vec<F> = match batch_open_to(owner, &rs) {
+            Some(rs) => fs
+                .into_iter()
+                .zip(rs)
+                .map(|(f, r)| f.expect("The input owner must know its inputs") - r)
+                .collect(),
+            None => vec![F::zero(); fs.len()],
+        };
+        let ds = Net::consistent_broadcast(&ds).swap_remove(owner);
+        rs.into_iter()
+            .zip(ds)
+            .map(|(mut r, d)| {
+                r.val += d;
+                r
+            })
+            .collect()
+    }
+
+    /// Turn additive shares into t-shares: each party inputs its additive shares, and everyone
+    /// adds up the inputs.
+    pub fn from_add_shared<F: FftField>(fs: Vec<F>) -> Vec<GszFieldShare<F>> {
+        let me = Net::party_id();
+        (0..Net::n_parties())
+            .map(|owner| {
+                let mine = fs.iter().map(|f| (owner == me).then_some(*f)).collect();
+                input_from(owner, mine)
+            })
+            .reduce(|mut sums, inputs| {
+                for (s, x) in sums.iter_mut().zip(inputs) {
+                    s.val += x.val;
+                }
+                sums
+            })
+            .unwrap()
+    }
+
     /// Open a t-share.
     pub fn open<F: FftField>(s: &GszFieldShare<F>) -> F {
         check_accumulated_field_products::<F>();
-        let shares = Net::broadcast(&s.val);
+        let shares = Net::consistent_broadcast(&s.val);
         open_degree_vec(shares, s.degree)
     }
 
-    fn open_degree_vec<F: FftField>(mut shares: Vec<F>, d: usize) -> F {
-        //let build_domain_timer = start_timer!(|| "domain");
-        let domain = domain::<F>();
-        //end_timer!(build_domain_timer);
-        //let ifft_timer = start_timer!(|| "ifft");
-        domain.ifft_in_place(&mut shares);
-        //end_timer!(ifft_timer);
-        //let eval_timer = start_timer!(|| "polyeval");
-        let p = ark_poly::univariate::DensePolynomial::from_coefficients_vec(shares);
-        assert!(
-            p.degree() <= d,
-            "Polynomial\n{:?}\nhas degree {} (> degree bound {})",
-            p,
-            p.degree(),
-            d
-        );
-        let r = p.evaluate(&F::zero());
-        //end_timer!(eval_timer);
-        r
+    /// Open a t-share to `party` only.
+    pub fn open_to<F: FftField>(party: usize, s: &GszFieldShare<F>) -> Option<F> {
+        check_accumulated_field_products::<F>();
+        batch_open_to(party, &[*s]).map(|mut fs| fs.pop().unwrap())
+    }
+
+    /// Reconstruct a secret from its degree-`d` shares, correcting bad ones if we can.
+    fn open_degree_vec<F: FftField>(shares: Vec<F>, d: usize) -> F {
+        super::decode::decode_field(shares, d).unwrap_or_else(|e| panic!("{}", e))
     }
 
     /// Given
@@ -464,18 +731,16 @@ pub mod field {
     ///
     /// 1. Opens the share to King.
     /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 3. King reshares the result, with a fresh random polynomial of degree `new_degree`.
     pub fn king_compute<F: FftField, Func: FnOnce(F) -> F>(
         share: &GszFieldShare<F>,
         new_degree: usize,
         f: Func,
     ) -> GszFieldShare<F> {
         let king_answer = Net::send_to_king(&share.val).map(|shares| {
-            let n = shares.len();
             let value = open_degree_vec(shares, share.degree);
             let output = f(value);
-            // TODO: randomize
-            vec![output; n]
+            share_poly(output, new_degree)
         });
         let from_king = Net::recv_from_king(king_answer);
         GszFieldShare {
@@ -490,7 +755,7 @@ pub mod field {
     ///
     /// 1. Opens the share to King.
     /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 3. King reshares the result, with a fresh random polynomial of degree `new_degree`.
     pub fn batch_king_compute<F: FftField, Func: Fn(F) -> F>(
         shares: &[GszFieldShare<F>],
         new_degree: usize,
@@ -505,8 +770,9 @@ pub mod field {
                 let these_shares: Vec<F> = all_shares.iter().map(|s| s[i]).collect();
                 let value = open_degree_vec(these_shares, shares[i].degree);
                 let output = f(value);
-                // TODO: randomize
-                outputs.iter_mut().for_each(|o| o.push(output));
+                for (o, share) in outputs.iter_mut().zip(share_poly(output, new_degree)) {
+                    o.push(share);
+                }
             }
             assert_eq!(outputs.len(), all_shares.len());
             assert_eq!(outputs[0].len(), all_shares[0].len());
@@ -541,10 +807,10 @@ pub mod field {
         let (r, r2) = double_rand::<F>();
         let mut x_cp = x.clone();
         x_cp.val *= y.val;
-        x_cp.degree *= 2;
+        x_cp.degree = std::cmp::max(x_cp.degree + y.degree, r2.degree);
         x_cp.val += r2.val;
         // king just reduces the sharing degree
-        let mut shift_res = king_compute(&x_cp, x_cp.degree / 2, |r| r);
+        let mut shift_res = king_compute(&x_cp, r.degree, |r| r);
         shift_res.val -= r.val;
         if queue_check {
             let triple = GszFieldTriple(x, y.clone(), shift_res);
@@ -570,12 +836,12 @@ pub mod field {
         for ((x, y), r2) in x_cp.iter_mut().zip(y).zip(r2) {
             assert_eq!(x.degree, d);
             x.val *= y.val;
-            x.degree *= 2;
+            x.degree = std::cmp::max(x.degree + y.degree, r2.degree);
             x.val += r2.val;
         }
         // king just reduces the sharing degree
         let kc_timer = start_timer!(|| format!("King compute wrapper"));
-        let mut shift_res = batch_king_compute(&x_cp, x_cp[0].degree / 2, |r| r);
+        let mut shift_res = batch_king_compute(&x_cp, t(), |r| r);
         end_timer!(kc_timer);
         for (shift_res, r) in shift_res.iter_mut().zip(r) {
             shift_res.val -= r.val;
@@ -801,8 +1067,11 @@ pub mod field {
         }
         let (r, r2) = double_rand::<F>();
         acc += r2.val;
-        let acc_share = GszFieldShare { val: acc, degree };
-        let mut shifted_result = king_compute(&acc_share, degree / 2, |r| r);
+        let acc_share = GszFieldShare {
+            val: acc,
+            degree: std::cmp::max(degree, r2.degree),
+        };
+        let mut shifted_result = king_compute(&acc_share, r.degree, |r| r);
         shifted_result.sub(&r);
         shifted_result
     }
@@ -817,7 +1086,7 @@ pub mod field {
 pub use field::GszFieldShare;
 
 pub mod group {
-    use super::super::group::GroupShare;
+    use super::super::group::{no_flags, GroupShare};
     use super::*;
     use ark_ec::group::Group;
     use std::marker::PhantomData;
@@ -848,51 +1117,65 @@ pub mod group {
         }
     }
     impl<T: Group, M> ToBytes for GszGroupShare<T, M> {
-        fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-            unimplemented!("write")
+        fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            (self.degree as u64).write(&mut writer)?;
+            self.val.write(writer)
         }
     }
     impl<T: Group, M> FromBytes for GszGroupShare<T, M> {
-        fn read<R: Read>(_reader: R) -> io::Result<Self> {
-            unimplemented!("read")
+        fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+            let degree = u64::read(&mut reader)? as usize;
+            Ok(Self {
+                val: T::read(reader)?,
+                degree,
+                _phants: PhantomData,
+            })
         }
     }
     impl<T: Group, M> CanonicalSerialize for GszGroupShare<T, M> {
-        fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-            unimplemented!("serialize")
+        fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+            self.degree.serialize(&mut writer)?;
+            self.val.serialize(writer)
         }
         fn serialized_size(&self) -> usize {
-            unimplemented!("serialized_size")
+            self.degree.serialized_size() + self.val.serialized_size()
         }
     }
     impl<T: Group, M> CanonicalSerializeWithFlags for GszGroupShare<T, M> {
         fn serialize_with_flags<W: Write, F: Flags>(
             &self,
-            _writer: W,
+            writer: W,
             _flags: F,
         ) -> Result<(), SerializationError> {
-            unimplemented!("serialize_with_flags")
+            no_flags::<F>()?;
+            self.serialize(writer)
         }
 
         fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-            unimplemented!("serialized_size_with_flags")
+            self.serialized_size()
         }
     }
     impl<T: Group, M> CanonicalDeserialize for GszGroupShare<T, M> {
-        fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-            unimplemented!("deserialize")
+        fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+            let degree = usize::deserialize(&mut reader)?;
+            Ok(Self {
+                val: T::deserialize(reader)?,
+                degree,
+                _phants: PhantomData,
+            })
         }
     }
     impl<T: Group, M> CanonicalDeserializeWithFlags for GszGroupShare<T, M> {
         fn deserialize_with_flags<R: Read, F: Flags>(
-            _reader: R,
+            reader: R,
         ) -> Result<(Self, F), SerializationError> {
-            unimplemented!("deserialize_with_flags")
+            no_flags::<F>()?;
+            Ok((Self::deserialize(reader)?, F::default()))
         }
     }
     impl<T: Group, M> UniformRand for GszGroupShare<T, M> {
         fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
-            todo!()
+            rand()
         }
     }
 
@@ -903,6 +1186,10 @@ pub mod group {
             M::pre_reveal_check();
             open(&self)
         }
+        fn reveal_to(self, party: usize) -> Option<G> {
+            M::pre_reveal_check();
+            batch_open_to(party, &[self]).map(|mut gs| gs.pop().unwrap())
+        }
         fn from_public(f: G) -> Self {
             Self {
                 val: f,
@@ -910,32 +1197,24 @@ pub mod group {
                 _phants: PhantomData::default(),
             }
         }
-        fn from_add_shared(_f: G) -> Self {
-            unimplemented!("from_add_shared")
+        fn from_add_shared(f: G) -> Self {
+            from_add_shared(vec![f]).pop().unwrap()
+        }
+        fn from_add_shared_batch(fs: Vec<G>) -> Vec<Self> {
+            from_add_shared(fs)
         }
         fn unwrap_as_public(self) -> G {
             self.val
         }
         fn king_share<R: Rng>(f: Self::Base, _rng: &mut R) -> Self {
-            let fs = vec![f; Net::n_parties()];
-            let king_f = Net::recv_from_king(if Net::am_king() { Some(fs) } else { None });
-            Self {
-                val: king_f,
-                degree: t(),
-                _phants: Default::default(),
-            }
+            Self::input_from(Net::king(), Net::am_king().then_some(f))
         }
         fn king_share_batch<R: Rng>(f: Vec<Self::Base>, _rng: &mut R) -> Vec<Self> {
-            let fs = vec![f; Net::n_parties()];
-            let king_fs = Net::recv_from_king(if Net::am_king() { Some(fs) } else { None });
-            king_fs
-                .into_iter()
-                .map(|king_f| Self {
-                    val: king_f,
-                    degree: t(),
-                    _phants: Default::default(),
-                })
-                .collect()
+            let am_king = Net::am_king();
+            input_from(Net::king(), f.into_iter().map(|f| am_king.then_some(f)).collect())
+        }
+        fn input_from_batch(owner: usize, fs: Vec<Option<G>>) -> Vec<Self> {
+            input_from(owner, fs)
         }
     }
 
@@ -944,11 +1223,13 @@ pub mod group {
 
         fn add(&mut self, other: &Self) -> &mut Self {
             self.val += &other.val;
+            self.degree = self.degree.max(other.degree);
             self
         }
 
         fn sub(&mut self, other: &Self) -> &mut Self {
             self.val -= &other.val;
+            self.degree = self.degree.max(other.degree);
             self
         }
 
@@ -994,40 +1275,46 @@ pub mod group {
             end_timer!(msm_t);
             Self {
                 val: msm,
-                degree,
+                degree: M::output_degree(degree),
                 _phants: Default::default(),
             }
         }
     }
 
-    /// Yields a t-share of a random r.
+    /// A public base point, the same for all parties, with no known discrete logarithm.
     ///
-    /// Stubbed b/c it can be pre-processed.
+    /// Random group shares are random field shares times this point.
+    pub(super) fn rand_base<G: Group>() -> G {
+        crate::share::group::hash_to_group(b"gsz20 random share base")
+    }
+
+    /// Yields a t-share of a random r.
     ///
-    /// Protocol 3.
+    /// Protocol 3, over the scalar field.
     pub fn rand<G: Group, M>() -> GszGroupShare<G, M> {
+        let r = super::field::rand::<G::ScalarField>();
         GszGroupShare {
-            val: G::zero(),
-            degree: t(),
+            val: rand_base::<G>().mul(&r.val),
+            degree: r.degree,
             _phants: Default::default(),
         }
     }
 
     /// Yields two shares of a random `r`, one of degree t, one of degree 2t
     ///
-    /// Stubbed b/c it can be pre-processed.
-    ///
-    /// Protocol 4.
+    /// Protocol 4, over the scalar field.
     pub fn double_rand<G: Group, M>() -> (GszGroupShare<G, M>, GszGroupShare<G, M>) {
+        let base = rand_base::<G>();
+        let (r, r2) = super::field::double_rand::<G::ScalarField>();
         (
             GszGroupShare {
-                val: G::zero(),
-                degree: t(),
+                val: base.mul(&r.val),
+                degree: r.degree,
                 _phants: Default::default(),
             },
             GszGroupShare {
-                val: G::zero(),
-                degree: 2 * t(),
+                val: base.mul(&r2.val),
+                degree: r2.degree,
                 _phants: Default::default(),
             },
         )
@@ -1039,44 +1326,65 @@ pub mod group {
         pub GszGroupShare<G, M>,
     );
 
+    /// Open shares to `owner` only, as for field elements.
+    fn batch_open_to<G: Group, M>(owner: usize, shares: &[GszGroupShare<G, M>]) -> Option<Vec<G>> {
+        let vals: Vec<G> = shares.iter().map(|s| s.val).collect();
+        let received = Net::send_to(owner, &vals)?;
+        Some(
+            shares
+                .iter()
+                .enumerate()
+                .map(|(i, s)| open_degree_vec(received.iter().map(|r| r[i]).collect(), s.degree))
+                .collect(),
+        )
+    }
+
+    /// Input `owner`'s `gs`, masked by random shares as for field elements.
+    pub fn input_from<G: Group, M>(owner: usize, gs: Vec<Option<G>>) -> Vec<GszGroupShare<G, M>> {
+        let rs: Vec<GszGroupShare<G, M>> = (0..gs.len()).map(|_| rand()).collect();
+        let ds: Vec<G> = match batch_open_to(owner, &rs) {
+            Some(rs) => gs
+                .into_iter()
+                .zip(rs)
+                .map(|(g, r)| g.expect("The input owner must know its inputs") - r)
+                .collect(),
+            None => vec![G::zero(); gs.len()],
+        };
+        let ds = Net::consistent_broadcast(&ds).swap_remove(owner);
+        rs.into_iter()
+            .zip(ds)
+            .map(|(mut r, d)| {
+                r.val += d;
+                r
+            })
+            .collect()
+    }
+
+    /// Turn additive shares into t-shares, as for field elements.
+    pub fn from_add_shared<G: Group, M>(gs: Vec<G>) -> Vec<GszGroupShare<G, M>> {
+        let me = Net::party_id();
+        (0..Net::n_parties())
+            .map(|owner| {
+                let mine = gs.iter().map(|g| (owner == me).then_some(*g)).collect();
+                input_from(owner, mine)
+            })
+            .reduce(|mut sums, inputs| {
+                for (s, x) in sums.iter_mut().zip(inputs) {
+                    s.val += x.val;
+                }
+                sums
+            })
+            .unwrap()
+    }
+
     /// Open a t-share.
     pub fn open<G: Group, M: Send + 'static>(s: &GszGroupShare<G, M>) -> G {
-        let shares = Net::broadcast(&s.val);
+        let shares = Net::consistent_broadcast(&s.val);
         open_degree_vec(shares, s.degree)
     }
 
     fn open_degree_vec<G: Group>(shares: Vec<G>, d: usize) -> G {
-        let domain = domain::<G::ScalarField>();
-        let n = Net::n_parties();
-        let n_inv = G::ScalarField::from(n as u32).inverse().unwrap();
-        let w = domain.element(1);
-        let w_inv = w.inverse().unwrap();
-        // w^{-i}
-        let mut w_inv_i = G::ScalarField::one();
-        let coeffs: Vec<G> = (0..n)
-            .map(|i| {
-                let mut coeff = G::zero();
-                // 1/N * w^{-ij}
-                let mut w_inv_ij = n_inv;
-                for _j in 0..n {
-                    coeff += shares[i].mul(&w_inv_ij);
-                    w_inv_ij *= &w_inv_i;
-                }
-                w_inv_i *= &w_inv;
-                coeff
-            })
-            .collect();
-        assert_eq!(coeffs.len(), n);
-        for i in d + 1..n {
-            assert!(
-                coeffs[i].is_zero(),
-                "Non-identity coeffs {} ({}), when expecting a degree <= {} poly",
-                i,
-                coeffs[i],
-                d
-            );
-        }
-        coeffs[0]
+        super::decode::decode_group(&shares, d).unwrap_or_else(|e| panic!("{}", e))
     }
 
     /// Given
@@ -1086,18 +1394,21 @@ pub mod group {
     ///
     /// 1. Opens the share to King.
     /// 2. King performs the function.
-    /// 3. King reshares the result.
+    /// 3. King reshares the result, with a fresh random polynomial of degree `new_degree`.
     pub fn king_compute<G: Group, M, Func: FnOnce(G) -> G>(
         share: &GszGroupShare<G, M>,
         new_degree: usize,
         f: Func,
     ) -> GszGroupShare<G, M> {
         let king_answer = Net::send_to_king(&share.val).map(|shares| {
-            let n = shares.len();
             let value = open_degree_vec(shares, share.degree);
             let output = f(value);
-            // TODO: randomize
-            vec![output; n]
+            // output + p(x) * base, for a random p with p(0) = 0
+            let base = rand_base::<G>();
+            super::field::share_poly(G::ScalarField::zero(), new_degree)
+                .into_iter()
+                .map(|p| output + base.mul(&p))
+                .collect::<Vec<_>>()
         });
         let from_king = Net::recv_from_king(king_answer);
         GszGroupShare {
@@ -1117,10 +1428,10 @@ pub mod group {
         let mut y_cp = y.clone();
         let (r, r2) = double_rand::<G, M>();
         y_cp.val *= x.val;
-        y_cp.degree *= 2;
+        y_cp.degree = std::cmp::max(x.degree + y_cp.degree, r2.degree);
         y_cp.val += r2.val;
         // king just reduces the sharing degree
-        let mut shift_res = king_compute(&y_cp, x.degree / 2, |r| r);
+        let mut shift_res = king_compute(&y_cp, r.degree, |r| r);
         shift_res.val -= r.val;
         if queue_check {
             let t = GszGroupTriple(x.clone(), y, shift_res);
@@ -1146,10 +1457,10 @@ pub mod group {
         acc += r2.val;
         let acc_share = GszGroupShare {
             val: acc,
-            degree,
+            degree: std::cmp::max(degree, r2.degree),
             _phants: Default::default(),
         };
-        let mut shifted_result = king_compute(&acc_share, degree / 2, |r| r);
+        let mut shifted_result = king_compute(&acc_share, r.degree, |r| r);
         shifted_result.sub(&r);
         shifted_result
     }
@@ -1397,13 +1708,13 @@ pub use group::GszGroupShare;
 // }
 
 macro_rules! groups_share {
-    ($struct_name:ident, $affine:ident, $proj:ident, $aff_msm:ident, $proj_msm:ident) => {
+    ($struct_name:ident, $affine:ident, $proj:ident, $aff_msm:ty, $proj_msm:ty) => {
         pub struct $struct_name<E: PairingEngine>(pub PhantomData<E>);
 
         impl<E: PairingEngine> AffProjShare<E::Fr, E::$affine, E::$proj> for $struct_name<E> {
             type FrShare = GszFieldShare<E::Fr>;
-            type AffineShare = GszGroupShare<E::$affine, msm::$aff_msm<E>>;
-            type ProjectiveShare = GszGroupShare<E::$proj, msm::$proj_msm<E>>;
+            type AffineShare = GszGroupShare<E::$affine, $aff_msm>;
+            type ProjectiveShare = GszGroupShare<E::$proj, $proj_msm>;
 
             fn sh_aff_to_proj(g: Self::AffineShare) -> Self::ProjectiveShare {
                 GszGroupShare {
@@ -1435,8 +1746,10 @@ macro_rules! groups_share {
                 a.val.add_assign_mixed(&o);
                 a
             }
-            fn add_pub_proj_sh_aff(_a: &E::$proj, _o: Self::AffineShare) -> Self::ProjectiveShare {
-                unimplemented!()
+            fn add_pub_proj_sh_aff(a: &E::$proj, o: Self::AffineShare) -> Self::ProjectiveShare {
+                let mut o = Self::sh_aff_to_proj(o);
+                o.val += a;
+                o
             }
         }
     };
@@ -1446,19 +1759,23 @@ groups_share!(
     GszG1Share,
     G1Affine,
     G1Projective,
-    GszG1AffineMsm,
-    GszG1ProjectiveMsm
+    msm::GszG1AffineMsm<E>,
+    msm::GszG1ProjectiveMsm<E>
 );
 groups_share!(
     GszG2Share,
     G2Affine,
     G2Projective,
-    GszG2AffineMsm,
-    GszG2ProjectiveMsm
+    msm::GszG2AffineMsm<E>,
+    msm::GszG2ProjectiveMsm<E>
 );
 
 pub mod mul_field {
+    use super::ext_field::{self, ExtShare};
     use super::*;
+    use super::super::PanicBeaverSource;
+    use num_bigint::BigUint;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[derive(Derivative)]
     #[derivative(
@@ -1489,52 +1806,68 @@ pub mod mul_field {
                 }
             }
             impl<T: $bound, M> ToBytes for $share<T, M> {
-                fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                    unimplemented!("write")
+                fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+                    (self.degree as u64).write(&mut writer)?;
+                    self.val.write(writer)
                 }
             }
             impl<T: $bound, M> FromBytes for $share<T, M> {
-                fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                    unimplemented!("read")
+                fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+                    let degree = u64::read(&mut reader)? as usize;
+                    Ok(Self {
+                        val: T::read(reader)?,
+                        degree,
+                        _phants: PhantomData,
+                    })
                 }
             }
             impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-                fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                    unimplemented!("serialize")
+                fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+                    self.degree.serialize(&mut writer)?;
+                    self.val.serialize(writer)
                 }
                 fn serialized_size(&self) -> usize {
-                    unimplemented!("serialized_size")
+                    self.degree.serialized_size() + self.val.serialized_size()
                 }
             }
             impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
                 fn serialize_with_flags<W: Write, F: Flags>(
                     &self,
-                    _writer: W,
-                    _flags: F,
+                    mut writer: W,
+                    flags: F,
                 ) -> Result<(), SerializationError> {
-                    unimplemented!("serialize_with_flags")
+                    self.degree.serialize(&mut writer)?;
+                    self.val.serialize_with_flags(writer, flags)
                 }
 
                 fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                    unimplemented!("serialized_size_with_flags")
+                    self.degree.serialized_size() + self.val.serialized_size_with_flags::<F>()
                 }
             }
             impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-                fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                    unimplemented!("deserialize")
+                fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                    let degree = usize::deserialize(&mut reader)?;
+                    Ok(Self {
+                        val: T::deserialize(reader)?,
+                        degree,
+                        _phants: PhantomData,
+                    })
                 }
             }
             impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
                 fn deserialize_with_flags<R: Read, F: Flags>(
-                    _reader: R,
+                    mut reader: R,
                 ) -> Result<(Self, F), SerializationError> {
-                    unimplemented!("deserialize_with_flags")
-                }
-            }
-            impl<T: $bound, M> UniformRand for $share<T, M> {
-                fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
-                    todo!()
-                    //Reveal::from_add_shared(<T as UniformRand>::rand(rng))
+                    let degree = usize::deserialize(&mut reader)?;
+                    let (val, flags) = T::deserialize_with_flags(reader)?;
+                    Ok((
+                        Self {
+                            val,
+                            degree,
+                            _phants: PhantomData,
+                        },
+                        flags,
+                    ))
                 }
             }
         };
@@ -1542,12 +1875,22 @@ pub mod mul_field {
 
     impl_basics_2_param!(MulFieldShare, Field);
 
+    impl<F: Field, S: PrimeField> UniformRand for MulFieldShare<F, S> {
+        fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
+            rand()
+        }
+    }
+
     impl<F: Field, S: PrimeField> Reveal for MulFieldShare<F, S> {
         type Base = F;
 
         fn reveal(self) -> F {
             open_mul_field(&self)
         }
+        fn reveal_to(self, party: usize) -> Option<F> {
+            run_checks::<F, S>();
+            Net::send_to(party, &self.val).map(|shares| open_degree_vec::<F, S>(shares, self.degree))
+        }
         fn from_public(f: F) -> Self {
             Self {
                 val: f,
@@ -1555,21 +1898,44 @@ pub mod mul_field {
                 _phants: Default::default(),
             }
         }
-        fn from_add_shared(_f: F) -> Self {
-            unimplemented!()
+        /// The secret must lie in the order-`|S|` subgroup (see [from_linear]).
+        fn from_add_shared(f: F) -> Self {
+            Self::from_add_shared_batch(vec![f]).pop().unwrap()
+        }
+        fn from_add_shared_batch(fs: Vec<F>) -> Vec<Self> {
+            from_linear(ext_field::from_add_shared(fs))
         }
         fn unwrap_as_public(self) -> F {
             self.val
         }
+        fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+            input_from(owner, fs)
+        }
     }
 
     impl<F: Field, S: PrimeField> FieldShare<F> for MulFieldShare<F, S> {
-        fn map_homo<FF: Field, SS: FieldShare<FF>, Fun: Fn(F) -> FF>(self, _f: Fun) -> SS {
-            unimplemented!()
+        /// Goes through linear shares, which are turned into additive ones by scaling them by
+        /// their Lagrange coefficients.
+        fn map_homo<FF: Field, SS: FieldShare<FF>, Fun: Fn(F) -> FF>(self, f: Fun) -> SS {
+            let x = to_linear(&[self]).pop().unwrap();
+            let d = x.degree.min(Net::n_parties() - 1);
+            let me = Net::party_id();
+            let l = if me <= d {
+                lagrange(&points::<F::BasePrimeField>()[..=d], F::BasePrimeField::zero())[me]
+            } else {
+                F::BasePrimeField::zero()
+            };
+            SS::from_add_shared(f(x.val * ext_field::embed::<F>(l)))
         }
 
-        fn add(&mut self, _other: &Self) -> &mut Self {
-            unimplemented!("add for MulFieldShare")
+        /// Products do not add locally, so this goes through linear shares, which takes a few
+        /// rounds.
+        fn add(&mut self, other: &Self) -> &mut Self {
+            let mut xs = to_linear(&[*self, *other]);
+            let y = xs.pop().unwrap();
+            xs[0].add(&y);
+            *self = from_linear(xs).pop().unwrap();
+            self
         }
 
         fn scale(&mut self, other: &F) -> &mut Self {
@@ -1577,8 +1943,11 @@ pub mod mul_field {
             self
         }
 
-        fn shift(&mut self, _other: &F) -> &mut Self {
-            unimplemented!("add for MulFieldShare")
+        fn shift(&mut self, other: &F) -> &mut Self {
+            let mut xs = to_linear(&[*self]);
+            xs[0].shift(other);
+            *self = from_linear(xs).pop().unwrap();
+            self
         }
 
         fn mul<SS: BeaverSource<Self, Self, Self>>(self, other: Self, _source: &mut SS) -> Self {
@@ -1612,44 +1981,159 @@ pub mod mul_field {
         }
     }
 
+    /// Input `owner`'s non-zero `fs`; everyone else passes `None`s.
+    ///
+    /// The owner deals t-shares in the exponent: party `j` gets `f^p(x_j)`, for a random
+    /// degree-t `p` with `p(0) = 1`. That only reconstructs to `f` if the order of `f` divides
+    /// that of `S`, as it does for pairing outputs. The owner's shares are not checked.
+    pub fn input_from<F: Field, S: PrimeField>(
+        owner: usize,
+        fs: Vec<Option<F>>,
+    ) -> Vec<MulFieldShare<F, S>> {
+        let n = Net::n_parties();
+        let t = t();
+        let outs = if Net::party_id() == owner {
+            let rng = &mut rand::thread_rng();
+            let mut outs = vec![Vec::with_capacity(fs.len()); n];
+            for f in fs {
+                let f = f.expect("The input owner must know its inputs");
+                let mut coeffs = vec![S::one()];
+                coeffs.extend((0..t).map(|_| S::rand(rng)));
+                for (out, x) in outs.iter_mut().zip(points::<S>()) {
+                    out.push(f.pow(evaluate(&coeffs, x).into_repr()));
+                }
+            }
+            outs
+        } else {
+            vec![Vec::new(); n]
+        };
+        Net::all_to_all(&outs)
+            .swap_remove(owner)
+            .into_iter()
+            .map(|val| MulFieldShare {
+                val,
+                degree: t,
+                _phants: PhantomData,
+            })
+            .collect()
+    }
+
+    /// A public element of order `|S|` in the multiplicative group of `F`, which must have such a
+    /// subgroup, as `Fqk` does for pairing outputs.
+    fn generator<F: Field, S: PrimeField>() -> F {
+        /// The generator for `F` and `S`, once found.
+        struct Generator<F, S>(Option<F>, PhantomData<S>);
+        impl<F, S> Default for Generator<F, S> {
+            fn default() -> Self {
+                Self(None, PhantomData)
+            }
+        }
+        session::with_state(|g: &mut Generator<F, S>| {
+            *g.0.get_or_insert_with(|| {
+                let limbs = |ls: &[u64]| {
+                    BigUint::from_bytes_le(
+                        &ls.iter().flat_map(|l| l.to_le_bytes()).collect::<Vec<u8>>(),
+                    )
+                };
+                let order = limbs(F::characteristic()).pow(F::extension_degree() as u32) - 1u32;
+                let modulus = limbs(S::characteristic());
+                assert!(
+                    (&order % &modulus).is_zero(),
+                    "Shares in the exponent need a subgroup of order |S|"
+                );
+                let cofactor = (order / modulus).to_u64_digits();
+                let rng = &mut StdRng::seed_from_u64(0);
+                std::iter::repeat_with(|| F::rand(rng).pow(&cofactor))
+                    .find(|g| !g.is_one())
+                    .unwrap()
+            })
+        })
+    }
+
+    /// Yields a share of a random element of the order-`|S|` subgroup: the [generator], to the
+    /// power of a random t-share.
+    pub fn rand<F: Field, S: PrimeField>() -> MulFieldShare<F, S> {
+        let r = super::field::rand::<S>();
+        MulFieldShare {
+            val: generator::<F, S>().pow(r.val.into_repr()),
+            degree: r.degree,
+            _phants: PhantomData,
+        }
+    }
+
+    /// Linear t-shares of the same secrets.
+    ///
+    /// A component, to the power of its Lagrange coefficient, is a factor of the secret. Each of
+    /// the first `t + 1` parties inputs its factors, and they are multiplied together.
+    fn to_linear<F: Field, S: PrimeField>(xs: &[MulFieldShare<F, S>]) -> Vec<ExtShare<F>> {
+        let me = Net::party_id();
+        let d = xs.iter().map(|x| x.degree).max().unwrap_or(0).min(Net::n_parties() - 1);
+        let factor = |x: &MulFieldShare<F, S>| {
+            let d = x.degree.min(Net::n_parties() - 1);
+            if me <= d {
+                x.val.pow(lagrange(&points::<S>()[..=d], S::zero())[me].into_repr())
+            } else {
+                F::one()
+            }
+        };
+        (0..=d)
+            .map(|owner| {
+                let mine = xs.iter().map(|x| (owner == me).then(|| factor(x))).collect();
+                ext_field::input_from(owner, mine)
+            })
+            .reduce(|acc, fs| ExtShare::batch_mul(acc, fs, &mut PanicBeaverSource::default()))
+            .unwrap()
+    }
+
+    /// Shares in the exponent of the same secrets as `xs`, which must lie in the order-`|S|`
+    /// subgroup.
+    ///
+    /// Takes random shares `r`, opens `x / r`, and scales `r` by it. That reveals whether `x` is
+    /// zero, but nothing else.
+    fn from_linear<F: Field, S: PrimeField>(xs: Vec<ExtShare<F>>) -> Vec<MulFieldShare<F, S>> {
+        let rs: Vec<MulFieldShare<F, S>> = (0..xs.len()).map(|_| rand()).collect();
+        let r_invs: Vec<_> = rs
+            .iter()
+            .map(|r| r.inv(&mut PanicBeaverSource::default()))
+            .collect();
+        let r_invs = to_linear(&r_invs);
+        let ys = ExtShare::batch_open(ExtShare::batch_mul(
+            xs,
+            r_invs,
+            &mut PanicBeaverSource::default(),
+        ));
+        rs.into_iter()
+            .zip(ys)
+            .map(|(mut r, y)| {
+                r.val *= y;
+                r
+            })
+            .collect()
+    }
+
+    /// Check the products that went into shares before opening them: those of the scalars `S`,
+    /// and those of the linear shares the conversions use.
+    fn run_checks<F: Field, S: PrimeField>() {
+        super::field::check_accumulated_field_products::<S>();
+        ext_field::check_accumulated_ext_products::<F>();
+    }
+
     /// Open a t-share.
     pub fn open_mul_field<F: Field, S: PrimeField>(s: &MulFieldShare<F, S>) -> F {
-        let shares = Net::broadcast(&s.val);
+        run_checks::<F, S>();
+        let shares = Net::consistent_broadcast(&s.val);
         open_degree_vec::<F, S>(shares, s.degree)
     }
 
     fn open_degree_vec<F: Field, S: PrimeField>(shares: Vec<F>, d: usize) -> F {
-        let domain = domain::<S>();
-        let n = Net::n_parties();
-        let n_inv = S::from(n as u32).inverse().unwrap();
-        let w = domain.element(1);
-        let w_inv = w.inverse().unwrap();
-        // w^{-i}
-        let mut w_inv_i = S::one();
-        let coeffs: Vec<F> = (0..n)
-            .map(|i| {
-                let mut coeff = F::one();
-                // 1/N * w^{-ij}
-                let mut w_inv_ij = n_inv;
-                for _j in 0..n {
-                    coeff *= shares[i].pow(&w_inv_ij.into_repr());
-                    w_inv_ij *= &w_inv_i;
-                }
-                w_inv_i *= &w_inv;
-                coeff
-            })
-            .collect();
-        assert_eq!(coeffs.len(), n);
-        for i in d + 1..n {
-            assert!(
-                coeffs[i].is_one(),
-                "Non-one coeffs {} ({}), when expecting a degree <= {} poly",
-                i,
-                coeffs[i],
-                d
-            );
-        }
-        coeffs[0]
+        // Shares are in the exponent, so combine them by products of powers.
+        let combine = |ls: &[S], ss: &[F]| {
+            ls.iter()
+                .zip(ss)
+                .fold(F::one(), |acc, (l, s)| acc * s.pow(l.into_repr()))
+        };
+        interpolate_at_zero(&shares, d, combine)
+            .unwrap_or_else(|| panic!("Shares do not lie on a degree-{} polynomial", d))
     }
 }
 
@@ -1669,6 +2153,463 @@ impl<F: Field, S: PrimeField> ExtFieldShare<F> for GszMulExtFieldShare<F, S> {
     type Base = mul_field::MulFieldShare<F::BasePrimeField, S>;
 }
 
+pub mod ext_field {
+    use super::*;
+    use super::field::{batch_double_rand, batch_rand};
+
+    /// A Goyal-Song '20 share of an element of an extension field `F`.
+    ///
+    /// The evaluation points are those of the base prime field's shares. Shamir sharing is linear
+    /// over that field, so a sharing of `F` is the same as one sharing per coordinate: we get
+    /// random sharings of `F` by packing together random sharings of the base prime field.
+    #[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub struct ExtShare<F: Field> {
+        pub val: F,
+        pub degree: usize,
+    }
+
+    impl<T: Field> Display for ExtShare<T> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.val)
+        }
+    }
+    impl<T: Field> Debug for ExtShare<T> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.val)
+        }
+    }
+    impl<T: Field> ToBytes for ExtShare<T> {
+        fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            (self.degree as u64).write(&mut writer)?;
+            self.val.write(writer)
+        }
+    }
+    impl<T: Field> FromBytes for ExtShare<T> {
+        fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+            let degree = u64::read(&mut reader)? as usize;
+            Ok(Self {
+                val: T::read(reader)?,
+                degree,
+            })
+        }
+    }
+    impl<T: Field> CanonicalSerialize for ExtShare<T> {
+        fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+            self.degree.serialize(&mut writer)?;
+            self.val.serialize(writer)
+        }
+        fn serialized_size(&self) -> usize {
+            self.degree.serialized_size() + self.val.serialized_size()
+        }
+    }
+    impl<T: Field> CanonicalSerializeWithFlags for ExtShare<T> {
+        fn serialize_with_flags<W: Write, F: Flags>(
+            &self,
+            mut writer: W,
+            flags: F,
+        ) -> Result<(), SerializationError> {
+            self.degree.serialize(&mut writer)?;
+            self.val.serialize_with_flags(writer, flags)
+        }
+
+        fn serialized_size_with_flags<F: Flags>(&self) -> usize {
+            self.degree.serialized_size() + self.val.serialized_size_with_flags::<F>()
+        }
+    }
+    impl<T: Field> CanonicalDeserialize for ExtShare<T> {
+        fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+            let degree = usize::deserialize(&mut reader)?;
+            Ok(Self {
+                val: T::deserialize(reader)?,
+                degree,
+            })
+        }
+    }
+    impl<T: Field> CanonicalDeserializeWithFlags for ExtShare<T> {
+        fn deserialize_with_flags<R: Read, F: Flags>(
+            mut reader: R,
+        ) -> Result<(Self, F), SerializationError> {
+            let degree = usize::deserialize(&mut reader)?;
+            let (val, flags) = T::deserialize_with_flags(reader)?;
+            Ok((Self { val, degree }, flags))
+        }
+    }
+    impl<T: Field> UniformRand for ExtShare<T> {
+        fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
+            rand()
+        }
+    }
+
+    impl<F: Field> Reveal for ExtShare<F> {
+        type Base = F;
+
+        fn reveal(self) -> F {
+            open(&self)
+        }
+        fn reveal_to(self, party: usize) -> Option<F> {
+            check_accumulated_ext_products::<F>();
+            batch_open_to(party, &[self]).map(|mut fs| fs.pop().unwrap())
+        }
+        fn from_public(f: F) -> Self {
+            Self {
+                val: f,
+                degree: t(),
+            }
+        }
+        fn from_add_shared(f: F) -> Self {
+            from_add_shared(vec![f]).pop().unwrap()
+        }
+        fn from_add_shared_batch(fs: Vec<F>) -> Vec<Self> {
+            from_add_shared(fs)
+        }
+        fn unwrap_as_public(self) -> F {
+            self.val
+        }
+        fn king_share<R: Rng>(f: Self::Base, _rng: &mut R) -> Self {
+            Self::input_from(Net::king(), Net::am_king().then_some(f))
+        }
+        fn king_share_batch<R: Rng>(f: Vec<Self::Base>, _rng: &mut R) -> Vec<Self> {
+            let am_king = Net::am_king();
+            input_from(Net::king(), f.into_iter().map(|f| am_king.then_some(f)).collect())
+        }
+        fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+            input_from(owner, fs)
+        }
+    }
+
+    impl<F: Field> FieldShare<F> for ExtShare<F> {
+        fn add(&mut self, other: &Self) -> &mut Self {
+            self.val += other.val;
+            self.degree = self.degree.max(other.degree);
+            self
+        }
+
+        fn shift(&mut self, other: &F) -> &mut Self {
+            self.val += other;
+            self
+        }
+
+        fn scale(&mut self, other: &F) -> &mut Self {
+            self.val *= other;
+            self
+        }
+
+        fn sub(&mut self, other: &Self) -> &mut Self {
+            self.val -= other.val;
+            self.degree = self.degree.max(other.degree);
+            self
+        }
+
+        fn neg(&mut self) -> &mut Self {
+            self.val = -self.val;
+            self
+        }
+
+        fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+            check_accumulated_ext_products::<F>();
+            let (vals, degrees): (Vec<F>, Vec<usize>) =
+                selfs.into_iter().map(|s| (s.val, s.degree)).unzip();
+            let all_vals = Net::consistent_broadcast(&vals);
+            degrees
+                .into_iter()
+                .enumerate()
+                .map(|(i, d)| open_degree_vec(all_vals.iter().map(|v| v[i]).collect(), d))
+                .collect()
+        }
+
+        /// Multiply two t-shares, consuming a double-share.
+        ///
+        /// As with base field products, the product is queued for a check (see
+        /// [check_ext_products]).
+        fn mul<S: BeaverSource<Self, Self, Self>>(self, other: Self, source: &mut S) -> Self {
+            Self::batch_mul(vec![self], vec![other], source).pop().unwrap()
+        }
+
+        fn batch_mul<S: BeaverSource<Self, Self, Self>>(
+            xs: Vec<Self>,
+            ys: Vec<Self>,
+            _source: &mut S,
+        ) -> Vec<Self> {
+            assert_eq!(xs.len(), ys.len());
+            let (rs, r2s) = batch_double_rand_ext::<F>(xs.len());
+            let xys: Vec<Self> = xs
+                .iter()
+                .zip(&ys)
+                .zip(r2s)
+                .map(|((x, y), r2)| Self {
+                    val: x.val * y.val + r2.val,
+                    degree: std::cmp::max(x.degree + y.degree, r2.degree),
+                })
+                .collect();
+            // king just reduces the sharing degree
+            let zs: Vec<Self> = batch_king_compute(&xys, t())
+                .into_iter()
+                .zip(rs)
+                .map(|(mut xy, r)| {
+                    xy.val -= r.val;
+                    xy
+                })
+                .collect();
+            add_types(
+                xs.into_iter()
+                    .zip(ys)
+                    .zip(&zs)
+                    .map(|((x, y), z)| GszExtTriple(x, y, *z))
+                    .collect(),
+            );
+            zs
+        }
+
+        fn inv<S: BeaverSource<Self, Self, Self>>(self, source: &mut S) -> Self {
+            Self::batch_inv(vec![self], source).pop().unwrap()
+        }
+
+        fn batch_inv<S: BeaverSource<Self, Self, Self>>(
+            xs: Vec<Self>,
+            source: &mut S,
+        ) -> Vec<Self> {
+            let rs = batch_rand_ext::<F>(xs.len());
+            let xrs = Self::batch_open(Self::batch_mul(xs, rs.clone(), source));
+            rs.into_iter()
+                .zip(xrs)
+                .map(|(mut r, xr)| {
+                    r.scale(&xr.inverse().unwrap());
+                    r
+                })
+                .collect()
+        }
+
+        fn partial_products<S: BeaverSource<Self, Self, Self>>(
+            x: Vec<Self>,
+            src: &mut S,
+        ) -> Vec<Self> {
+            let n = x.len();
+            let m = batch_rand_ext::<F>(n + 1);
+            let m_inv = Self::batch_inv(m.clone(), src);
+            let mx = Self::batch_mul(m[..n].to_vec(), x, src);
+            let mxm = Self::batch_mul(mx, m_inv[1..].to_vec(), src);
+            let mut mxm_pub = Self::batch_open(mxm);
+            for i in 1..mxm_pub.len() {
+                let last = mxm_pub[i - 1];
+                mxm_pub[i] *= &last;
+            }
+            let mms = Self::batch_mul(vec![m[0]; n], m_inv[1..].to_vec(), src);
+            let mut mms_inv = Self::batch_inv(mms, src);
+            for (m, p) in mms_inv.iter_mut().zip(&mxm_pub) {
+                m.scale(p);
+            }
+            mms_inv
+        }
+    }
+
+    /// Pack `k = [F : F::BasePrimeField]` base field shares into one share of `F`.
+    fn pack<F: Field>(coords: &[GszFieldShare<F::BasePrimeField>]) -> ExtShare<F> {
+        let vals: Vec<_> = coords.iter().map(|c| c.val).collect();
+        ExtShare {
+            val: F::from_base_prime_field_elems(&vals).unwrap(),
+            degree: coords[0].degree,
+        }
+    }
+
+    /// The coordinates of a share over the base prime field, as base field shares: the inverse of
+    /// [pack]. Fields serialize their coordinates in the order `from_base_prime_field_elems`
+    /// takes them.
+    fn unpack<F: Field>(s: &ExtShare<F>) -> Vec<GszFieldShare<F::BasePrimeField>> {
+        let mut bytes = Vec::new();
+        s.val.serialize(&mut bytes).unwrap();
+        let mut reader = &bytes[..];
+        (0..F::extension_degree())
+            .map(|_| GszFieldShare {
+                val: F::BasePrimeField::deserialize(&mut reader).unwrap(),
+                degree: s.degree,
+            })
+            .collect()
+    }
+
+    /// `x`, as an element of `F`.
+    pub(super) fn embed<F: Field>(x: F::BasePrimeField) -> F {
+        let mut elems = vec![F::BasePrimeField::zero(); F::extension_degree() as usize];
+        elems[0] = x;
+        F::from_base_prime_field_elems(&elems).unwrap()
+    }
+
+    /// Yields t-shares of `n` random values.
+    pub fn batch_rand_ext<F: Field>(n: usize) -> Vec<ExtShare<F>> {
+        let k = F::extension_degree() as usize;
+        batch_rand::<F::BasePrimeField>(n * k)
+            .chunks(k)
+            .map(pack)
+            .collect()
+    }
+
+    /// Yields a t-share of a random value.
+    pub fn rand<F: Field>() -> ExtShare<F> {
+        batch_rand_ext(1).pop().unwrap()
+    }
+
+    /// Yields t- and 2t-shares of `n` random values.
+    pub fn batch_double_rand_ext<F: Field>(n: usize) -> (Vec<ExtShare<F>>, Vec<ExtShare<F>>) {
+        let k = F::extension_degree() as usize;
+        let (rs, r2s) = batch_double_rand::<F::BasePrimeField>(n * k);
+        (
+            rs.chunks(k).map(pack).collect(),
+            r2s.chunks(k).map(pack).collect(),
+        )
+    }
+
+    /// Open shares to `owner` only, which gets `Some` of the values. Everyone else gets `None`.
+    fn batch_open_to<F: Field>(owner: usize, shares: &[ExtShare<F>]) -> Option<Vec<F>> {
+        let vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+        let received = Net::send_to(owner, &vals)?;
+        Some(
+            shares
+                .iter()
+                .enumerate()
+                .map(|(i, s)| open_degree_vec(received.iter().map(|r| r[i]).collect(), s.degree))
+                .collect(),
+        )
+    }
+
+    /// Input `owner`'s `fs`, masked by random shares as for base field elements.
+    pub fn input_from<F: Field>(owner: usize, fs: Vec<Option<F>>) -> Vec<ExtShare<F>> {
+        let rs = batch_rand_ext::<F>(fs.len());
+        let ds: Vec<F> = match batch_open_to(owner, &rs) {
+            Some(rs) => fs
+                .into_iter()
+                .zip(rs)
+                .map(|(f, r)| f.expect("The input owner must know its inputs") - r)
+                .collect(),
+            None => vec![F::zero(); fs.len()],
+        };
+        let ds = Net::consistent_broadcast(&ds).swap_remove(owner);
+        rs.into_iter()
+            .zip(ds)
+            .map(|(mut r, d)| {
+                r.val += d;
+                r
+            })
+            .collect()
+    }
+
+    /// Turn additive shares into t-shares, as for base field elements.
+    pub fn from_add_shared<F: Field>(fs: Vec<F>) -> Vec<ExtShare<F>> {
+        let me = Net::party_id();
+        (0..Net::n_parties())
+            .map(|owner| {
+                let mine = fs.iter().map(|f| (owner == me).then_some(*f)).collect();
+                input_from(owner, mine)
+            })
+            .reduce(|mut sums, inputs| {
+                for (s, x) in sums.iter_mut().zip(inputs) {
+                    s.val += x.val;
+                }
+                sums
+            })
+            .unwrap()
+    }
+
+    /// Open a t-share.
+    pub fn open<F: Field>(s: &ExtShare<F>) -> F {
+        check_accumulated_ext_products::<F>();
+        let shares = Net::consistent_broadcast(&s.val);
+        open_degree_vec(shares, s.degree)
+    }
+
+    fn open_degree_vec<F: Field>(shares: Vec<F>, d: usize) -> F {
+        let combine = |ls: &[F::BasePrimeField], ss: &[F]| {
+            ls.iter().zip(ss).map(|(l, s)| *s * embed::<F>(*l)).sum()
+        };
+        interpolate_at_zero(&shares, d, combine)
+            .unwrap_or_else(|| panic!("Shares do not lie on a degree-{} polynomial", d))
+    }
+
+    /// Shamir-share `secret` with a random polynomial of degree `degree`.
+    fn share_poly<F: Field>(secret: F, degree: usize) -> Vec<F> {
+        let rng = &mut rand::thread_rng();
+        let mut coeffs = vec![secret];
+        coeffs.extend((0..degree).map(|_| F::rand(rng)));
+        points::<F::BasePrimeField>()
+            .into_iter()
+            .map(|x| evaluate(&coeffs, embed::<F>(x)))
+            .collect()
+    }
+
+    /// Open shares to the king, who reshares them with fresh polynomials of degree `new_degree`.
+    fn batch_king_compute<F: Field>(shares: &[ExtShare<F>], new_degree: usize) -> Vec<ExtShare<F>> {
+        let values: Vec<F> = shares.iter().map(|s| s.val).collect();
+        let king_answer = Net::send_to_king(&values).map(|all_shares| {
+            let mut outputs = vec![Vec::new(); all_shares.len()];
+            for (i, s) in shares.iter().enumerate() {
+                let value = open_degree_vec(all_shares.iter().map(|v| v[i]).collect(), s.degree);
+                for (o, share) in outputs.iter_mut().zip(share_poly(value, new_degree)) {
+                    o.push(share);
+                }
+            }
+            outputs
+        });
+        Net::recv_from_king(king_answer)
+            .into_iter()
+            .map(|val| ExtShare {
+                val,
+                degree: new_degree,
+            })
+            .collect()
+    }
+
+    pub fn check_accumulated_ext_products<F: Field>() {
+        let to_check = take_types::<GszExtTriple<F>>();
+        check_ext_products(to_check);
+    }
+
+    /// Check products in `F` over its base prime field.
+    ///
+    /// With `e_a` the basis of `F`, coordinate `c` of `x * y` is the inner product of the
+    /// coordinates `x_a` of `x` with coordinates `c` of the `e_a * y`, which are linear in `y`. So
+    /// each product is `k = [F : F::BasePrimeField]` inner products of length `k`, and a random
+    /// combination of all of them is one inner product, which [field::ip_check] checks.
+    pub fn check_ext_products<F: Field>(to_check: Vec<GszExtTriple<F>>) {
+        if !to_check.is_empty() {
+            let timer = start_timer!(|| format!("Ext product check: {}", to_check.len()));
+            debug!("Open Ext: {} checks", to_check.len());
+            let k = F::extension_degree() as usize;
+            let basis: Vec<F> = (0..k)
+                .map(|a| {
+                    let mut elems = vec![F::BasePrimeField::zero(); k];
+                    elems[a] = F::BasePrimeField::one();
+                    F::from_base_prime_field_elems(&elems).unwrap()
+                })
+                .collect();
+            let r = super::field::coin::<F::BasePrimeField>();
+            let mut r_i = F::BasePrimeField::one();
+            let mut xs = Vec::new();
+            let mut ys = Vec::new();
+            let mut ip = GszFieldShare::from_public(F::BasePrimeField::zero());
+            for GszExtTriple(x, y, z) in to_check {
+                let x = unpack(&x);
+                let eys: Vec<_> = basis
+                    .iter()
+                    .map(|e| unpack(&ExtShare { val: *e * y.val, degree: y.degree }))
+                    .collect();
+                for (c, mut z_c) in unpack(&z).into_iter().enumerate() {
+                    for (x_a, ey) in x.iter().zip(&eys) {
+                        let mut x_a = *x_a;
+                        x_a.scale(&r_i);
+                        xs.push(x_a);
+                        ys.push(ey[c]);
+                    }
+                    z_c.scale(&r_i);
+                    ip.add(&z_c);
+                    r_i *= &r;
+                }
+            }
+            super::field::ip_check(xs, ys, ip);
+            end_timer!(timer);
+        }
+    }
+
+    pub struct GszExtTriple<F: Field>(pub ExtShare<F>, pub ExtShare<F>, pub ExtShare<F>);
+}
+
 #[derive(Debug, Derivative)]
 #[derivative(
     Default(bound = ""),
@@ -1681,8 +2622,7 @@ impl<F: Field, S: PrimeField> ExtFieldShare<F> for GszMulExtFieldShare<F, S> {
 pub struct GszExtFieldShare<F: Field>(pub PhantomData<F>);
 
 impl<F: Field> ExtFieldShare<F> for GszExtFieldShare<F> {
-    // TODO: wrong!
-    type Ext = mul_field::MulFieldShare<F, F::BasePrimeField>;
+    type Ext = ext_field::ExtShare<F>;
     type Base = GszFieldShare<F::BasePrimeField>;
 }
 
@@ -1700,7 +2640,7 @@ pub struct GszPairingShare<E: PairingEngine>(pub PhantomData<E>);
 pub mod msm {
     use super::*;
 
-    fn run_all_checks<E: PairingEngine>() {
+    pub(super) fn run_all_checks<E: PairingEngine>() {
         let t = start_timer!(|| "All opening checks");
         field::check_accumulated_field_products::<E::Fr>();
         group::check_accumulated_group_products::<E::G1Affine, GszG1AffineMsm<E>>();
@@ -1788,4 +2728,42 @@ impl<E: PairingEngine> PairingShare<E> for GszPairingShare<E> {
     type G2ProjectiveShare = GszGroupShare<E::G2Projective, msm::GszG2ProjectiveMsm<E>>;
     type G1 = GszG1Share<E>;
     type G2 = GszG2Share<E>;
+
+    /// Shares in `Fqk` are not checked when they are opened against the group products that went
+    /// into them, so those are checked first.
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        msm::run_all_checks::<E>();
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
+    /// See [GszPairingShare::g1_map_to_fqk].
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        msm::run_all_checks::<E>();
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
+    fn fqk_map(
+        s: mul_field::MulFieldShare<E::Fqk, E::Fr>,
+        f: impl Fn(E::Fqk) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
 }
+
+pub mod packed;
@@ -0,0 +1,792 @@
+//! Packed Shamir sharing for large committees, after ["zkSaaS: Zero-Knowledge SNARKs as a
+//! Service"](https://ia.cr/2023/905) by Garg, Goel, Jain, Policharla and Sekar.
+//!
+//! A packed share carries `k` secrets at once, at the slots `0, -1, ..., -(k - 1)` of its
+//! polynomial; as in GSZ, party `j` holds the value at [point] `j`. To stay private against `t`
+//! parties the polynomial needs degree `t + k - 1`, and products of two such shares must still be
+//! opened, so `2 (t + k - 1) < n`: with `n` parties, packing trades threshold for throughput.
+//! Slot 0 is where GSZ keeps its secret, so a GSZ share is a packed share with one slot in use.
+//!
+//! Once `m` values are [pack]ed, each party holds `m / k` shares, so linear work on them (an MSM,
+//! or the first layers of an FFT) falls by about `k`. Work that mixes slots goes through the
+//! king, which opens masked secrets, does it in the clear, and deals the result back out packed:
+//! [mul] uses this to reduce the degree of products, and [apply] to run any public linear map,
+//! such as a [permute]ation or the last `log k` layers of an [fft].
+//!
+//! [PackedPairingShare] is [GszPairingShare] with packed MSMs, for the provers in `mpc-snarks`.
+//!
+//! Unlike GSZ multiplication, nothing here checks the king's work: these protocols are only secure
+//! against honest-but-curious parties.
+use ark_ec::group::Group;
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+use digest::Digest;
+use sha2::Sha256;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::*;
+use crate::msm::{AffineMsm, ProjectiveMsm};
+
+/// The pack size set for the current session, if any.
+#[derive(Default)]
+struct PackSize(Option<usize>);
+
+/// The number `k` of secrets in each packed share. Unless set with [set_pack_size], it is the
+/// most that the threshold allows: `ceil(n / 2) - t`.
+pub fn pack_size() -> usize {
+    session::with_state(|k: &mut PackSize| k.0).unwrap_or(Net::n_parties().div_ceil(2) - t())
+}
+
+/// Pack `k` secrets into each share for the rest of the session. Set the threshold first.
+pub fn set_pack_size(k: usize) {
+    let n = Net::n_parties();
+    assert!(
+        k > 0 && 2 * (t() + k - 1) < n,
+        "Cannot pack {} secrets with threshold {} and {} parties",
+        k,
+        t(),
+        n
+    );
+    session::with_state(|s: &mut PackSize| s.0 = Some(k));
+}
+
+/// Whether GSZ FFTs are done on packed shares, as set with [set_packed_ffts].
+#[derive(Default)]
+struct PackedFfts(bool);
+
+/// Do the session's FFTs over GSZ shares on their packed sharings (see [fft_shares]), or stop.
+///
+/// The outputs are fresh t-shares. Code that reads degrees off the local shares, as GSZ division
+/// does, needs the local FFTs.
+pub fn set_packed_ffts(on: bool) {
+    session::with_state(|s: &mut PackedFfts| s.0 = on);
+}
+
+/// The degree of packed shares: `t + k - 1`.
+pub fn degree() -> usize {
+    t() + pack_size() - 1
+}
+
+/// Where the secrets of a packed share sit: `0, -1, ..., -(k - 1)`.
+pub fn slots<F: Field>() -> Vec<F> {
+    (0..pack_size()).map(|l| -F::from(l as u64)).collect()
+}
+
+/// A party's share of `k` secrets: the value of their polynomial at the party's [point].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedFieldShare<F: Field> {
+    pub val: F,
+    pub degree: usize,
+}
+
+impl<F: Field> PackedFieldShare<F> {
+    pub fn add(&mut self, other: &Self) -> &mut Self {
+        self.val += other.val;
+        self.degree = self.degree.max(other.degree);
+        self
+    }
+
+    pub fn sub(&mut self, other: &Self) -> &mut Self {
+        self.val -= other.val;
+        self.degree = self.degree.max(other.degree);
+        self
+    }
+
+    /// Multiply every secret by `f`.
+    pub fn scale(&mut self, f: &F) -> &mut Self {
+        self.val *= f;
+        self
+    }
+}
+
+/// Lagrange coefficients, one row for each point they evaluate a polynomial at.
+type Rows<F> = Vec<Vec<F>>;
+
+/// Lagrange coefficients over the first `d + 1` points, by `(d, k)`: for the value at each slot,
+/// and at each later point.
+struct SlotInterpolators<F>(HashMap<(usize, usize), (Rows<F>, Rows<F>)>);
+
+impl<F> Default for SlotInterpolators<F> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// The secrets in the slots of the degree-`d` polynomial through the parties' `shares`, which may
+/// live in any `F`-module (see [interpolate_at_zero]).
+///
+/// Panics if the shares do not all lie on one such polynomial.
+fn decode_with<F: Field, T: PartialEq>(
+    shares: &[T],
+    d: usize,
+    combine: impl Fn(&[F], &[T]) -> T,
+) -> Vec<T> {
+    let d = d.min(shares.len() - 1);
+    let (at_slots, at_rest) = session::with_state(|i: &mut SlotInterpolators<F>| {
+        i.0.entry((d, pack_size()))
+            .or_insert_with(|| {
+                let xs = points::<F>();
+                let at = |zs: &[F]| zs.iter().map(|z| lagrange(&xs[..=d], *z)).collect();
+                (at(&slots()), at(&xs[d + 1..]))
+            })
+            .clone()
+    });
+    let basis = &shares[..=d];
+    assert!(
+        at_rest
+            .iter()
+            .zip(&shares[d + 1..])
+            .all(|(ls, s)| combine(ls, basis) == *s),
+        "Packed shares do not lie on a degree-{} polynomial",
+        d
+    );
+    at_slots.iter().map(|ls| combine(ls, basis)).collect()
+}
+
+fn decode<F: Field>(shares: &[F], d: usize) -> Vec<F> {
+    decode_with(shares, d, |ls: &[F], ss: &[F]| {
+        ls.iter().zip(ss).map(|(l, s)| *l * s).sum()
+    })
+}
+
+/// For each party, the Lagrange coefficients that give its share of a degree-`d` polynomial from
+/// its values at the slots and then at `-k, ..., -d`. By `(d, k)`.
+struct Dealers<F>(HashMap<(usize, usize), Rows<F>>);
+
+impl<F> Default for Dealers<F> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// Every party's share of a fresh random degree-`d` polynomial with `secrets` in its slots. Any
+/// slots past the end of `secrets` hold zero.
+fn deal<F: Field>(secrets: &[F], d: usize) -> Vec<F> {
+    let k = pack_size();
+    assert!(secrets.len() <= k && k <= d + 1);
+    let dealers = session::with_state(|s: &mut Dealers<F>| {
+        s.0.entry((d, k))
+            .or_insert_with(|| {
+                let xs: Vec<F> = (0..=d).map(|i| -F::from(i as u64)).collect();
+                points().into_iter().map(|x| lagrange(&xs, x)).collect()
+            })
+            .clone()
+    });
+    let rng = &mut rand::thread_rng();
+    let mut values = secrets.to_vec();
+    values.resize(k, F::zero());
+    values.extend((k..=d).map(|_| F::rand(rng)));
+    dealers
+        .iter()
+        .map(|ls| ls.iter().zip(&values).map(|(l, v)| *l * v).sum())
+        .collect()
+}
+
+/// Deal `secrets`, `k` to a sharing, at degree `d`: the shares to send each party.
+fn deal_all<F: Field>(secrets: &[F], d: usize) -> Vec<Vec<F>> {
+    let mut out = vec![Vec::new(); Net::n_parties()];
+    for block in secrets.chunks(pack_size()) {
+        for (o, share) in out.iter_mut().zip(deal(block, d)) {
+            o.push(share);
+        }
+    }
+    out
+}
+
+/// Random packed sharings: `count` of them for each entry of `degrees`, with `deal` giving every
+/// party's shares of each from the `k` random secrets it is passed.
+fn extract_packed<F: FftField>(
+    count: usize,
+    degrees: &[usize],
+    deal: impl Fn(&[F]) -> Vec<Vec<F>>,
+) -> Vec<Vec<GszFieldShare<F>>> {
+    let k = pack_size();
+    let rng = &mut rand::thread_rng();
+    field::extract_rand_with(count, degrees, || {
+        let secrets: Vec<F> = (0..k).map(|_| F::rand(rng)).collect();
+        deal(&secrets)
+    })
+}
+
+fn packed<F: Field>(s: &GszFieldShare<F>) -> PackedFieldShare<F> {
+    PackedFieldShare {
+        val: s.val,
+        degree: s.degree,
+    }
+}
+
+/// Public values, `k` to a share, padding the last with zeros. The shares have degree `k - 1`.
+pub fn pack_public<F: Field>(vals: &[F]) -> Vec<PackedFieldShare<F>> {
+    let ls = lagrange(&slots(), point(Net::party_id()));
+    vals.chunks(pack_size())
+        .map(|c| PackedFieldShare {
+            val: c.iter().zip(&ls).map(|(v, l)| *v * l).sum(),
+            degree: ls.len() - 1,
+        })
+        .collect()
+}
+
+/// `count` groups of `k` random t-shares, each followed by the packed share of all `k`.
+fn pack_masks<F: FftField>(count: usize) -> Vec<Vec<GszFieldShare<F>>> {
+    let k = pack_size();
+    extract_packed(count, &[vec![t(); k], vec![degree()]].concat(), |rs| {
+        let mut dealt: Vec<Vec<F>> = rs.iter().map(|r| field::share_poly(*r, t())).collect();
+        dealt.push(deal(rs, degree()));
+        dealt
+    })
+}
+
+/// Pack GSZ t-shares, `k` to a share, padding the last with zeros.
+///
+/// Each block of `k` is masked with random t-shares whose packed sharing is also known, and
+/// opened. Its packed share is then the public packing of the masked values, less that of the
+/// masks.
+pub fn pack<F: FftField>(shares: &[GszFieldShare<F>]) -> Vec<PackedFieldShare<F>> {
+    let k = pack_size();
+    let blocks = shares.len().div_ceil(k);
+    let masks = pack_masks::<F>(blocks);
+    let padding = GszFieldShare::from_public(F::zero());
+    let masked = GszFieldShare::batch_open(
+        shares
+            .iter()
+            .chain(std::iter::repeat(&padding))
+            .zip(masks.iter().flat_map(|m| &m[..k]))
+            .map(|(x, r)| *x.clone().add(r)),
+    );
+    pack_public(&masked)
+        .into_iter()
+        .zip(&masks)
+        .map(|(mut x, m)| *x.sub(&packed(&m[k])))
+        .collect()
+}
+
+/// How many packings [pack_kept] keeps. Past that, the least recently used go first.
+const MAX_KEPT_PACKINGS: usize = 64;
+
+/// A digest that every party agrees on, and `k`.
+type PackingKey = (Vec<u8>, usize);
+
+type Packing<F> = Arc<Vec<PackedFieldShare<F>>>;
+
+/// Packings from [pack_kept], least recently used first.
+struct KeptPackings<F: Field>(VecDeque<(PackingKey, Packing<F>)>);
+
+impl<F: Field> Default for KeptPackings<F> {
+    fn default() -> Self {
+        Self(VecDeque::new())
+    }
+}
+
+impl<F: Field> KeptPackings<F> {
+    fn get(&mut self, key: &PackingKey) -> Option<Packing<F>> {
+        let i = self.0.iter().position(|(k, _)| k == key)?;
+        let entry = self.0.remove(i).unwrap();
+        let packing = entry.1.clone();
+        self.0.push_back(entry);
+        Some(packing)
+    }
+
+    fn insert(&mut self, key: PackingKey, packing: Packing<F>) {
+        self.0.retain(|(k, _)| *k != key);
+        if self.0.len() == MAX_KEPT_PACKINGS {
+            self.0.pop_front();
+        }
+        self.0.push_back((key, packing));
+    }
+}
+
+/// The key for the packing of `shares`: a digest of every party's digest of its own shares.
+/// Parties broadcast their digests for it, so they all look up (and keep) the same packings.
+fn packing_key<F: FftField>(shares: &[GszFieldShare<F>]) -> PackingKey {
+    let mut bytes = Vec::new();
+    shares.serialize(&mut bytes).unwrap();
+    let digests: Vec<Vec<u8>> = Net::broadcast(&Sha256::digest(&bytes).to_vec());
+    (Sha256::digest(&digests.concat()).to_vec(), pack_size())
+}
+
+/// Remember `packed` as the packing of `shares`, for [pack_kept].
+fn keep_packing<F: FftField>(shares: &[GszFieldShare<F>], packed: Packing<F>) {
+    let key = packing_key(shares);
+    session::with_state(|p: &mut KeptPackings<F>| p.insert(key, packed));
+}
+
+/// [pack], but the packings of the most recently used vectors of shares are kept, as are the
+/// outputs of packed FFTs, so that a witness goes through its FFTs and MSMs packed.
+///
+/// Finding a kept packing costs a broadcast of a digest, rather than the rounds of [pack].
+pub fn pack_kept<F: FftField>(shares: &[GszFieldShare<F>]) -> Packing<F> {
+    let key = packing_key(shares);
+    if let Some(p) = session::with_state(|p: &mut KeptPackings<F>| p.get(&key)) {
+        return p;
+    }
+    let packed = Arc::new(pack(shares));
+    session::with_state(|p: &mut KeptPackings<F>| p.insert(key, packed.clone()));
+    packed
+}
+
+/// GSZ t-shares of the secrets of packed `shares`, `k` for each.
+pub fn unpack<F: FftField>(shares: &[PackedFieldShare<F>]) -> Vec<GszFieldShare<F>> {
+    let k = pack_size();
+    let masks = pack_masks::<F>(shares.len());
+    let masked: Vec<PackedFieldShare<F>> = shares
+        .iter()
+        .zip(&masks)
+        .map(|(x, m)| *x.clone().add(&packed(&m[k])))
+        .collect();
+    open(&masked)
+        .into_iter()
+        .zip(masks.iter().flat_map(|m| &m[..k]))
+        .map(|(x, r)| GszFieldShare {
+            val: x - r.val,
+            degree: r.degree,
+        })
+        .collect()
+}
+
+/// Open packed shares: the secrets of each, in order.
+pub fn open<F: FftField>(shares: &[PackedFieldShare<F>]) -> Vec<F> {
+    let vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+    let all_vals = Net::broadcast(&vals);
+    shares
+        .iter()
+        .enumerate()
+        .flat_map(|(i, s)| {
+            let column: Vec<F> = all_vals.iter().map(|v| v[i]).collect();
+            decode(&column, s.degree)
+        })
+        .collect()
+}
+
+/// Open `shares` to the king, which applies `f` to their secrets and deals its output back out,
+/// `k` to a share, at the usual degree.
+fn through_king<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    f: impl FnOnce(Vec<F>) -> Vec<F>,
+) -> Vec<PackedFieldShare<F>> {
+    let vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+    let king_answer = Net::send_to_king(&vals).map(|all_vals| {
+        let kc_timer = start_timer!(|| "Packed king computation");
+        let secrets = shares
+            .iter()
+            .enumerate()
+            .flat_map(|(i, s)| {
+                let column: Vec<F> = all_vals.iter().map(|v| v[i]).collect();
+                decode(&column, s.degree)
+            })
+            .collect();
+        let out = deal_all(&f(secrets), degree());
+        end_timer!(kc_timer);
+        out
+    });
+    Net::recv_from_king(king_answer)
+        .into_iter()
+        .map(|val| PackedFieldShare {
+            val,
+            degree: degree(),
+        })
+        .collect()
+}
+
+/// Multiply packed shares slot by slot.
+///
+/// A product has twice the usual degree. Masked by a random sharing of that degree, it goes to the
+/// king, which deals it back out at the usual degree, and the parties take the mask off again.
+pub fn mul<F: FftField>(
+    xs: &[PackedFieldShare<F>],
+    ys: &[PackedFieldShare<F>],
+) -> Vec<PackedFieldShare<F>> {
+    assert_eq!(xs.len(), ys.len());
+    let timer = start_timer!(|| format!("Packed mult: {}", xs.len()));
+    let d = degree();
+    let masks = extract_packed::<F>(xs.len(), &[d, 2 * d], |rs| {
+        vec![deal(rs, d), deal(rs, 2 * d)]
+    });
+    let masked: Vec<PackedFieldShare<F>> = xs
+        .iter()
+        .zip(ys)
+        .zip(&masks)
+        .map(|((x, y), m)| PackedFieldShare {
+            val: x.val * y.val + m[1].val,
+            degree: (x.degree + y.degree).max(m[1].degree),
+        })
+        .collect();
+    let out = through_king(&masked, |v| v)
+        .into_iter()
+        .zip(&masks)
+        .map(|(mut z, m)| *z.sub(&packed(&m[0])))
+        .collect();
+    end_timer!(timer);
+    out
+}
+
+/// Apply the public linear map `f` to the secrets of `shares`, `k` to a share; its output comes
+/// back packed the same way.
+///
+/// Parties `0..=t` each deal a random vector, packed both before and after `f`. Their sums are a
+/// random `r` and `f(r)`, which nobody knows, as at least one of the dealers is honest. The king
+/// opens `x + r`, and deals `f(x + r)`, from which the parties take `f(r)`.
+pub fn apply<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    f: impl Fn(&[F]) -> Vec<F>,
+) -> Vec<PackedFieldShare<F>> {
+    let timer = start_timer!(|| format!("Packed linear map: {}", shares.len()));
+    let n = Net::n_parties();
+    let d = degree();
+    let dealers = t() + 1;
+    let to_send = if Net::party_id() < dealers {
+        let rng = &mut rand::thread_rng();
+        let r: Vec<F> = (0..shares.len() * pack_size())
+            .map(|_| F::rand(rng))
+            .collect();
+        let mut out = deal_all(&r, d);
+        for (o, fr) in out.iter_mut().zip(deal_all(&f(&r), d)) {
+            o.extend(fr);
+        }
+        out
+    } else {
+        vec![Vec::new(); n]
+    };
+    let received = Net::all_to_all(&to_send);
+    let masks: Vec<F> = (0..received[0].len())
+        .map(|i| received[..dealers].iter().map(|r| r[i]).sum())
+        .collect();
+    let (r, fr) = masks.split_at(shares.len());
+    let masked: Vec<PackedFieldShare<F>> = shares
+        .iter()
+        .zip(r)
+        .map(|(x, r)| PackedFieldShare {
+            val: x.val + r,
+            degree: x.degree.max(d),
+        })
+        .collect();
+    let out = through_king(&masked, |v| f(&v))
+        .into_iter()
+        .zip(fr)
+        .map(|(mut y, r)| {
+            y.val -= r;
+            y
+        })
+        .collect();
+    end_timer!(timer);
+    out
+}
+
+/// Permute the secrets of `shares`: secret `i` of the output is secret `perm[i]` of the input.
+pub fn permute<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    perm: &[usize],
+) -> Vec<PackedFieldShare<F>> {
+    apply(shares, |v| perm.iter().map(|i| v[*i]).collect())
+}
+
+/// The FFT of the vector packed in `shares`, `k` consecutive entries to a share, over the domain of
+/// size `m = shares.len() * k`. The output is packed the same way. `k` must be a power of two.
+///
+/// With `b = m / k`, the input is `k` interleaved vectors of length `b`, one per slot. Their FFTs
+/// are the first `log b` layers of the whole FFT, and apply to every slot at once, so each party
+/// runs them on its `b` shares. The king does the other `log k` layers, and puts the output in
+/// order.
+pub fn fft<F: FftField>(shares: &[PackedFieldShare<F>]) -> Vec<PackedFieldShare<F>> {
+    dft(shares, F::one(), false)
+}
+
+/// The inverse of [fft].
+pub fn ifft<F: FftField>(shares: &[PackedFieldShare<F>]) -> Vec<PackedFieldShare<F>> {
+    dft(shares, F::one(), true)
+}
+
+/// [fft] over the coset `offset * H` of the domain `H`.
+///
+/// Entry `o * k + l` is scaled by `offset^(o * k)` before the parties' layers, which is the same
+/// for every slot of share `o`, and by `offset^l` in the king's.
+pub fn coset_fft<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    offset: F,
+) -> Vec<PackedFieldShare<F>> {
+    dft(shares, offset, false)
+}
+
+/// The inverse of [coset_fft]. The king scales its output.
+pub fn coset_ifft<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    offset: F,
+) -> Vec<PackedFieldShare<F>> {
+    dft(shares, offset, true)
+}
+
+fn dft<F: FftField>(
+    shares: &[PackedFieldShare<F>],
+    offset: F,
+    inverse: bool,
+) -> Vec<PackedFieldShare<F>> {
+    let k = pack_size();
+    assert!(
+        k.is_power_of_two(),
+        "FFTs need a power-of-two pack size, not {}",
+        k
+    );
+    let b = shares.len();
+    let m = b * k;
+    let domain = Radix2EvaluationDomain::<F>::new(m)
+        .filter(|d| d.size() == m)
+        .unwrap_or_else(|| panic!("No FFT domain of size {}", m));
+    let inner = Radix2EvaluationDomain::<F>::new(b).unwrap();
+    let mut vals: Vec<F> = shares.iter().map(|s| s.val).collect();
+    if inverse {
+        inner.ifft_in_place(&mut vals);
+    } else {
+        let offset_k = offset.pow([k as u64]);
+        let mut pow = F::one();
+        for v in &mut vals {
+            *v *= pow;
+            pow *= offset_k;
+        }
+        inner.fft_in_place(&mut vals);
+    }
+    let degree = shares.iter().map(|s| s.degree).max().unwrap_or(0);
+    let inner_out: Vec<PackedFieldShare<F>> = vals
+        .into_iter()
+        .map(|val| PackedFieldShare { val, degree })
+        .collect();
+    let (w, shift, scale) = if inverse {
+        let k_inv = F::from(k as u64).inverse().unwrap();
+        (domain.group_gen_inv, F::one(), k_inv)
+    } else {
+        (domain.group_gen, offset, F::one())
+    };
+    let offset_inv = offset.inverse().unwrap();
+    // Slot l of share o1 now holds Y[o1][l]; output i = o1 + b * o2 is
+    // sum_l (shift * w^i)^l * Y[o1][l], and then the inverse takes offset^i back off.
+    apply(&inner_out, |y| {
+        let mut out = vec![F::zero(); m];
+        for o1 in 0..b {
+            let ys = &y[o1 * k..(o1 + 1) * k];
+            for o2 in 0..k {
+                let i = (o1 + b * o2) as u64;
+                out[o1 + b * o2] = evaluate(ys, shift * w.pow([i])) * scale;
+                if inverse {
+                    out[o1 + b * o2] *= offset_inv.pow([i]);
+                }
+            }
+        }
+        out
+    })
+}
+
+/// The FFT of GSZ t-`shares` over the coset `offset * H`, or its inverse, done on their packing:
+/// see [FieldShare::coset_fft]. `None` unless [set_packed_ffts] is on and the length is a
+/// multiple of a power-of-two `k` for which there is a domain.
+///
+/// The input's packing comes from [pack_kept], and the output's is kept for it.
+pub fn fft_shares<F: FftField>(
+    shares: &[GszFieldShare<F>],
+    offset: F,
+    inverse: bool,
+) -> Option<Vec<GszFieldShare<F>>> {
+    let k = pack_size();
+    let m = shares.len();
+    let fits = k.is_power_of_two()
+        && m >= k
+        && Radix2EvaluationDomain::<F>::new(m).map(|d| d.size()) == Some(m);
+    if !session::with_state(|p: &mut PackedFfts| p.0) || !fits {
+        return None;
+    }
+    let timer = start_timer!(|| format!("Packed FFT: {}", m));
+    let xs = pack_kept(shares);
+    let ys = Arc::new(dft(&xs, offset, inverse));
+    let out = unpack(&ys);
+    keep_packing(&out, ys);
+    end_timer!(timer);
+    Some(out)
+}
+
+/// Bases packed by [pack_bases], by a digest of the bases and `k`.
+struct PackedBases<G>(HashMap<(Vec<u8>, usize), Arc<Vec<G>>>);
+
+impl<G> Default for PackedBases<G> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// This party's shares of public `bases`, packed `k` to a share.
+///
+/// Packing costs about as much as an MSM over the bases. The prover's keys are the same for every
+/// proof, though, so each set of bases is packed once per session and kept.
+pub fn pack_bases<G: Group>(bases: &[G]) -> Arc<Vec<G>> {
+    let k = pack_size();
+    let mut bytes = Vec::new();
+    bases.serialize(&mut bytes).unwrap();
+    let key = (Sha256::digest(&bytes).to_vec(), k);
+    if let Some(p) = session::with_state(|p: &mut PackedBases<G>| p.0.get(&key).cloned()) {
+        return p;
+    }
+    let timer = start_timer!(|| format!("Packing bases: {}", bases.len()));
+    let ls = lagrange(&slots::<G::ScalarField>(), point(Net::party_id()));
+    let packed_bases: Arc<Vec<G>> = Arc::new(
+        bases
+            .chunks(k)
+            .map(|c| c.iter().zip(&ls).map(|(g, l)| g.mul(l)).sum())
+            .collect(),
+    );
+    end_timer!(timer);
+    session::with_state(|p: &mut PackedBases<G>| p.0.insert(key, packed_bases.clone()));
+    packed_bases
+}
+
+/// This party's t-share of the MSM of public `bases` and the secrets of its t-shares `scalars`,
+/// with `M` for the local MSM.
+///
+/// Each party multiplies its `m / k` packed scalars, kept by [pack_kept], by its shares of the
+/// [pack_bases]. That is a share, of degree `t + 2(k - 1)`, of the `k` partial MSMs
+/// `sum_b x_{b,l} G_{b,l}` in the slots. To sum them, it is masked with a random sharing `r` of
+/// that degree, over a public base, and sent to the king. The king opens it, sums the slots, and
+/// publishes the result, from which the parties take their t-shares of the sum of the slots of `r`.
+///
+/// All of `scalars` is packed, even past the bases, so that the packing is the one kept for them.
+/// Scalars past the last base sit in slots whose packed base is zero.
+pub fn msm<G: Group, M: Msm<G, G::ScalarField>>(bases: &[G], scalars: &[G::ScalarField]) -> G {
+    let timer = start_timer!(|| format!("Packed MSM: {}", scalars.len()));
+    let m = bases.len().min(scalars.len());
+    let scalars: Vec<GszFieldShare<G::ScalarField>> = scalars
+        .iter()
+        .map(|val| GszFieldShare {
+            val: *val,
+            degree: t(),
+        })
+        .collect();
+    let scalars: Vec<G::ScalarField> = pack_kept(&scalars)[..m.div_ceil(pack_size())]
+        .iter()
+        .map(|s| s.val)
+        .collect();
+    let partial = M::msm(&pack_bases(&bases[..m]), &scalars);
+
+    let d = degree() + pack_size() - 1;
+    let mask = extract_packed::<G::ScalarField>(1, &[d, t()], |rs| {
+        vec![deal(rs, d), field::share_poly(rs.iter().sum(), t())]
+    })
+    .pop()
+    .unwrap();
+    let base = group::rand_base::<G>();
+    let king_answer = Net::send_to_king(&(partial + base.mul(&mask[0].val))).map(|all| {
+        let combine = |ls: &[G::ScalarField], gs: &[G]| -> G {
+            ls.iter().zip(gs).map(|(l, g)| g.mul(l)).sum()
+        };
+        let total: G = decode_with(&all, d, combine).into_iter().sum();
+        vec![total; all.len()]
+    });
+    let out = Net::recv_from_king(king_answer) - base.mul(&mask[1].val);
+    end_timer!(timer);
+    out
+}
+
+pub mod msm {
+    use super::*;
+
+    fn run_all_checks<E: PairingEngine>() {
+        let t = start_timer!(|| "All opening checks");
+        field::check_accumulated_field_products::<E::Fr>();
+        group::check_accumulated_group_products::<E::G1Affine, PackedG1AffineMsm<E>>();
+        group::check_accumulated_group_products::<E::G2Affine, PackedG2AffineMsm<E>>();
+        group::check_accumulated_group_products::<E::G1Projective, PackedG1ProjectiveMsm<E>>();
+        group::check_accumulated_group_products::<E::G2Projective, PackedG2ProjectiveMsm<E>>();
+        end_timer!(t);
+    }
+
+    macro_rules! packed_msm {
+        ($name:ident, $g:ident, $local:ident) => {
+            #[derive(Debug, Derivative)]
+            #[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
+            /// A packed MSM with a pre-reveal check.
+            pub struct $name<E: PairingEngine>(pub PhantomData<E>);
+
+            impl<E: PairingEngine> Msm<E::$g, E::Fr> for $name<E> {
+                fn msm(bases: &[E::$g], scalars: &[E::Fr]) -> E::$g {
+                    super::msm::<E::$g, $local<E::$g>>(bases, scalars)
+                }
+                fn pre_reveal_check() {
+                    run_all_checks::<E>();
+                }
+                fn output_degree(_d: usize) -> usize {
+                    t()
+                }
+            }
+        };
+    }
+
+    packed_msm!(PackedG1AffineMsm, G1Affine, AffineMsm);
+    packed_msm!(PackedG2AffineMsm, G2Affine, AffineMsm);
+    packed_msm!(PackedG1ProjectiveMsm, G1Projective, ProjectiveMsm);
+    packed_msm!(PackedG2ProjectiveMsm, G2Projective, ProjectiveMsm);
+}
+
+groups_share!(
+    PackedG1Share,
+    G1Affine,
+    G1Projective,
+    msm::PackedG1AffineMsm<E>,
+    msm::PackedG1ProjectiveMsm<E>
+);
+groups_share!(
+    PackedG2Share,
+    G2Affine,
+    G2Projective,
+    msm::PackedG2AffineMsm<E>,
+    msm::PackedG2ProjectiveMsm<E>
+);
+
+#[derive(Debug, Derivative)]
+#[derivative(
+    Default(bound = ""),
+    Clone(bound = ""),
+    Copy(bound = ""),
+    PartialEq(bound = "E::G1Affine: PartialEq"),
+    Eq(bound = "E::G1Affine: Eq"),
+    Hash(bound = "E::G1Affine: Hash")
+)]
+pub struct PackedPairingShare<E: PairingEngine>(pub PhantomData<E>);
+
+impl<E: PairingEngine> PairingShare<E> for PackedPairingShare<E> {
+    type FrShare = GszFieldShare<E::Fr>;
+    type FqShare = GszFieldShare<E::Fq>;
+    type FqeShare = GszExtFieldShare<E::Fqe>;
+    type FqkShare = GszMulExtFieldShare<E::Fqk, E::Fr>;
+    type G1AffineShare = GszGroupShare<E::G1Affine, msm::PackedG1AffineMsm<E>>;
+    type G2AffineShare = GszGroupShare<E::G2Affine, msm::PackedG2AffineMsm<E>>;
+    type G1ProjectiveShare = GszGroupShare<E::G1Projective, msm::PackedG1ProjectiveMsm<E>>;
+    type G2ProjectiveShare = GszGroupShare<E::G2Projective, msm::PackedG2ProjectiveMsm<E>>;
+    type G1 = PackedG1Share<E>;
+    type G2 = PackedG2Share<E>;
+
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
+    fn fqk_map(
+        s: mul_field::MulFieldShare<E::Fqk, E::Fr>,
+        f: impl Fn(E::Fqk) -> E::Fqk,
+    ) -> mul_field::MulFieldShare<E::Fqk, E::Fr> {
+        mul_field::MulFieldShare {
+            val: f(s.val),
+            degree: s.degree,
+            _phants: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Pedersen verifiable secret sharing, so that parties can check the shares a dealer hands out.
+//!
+//! Plain GSZ king sharing trusts the king: nothing stops it from handing out shares that do not
+//! lie on one degree-t polynomial. Here the dealer also shares a random blinding polynomial, and
+//! commits to the coefficients `a_k` of each sharing polynomial and `b_k` of its blinding
+//! polynomial, as `a_k * G + b_k * H` for public points `G` and `H` of a group over the same scalar
+//! field, with no known discrete log relation. Party `j` then checks its share `s_j` and blinding
+//! share `r_j` by `s_j * G + r_j * H = sum_k (j + 1)^k * (a_k * G + b_k * H)`. Parties whose
+//! shares fail complain, and the dealer must publish their shares; it is caught if it cannot.
+//! Everything that must reach all parties the same (the commitments, complaints and answers) goes
+//! out by echo broadcast, whether or not that is on for the session.
+//!
+//! Unlike Feldman commitments (`a_k * G` alone), these reveal nothing about the secrets.
+//!
+//! Only GSZ field king shares are dealt this way (see [enable]).
+use ark_ec::group::Group;
+use ark_ff::{FftField, Field, One, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use digest::Digest;
+use sha2::Sha256;
+
+use std::fmt::{self, Display, Formatter};
+
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+
+use super::field::GszFieldShare;
+use super::{evaluate, point, t};
+use crate::channel::{or_abort, MpcSerNet};
+use crate::msm::Msm;
+
+/// How a dealer was caught cheating. Every honest party reaches the same verdict, except that
+/// when someone equivocates, some may stop with [VssError::Equivocation] while others carry on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VssError {
+    /// The dealer sent different parties different commitments or answers.
+    Equivocation,
+    /// The dealer sent commitments or answers that do not decode, or too few or too many of
+    /// them.
+    Malformed,
+    /// More than `t` parties complained about their shares.
+    TooManyComplaints(Vec<usize>),
+    /// The shares the dealer published for `party`, after it complained, do not match the
+    /// commitments.
+    BadAnswer { party: usize },
+}
+
+impl Display for VssError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VssError::Equivocation => write!(f, "the dealer equivocated"),
+            VssError::Malformed => write!(f, "the dealer sent malformed commitments or answers"),
+            VssError::TooManyComplaints(parties) => {
+                write!(f, "parties {:?} complained about their shares", parties)
+            }
+            VssError::BadAnswer { party } => {
+                write!(f, "the dealer could not justify the shares of party {}", party)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VssError {}
+
+/// A dealer's sharing of some secrets.
+pub struct Dealing<G: Group> {
+    /// `shares[j][i]` is party `j`'s share of secret `i`.
+    pub shares: Vec<Vec<G::ScalarField>>,
+    /// `blinds[j][i]` is party `j`'s share of the blinding polynomial of secret `i`.
+    pub blinds: Vec<Vec<G::ScalarField>>,
+    /// The commitments to the `t + 1` coefficients of each secret's polynomial, in order.
+    pub commitments: Vec<G>,
+}
+
+impl<G: Group> Dealing<G> {
+    /// Share `fs` with fresh random degree-t polynomials.
+    pub fn new<R: rand::Rng>(fs: &[G::ScalarField], rng: &mut R) -> Self {
+        let (g, h) = commitment_bases::<G>();
+        // A random polynomial with constant term `a_0`, or a random one if `None`.
+        let mut random_poly = |a_0: Option<G::ScalarField>| {
+            let mut coeffs = vec![a_0.unwrap_or_else(|| G::ScalarField::rand(rng))];
+            coeffs.extend((0..t()).map(|_| G::ScalarField::rand(rng)));
+            coeffs
+        };
+        let polys: Vec<Vec<G::ScalarField>> = fs.iter().map(|f| random_poly(Some(*f))).collect();
+        let blind_polys: Vec<Vec<G::ScalarField>> = fs.iter().map(|_| random_poly(None)).collect();
+        let share_out = |polys: &[Vec<G::ScalarField>]| {
+            (0..Net::n_parties())
+                .map(|j| polys.iter().map(|p| evaluate(p, point(j))).collect())
+                .collect()
+        };
+        Self {
+            shares: share_out(&polys),
+            blinds: share_out(&blind_polys),
+            commitments: polys
+                .iter()
+                .flatten()
+                .zip(blind_polys.iter().flatten())
+                .map(|(a, b)| g.mul(a) + h.mul(b))
+                .collect(),
+        }
+    }
+
+    /// Party `j`'s shares and blinding shares.
+    fn shares_of(&self, j: usize) -> (Vec<G::ScalarField>, Vec<G::ScalarField>) {
+        (self.shares[j].clone(), self.blinds[j].clone())
+    }
+}
+
+/// The public base points `G` and `H` for commitments. They are hashed to the group, so nobody
+/// knows the discrete log of `H` to the base `G`, and the dealer cannot open a commitment two
+/// ways.
+fn commitment_bases<G: Group>() -> (G, G) {
+    (
+        crate::share::group::hash_to_group(b"gsz20 vss base"),
+        crate::share::group::hash_to_group(b"gsz20 vss blinding base"),
+    )
+}
+
+/// Whether `s` and `r` are party `j`'s shares of the polynomials committed to by
+/// `commitments`.
+fn check_share<G: Group, M: Msm<G, G::ScalarField>>(
+    j: usize,
+    s: G::ScalarField,
+    r: G::ScalarField,
+    commitments: &[G],
+) -> bool {
+    let x = point::<G::ScalarField>(j);
+    let powers: Vec<G::ScalarField> =
+        std::iter::successors(Some(G::ScalarField::one()), |p| Some(*p * x))
+            .take(commitments.len())
+            .collect();
+    let (g, h) = commitment_bases::<G>();
+    g.mul(&s) + h.mul(&r) == M::msm(commitments, &powers)
+}
+
+/// Whether every party got the same `bytes`, compared by hash. The hashes go out by echo
+/// broadcast, whether or not it is on for the session, so every honest party that gets `true`
+/// knows every other honest party does too.
+fn same_everywhere(bytes: &[u8]) -> bool {
+    let digest = Sha256::digest(bytes).to_vec();
+    Net::echo_broadcast(&digest).is_ok_and(|ds| ds.iter().all(|d| *d == digest))
+}
+
+/// Get our message from the king, which passes one for each party, without decoding it: a king
+/// that sends bytes we cannot decode is cheating, not a bug.
+fn recv_bytes_from_king<T: CanonicalSerialize>(outs: Option<Vec<T>>) -> Vec<u8> {
+    or_abort(Net::recv_bytes_from_king(outs.map(|outs| {
+        outs.iter()
+            .map(|out| {
+                let mut bytes = Vec::new();
+                out.serialize(&mut bytes).unwrap();
+                bytes
+            })
+            .collect()
+    })))
+}
+
+/// Receive `n` secrets dealt by the king, which passes its `dealing`, and check them.
+///
+/// Parties whose shares do not match the commitments complain. The king answers by sending
+/// their shares to everyone; if every answer checks out, the complainers use them instead.
+pub fn receive<G: Group, M: Msm<G, G::ScalarField>>(
+    n: usize,
+    dealing: Option<Dealing<G>>,
+) -> Result<Vec<GszFieldShare<G::ScalarField>>, VssError> {
+    let n_parties = Net::n_parties();
+    let (shares, commitments, dealing) = match dealing {
+        Some(d) => (
+            Some((0..n_parties).map(|j| d.shares_of(j)).collect()),
+            Some(vec![d.commitments.clone(); n_parties]),
+            Some(d),
+        ),
+        None => (None, None, None),
+    };
+    // Shares we cannot decode are as bad as shares that do not match: we complain about both.
+    let (mut shares, blinds): (Vec<G::ScalarField>, Vec<G::ScalarField>) =
+        CanonicalDeserialize::deserialize(&recv_bytes_from_king(shares)[..]).unwrap_or_default();
+    let commitments = recv_bytes_from_king(commitments);
+    if !same_everywhere(&commitments) {
+        return Err(VssError::Equivocation);
+    }
+    let commitments: Vec<G> =
+        CanonicalDeserialize::deserialize(&commitments[..]).map_err(|_| VssError::Malformed)?;
+    let per_poly = t() + 1;
+    if commitments.len() != n * per_poly {
+        return Err(VssError::Malformed);
+    }
+    let me = Net::party_id();
+    let check = |j: usize, shares: &[G::ScalarField], blinds: &[G::ScalarField]| {
+        shares.len() == n
+            && blinds.len() == n
+            && shares
+                .iter()
+                .zip(blinds)
+                .zip(commitments.chunks(per_poly))
+                .all(|((s, r), c)| check_share::<G, M>(j, *s, *r, c))
+    };
+
+    let complaints: Vec<usize> = Net::echo_broadcast(&!check(me, &shares, &blinds))
+        .map_err(|_| VssError::Equivocation)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(j, complained)| complained.then_some(j))
+        .collect();
+    if complaints.len() > t() {
+        return Err(VssError::TooManyComplaints(complaints));
+    }
+    if !complaints.is_empty() {
+        type Answer<F> = (Vec<F>, Vec<F>);
+        let answers = dealing.map(|d| {
+            let answers: Vec<Answer<G::ScalarField>> =
+                complaints.iter().map(|j| d.shares_of(*j)).collect();
+            vec![answers; n_parties]
+        });
+        let answers = recv_bytes_from_king(answers);
+        if !same_everywhere(&answers) {
+            return Err(VssError::Equivocation);
+        }
+        let answers: Vec<Answer<G::ScalarField>> =
+            CanonicalDeserialize::deserialize(&answers[..]).map_err(|_| VssError::Malformed)?;
+        if answers.len() != complaints.len() {
+            return Err(VssError::Malformed);
+        }
+        for (j, (answer, answer_blinds)) in complaints.iter().zip(answers) {
+            if !check(*j, &answer, &answer_blinds) {
+                return Err(VssError::BadAnswer { party: *j });
+            }
+            if *j == me {
+                shares = answer;
+            }
+        }
+    }
+    Ok(shares
+        .into_iter()
+        .map(|val| GszFieldShare { val, degree: t() })
+        .collect())
+}
+
+/// Have the king deal `fs` with Pedersen VSS over `G`. Every other party passes as many (ignored)
+/// values.
+pub fn king_share_batch<G: Group, M: Msm<G, G::ScalarField>>(
+    fs: Vec<G::ScalarField>,
+) -> Result<Vec<GszFieldShare<G::ScalarField>>, VssError> {
+    let dealing = if Net::am_king() {
+        Some(Dealing::new(&fs, &mut rand::thread_rng()))
+    } else {
+        None
+    };
+    receive::<G, M>(fs.len(), dealing)
+}
+
+type Dealer<F> = fn(Vec<F>) -> Result<Vec<GszFieldShare<F>>, VssError>;
+
+/// How king shares of `F` are dealt in this session: with VSS, or (if `None`) by trusting the
+/// king.
+struct VssMode<F: Field>(Option<Dealer<F>>);
+
+impl<F: Field> Default for VssMode<F> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+/// From now on in this session, deal GSZ king shares of `G::ScalarField` with Pedersen VSS over
+/// `G`. A cheating king then makes `king_share` panic with a [VssError].
+///
+/// That is the only king sharing this covers. GSZ group and extension field king shares stay
+/// inputs masked by random sharings: those always lie on a degree-t polynomial, but only echo
+/// broadcast (see [crate::channel::set_echo_broadcast]) keeps the king from giving parties shares
+/// of different values. Additive and SPDZ king shares are inputs too, and any additive shares are
+/// consistent; SPDZ ones are then checked by their MACs when opened.
+pub fn enable<G: Group, M: Msm<G, G::ScalarField>>() {
+    session::with_state(|m: &mut VssMode<G::ScalarField>| m.0 = Some(king_share_batch::<G, M>));
+}
+
+/// Go back to trusting the king with king shares of `F`.
+pub fn disable<F: FftField>() {
+    session::with_state(|m: &mut VssMode<F>| m.0 = None);
+}
+
+/// The VSS dealer for king shares of `F`, if enabled.
+pub(super) fn dealer<F: FftField>() -> Option<Dealer<F>> {
+    session::with_state(|m: &mut VssMode<F>| m.0)
+}
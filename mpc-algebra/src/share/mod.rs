@@ -5,12 +5,16 @@ pub use group::*;
 pub mod pairing;
 pub use pairing::*;
 pub mod msm;
+pub mod beaver;
+pub mod bits;
 pub mod add;
 pub use add::*;
 pub mod spdz;
 pub use spdz::*;
 pub mod gsz20;
 pub use gsz20::*;
+pub mod rss;
+pub use rss::*;
 
 use std::marker::PhantomData;
 use derivative::Derivative;
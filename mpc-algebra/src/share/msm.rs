@@ -6,6 +6,11 @@ use std::marker::PhantomData;
 pub trait Msm<G, S>: Send + Sync + 'static {
     fn msm(bases: &[G], scalars: &[S]) -> G;
     fn pre_reveal_check() {}
+    /// For Shamir shares: the degree of the sharing `msm` gives, from scalars shared at degree
+    /// `d`. A local MSM keeps the degree; one that reshares its output may not.
+    fn output_degree(d: usize) -> usize {
+        d
+    }
 }
 
 #[derive(Debug, Derivative)]
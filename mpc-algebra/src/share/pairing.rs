@@ -60,4 +60,27 @@ pub trait PairingShare<E: PairingEngine>:
         AffineShare = Self::G2AffineShare,
         ProjectiveShare = Self::G2ProjectiveShare,
     >;
+
+    /// Map a shared G1 point into a shared `Fqk` element, through `f`.
+    ///
+    /// Each party applies `f` to its own share, so `f` must be a homomorphism into the
+    /// multiplicative group of `Fqk`. The Miller loop with a fixed (public) G2 point is one only up
+    /// to the kernel of the final exponentiation, so its output must be exponentiated (see
+    /// [PairingShare::fqk_map]) before it is opened.
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> <Self::FqkShare as ExtFieldShare<E::Fqk>>::Ext;
+    /// Map a shared G2 point into a shared `Fqk` element, through `f`. See
+    /// [PairingShare::g1_map_to_fqk].
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> <Self::FqkShare as ExtFieldShare<E::Fqk>>::Ext;
+    /// Map a shared `Fqk` element through `f`, an endomorphism of the multiplicative group of
+    /// `Fqk` (e.g. the final exponentiation).
+    fn fqk_map(
+        s: <Self::FqkShare as ExtFieldShare<E::Fqk>>::Ext,
+        f: impl Fn(E::Fqk) -> E::Fqk,
+    ) -> <Self::FqkShare as ExtFieldShare<E::Fqk>>::Ext;
 }
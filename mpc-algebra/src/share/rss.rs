@@ -0,0 +1,874 @@
+//! Replicated secret sharing for three parties, with an honest majority.
+//!
+//! A secret `x` is split into additive shares `x_0 + x_1 + x_2`, and party `i` holds `x_i` and
+//! `x_{i+1}` (indices mod 3). Any two parties can open `x`; one party alone learns nothing.
+//!
+//! Linear operations are local. So, almost, are products: party `i` can compute
+//! `x_i y_i + x_i y_{i+1} + x_{i+1} y_i`, and these sum to `xy`. It masks its sum with a share of
+//! zero and sends it to party `i - 1`, which restores the replication. That is one round, and one
+//! field element per product per party, with no preprocessing. Zero shares and random shares come
+//! from generators that each party seeds together with its neighbours, so they are free after
+//! setup.
+//!
+//! This is secure against one semi-honest party. When opening, each party also checks the share
+//! its neighbour sends against its own copy, which catches a party that lies at that point (but
+//! not one that cheats in a multiplication).
+use derivative::Derivative;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use ark_ec::{group::Group, PairingEngine, ProjectiveCurve};
+use ark_ff::bytes::{FromBytes, ToBytes};
+use ark_ff::prelude::*;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
+    CanonicalSerializeWithFlags, Flags, SerializationError,
+};
+
+use std::cmp::Ord;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+use crate::channel::MpcSerNet;
+
+use super::add::{AdditiveFieldShare, AdditiveGroupShare, MulFieldShare};
+use super::field::{DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare};
+use super::group::GroupShare;
+use super::msm::*;
+use super::pairing::{AffProjShare, PairingShare};
+use super::{BeaverSource, PanicBeaverSource};
+use crate::Reveal;
+
+/// The generators this party shares with its neighbours: `mine` with the previous party (which
+/// runs it as its `next`), and `next` with the next party.
+struct Prss {
+    mine: StdRng,
+    next: StdRng,
+}
+
+/// This session's generators, once seeded.
+#[derive(Default)]
+struct PrssState(Option<Prss>);
+
+fn prev_party() -> usize {
+    (Net::party_id() + Net::n_parties() - 1) % Net::n_parties()
+}
+
+fn next_party() -> usize {
+    (Net::party_id() + 1) % Net::n_parties()
+}
+
+/// Whether this party holds the king's component as its `next`. Public values are added to the
+/// king's component.
+fn next_is_king() -> bool {
+    next_party() == Net::king()
+}
+
+/// Run `f` on this party's generators, seeding them first if this session has none yet.
+fn with_prss<R>(f: impl FnOnce(&mut Prss) -> R) -> R {
+    if session::with_state(|p: &mut PrssState| p.0.is_none()) {
+        let n = Net::n_parties();
+        assert_eq!(n, 3, "Replicated secret sharing needs exactly three parties");
+        let mut mine = <StdRng as SeedableRng>::Seed::default();
+        rand::thread_rng().fill(&mut mine);
+        let mut outs = vec![Vec::new(); n];
+        outs[prev_party()] = mine.to_vec();
+        let mut next = <StdRng as SeedableRng>::Seed::default();
+        next.copy_from_slice(&Net::all_to_all(&outs)[next_party()]);
+        session::with_state(|p: &mut PrssState| {
+            p.0 = Some(Prss {
+                mine: StdRng::from_seed(mine),
+                next: StdRng::from_seed(next),
+            })
+        });
+    }
+    session::with_state(|p: &mut PrssState| f(p.0.as_mut().unwrap()))
+}
+
+/// Send this party's components `zs` to the previous party, and get the next party's.
+fn reshare<T: CanonicalSerialize + CanonicalDeserialize + Clone>(zs: &[T]) -> Vec<T> {
+    let mut outs = vec![Vec::new(); Net::n_parties()];
+    outs[prev_party()] = zs.to_vec();
+    Net::all_to_all(&outs).swap_remove(next_party())
+}
+
+/// Every party's components of some shares, given this party's (`shs`) and its copies of the next
+/// party's (`nexts`), which the next party's must match.
+fn open_components<T: CanonicalSerialize + CanonicalDeserialize + PartialEq>(
+    shs: Vec<T>,
+    nexts: &[T],
+) -> Vec<Vec<T>> {
+    let all = Net::broadcast(&shs);
+    check_components(&all, nexts);
+    all
+}
+
+/// As [open_components], but to `party` only.
+fn open_components_to<T: CanonicalSerialize + CanonicalDeserialize + PartialEq>(
+    party: usize,
+    shs: Vec<T>,
+    nexts: &[T],
+) -> Option<Vec<Vec<T>>> {
+    let all = Net::send_to(party, &shs)?;
+    check_components(&all, nexts);
+    Some(all)
+}
+
+fn check_components<T: PartialEq>(all: &[Vec<T>], nexts: &[T]) {
+    assert!(
+        all[next_party()] == nexts,
+        "Party {} opened shares that do not match their copies",
+        next_party()
+    );
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RssFieldShare<T> {
+    /// This party's additive component.
+    pub sh: AdditiveFieldShare<T>,
+    /// The next party's additive component.
+    pub next: AdditiveFieldShare<T>,
+}
+
+macro_rules! impl_rss_basics {
+    ($share:ident, $bound:ident $(, $m:ident)?) => {
+        impl<T: $bound $(, $m)?> Display for $share<T $(, $m)?> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.sh)
+            }
+        }
+        impl<T: $bound $(, $m)?> Debug for $share<T $(, $m)?> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "{:?}", self.sh)
+            }
+        }
+        impl<T: $bound $(, $m)?> ToBytes for $share<T $(, $m)?> {
+            fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+                self.sh.write(&mut writer)?;
+                self.next.write(writer)
+            }
+        }
+        impl<T: $bound $(, $m)?> FromBytes for $share<T $(, $m)?> {
+            fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    sh: FromBytes::read(&mut reader)?,
+                    next: FromBytes::read(reader)?,
+                })
+            }
+        }
+        impl<T: $bound $(, $m)?> CanonicalSerialize for $share<T $(, $m)?> {
+            fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+                self.sh.serialize(&mut writer)?;
+                self.next.serialize(writer)
+            }
+            fn serialized_size(&self) -> usize {
+                self.sh.serialized_size() + self.next.serialized_size()
+            }
+        }
+        impl<T: $bound $(, $m)?> CanonicalSerializeWithFlags for $share<T $(, $m)?> {
+            fn serialize_with_flags<W: Write, F: Flags>(
+                &self,
+                mut writer: W,
+                flags: F,
+            ) -> Result<(), SerializationError> {
+                self.sh.serialize(&mut writer)?;
+                self.next.serialize_with_flags(writer, flags)
+            }
+
+            fn serialized_size_with_flags<F: Flags>(&self) -> usize {
+                self.sh.serialized_size() + self.next.serialized_size_with_flags::<F>()
+            }
+        }
+        impl<T: $bound $(, $m)?> CanonicalDeserialize for $share<T $(, $m)?> {
+            fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    sh: CanonicalDeserialize::deserialize(&mut reader)?,
+                    next: CanonicalDeserialize::deserialize(reader)?,
+                })
+            }
+        }
+        impl<T: $bound $(, $m)?> CanonicalDeserializeWithFlags for $share<T $(, $m)?> {
+            fn deserialize_with_flags<R: Read, F: Flags>(
+                mut reader: R,
+            ) -> Result<(Self, F), SerializationError> {
+                let sh = CanonicalDeserialize::deserialize(&mut reader)?;
+                let (next, flags) = CanonicalDeserializeWithFlags::deserialize_with_flags(reader)?;
+                Ok((Self { sh, next }, flags))
+            }
+        }
+    };
+}
+
+impl_rss_basics!(RssFieldShare, Field);
+
+impl<F: Field> RssFieldShare<F> {
+    fn new(sh: F, next: F) -> Self {
+        Self {
+            sh: AdditiveFieldShare { val: sh },
+            next: AdditiveFieldShare { val: next },
+        }
+    }
+}
+
+impl<F: Field> UniformRand for RssFieldShare<F> {
+    fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
+        with_prss(|p| Self::new(F::rand(&mut p.mine), F::rand(&mut p.next)))
+    }
+}
+
+impl<F: Field> Reveal for RssFieldShare<F> {
+    type Base = F;
+
+    fn reveal(self) -> F {
+        Self::batch_open(vec![self]).pop().unwrap()
+    }
+    fn reveal_to(self, party: usize) -> Option<F> {
+        open_components_to(party, vec![self.sh.val], &[self.next.val])
+            .map(|all| all.iter().map(|v| v[0]).sum())
+    }
+    fn from_public(f: F) -> Self {
+        Self {
+            sh: Reveal::from_public(f),
+            next: AdditiveFieldShare {
+                val: if next_is_king() { f } else { F::zero() },
+            },
+        }
+    }
+    fn from_add_shared(f: F) -> Self {
+        Self::from_add_shared_batch(vec![f]).pop().unwrap()
+    }
+    /// Mask each additive share with a share of zero, and send it to the previous party.
+    fn from_add_shared_batch(fs: Vec<F>) -> Vec<Self> {
+        let zs: Vec<F> = with_prss(|p| {
+            fs.into_iter()
+                .map(|f| f + F::rand(&mut p.mine) - F::rand(&mut p.next))
+                .collect()
+        });
+        let nexts = reshare(&zs);
+        zs.into_iter()
+            .zip(nexts)
+            .map(|(sh, next)| Self::new(sh, next))
+            .collect()
+    }
+    fn unwrap_as_public(self) -> F {
+        self.sh.val
+    }
+    fn king_share<R: Rng>(f: F, rng: &mut R) -> Self {
+        Self::from_add_shared(AdditiveFieldShare::king_share(f, rng).val)
+    }
+    fn king_share_batch<R: Rng>(fs: Vec<F>, rng: &mut R) -> Vec<Self> {
+        Self::from_add_shared_batch(
+            AdditiveFieldShare::king_share_batch(fs, rng)
+                .into_iter()
+                .map(|s| s.val)
+                .collect(),
+        )
+    }
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        Self::from_add_shared_batch(
+            AdditiveFieldShare::input_from_batch(owner, fs)
+                .into_iter()
+                .map(|s| s.val)
+                .collect(),
+        )
+    }
+}
+
+impl<F: Field> FieldShare<F> for RssFieldShare<F> {
+    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+        let (shs, nexts): (Vec<F>, Vec<F>) =
+            selfs.into_iter().map(|s| (s.sh.val, s.next.val)).unzip();
+        let all = open_components(shs, &nexts);
+        (0..nexts.len())
+            .map(|i| all.iter().map(|v| v[i]).sum())
+            .collect()
+    }
+
+    fn batch_rand<R: Rng>(n: usize, rng: &mut R) -> Vec<Self> {
+        (0..n).map(|_| Self::rand(rng)).collect()
+    }
+
+    fn add(&mut self, other: &Self) -> &mut Self {
+        self.sh.add(&other.sh);
+        self.next.add(&other.next);
+        self
+    }
+
+    fn sub(&mut self, other: &Self) -> &mut Self {
+        self.sh.sub(&other.sh);
+        self.next.sub(&other.next);
+        self
+    }
+
+    fn scale(&mut self, other: &F) -> &mut Self {
+        self.sh.scale(other);
+        self.next.scale(other);
+        self
+    }
+
+    fn shift(&mut self, other: &F) -> &mut Self {
+        self.sh.shift(other);
+        if next_is_king() {
+            self.next.val += other;
+        }
+        self
+    }
+
+    fn mul<S: BeaverSource<Self, Self, Self>>(self, other: Self, source: &mut S) -> Self {
+        Self::batch_mul(vec![self], vec![other], source)
+            .pop()
+            .unwrap()
+    }
+
+    /// Multiply locally, into additive shares, and replicate those.
+    fn batch_mul<S: BeaverSource<Self, Self, Self>>(
+        xs: Vec<Self>,
+        ys: Vec<Self>,
+        _source: &mut S,
+    ) -> Vec<Self> {
+        Self::from_add_shared_batch(
+            xs.iter()
+                .zip(&ys)
+                .map(|(x, y)| x.sh.val * (y.sh.val + y.next.val) + x.next.val * y.sh.val)
+                .collect(),
+        )
+    }
+
+    fn inv<S: BeaverSource<Self, Self, Self>>(self, source: &mut S) -> Self {
+        Self::batch_inv(vec![self], source).pop().unwrap()
+    }
+
+    /// Open `x * r` for a random `r`, and scale `r` by its inverse.
+    fn batch_inv<S: BeaverSource<Self, Self, Self>>(xs: Vec<Self>, source: &mut S) -> Vec<Self> {
+        let rs = Self::batch_rand(xs.len(), &mut rand::thread_rng());
+        let xrs = Self::batch_open(Self::batch_mul(xs, rs.clone(), source));
+        rs.into_iter()
+            .zip(xrs)
+            .map(|(mut r, xr)| {
+                r.scale(&xr.inverse().unwrap());
+                r
+            })
+            .collect()
+    }
+
+    fn partial_products<S: BeaverSource<Self, Self, Self>>(x: Vec<Self>, src: &mut S) -> Vec<Self> {
+        let n = x.len();
+        let m = Self::batch_rand(n + 1, &mut rand::thread_rng());
+        let m_inv = Self::batch_inv(m.clone(), src);
+        let mx = Self::batch_mul(m[..n].to_vec(), x, src);
+        let mxm = Self::batch_mul(mx, m_inv[1..].to_vec(), src);
+        let mut mxm_pub = Self::batch_open(mxm);
+        for i in 1..mxm_pub.len() {
+            let last = mxm_pub[i - 1];
+            mxm_pub[i] *= &last;
+        }
+        let mms = Self::batch_mul(vec![m[0]; n], m_inv[1..].to_vec(), src);
+        let mut mms_inv = Self::batch_inv(mms, src);
+        for (m, p) in mms_inv.iter_mut().zip(&mxm_pub) {
+            m.scale(p);
+        }
+        mms_inv
+    }
+
+    /// Division by a public polynomial is linear, so divide each component.
+    fn univariate_div_qr<'a>(
+        num: DenseOrSparsePolynomial<Self>,
+        den: DenseOrSparsePolynomial<F>,
+    ) -> Option<(DensePolynomial<Self>, DensePolynomial<Self>)> {
+        let (sh, next) = match num {
+            Ok(p) => {
+                let (sh, next) = p.into_iter().map(|s| (s.sh, s.next)).unzip();
+                (Ok(sh), Ok(next))
+            }
+            Err(p) => {
+                let (sh, next) = p.into_iter().map(|(i, s)| ((i, s.sh), (i, s.next))).unzip();
+                (Err(sh), Err(next))
+            }
+        };
+        let (sh_q, sh_r) = AdditiveFieldShare::univariate_div_qr(sh, den.clone())?;
+        let (next_q, next_r) = AdditiveFieldShare::univariate_div_qr(next, den)?;
+        let zip = |shs: DensePolynomial<AdditiveFieldShare<F>>, nexts| {
+            shs.into_iter()
+                .zip(nexts)
+                .map(|(sh, next)| Self { sh, next })
+                .collect()
+        };
+        Some((zip(sh_q, next_q), zip(sh_r, next_r)))
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(
+    Default(bound = "T: Default"),
+    Clone(bound = "T: Clone"),
+    Copy(bound = "T: Copy"),
+    PartialEq(bound = "T: PartialEq"),
+    Eq(bound = "T: Eq"),
+    PartialOrd(bound = "T: PartialOrd"),
+    Ord(bound = "T: Ord"),
+    Hash(bound = "T: Hash")
+)]
+pub struct RssGroupShare<T, M> {
+    /// This party's additive component.
+    pub sh: AdditiveGroupShare<T, M>,
+    /// The next party's additive component.
+    pub next: AdditiveGroupShare<T, M>,
+}
+
+impl_rss_basics!(RssGroupShare, Group, M);
+
+impl<G: Group, M> RssGroupShare<G, M> {
+    fn new(sh: G, next: G) -> Self {
+        Self {
+            sh: Reveal::from_add_shared(sh),
+            next: Reveal::from_add_shared(next),
+        }
+    }
+}
+
+impl<G: Group, M> UniformRand for RssGroupShare<G, M> {
+    fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
+        with_prss(|p| Self::new(G::rand(&mut p.mine), G::rand(&mut p.next)))
+    }
+}
+
+impl<G: Group, M> Reveal for RssGroupShare<G, M> {
+    type Base = G;
+
+    fn reveal(self) -> G {
+        let all = open_components(vec![self.sh.val], &[self.next.val]);
+        all.iter().map(|v| v[0]).sum()
+    }
+    fn reveal_to(self, party: usize) -> Option<G> {
+        open_components_to(party, vec![self.sh.val], &[self.next.val])
+            .map(|all| all.iter().map(|v| v[0]).sum())
+    }
+    fn from_public(g: G) -> Self {
+        Self {
+            sh: Reveal::from_public(g),
+            next: Reveal::from_add_shared(if next_is_king() { g } else { G::zero() }),
+        }
+    }
+    fn from_add_shared(g: G) -> Self {
+        Self::from_add_shared_batch(vec![g]).pop().unwrap()
+    }
+    /// As for field shares, mask with shares of zero and send to the previous party.
+    fn from_add_shared_batch(gs: Vec<G>) -> Vec<Self> {
+        let zs: Vec<G> = with_prss(|p| {
+            gs.into_iter()
+                .map(|g| g + G::rand(&mut p.mine) - G::rand(&mut p.next))
+                .collect()
+        });
+        let nexts = reshare(&zs);
+        zs.into_iter()
+            .zip(nexts)
+            .map(|(sh, next)| Self::new(sh, next))
+            .collect()
+    }
+    fn unwrap_as_public(self) -> G {
+        self.sh.val
+    }
+    fn king_share<R: Rng>(g: G, rng: &mut R) -> Self {
+        Self::from_add_shared(AdditiveGroupShare::<G, M>::king_share(g, rng).val)
+    }
+    fn king_share_batch<R: Rng>(gs: Vec<G>, rng: &mut R) -> Vec<Self> {
+        Self::from_add_shared_batch(
+            AdditiveGroupShare::<G, M>::king_share_batch(gs, rng)
+                .into_iter()
+                .map(|s| s.val)
+                .collect(),
+        )
+    }
+    fn input_from_batch(owner: usize, gs: Vec<Option<G>>) -> Vec<Self> {
+        Self::from_add_shared_batch(
+            AdditiveGroupShare::<G, M>::input_from_batch(owner, gs)
+                .into_iter()
+                .map(|s| s.val)
+                .collect(),
+        )
+    }
+}
+
+impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for RssGroupShare<G, M> {
+    type FieldShare = RssFieldShare<G::ScalarField>;
+
+    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<G> {
+        let (shs, nexts): (Vec<G>, Vec<G>) =
+            selfs.into_iter().map(|s| (s.sh.val, s.next.val)).unzip();
+        let all = open_components(shs, &nexts);
+        (0..nexts.len())
+            .map(|i| all.iter().map(|v| v[i]).sum())
+            .collect()
+    }
+
+    fn add(&mut self, other: &Self) -> &mut Self {
+        self.sh.add(&other.sh);
+        self.next.add(&other.next);
+        self
+    }
+
+    fn sub(&mut self, other: &Self) -> &mut Self {
+        self.sh.sub(&other.sh);
+        self.next.sub(&other.next);
+        self
+    }
+
+    fn scale_pub_scalar(&mut self, scalar: &G::ScalarField) -> &mut Self {
+        self.sh.scale_pub_scalar(scalar);
+        self.next.scale_pub_scalar(scalar);
+        self
+    }
+
+    fn scale_pub_group(base: G, scalar: &Self::FieldShare) -> Self {
+        Self {
+            sh: AdditiveGroupShare::scale_pub_group(base, &scalar.sh),
+            next: AdditiveGroupShare::scale_pub_group(base, &scalar.next),
+        }
+    }
+
+    fn shift(&mut self, other: &G) -> &mut Self {
+        self.sh.shift(other);
+        if next_is_king() {
+            self.next.val += other;
+        }
+        self
+    }
+
+    /// Multiply locally, as for field shares.
+    fn scale<S: BeaverSource<Self, Self::FieldShare, Self>>(
+        self,
+        other: Self::FieldShare,
+        _source: &mut S,
+    ) -> Self {
+        let z =
+            self.sh.val.mul(&(other.sh.val + other.next.val)) + self.next.val.mul(&other.sh.val);
+        Self::from_add_shared(z)
+    }
+
+    fn multi_scale_pub_group(bases: &[G], scalars: &[Self::FieldShare]) -> Self {
+        let (shs, nexts): (Vec<_>, Vec<_>) = scalars.iter().map(|s| (s.sh, s.next)).unzip();
+        Self {
+            sh: AdditiveGroupShare::multi_scale_pub_group(bases, &shs),
+            next: AdditiveGroupShare::multi_scale_pub_group(bases, &nexts),
+        }
+    }
+}
+
+#[derive(Debug, Derivative)]
+#[derivative(
+    Default(bound = ""),
+    Clone(bound = ""),
+    Copy(bound = ""),
+    PartialEq(bound = "F: PartialEq"),
+    Eq(bound = "F: Eq"),
+    Hash(bound = "F: Hash")
+)]
+pub struct RssExtFieldShare<F: Field>(pub PhantomData<F>);
+
+impl<F: Field> ExtFieldShare<F> for RssExtFieldShare<F> {
+    type Ext = RssFieldShare<F>;
+    type Base = RssFieldShare<F::BasePrimeField>;
+}
+
+/// A replicated share of a product: the components multiply to the secret.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RssMulFieldShare<T> {
+    /// This party's multiplicative component.
+    pub sh: MulFieldShare<T>,
+    /// The next party's multiplicative component.
+    pub next: MulFieldShare<T>,
+}
+
+impl_rss_basics!(RssMulFieldShare, Field);
+
+impl<F: Field> UniformRand for RssMulFieldShare<F> {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::from_add_shared(<F as UniformRand>::rand(rng))
+    }
+}
+
+impl<F: Field> Reveal for RssMulFieldShare<F> {
+    type Base = F;
+
+    fn reveal(self) -> F {
+        Self::batch_open(vec![self]).pop().unwrap()
+    }
+    fn reveal_to(self, party: usize) -> Option<F> {
+        open_components_to(party, vec![self.sh.val], &[self.next.val])
+            .map(|all| all.iter().map(|v| v[0]).product())
+    }
+    fn from_public(f: F) -> Self {
+        Self {
+            sh: Reveal::from_public(f),
+            next: MulFieldShare {
+                val: if next_is_king() { f } else { F::one() },
+            },
+        }
+    }
+    /// Mask with a multiplicative share of one, and send to the previous party.
+    fn from_add_shared(f: F) -> Self {
+        let z = with_prss(|p| f * F::rand(&mut p.mine) / F::rand(&mut p.next));
+        Self {
+            sh: MulFieldShare { val: z },
+            next: MulFieldShare {
+                val: reshare(&[z])[0],
+            },
+        }
+    }
+    fn unwrap_as_public(self) -> F {
+        self.sh.val
+    }
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        MulFieldShare::input_from_batch(owner, fs)
+            .into_iter()
+            .map(|s| Self::from_add_shared(s.val))
+            .collect()
+    }
+}
+
+impl<F: Field> RssMulFieldShare<F> {
+    /// Replicated additive shares of the same secrets. Each party inputs its components, and the
+    /// inputs are multiplied together.
+    fn batch_to_additive(xs: &[Self]) -> Vec<RssFieldShare<F>> {
+        let me = Net::party_id();
+        (0..Net::n_parties())
+            .map(|owner| {
+                let mine = xs
+                    .iter()
+                    .map(|x| (owner == me).then_some(x.sh.val))
+                    .collect();
+                RssFieldShare::input_from_batch(owner, mine)
+            })
+            .reduce(|acc, zs| RssFieldShare::batch_mul(acc, zs, &mut PanicBeaverSource::default()))
+            .unwrap()
+    }
+
+    /// Multiplicative shares of the same secrets as `xs`.
+    ///
+    /// Takes random multiplicative shares `r`, opens `x / r`, and scales `r` by it. That reveals
+    /// whether `x` is zero, but nothing else.
+    fn batch_from_additive(xs: Vec<RssFieldShare<F>>) -> Vec<Self> {
+        let rng = &mut rand::thread_rng();
+        let rs: Vec<Self> = (0..xs.len()).map(|_| Self::rand(rng)).collect();
+        let r_invs: Vec<Self> = rs
+            .iter()
+            .map(|r| r.inv(&mut PanicBeaverSource::default()))
+            .collect();
+        let r_invs = Self::batch_to_additive(&r_invs);
+        let ys = RssFieldShare::batch_open(RssFieldShare::batch_mul(
+            xs,
+            r_invs,
+            &mut PanicBeaverSource::default(),
+        ));
+        rs.into_iter()
+            .zip(ys)
+            .map(|(mut r, y)| {
+                r.scale(&y);
+                r
+            })
+            .collect()
+    }
+
+    /// Apply `f` to replicated additive shares of this secret, and convert back.
+    fn via_additive(&mut self, f: impl FnOnce(&mut RssFieldShare<F>)) -> &mut Self {
+        let mut x = Self::batch_to_additive(&[*self]).pop().unwrap();
+        f(&mut x);
+        *self = Self::batch_from_additive(vec![x]).pop().unwrap();
+        self
+    }
+}
+
+impl<F: Field> FieldShare<F> for RssMulFieldShare<F> {
+    fn map_homo<FF: Field, SS: FieldShare<FF>, Fun: Fn(F) -> FF>(self, f: Fun) -> SS {
+        Self::batch_to_additive(&[self]).pop().unwrap().map_homo(f)
+    }
+
+    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+        let (shs, nexts): (Vec<F>, Vec<F>) =
+            selfs.into_iter().map(|s| (s.sh.val, s.next.val)).unzip();
+        let all = open_components(shs, &nexts);
+        (0..nexts.len())
+            .map(|i| all.iter().map(|v| v[i]).product())
+            .collect()
+    }
+
+    /// Products do not add locally, so this goes through additive shares, which takes a few
+    /// rounds.
+    fn add(&mut self, other: &Self) -> &mut Self {
+        let other = Self::batch_to_additive(&[*other]).pop().unwrap();
+        self.via_additive(|x| {
+            x.add(&other);
+        })
+    }
+
+    fn scale(&mut self, other: &F) -> &mut Self {
+        self.sh.scale(other);
+        if next_is_king() {
+            self.next.val *= other;
+        }
+        self
+    }
+
+    fn shift(&mut self, other: &F) -> &mut Self {
+        self.via_additive(|x| {
+            x.shift(other);
+        })
+    }
+
+    fn mul<S: BeaverSource<Self, Self, Self>>(self, other: Self, _source: &mut S) -> Self {
+        Self {
+            sh: self.sh.mul(other.sh, &mut PanicBeaverSource::default()),
+            next: self.next.mul(other.next, &mut PanicBeaverSource::default()),
+        }
+    }
+
+    fn batch_mul<S: BeaverSource<Self, Self, Self>>(
+        xs: Vec<Self>,
+        ys: Vec<Self>,
+        source: &mut S,
+    ) -> Vec<Self> {
+        xs.into_iter()
+            .zip(ys)
+            .map(|(x, y)| x.mul(y, source))
+            .collect()
+    }
+
+    fn inv<S: BeaverSource<Self, Self, Self>>(self, _source: &mut S) -> Self {
+        Self {
+            sh: self.sh.inv(&mut PanicBeaverSource::default()),
+            next: self.next.inv(&mut PanicBeaverSource::default()),
+        }
+    }
+
+    fn batch_inv<S: BeaverSource<Self, Self, Self>>(xs: Vec<Self>, source: &mut S) -> Vec<Self> {
+        xs.into_iter().map(|x| x.inv(source)).collect()
+    }
+}
+
+#[derive(Debug, Derivative)]
+#[derivative(
+    Default(bound = ""),
+    Clone(bound = ""),
+    Copy(bound = ""),
+    PartialEq(bound = "F: PartialEq"),
+    Eq(bound = "F: Eq"),
+    Hash(bound = "F: Hash")
+)]
+pub struct RssMulExtFieldShare<F: Field>(pub PhantomData<F>);
+
+impl<F: Field> ExtFieldShare<F> for RssMulExtFieldShare<F> {
+    type Ext = RssMulFieldShare<F>;
+    type Base = RssMulFieldShare<F::BasePrimeField>;
+}
+
+macro_rules! groups_share {
+    ($struct_name:ident, $affine:ident, $proj:ident) => {
+        pub struct $struct_name<E: PairingEngine>(pub PhantomData<E>);
+
+        impl<E: PairingEngine> AffProjShare<E::Fr, E::$affine, E::$proj> for $struct_name<E> {
+            type FrShare = RssFieldShare<E::Fr>;
+            type AffineShare = RssGroupShare<E::$affine, AffineMsm<E::$affine>>;
+            type ProjectiveShare = RssGroupShare<E::$proj, ProjectiveMsm<E::$proj>>;
+
+            fn sh_aff_to_proj(g: Self::AffineShare) -> Self::ProjectiveShare {
+                RssGroupShare {
+                    sh: g.sh.map_homo(|s| s.into()),
+                    next: g.next.map_homo(|s| s.into()),
+                }
+            }
+
+            fn sh_proj_to_aff(g: Self::ProjectiveShare) -> Self::AffineShare {
+                RssGroupShare {
+                    sh: g.sh.map_homo(|s| s.into()),
+                    next: g.next.map_homo(|s| s.into()),
+                }
+            }
+
+            fn add_sh_proj_sh_aff(
+                mut a: Self::ProjectiveShare,
+                o: &Self::AffineShare,
+            ) -> Self::ProjectiveShare {
+                a.sh.val.add_assign_mixed(&o.sh.val);
+                a.next.val.add_assign_mixed(&o.next.val);
+                a
+            }
+            fn add_sh_proj_pub_aff(
+                mut a: Self::ProjectiveShare,
+                o: &E::$affine,
+            ) -> Self::ProjectiveShare {
+                if Net::am_king() {
+                    a.sh.val.add_assign_mixed(&o);
+                }
+                if next_is_king() {
+                    a.next.val.add_assign_mixed(&o);
+                }
+                a
+            }
+            fn add_pub_proj_sh_aff(a: &E::$proj, o: Self::AffineShare) -> Self::ProjectiveShare {
+                let mut o = Self::sh_aff_to_proj(o);
+                o.shift(a);
+                o
+            }
+        }
+    };
+}
+
+groups_share!(RssG1Share, G1Affine, G1Projective);
+groups_share!(RssG2Share, G2Affine, G2Projective);
+
+#[derive(Debug, Derivative)]
+#[derivative(
+    Default(bound = ""),
+    Clone(bound = ""),
+    Copy(bound = ""),
+    PartialEq(bound = "E::G1Affine: PartialEq"),
+    Eq(bound = "E::G1Affine: Eq"),
+    Hash(bound = "E::G1Affine: Hash")
+)]
+pub struct RssPairingShare<E: PairingEngine>(pub PhantomData<E>);
+
+impl<E: PairingEngine> PairingShare<E> for RssPairingShare<E> {
+    type FrShare = RssFieldShare<E::Fr>;
+    type FqShare = RssFieldShare<E::Fq>;
+    type FqeShare = RssExtFieldShare<E::Fqe>;
+    // Not a typo. We want a multiplicative subgroup.
+    type FqkShare = RssMulExtFieldShare<E::Fqk>;
+    type G1AffineShare = RssGroupShare<E::G1Affine, AffineMsm<E::G1Affine>>;
+    type G2AffineShare = RssGroupShare<E::G2Affine, AffineMsm<E::G2Affine>>;
+    type G1ProjectiveShare = RssGroupShare<E::G1Projective, ProjectiveMsm<E::G1Projective>>;
+    type G2ProjectiveShare = RssGroupShare<E::G2Projective, ProjectiveMsm<E::G2Projective>>;
+    type G1 = RssG1Share<E>;
+    type G2 = RssG2Share<E>;
+
+    // Each component maps on its own, as for additive shares.
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> RssMulFieldShare<E::Fqk> {
+        RssMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            next: MulFieldShare { val: f(s.next.val) },
+        }
+    }
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> RssMulFieldShare<E::Fqk> {
+        RssMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            next: MulFieldShare { val: f(s.next.val) },
+        }
+    }
+    fn fqk_map(
+        s: RssMulFieldShare<E::Fqk>,
+        f: impl Fn(E::Fqk) -> E::Fqk,
+    ) -> RssMulFieldShare<E::Fqk> {
+        RssMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            next: MulFieldShare { val: f(s.next.val) },
+        }
+    }
+}
@@ -15,9 +15,14 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::ops::Sub;
 
-use mpc_net::{MpcNet, MpcMultiNet as Net};
-use crate::channel::{can_cheat, MpcSerNet};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use mpc_net::{session, ActiveNet as Net, MpcNet};
+use crate::channel::{self, MpcSerNet};
+use crate::ot;
 
 use super::add::{AdditiveFieldShare, AdditiveGroupShare, MulFieldShare};
 use super::field::{DenseOrSparsePolynomial, DensePolynomial, ExtFieldShare, FieldShare};
@@ -27,29 +32,355 @@ use super::pairing::{AffProjShare, PairingShare};
 use super::{BeaverSource, PanicBeaverSource};
 use crate::Reveal;
 
+/// MAC key shares, by field, for the current session.
+#[derive(Default)]
+struct MacKeys(HashMap<TypeId, Box<dyn Any + Send>>);
+
+/// Whether to run MAC checks in identifiable-abort mode, for the current session.
+#[derive(Default)]
+struct IdentifiableAbort(bool);
+
+/// A failed MAC check: some opened value is not the one the parties computed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MacCheckFailed {
+    /// The parties caught cheating in the MAC check itself. Only found in identifiable-abort
+    /// mode, and empty if the shares were corrupted before they were opened.
+    pub culprits: Vec<usize>,
+}
+
+impl Display for MacCheckFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.culprits.is_empty() {
+            write!(f, "MAC check failed; the cheater is unknown")
+        } else {
+            write!(f, "MAC check failed; parties {:?} cheated", self.culprits)
+        }
+    }
+}
+
+impl std::error::Error for MacCheckFailed {}
+
+/// Turn identifiable-abort mode on or off for the rest of the session.
+///
+/// In this mode, each party commits to its shares, MAC shares and MAC key share before opening
+/// anything. If a MAC check then fails, everyone opens those commitments, and every party whose
+/// part of the check does not follow from them is named in the [MacCheckFailed]. This costs one
+/// more (small) broadcast per opening. Opening the commitments reveals the MAC key shares, so
+/// call [clear_mac_keys] before carrying on without the culprits.
+///
+/// A party that corrupts its shares before they are opened still causes a failure, but cannot
+/// be named: with a single global MAC key, its shares look like anyone else's.
+pub fn set_identifiable_abort(on: bool) {
+    session::with_state(|m: &mut IdentifiableAbort| m.0 = on);
+}
+
+fn identifiable_abort() -> bool {
+    session::with_state(|m: &mut IdentifiableAbort| m.0)
+}
+
+/// Open values with the MAC check of _Pragmatic MPC_ 6.6.2, given this party's shares of them
+/// (`vals`) and of their MACs (`macs`).
+///
+/// `tag(x, key)` is `x` times the MAC key share `key`.
+pub(crate) fn mac_checked_open<T, K>(
+    vals: Vec<T>,
+    macs: Vec<T>,
+    key: K,
+    tag: impl Fn(&T, &K) -> T,
+) -> Result<Vec<T>, MacCheckFailed>
+where
+    T: Copy + Eq + Zero + Sub<Output = T> + CanonicalSerialize + CanonicalDeserialize,
+    K: CanonicalSerialize + CanonicalDeserialize,
+{
+    let n = vals.len();
+    let committed = identifiable_abort().then(|| {
+        let mut bytes = Vec::new();
+        vals.serialize(&mut bytes).unwrap();
+        macs.serialize(&mut bytes).unwrap();
+        key.serialize(&mut bytes).unwrap();
+        let (commitment, opening) = channel::commit(&bytes);
+        (Net::consistent_broadcast(&commitment), opening)
+    });
+    let all_vals = Net::consistent_broadcast(&vals);
+    let xs: Vec<T> = (0..n)
+        .map(|i| all_vals.iter().fold(T::zero(), |acc, v| acc + v[i]))
+        .collect();
+    let dx_ts: Vec<T> = macs.iter().zip(&xs).map(|(m, x)| tag(x, &key) - *m).collect();
+    let all_dx_ts: Vec<Vec<T>> = Net::atomic_broadcast(&dx_ts);
+    if (0..n).all(|i| all_dx_ts.iter().fold(T::zero(), |acc, d| acc + d[i]).is_zero()) {
+        return Ok(xs);
+    }
+    let culprits = match committed {
+        None => Vec::new(),
+        Some((commitments, opening)) => {
+            let openings: Vec<Vec<u8>> = Net::broadcast(&opening);
+            // Whether party j's shares, MAC shares and key share, as committed, give what it
+            // sent.
+            let honest = |j: usize| -> Option<bool> {
+                let mut r = channel::try_open_commitment(&commitments[j], &openings[j])?;
+                let vals = Vec::<T>::deserialize(&mut r).ok()?;
+                let macs = Vec::<T>::deserialize(&mut r).ok()?;
+                let key = K::deserialize(&mut r).ok()?;
+                Some(
+                    vals == all_vals[j]
+                        && macs.len() == n
+                        && (0..n).all(|i| tag(&xs[i], &key) - macs[i] == all_dx_ts[j][i]),
+                )
+            };
+            (0..Net::n_parties())
+                .filter(|j| honest(*j) != Some(true))
+                .collect()
+        }
+    };
+    Err(MacCheckFailed { culprits })
+}
+
+/// Unused input masks, by field, for the current session.
+#[derive(Default)]
+struct InputMaskStore(HashMap<TypeId, Box<dyn Any + Send>>);
+
+/// Input masks owned by each party: the owner's value (zero for everyone else) and its shares.
+type InputMasks<F> = Vec<Vec<(F, SpdzFieldShare<F>)>>;
+
+/// Number of input masks generated at a time, per owner.
+pub const INPUT_MASK_BATCH_SIZE: usize = 256;
+
+/// This party's share of the global MAC key for prime field `K`.
+///
+/// The SPDZ setup phase: each party samples its share uniformly, the first time it is needed.
+/// The global key (the sum of the shares) is never known to anyone.
+pub fn mac_key_share<K: PrimeField>() -> K {
+    session::with_state(|keys: &mut MacKeys| {
+        *keys
+            .0
+            .entry(TypeId::of::<K>())
+            .or_insert_with(|| Box::new(K::rand(&mut rand::thread_rng())))
+            .downcast_ref::<K>()
+            .unwrap()
+    })
+}
+
+/// Forget this party's MAC key shares (and any input masks made with them).
+///
+/// Shares created before this call can no longer be opened.
+pub fn clear_mac_keys() {
+    session::with_state(|keys: &mut MacKeys| keys.0.clear());
+    session::with_state(|masks: &mut InputMaskStore| masks.0.clear());
+}
+
 #[inline]
+/// This party's MAC key share, embedded in `F`.
+///
+/// Extension fields are authenticated with a key from their base prime field.
 pub fn mac_share<F: Field>() -> F {
-    if Net::am_king() {
-        F::one()
-    } else {
-        F::zero()
+    let k = mac_key_share::<F::BasePrimeField>();
+    let mut elems = vec![F::BasePrimeField::zero(); F::extension_degree() as usize];
+    elems[0] = k;
+    F::from_base_prime_field_elems(&elems).unwrap()
+}
+
+/// Generate `n` input masks owned by `owner`: random values `r` known to `owner`, shared with
+/// MACs.
+///
+/// The MACs of `r` are computed with OLEs between `owner` and every other party (see
+/// [crate::ot]), coordinate by coordinate over `F`'s base prime field. The OLEs let the owner
+/// authenticate different values towards different parties, so the masks are checked as in
+/// MASCOT's input protocol (<https://ia.cr/2016/505>, figure 7): one extra mask is made, and a
+/// random combination of all of them is opened, with its MAC checked. The extra mask hides the
+/// combination, and is thrown away.
+///
+/// A cheating owner passes the check only if its errors happen to cancel, which depends on bits
+/// of the other parties' MAC key shares. So it can learn a key bit by guessing it, but a wrong
+/// guess (half the time) aborts the computation.
+fn gen_input_masks<F: Field>(owner: usize, n: usize) -> Vec<(F, SpdzFieldShare<F>)> {
+    let mut masks = gen_unchecked_input_masks(owner, n + 1);
+    let mut coins = Net::coin_rng();
+    let (_, mut check) = masks.pop().unwrap();
+    for (_, sh) in &masks {
+        let mut term = *sh;
+        term.scale(&F::rand(&mut coins));
+        check.add(&term);
     }
+    if let Err(e) = check.try_reveal() {
+        Net::abort();
+        panic!("bad input masks from party {}: {}", owner, e);
+    }
+    masks
 }
 
-#[inline]
-/// A huge cheat. Useful for importing shares.
-pub fn mac<F: Field>() -> F {
-    if can_cheat() {
-        F::one()
-    } else {
-        panic!("Attempted to grab the MAC secret while cheating was not allowed")
+/// [gen_input_masks], without the check.
+fn gen_unchecked_input_masks<F: Field>(owner: usize, n: usize) -> Vec<(F, SpdzFieldShare<F>)> {
+    let rng = &mut rand::thread_rng();
+    let d = F::extension_degree() as usize;
+    let am_owner = Net::party_id() == owner;
+    let coords: Vec<F::BasePrimeField> = (0..n * d)
+        .map(|_| {
+            if am_owner {
+                F::BasePrimeField::rand(rng)
+            } else {
+                F::BasePrimeField::zero()
+            }
+        })
+        .collect();
+    let keys = vec![mac_key_share::<F::BasePrimeField>(); n * d];
+    let macs = ot::mul_add_shared(&coords, &keys);
+    coords
+        .chunks(d)
+        .zip(macs.chunks(d))
+        .map(|(r, mac)| {
+            let r = F::from_base_prime_field_elems(r).unwrap();
+            let mac = F::from_base_prime_field_elems(mac).unwrap();
+            (
+                r,
+                SpdzFieldShare {
+                    sh: AdditiveFieldShare::from_add_shared(r),
+                    mac: AdditiveFieldShare::from_add_shared(mac),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Take `n` input masks owned by `owner`, generating more if we have run out.
+///
+/// Each mask is the owner's value (zero for everyone else) and its shares.
+fn take_input_masks<F: Field>(owner: usize, n: usize) -> Vec<(F, SpdzFieldShare<F>)> {
+    let id = TypeId::of::<F>();
+    let mut masks: Box<InputMasks<F>> = session::with_state(|m: &mut InputMaskStore| m.0.remove(&id))
+        .map(|m| m.downcast().unwrap())
+        .unwrap_or_default();
+    masks.resize(Net::n_parties(), Vec::new());
+    let have = masks[owner].len();
+    if have < n {
+        let more = gen_input_masks(owner, std::cmp::max(INPUT_MASK_BATCH_SIZE, n - have));
+        masks[owner].extend(more);
     }
+    let rest = masks[owner].len() - n;
+    let out = masks[owner].split_off(rest);
+    session::with_state(|m: &mut InputMaskStore| m.0.insert(id, masks));
+    out
+}
+
+/// The owner's inputs, or zeros (which are ignored) for everyone else.
+fn owned_inputs<T: Zero>(owner: usize, xs: Vec<Option<T>>) -> Vec<T> {
+    let am_owner = Net::party_id() == owner;
+    xs.into_iter()
+        .map(|x| {
+            if am_owner {
+                x.expect("The input owner must know its inputs")
+            } else {
+                T::zero()
+            }
+        })
+        .collect()
+}
+
+/// Authenticated input of the `xs` held by `owner`.
+///
+/// The owner broadcasts `x - r` for a fresh mask `r`, and everyone shifts their share of `r` by
+/// it. Other parties' `xs` are ignored, but must have the same length.
+fn input_from_owner<F: Field>(owner: usize, xs: Vec<F>) -> Vec<SpdzFieldShare<F>> {
+    let masks = take_input_masks::<F>(owner, xs.len());
+    let ds: Vec<F> = xs.iter().zip(&masks).map(|(x, (r, _))| *x - r).collect();
+    let ds = Net::broadcast(&ds).swap_remove(owner);
+    masks
+        .into_iter()
+        .zip(ds)
+        .map(|((_, mut sh), d)| {
+            sh.shift(&d);
+            sh
+        })
+        .collect()
+}
+
+/// Authenticated input of the sums of the `xs` over all parties.
+///
+/// Every party inputs its `xs` under its own masks; all the differences travel in one broadcast.
+fn input_add_shared<F: Field>(xs: Vec<F>) -> Vec<SpdzFieldShare<F>> {
+    let n = Net::n_parties();
+    let masks: InputMasks<F> = (0..n).map(|o| take_input_masks::<F>(o, xs.len())).collect();
+    let me = Net::party_id();
+    let ds: Vec<F> = xs.iter().zip(&masks[me]).map(|(x, (r, _))| *x - r).collect();
+    let all_ds = Net::broadcast(&ds);
+    (0..xs.len())
+        .map(|i| {
+            let mut sh = masks[0][i].1;
+            for m in &masks[1..] {
+                sh.add(&m[i].1);
+            }
+            sh.shift(&all_ds.iter().map(|ds| ds[i]).sum());
+            sh
+        })
+        .collect()
+}
+
+/// A fixed, public base point for group input masks.
+///
+/// A group mask is `s * P` for a field mask `s`, so it is uniform in the (prime-order) group
+/// and needs no preprocessing of its own.
+fn group_mask_base<G: Group>() -> G {
+    super::group::hash_to_group(b"spdz group input mask base")
+}
+
+fn group_masks<G: Group, M>(
+    masks: Vec<(G::ScalarField, SpdzFieldShare<G::ScalarField>)>,
+) -> Vec<(G, SpdzGroupShare<G, M>)> {
+    let base = group_mask_base::<G>();
+    masks
+        .into_iter()
+        .map(|(s, sh)| {
+            (
+                base.mul(&s),
+                SpdzGroupShare {
+                    sh: AdditiveGroupShare::from_add_shared(base.mul(&sh.sh.val)),
+                    mac: AdditiveGroupShare::from_add_shared(base.mul(&sh.mac.val)),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Group analogue of [input_from_owner].
+fn group_input_from_owner<G: Group, M>(owner: usize, xs: Vec<G>) -> Vec<SpdzGroupShare<G, M>> {
+    let masks = group_masks(take_input_masks::<G::ScalarField>(owner, xs.len()));
+    let ds: Vec<G> = xs.iter().zip(&masks).map(|(x, (r, _))| *x - r).collect();
+    let ds = Net::broadcast(&ds).swap_remove(owner);
+    masks
+        .into_iter()
+        .zip(ds)
+        .map(|((_, mut sh), d)| {
+            sh.shift_impl(&d);
+            sh
+        })
+        .collect()
+}
+
+/// Group analogue of [input_add_shared].
+fn group_input_add_shared<G: Group, M>(xs: Vec<G>) -> Vec<SpdzGroupShare<G, M>> {
+    let n = Net::n_parties();
+    let masks: Vec<Vec<(G, SpdzGroupShare<G, M>)>> = (0..n)
+        .map(|o| group_masks(take_input_masks::<G::ScalarField>(o, xs.len())))
+        .collect();
+    let me = Net::party_id();
+    let ds: Vec<G> = xs.iter().zip(&masks[me]).map(|(x, (r, _))| *x - r).collect();
+    let all_ds = Net::broadcast(&ds);
+    (0..xs.len())
+        .map(|i| {
+            let mut sh = masks[0][i].1;
+            for m in &masks[1..] {
+                sh.sh.val += &m[i].1.sh.val;
+                sh.mac.val += &m[i].1.mac.val;
+            }
+            sh.shift_impl(&all_ds.iter().map(|ds| ds[i]).sum());
+            sh
+        })
+        .collect()
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SpdzFieldShare<T> {
-    sh: AdditiveFieldShare<T>,
-    mac: AdditiveFieldShare<T>,
+    pub(crate) sh: AdditiveFieldShare<T>,
+    pub(crate) mac: AdditiveFieldShare<T>,
 }
 
 macro_rules! impl_basics_spdz {
@@ -65,46 +396,57 @@ macro_rules! impl_basics_spdz {
             }
         }
         impl<T: $bound> ToBytes for $share<T> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+                self.sh.write(&mut writer)?;
+                self.mac.write(writer)
             }
         }
         impl<T: $bound> FromBytes for $share<T> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    sh: FromBytes::read(&mut reader)?,
+                    mac: FromBytes::read(reader)?,
+                })
             }
         }
         impl<T: $bound> CanonicalSerialize for $share<T> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+                self.sh.serialize(&mut writer)?;
+                self.mac.serialize(writer)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.sh.serialized_size() + self.mac.serialized_size()
             }
         }
         impl<T: $bound> CanonicalSerializeWithFlags for $share<T> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                self.sh.serialize(&mut writer)?;
+                self.mac.serialize_with_flags(writer, flags)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                self.sh.serialized_size() + self.mac.serialized_size_with_flags::<F>()
             }
         }
         impl<T: $bound> CanonicalDeserialize for $share<T> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    sh: CanonicalDeserialize::deserialize(&mut reader)?,
+                    mac: CanonicalDeserialize::deserialize(reader)?,
+                })
             }
         }
         impl<T: $bound> CanonicalDeserializeWithFlags for $share<T> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let sh = CanonicalDeserialize::deserialize(&mut reader)?;
+                let (mac, flags) = CanonicalDeserializeWithFlags::deserialize_with_flags(reader)?;
+                Ok((Self { sh, mac }, flags))
             }
         }
         impl<T: $bound> UniformRand for $share<T> {
@@ -120,14 +462,15 @@ impl<F: Field> Reveal for SpdzFieldShare<F> {
     type Base = F;
 
     fn reveal(self) -> F {
-        let vals: Vec<F> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
-        let x: F = vals.iter().sum();
-        let dx_t: F = mac_share::<F>() * x - self.mac.val;
-        let all_dx_ts: Vec<F> = Net::atomic_broadcast(&dx_t);
-        let sum: F = all_dx_ts.iter().sum();
-        assert!(sum.is_zero());
-        x
+        self.try_reveal().unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn reveal_to(self, party: usize) -> Option<F> {
+        // Open x + r, for a mask r known only to `party`. The opening checks the MAC as usual.
+        let (r, r_sh) = take_input_masks::<F>(party, 1).pop().unwrap();
+        let mut masked = self;
+        masked.add(&r_sh);
+        let y = masked.reveal();
+        (Net::party_id() == party).then(|| y - r)
     }
     fn from_public(f: F) -> Self {
         Self {
@@ -136,52 +479,58 @@ impl<F: Field> Reveal for SpdzFieldShare<F> {
         }
     }
     fn from_add_shared(f: F) -> Self {
-        Self {
-            sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared(f * mac::<F>()),
-        }
+        input_add_shared(vec![f]).pop().unwrap()
     }
-    fn king_share<R: Rng>(f: Self::Base, rng: &mut R) -> Self {
-        let mut r: Vec<F> = (0..(Net::n_parties()-1)).map(|_| F::rand(rng)).collect();
-        let sum_r: F = r.iter().sum();
-        r.push(f - sum_r);
-        Self::from_add_shared(Net::recv_from_king( if Net::am_king() { Some(r) } else { None }))
+    fn from_add_shared_batch(fs: Vec<F>) -> Vec<Self> {
+        input_add_shared(fs)
     }
-    fn king_share_batch<R: Rng>(f: Vec<Self::Base>, rng: &mut R) -> Vec<Self> {
-        let mut rs: Vec<Vec<Self::Base>> =
-            (0..(Net::n_parties()-1)).map(|_| {
-            (0..f.len()).map(|_| {
-                F::rand(rng)
-            }).collect()
-        }).collect();
-        let final_shares: Vec<Self::Base> = (0..rs[0].len()).map(|i| {
-            f[i] - &rs.iter().map(|r| &r[i]).sum()
-        }).collect();
-        rs.push(final_shares);
-        Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
+    fn king_share<R: Rng>(f: Self::Base, _rng: &mut R) -> Self {
+        input_from_owner(Net::king(), vec![f]).pop().unwrap()
+    }
+    fn king_share_batch<R: Rng>(f: Vec<Self::Base>, _rng: &mut R) -> Vec<Self> {
+        input_from_owner(Net::king(), f)
+    }
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        input_from_owner(owner, owned_inputs(owner, fs))
+    }
+}
+
+impl<F: Field> SpdzFieldShare<F> {
+    /// Open shares, or report a failed MAC check.
+    pub fn try_batch_open(selfs: impl IntoIterator<Item = Self>) -> Result<Vec<F>, MacCheckFailed> {
+        let (vals, macs) = selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
+        mac_checked_open(vals, macs, mac_share::<F>(), |x, k| *k * x)
+    }
+
+    /// Open this share, or report a failed MAC check.
+    pub fn try_reveal(self) -> Result<F, MacCheckFailed> {
+        Self::try_batch_open(vec![self]).map(|mut fs| fs.pop().unwrap())
     }
 }
 
+/// Pair up the value and MAC share polynomials of a shared polynomial.
+///
+/// Each was computed locally, with its own leading zeros trimmed, so the shorter one is padded
+/// with zeros.
+fn zip_poly<F: Field>(
+    mut sh: DensePolynomial<AdditiveFieldShare<F>>,
+    mut mac: DensePolynomial<AdditiveFieldShare<F>>,
+) -> DensePolynomial<SpdzFieldShare<F>> {
+    let len = sh.len().max(mac.len());
+    sh.resize(len, AdditiveFieldShare::from_add_shared(F::zero()));
+    mac.resize(len, AdditiveFieldShare::from_add_shared(F::zero()));
+    sh.into_iter()
+        .zip(mac)
+        .map(|(sh, mac)| SpdzFieldShare { sh, mac })
+        .collect()
+}
+
 impl<F: Field> FieldShare<F> for SpdzFieldShare<F> {
     fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
-        let (s_vals, macs): (Vec<F>, Vec<F>) =
-            selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
-        let n = s_vals.len();
-        let all_vals = Net::broadcast(&s_vals);
-        let vals: Vec<F> =
-            (0..n).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect();
-        let dx_ts: Vec<F> =
-            macs
-            .iter()
-            .zip(vals.iter())
-            .map(|(mac, val)| mac_share::<F>() * val - mac)
-            .collect();
-        let all_dx_ts: Vec<Vec<F>> = Net::atomic_broadcast(&dx_ts);
-        for i in 0..n {
-            let sum: F = all_dx_ts.iter().map(|dx_ts| &dx_ts[i]).sum();
-            assert!(sum.is_zero());
-        }
-        vals
+        Self::try_batch_open(selfs).unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn batch_rand<R: Rng>(n: usize, rng: &mut R) -> Vec<Self> {
+        Self::from_add_shared_batch((0..n).map(|_| F::rand(rng)).collect())
     }
     fn add(&mut self, other: &Self) -> &mut Self {
         self.sh.add(&other.sh);
@@ -227,16 +576,7 @@ impl<F: Field> FieldShare<F> for SpdzFieldShare<F> {
         };
         let (q_sh, r_sh) = AdditiveFieldShare::univariate_div_qr(num_sh, den.clone()).unwrap();
         let (q_mac, r_mac) = AdditiveFieldShare::univariate_div_qr(num_mac, den).unwrap();
-        Some((
-            q_sh.into_iter()
-                .zip(q_mac)
-                .map(|(sh, mac)| Self { sh, mac })
-                .collect(),
-            r_sh.into_iter()
-                .zip(r_mac)
-                .map(|(sh, mac)| Self { sh, mac })
-                .collect(),
-        ))
+        Some((zip_poly(q_sh, q_mac), zip_poly(r_sh, r_mac)))
     }
 }
 
@@ -260,18 +600,15 @@ impl<G: Group, M> Reveal for SpdzGroupShare<G, M> {
     type Base = G;
 
     fn reveal(self) -> G {
-        let vals: Vec<G> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
-        let x: G = vals.iter().sum();
-        let dx_t: G = {
-            let mut t = x.clone();
-            t *= mac_share::<G::ScalarField>();
-            t - self.mac.val
-        };
-        let all_dx_ts: Vec<G> = Net::atomic_broadcast(&dx_t);
-        let sum: G = all_dx_ts.iter().sum();
-        assert!(sum.is_zero());
-        x
+        self.try_reveal().unwrap_or_else(|e| panic!("{}", e))
+    }
+    fn reveal_to(self, party: usize) -> Option<G> {
+        let (r, r_sh) = group_masks::<G, M>(take_input_masks(party, 1)).pop().unwrap();
+        let mut masked = self;
+        masked.sh.val += r_sh.sh.val;
+        masked.mac.val += r_sh.mac.val;
+        let y = masked.reveal();
+        (Net::party_id() == party).then(|| y - r)
     }
     fn from_public(f: G) -> Self {
         Self {
@@ -284,37 +621,44 @@ impl<G: Group, M> Reveal for SpdzGroupShare<G, M> {
         }
     }
     fn from_add_shared(f: G) -> Self {
-        Self {
-            sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared({
-                let mut t = f;
-                t *= mac::<G::ScalarField>();
-                t
-            }),
-        }
+        group_input_add_shared(vec![f]).pop().unwrap()
+    }
+    fn from_add_shared_batch(fs: Vec<G>) -> Vec<Self> {
+        group_input_add_shared(fs)
+    }
+    fn king_share<R: Rng>(f: Self::Base, _rng: &mut R) -> Self {
+        group_input_from_owner(Net::king(), vec![f]).pop().unwrap()
+    }
+    fn king_share_batch<R: Rng>(f: Vec<Self::Base>, _rng: &mut R) -> Vec<Self> {
+        group_input_from_owner(Net::king(), f)
     }
-    fn king_share<R: Rng>(f: Self::Base, rng: &mut R) -> Self {
-        let mut r: Vec<G> = (0..(Net::n_parties()-1)).map(|_| G::rand(rng)).collect();
-        let sum_r: G = r.iter().sum();
-        r.push(f - sum_r);
-        Self::from_add_shared(Net::recv_from_king( if Net::am_king() { Some(r) } else { None }))
-    }
-    fn king_share_batch<R: Rng>(f: Vec<Self::Base>, rng: &mut R) -> Vec<Self> {
-        let mut rs: Vec<Vec<Self::Base>> =
-            (0..(Net::n_parties()-1)).map(|_| {
-            (0..f.len()).map(|_| {
-                Self::Base::rand(rng)
-            }).collect()
-        }).collect();
-        let final_shares: Vec<Self::Base> = (0..rs[0].len()).map(|i| {
-            f[i] - &rs.iter().map(|r| &r[i]).sum()
-        }).collect();
-        rs.push(final_shares);
-        Net::recv_from_king(if Net::am_king() { Some(rs) } else {None}).into_iter().map(Self::from_add_shared).collect()
+    fn input_from_batch(owner: usize, fs: Vec<Option<G>>) -> Vec<Self> {
+        group_input_from_owner(owner, owned_inputs(owner, fs))
     }
 }
+
+impl<G: Group, M> SpdzGroupShare<G, M> {
+    /// Open shares, or report a failed MAC check.
+    pub fn try_batch_open(selfs: impl IntoIterator<Item = Self>) -> Result<Vec<G>, MacCheckFailed> {
+        let (vals, macs) = selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
+        mac_checked_open(vals, macs, mac_share::<G::ScalarField>(), |x, k| x.mul(k))
+    }
+
+    /// Open this share, or report a failed MAC check.
+    pub fn try_reveal(self) -> Result<G, MacCheckFailed> {
+        Self::try_batch_open(vec![self]).map(|mut gs| gs.pop().unwrap())
+    }
+
+    fn shift_impl(&mut self, other: &G) {
+        if Net::am_king() {
+            self.sh.val += other;
+        }
+        self.mac.val += other.mul(&mac_share::<G::ScalarField>());
+    }
+}
+
 macro_rules! impl_spdz_basics_2_param {
-    ($share:ident, $bound:ident) => {
+    ($share:ident, $bound:ident $(, $phants:ident)?) => {
         impl<T: $bound, M> Display for $share<T, M> {
             fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 write!(f, "{}", self.sh.val)
@@ -326,52 +670,66 @@ macro_rules! impl_spdz_basics_2_param {
             }
         }
         impl<T: $bound, M> ToBytes for $share<T, M> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("write")
+            fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+                self.sh.write(&mut writer)?;
+                self.mac.write(writer)
             }
         }
         impl<T: $bound, M> FromBytes for $share<T, M> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    sh: FromBytes::read(&mut reader)?,
+                    mac: FromBytes::read(reader)?,
+                    $($phants: PhantomData,)?
+                })
             }
         }
         impl<T: $bound, M> CanonicalSerialize for $share<T, M> {
-            fn serialize<W: Write>(&self, _writer: W) -> Result<(), SerializationError> {
-                unimplemented!("serialize")
+            fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+                self.sh.serialize(&mut writer)?;
+                self.mac.serialize(writer)
             }
             fn serialized_size(&self) -> usize {
-                unimplemented!("serialized_size")
+                self.sh.serialized_size() + self.mac.serialized_size()
             }
         }
         impl<T: $bound, M> CanonicalSerializeWithFlags for $share<T, M> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
-                _flags: F,
+                mut writer: W,
+                flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                self.sh.serialize(&mut writer)?;
+                self.mac.serialize_with_flags(writer, flags)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                self.sh.serialized_size() + self.mac.serialized_size_with_flags::<F>()
             }
         }
         impl<T: $bound, M> CanonicalDeserialize for $share<T, M> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    sh: CanonicalDeserialize::deserialize(&mut reader)?,
+                    mac: CanonicalDeserialize::deserialize(reader)?,
+                    $($phants: PhantomData,)?
+                })
             }
         }
         impl<T: $bound, M> CanonicalDeserializeWithFlags for $share<T, M> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                mut reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
-            }
-        }
-        impl<T: $bound, M> UniformRand for $share<T, M> {
-            fn rand<R: Rng + ?Sized>(_rng: &mut R) -> Self {
-                todo!()
-                //Self::from_add_shared(<T as UniformRand>::rand(rng))
+                let sh = CanonicalDeserialize::deserialize(&mut reader)?;
+                let (mac, flags) = CanonicalDeserializeWithFlags::deserialize_with_flags(reader)?;
+                Ok((
+                    Self {
+                        sh,
+                        mac,
+                        $($phants: PhantomData,)?
+                    },
+                    flags,
+                ))
             }
         }
     };
@@ -379,28 +737,17 @@ macro_rules! impl_spdz_basics_2_param {
 
 impl_spdz_basics_2_param!(SpdzGroupShare, Group);
 
+impl<G: Group, M> UniformRand for SpdzGroupShare<G, M> {
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::from_add_shared(<G as UniformRand>::rand(rng))
+    }
+}
+
 impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for SpdzGroupShare<G, M> {
     type FieldShare = SpdzFieldShare<G::ScalarField>;
 
     fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<G> {
-        let (s_vals, macs): (Vec<G>, Vec<G>) =
-            selfs.into_iter().map(|s| (s.sh.val, s.mac.val)).unzip();
-        let n = s_vals.len();
-        let all_vals = Net::broadcast(&s_vals);
-        let vals: Vec<G> =
-            (0..n).map(|i| all_vals.iter().map(|v| &v[i]).sum()).collect();
-        let dx_ts: Vec<G> =
-            macs
-            .iter()
-            .zip(vals.iter())
-            .map(|(mac, val)| val.mul(&mac_share::<G::ScalarField>()) - mac)
-            .collect();
-        let all_dx_ts: Vec<Vec<G>> = Net::atomic_broadcast(&dx_ts);
-        for i in 0..n {
-            let sum: G = all_dx_ts.iter().map(|dx_ts| &dx_ts[i]).sum();
-            assert!(sum.is_zero());
-        }
-        vals
+        Self::try_batch_open(selfs).unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn add(&mut self, other: &Self) -> &mut Self {
@@ -428,18 +775,13 @@ impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for SpdzGroupShare<G, M>
     }
 
     fn shift(&mut self, other: &G) -> &mut Self {
-        if Net::am_king() {
-            self.sh.shift(other);
-        }
-        let mut other = other.clone();
-        other *= mac_share::<G::ScalarField>();
-        self.mac.val += other;
+        self.shift_impl(other);
         self
     }
 
     fn multi_scale_pub_group(bases: &[G], scalars: &[Self::FieldShare]) -> Self {
         let shares: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.sh.val.clone()).collect();
-        let macs: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.sh.val.clone()).collect();
+        let macs: Vec<G::ScalarField> = scalars.into_iter().map(|s| s.mac.val.clone()).collect();
         let sh = AdditiveGroupShare::from_add_shared(M::msm(bases, &shares));
         let mac = AdditiveGroupShare::from_add_shared(M::msm(bases, &macs));
         Self { sh, mac }
@@ -457,24 +799,33 @@ impl<G: Group, M: Msm<G, G::ScalarField>> GroupShare<G> for SpdzGroupShare<G, M>
     Hash(bound = "T: Hash")
 )]
 pub struct SpdzMulFieldShare<T, S> {
-    sh: MulFieldShare<T>,
-    mac: MulFieldShare<T>,
+    pub(crate) sh: MulFieldShare<T>,
+    pub(crate) mac: MulFieldShare<T>,
     _phants: PhantomData<S>,
 }
-impl_spdz_basics_2_param!(SpdzMulFieldShare, Field);
+impl_spdz_basics_2_param!(SpdzMulFieldShare, Field, _phants);
+
+impl<F: Field, S: PrimeField> UniformRand for SpdzMulFieldShare<F, S> {
+    /// Each party picks a random non-zero factor.
+    fn rand<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::from_add_shared(ot::MulElem::<F>::rand(rng).0)
+    }
+}
 
 impl<F: Field, S: PrimeField> Reveal for SpdzMulFieldShare<F, S> {
     type Base = F;
 
     fn reveal(self) -> F {
-        let vals: Vec<F> = Net::broadcast(&self.sh.val);
-        // _Pragmatic MPC_ 6.6.2
-        let x: F = vals.iter().product();
-        let dx_t: F = x.pow(&mac_share::<S>().into_repr()) / self.mac.val;
-        let all_dx_ts: Vec<F> = Net::atomic_broadcast(&dx_t);
-        let prod: F = all_dx_ts.iter().product();
-        assert!(prod.is_one());
-        x
+        self.try_reveal().unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Open `x * r`, for a random `r` input by `party`. Shares are never zero, so that is uniform.
+    fn reveal_to(self, party: usize) -> Option<F> {
+        let rng = &mut rand::thread_rng();
+        let am_party = Net::party_id() == party;
+        let r = am_party.then(|| ot::MulElem::<F>::rand(rng).0);
+        let r_sh = Self::input_from(party, r);
+        let y = self.mul(r_sh, &mut PanicBeaverSource::default()).reveal();
+        r.map(|r| y / r)
     }
     fn from_public(f: F) -> Self {
         Self {
@@ -483,25 +834,58 @@ impl<F: Field, S: PrimeField> Reveal for SpdzMulFieldShare<F, S> {
             _phants: PhantomData::default(),
         }
     }
+    /// The MAC `f^alpha` is computed with OLEs in the exponent (see [crate::ot::MulElem]).
     fn from_add_shared(f: F) -> Self {
+        let mac = ot::mul_add_shared(&[ot::MulElem(f)], &[mac_key_share::<S>()])[0].0;
         Self {
             sh: Reveal::from_add_shared(f),
-            mac: Reveal::from_add_shared(f.pow(&mac::<S>().into_repr())),
+            mac: Reveal::from_add_shared(mac),
             _phants: PhantomData::default(),
         }
     }
+    /// The owner's factor is its input, and everyone else's is one.
+    fn input_from_batch(owner: usize, fs: Vec<Option<F>>) -> Vec<Self> {
+        MulFieldShare::input_from_batch(owner, fs)
+            .into_iter()
+            .map(|s| Self::from_add_shared(s.val))
+            .collect()
+    }
+}
+
+impl<F: Field, S: PrimeField> SpdzMulFieldShare<F, S> {
+    /// Open shares, or report a failed MAC check.
+    ///
+    /// This is the check for additive shares, in the multiplicative group (see
+    /// [crate::ot::MulElem]): the MAC of `x` is `x^alpha`.
+    pub fn try_batch_open(selfs: impl IntoIterator<Item = Self>) -> Result<Vec<F>, MacCheckFailed> {
+        let (vals, macs) = selfs
+            .into_iter()
+            .map(|s| (ot::MulElem(s.sh.val), ot::MulElem(s.mac.val)))
+            .unzip();
+        let xs = mac_checked_open(vals, macs, mac_key_share::<S>(), |x, k| {
+            ot::MulElem(x.0.pow(k.into_repr()))
+        })?;
+        Ok(xs.into_iter().map(|x| x.0).collect())
+    }
+
+    /// Open this share, or report a failed MAC check.
+    pub fn try_reveal(self) -> Result<F, MacCheckFailed> {
+        Self::try_batch_open(vec![self]).map(|mut fs| fs.pop().unwrap())
+    }
 }
 
 impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
+    fn batch_open(selfs: impl IntoIterator<Item = Self>) -> Vec<F> {
+        Self::try_batch_open(selfs).unwrap_or_else(|e| panic!("{}", e))
+    }
+
     fn add(&mut self, _other: &Self) -> &mut Self {
         unimplemented!("add for SpdzMulFieldShare")
     }
 
     fn scale(&mut self, other: &F) -> &mut Self {
-        if Net::am_king() {
-            self.sh.scale(other);
-        }
-        self.mac.scale(&other.pow(&mac_share::<S>().into_repr()));
+        self.sh.scale(other);
+        self.mac.val *= other.pow(&mac_share::<S>().into_repr());
         self
     }
 
@@ -510,9 +894,11 @@ impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
     }
 
     fn mul<S2: BeaverSource<Self, Self, Self>>(self, other: Self, _source: &mut S2) -> Self {
-        self.sh.mul(other.sh, &mut PanicBeaverSource::default());
-        self.mac.mul(other.mac, &mut PanicBeaverSource::default());
-        self
+        Self {
+            sh: self.sh.mul(other.sh, &mut PanicBeaverSource::default()),
+            mac: self.mac.mul(other.mac, &mut PanicBeaverSource::default()),
+            _phants: PhantomData,
+        }
     }
 
     fn batch_mul<S2: BeaverSource<Self, Self, Self>>(
@@ -521,8 +907,8 @@ impl<F: Field, S: PrimeField> FieldShare<F> for SpdzMulFieldShare<F, S> {
         _source: &mut S2,
     ) -> Vec<Self> {
         for (x, y) in xs.iter_mut().zip(ys.iter()) {
-            x.sh.mul(y.sh, &mut PanicBeaverSource::default());
-            x.mac.mul(y.mac, &mut PanicBeaverSource::default());
+            x.sh = x.sh.mul(y.sh, &mut PanicBeaverSource::default());
+            x.mac = x.mac.mul(y.mac, &mut PanicBeaverSource::default());
         }
         xs
     }
@@ -568,8 +954,8 @@ impl<F: Field, S: PrimeField> ExtFieldShare<F> for SpdzMulExtFieldShare<F, S> {
 pub struct SpdzExtFieldShare<F: Field>(pub PhantomData<F>);
 
 impl<F: Field> ExtFieldShare<F> for SpdzExtFieldShare<F> {
-    type Ext = AdditiveFieldShare<F>;
-    type Base = AdditiveFieldShare<F::BasePrimeField>;
+    type Ext = SpdzFieldShare<F>;
+    type Base = SpdzFieldShare<F::BasePrimeField>;
 }
 
 macro_rules! groups_share {
@@ -613,8 +999,10 @@ macro_rules! groups_share {
                 a.mac.val += &o.scalar_mul(mac_share::<E::Fr>());
                 a
             }
-            fn add_pub_proj_sh_aff(_a: &E::$proj, _o: Self::AffineShare) -> Self::ProjectiveShare {
-                unimplemented!()
+            fn add_pub_proj_sh_aff(a: &E::$proj, o: Self::AffineShare) -> Self::ProjectiveShare {
+                let mut o = Self::sh_aff_to_proj(o);
+                o.shift(a);
+                o
             }
         }
     };
@@ -648,4 +1036,36 @@ impl<E: PairingEngine> PairingShare<E> for SpdzPairingShare<E> {
         SpdzGroupShare<E::G2Projective, ProjectiveMsm<E::G2Projective>>;
     type G1 = SpdzG1Share<E>;
     type G2 = SpdzG2Share<E>;
+
+    // The MAC maps along with the share: f(alpha * x) = f(x)^alpha.
+    fn g1_map_to_fqk(
+        s: Self::G1AffineShare,
+        f: impl Fn(E::G1Affine) -> E::Fqk,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        SpdzMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            mac: MulFieldShare { val: f(s.mac.val) },
+            _phants: PhantomData,
+        }
+    }
+    fn g2_map_to_fqk(
+        s: Self::G2AffineShare,
+        f: impl Fn(E::G2Affine) -> E::Fqk,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        SpdzMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            mac: MulFieldShare { val: f(s.mac.val) },
+            _phants: PhantomData,
+        }
+    }
+    fn fqk_map(
+        s: SpdzMulFieldShare<E::Fqk, E::Fr>,
+        f: impl Fn(E::Fqk) -> E::Fqk,
+    ) -> SpdzMulFieldShare<E::Fqk, E::Fr> {
+        SpdzMulFieldShare {
+            sh: MulFieldShare { val: f(s.sh.val) },
+            mac: MulFieldShare { val: f(s.mac.val) },
+            _phants: PhantomData,
+        }
+    }
 }
@@ -5,7 +5,7 @@ use zeroize::Zeroize;
 
 use ark_ff::bytes::{FromBytes, ToBytes};
 use ark_ff::prelude::*;
-use ark_ff::{poly_stub, FftField};
+use ark_ff::{poly_stub, BitIteratorLE, FftField};
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize,
     CanonicalSerializeWithFlags, Flags, SerializationError,
@@ -19,9 +19,11 @@ use std::marker::PhantomData;
 use std::ops::*;
 
 use super::super::share::field::FieldShare;
+use super::super::share::beaver::PooledFieldTripleSource;
+use super::super::share::bits;
 use super::super::share::BeaverSource;
 use crate::Reveal;
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{ActiveNet as Net, MpcNet};
 
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MpcField<F: Field, S: FieldShare<F>> {
@@ -82,7 +84,7 @@ impl<T: Field, S: FieldShare<T>> MpcField<T, S> {
         match self {
             Self::Public(x) => x.inverse().map(MpcField::Public),
             Self::Shared(x) => Some(MpcField::Shared(
-                x.inv(&mut DummyFieldTripleSource::default()),
+                x.inv(&mut PooledFieldTripleSource::default()),
             )),
         }
     }
@@ -104,6 +106,79 @@ impl<T: Field, S: FieldShare<T>> MpcField<T, S> {
         }
     }
 }
+impl<F: PrimeField + SquareRootField, S: FieldShare<F>> MpcField<F, S> {
+    /// The bits of this value, as an integer in `[0, p)`, least significant first.
+    ///
+    /// Each bit is 0 or 1. The bits of a shared value are shared.
+    pub fn bits_le(&self) -> Vec<Self> {
+        match self {
+            Self::Public(x) => BitIteratorLE::new(x.into_repr())
+                .take(F::size_in_bits())
+                .map(|b| Self::from_public(F::from(b)))
+                .collect(),
+            Self::Shared(x) => bits::batch_bits::<F, S>(vec![*x])
+                .pop()
+                .unwrap()
+                .into_iter()
+                .map(Self::Shared)
+                .collect(),
+        }
+    }
+
+    /// 1 if `self < other`, comparing them as integers in `[0, p)`, and 0 otherwise.
+    pub fn lt_bit(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Public(x), Self::Public(y)) => {
+                Self::from_public(F::from(x.into_repr() < y.into_repr()))
+            }
+            _ => Self::Shared(
+                bits::batch_less_than::<F, S>(vec![self.to_share()], vec![other.to_share()])
+                    .pop()
+                    .unwrap(),
+            ),
+        }
+    }
+
+    /// 1 if `self == other`, and 0 otherwise.
+    pub fn eq_bit(&self, other: &Self) -> Self {
+        (*self - *other).is_zero_bit()
+    }
+
+    /// 1 if `self` is zero, and 0 otherwise.
+    pub fn is_zero_bit(&self) -> Self {
+        match self {
+            Self::Public(x) => Self::from_public(F::from(x.is_zero())),
+            Self::Shared(x) => Self::Shared(bits::batch_is_zero::<F, S>(vec![*x]).pop().unwrap()),
+        }
+    }
+
+    /// 1 if `self` is a square (zero included), and 0 otherwise.
+    ///
+    /// Unlike [SquareRootField::legendre], this reveals nothing about a shared value.
+    pub fn is_square_bit(&self) -> Self {
+        match self {
+            Self::Public(x) => Self::from_public(F::from(!x.legendre().is_qnr())),
+            Self::Shared(x) => {
+                Self::Shared(bits::batch_is_square::<F, S>(vec![*x]).pop().unwrap())
+            }
+        }
+    }
+
+    fn to_share(self) -> S {
+        match self {
+            Self::Public(x) => S::from_public(x),
+            Self::Shared(x) => x,
+        }
+    }
+
+    /// `(x * r^2, r)` for a fresh random `r`. `x * r^2` is a uniform square if `x` is a non-zero
+    /// square, and a uniform non-square if `x` is not a square.
+    fn mask_square(&self) -> (Self, Self) {
+        let r = Self::Shared(S::rand(&mut rand::thread_rng()));
+        (*self * (r * r), r)
+    }
+}
+
 impl<'a, T: Field, S: FieldShare<T>> MulAssign<&'a MpcField<T, S>> for MpcField<T, S> {
     #[inline]
     fn mul_assign(&mut self, other: &Self) {
@@ -125,7 +200,7 @@ impl<'a, T: Field, S: FieldShare<T>> MulAssign<&'a MpcField<T, S>> for MpcField<
                     x.scale(y);
                 }
                 MpcField::Shared(y) => {
-                    let t = x.mul(*y, &mut DummyFieldTripleSource::default());
+                    let t = x.mul(*y, &mut PooledFieldTripleSource::default());
                     *self = MpcField::Shared(t);
                 }
             },
@@ -162,7 +237,7 @@ impl<'a, T: Field, S: FieldShare<T>> DivAssign<&'a MpcField<T, S>> for MpcField<
                     *x /= y;
                 }
                 MpcField::Shared(y) => {
-                    let mut t = y.inv(&mut DummyFieldTripleSource::default());
+                    let mut t = y.inv(&mut PooledFieldTripleSource::default());
                     t.scale(&x);
                     *self = MpcField::Shared(t);
                 }
@@ -172,7 +247,7 @@ impl<'a, T: Field, S: FieldShare<T>> DivAssign<&'a MpcField<T, S>> for MpcField<
                     x.scale(&y.inverse().unwrap());
                 }
                 MpcField::Shared(y) => {
-                    let src = &mut DummyFieldTripleSource::default();
+                    let src = &mut PooledFieldTripleSource::default();
                     *x = x.div(*y, src);
                 }
             },
@@ -257,6 +332,13 @@ impl<T: Field, S: FieldShare<T>> Reveal for MpcField<T, S> {
         result
     }
     #[inline]
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        match self {
+            Self::Shared(s) => s.reveal_to(party),
+            Self::Public(s) => (Net::party_id() == party).then_some(s),
+        }
+    }
+    #[inline]
     fn from_public(b: Self::Base) -> Self {
         MpcField::Public(b)
     }
@@ -279,6 +361,10 @@ impl<T: Field, S: FieldShare<T>> Reveal for MpcField<T, S> {
     fn king_share_batch<R: Rng>(f: Vec<Self::Base>, rng: &mut R) -> Vec<Self> {
         S::king_share_batch(f, rng).into_iter().map(Self::Shared).collect()
     }
+    #[inline]
+    fn input_from_batch(owner: usize, fs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        S::input_from_batch(owner, fs).into_iter().map(Self::Shared).collect()
+    }
     fn init_protocol() {
         S::init_protocol()
     }
@@ -381,7 +467,7 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                     Self::Public(_) => unreachable!(),
                 })
                 .collect();
-            let nshares = S::batch_mul(sshares, oshares, &mut DummyFieldTripleSource::default());
+            let nshares = S::batch_mul(sshares, oshares, &mut PooledFieldTripleSource::default());
             for (self_, new) in selfs.iter_mut().zip(nshares.into_iter()) {
                 *self_ = Self::Shared(new);
             }
@@ -417,7 +503,7 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                     Self::Public(_) => unreachable!(),
                 })
                 .collect();
-            let nshares = S::batch_div(sshares, oshares, &mut DummyFieldTripleSource::default());
+            let nshares = S::batch_div(sshares, oshares, &mut PooledFieldTripleSource::default());
             for (self_, new) in selfs.iter_mut().zip(nshares.into_iter()) {
                 *self_ = Self::Shared(new);
             }
@@ -442,7 +528,7 @@ impl<F: PrimeField, S: FieldShare<F>> Field for MpcField<F, S> {
                 })
                 .collect();
             for (self_, new) in selfs.iter_mut().zip(
-                S::partial_products(sshares, &mut DummyFieldTripleSource::default()).into_iter(),
+                S::partial_products(sshares, &mut PooledFieldTripleSource::default()).into_iter(),
             ) {
                 *self_ = Self::Shared(new);
             }
@@ -511,36 +597,83 @@ impl<F: PrimeField, S: FieldShare<F>> FftField for MpcField<F, S> {
     fn multiplicative_generator() -> Self {
         Self::from_public(F::multiplicative_generator())
     }
+    /// Vectors with shared entries go to [FieldShare::coset_fft], with their public entries
+    /// shared as constants.
+    fn radix2_fft_in_place(selfs: &mut [Self], offset: Self, inverse: bool) -> bool {
+        let offset = match offset {
+            Self::Public(o) => o,
+            Self::Shared(_) => return false,
+        };
+        if !selfs.iter().any(|s| s.is_shared()) {
+            return false;
+        }
+        let shares: Vec<S> = selfs
+            .iter()
+            .map(|s| match s {
+                Self::Shared(s) => *s,
+                Self::Public(x) => S::from_public(*x),
+            })
+            .collect();
+        match S::coset_fft(&shares, offset, inverse) {
+            Some(out) => {
+                for (self_, new) in selfs.iter_mut().zip(out) {
+                    *self_ = Self::Shared(new);
+                }
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl<F: PrimeField, S: FieldShare<F>> PrimeField for MpcField<F, S> {
     type Params = F::Params;
     type BigInt = F::BigInt;
     #[inline]
-    fn from_repr(_r: <Self as PrimeField>::BigInt) -> Option<Self> {
-        unimplemented!("No BigInt reprs for shared fields! (from_repr)")
-        //F::from_repr(r).map(|v| Self::from_public(v))
+    fn from_repr(r: <Self as PrimeField>::BigInt) -> Option<Self> {
+        F::from_repr(r).map(Self::from_public)
     }
-    // We're assuming that into_repr is linear
+    /// Only public values have a repr. The bits of a shared value are shared, and cannot go in a
+    /// `BigInt`, so r1cs-std gadgets that read bits off the repr (such as `ToBitsGadget`) do not
+    /// run on shared witnesses. Use [MpcField::bits_le], and allocate the bits as field variables.
     #[inline]
     fn into_repr(&self) -> <Self as PrimeField>::BigInt {
-        unimplemented!("No BigInt reprs for shared fields! (into_repr)")
-        //self.unwrap_as_public().into_repr()
+        match self {
+            Self::Public(x) => x.into_repr(),
+            Self::Shared(_) => panic!("No BigInt reprs for shared fields! Use bits_le."),
+        }
     }
 }
 
-impl<F: PrimeField, S: FieldShare<F>> SquareRootField for MpcField<F, S> {
+/// Shared values are masked by a random square and opened, which reveals whether they are
+/// squares (or zero), but nothing else. This has a negligible chance of failing, when the mask
+/// is zero.
+impl<F: PrimeField + SquareRootField, S: FieldShare<F>> SquareRootField for MpcField<F, S> {
     #[inline]
     fn legendre(&self) -> ark_ff::LegendreSymbol {
-        todo!()
+        match self {
+            Self::Public(x) => x.legendre(),
+            Self::Shared(_) => self.mask_square().0.reveal().legendre(),
+        }
     }
+    /// The root of a shared value is `sqrt(x * r^2) / r`, so which of the two roots it is is
+    /// random.
     #[inline]
     fn sqrt(&self) -> Option<Self> {
-        todo!()
+        match self {
+            Self::Public(x) => x.sqrt().map(Self::Public),
+            Self::Shared(_) => {
+                let (masked, r) = self.mask_square();
+                let root = masked.reveal().sqrt()?;
+                Some(r.inverse().unwrap() * Self::from_public(root))
+            }
+        }
     }
     #[inline]
     fn sqrt_in_place(&mut self) -> Option<&mut Self> {
-        todo!()
+        let root = self.sqrt()?;
+        *self = root;
+        Some(self)
     }
 }
 
@@ -569,6 +702,13 @@ mod poly_impl {
             )
         }
 
+        fn reveal_to(self, party: usize) -> Option<Self::Base> {
+            let size = self.domain.size();
+            self.evals.reveal_to(party).map(|evals| {
+                Evaluations::from_vec_and_domain(evals, GeneralEvaluationDomain::new(size).unwrap())
+            })
+        }
+
         fn from_add_shared(b: Self::Base) -> Self {
             Evaluations::from_vec_and_domain(
                 Reveal::from_add_shared(b.evals),
@@ -582,5 +722,21 @@ mod poly_impl {
                 GeneralEvaluationDomain::new(b.domain.size()).unwrap(),
             )
         }
+
+        fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+            let bs = bs
+                .into_iter()
+                .map(|b| b.map(|b| (b.evals, b.domain.size())))
+                .collect();
+            <(Vec<MpcField<F, S>>, usize)>::input_from_batch(owner, bs)
+                .into_iter()
+                .map(|(evals, size)| {
+                    Evaluations::from_vec_and_domain(
+                        evals,
+                        GeneralEvaluationDomain::new(size).unwrap(),
+                    )
+                })
+                .collect()
+        }
     }
 }
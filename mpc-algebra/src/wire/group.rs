@@ -18,10 +18,11 @@ use std::iter::Sum;
 use std::marker::PhantomData;
 use std::ops::*;
 
+use super::super::share::beaver::PooledGroupTripleSource;
 use super::super::share::group::GroupShare;
 use super::super::share::BeaverSource;
 use super::field::MpcField;
-use mpc_net::{MpcNet, MpcMultiNet as Net};
+use mpc_net::{ActiveNet as Net, MpcNet};
 use crate::Reveal;
 
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -114,6 +115,13 @@ impl<T: Group, S: GroupShare<T>> Reveal for MpcGroup<T, S> {
         result
     }
     #[inline]
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        match self {
+            Self::Shared(s) => s.reveal_to(party),
+            Self::Public(s) => (Net::party_id() == party).then_some(s),
+        }
+    }
+    #[inline]
     fn from_public(b: Self::Base) -> Self {
         Self::Public(b)
     }
@@ -136,6 +144,10 @@ impl<T: Group, S: GroupShare<T>> Reveal for MpcGroup<T, S> {
     fn king_share_batch<R: Rng>(f: Vec<Self::Base>, rng: &mut R) -> Vec<Self> {
         S::king_share_batch(f, rng).into_iter().map(Self::Shared).collect()
     }
+    #[inline]
+    fn input_from_batch(owner: usize, fs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        S::input_from_batch(owner, fs).into_iter().map(Self::Shared).collect()
+    }
     fn init_protocol() {
         S::init_protocol()
     }
@@ -193,7 +205,7 @@ impl<'a, T: Group, S: GroupShare<T>> MulAssign<&'a MpcField<T::ScalarField, S::F
                     x.scale_pub_scalar(y);
                 }
                 MpcField::Shared(y) => {
-                    let t = x.scale(*y, &mut DummyGroupTripleSource::default());
+                    let t = x.scale(*y, &mut PooledGroupTripleSource::default());
                     *x = t;
                 }
             },
@@ -2,41 +2,34 @@
 
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 
-use crate::channel::{self, MpcSerNet};
+use crate::channel::MpcSerNet;
 use mpc_net::MpcNet;
 
 use std::fmt::Display;
 
 #[track_caller]
-/// Checks that both sides of the channel have the same value.
+/// Checks that all parties have the same value.
 pub fn check_eq<T: CanonicalSerialize + CanonicalDeserialize + Clone + Eq + Display>(t: T) {
     debug_assert!({
         use log::debug;
-        if mpc_net::two::is_init() {
-            let other = channel::exchange(&t);
-            if t == other {
-                debug!("Consistency check passed");
-                true
-            } else {
-                println!("\nConsistency check failed\n{}\nvs\n{}", t, other);
-                false
+        debug!("Consistency check");
+        let others = mpc_net::ActiveNet::broadcast(&t);
+        let mut result = true;
+        for (i, other_t) in others.iter().enumerate() {
+            if &t != other_t {
+                println!("\nConsistency check failed\nI (party {}) have {}\nvs\n  (party {}) has  {}", mpc_net::ActiveNet::party_id(), t, i, other_t);
+                result = false;
+                break;
             }
-        } else {
-            debug!("Consistency check");
-            let others = mpc_net::MpcMultiNet::broadcast(&t);
-            let mut result = true;
-            for (i, other_t) in others.iter().enumerate() {
-                if &t != other_t {
-                    println!("\nConsistency check failed\nI (party {}) have {}\nvs\n  (party {}) has  {}", mpc_net::MpcMultiNet::party_id(), t, i, other_t);
-                    result = false;
-                    break;
-                }
-            }
-            result
         }
+        result
     })
 }
 
+/// Serialization tags for [crate::MpcField] and [crate::MpcGroup] values.
+pub const PUBLIC_TAG: u8 = 0;
+pub const SHARED_TAG: u8 = 1;
+
 macro_rules! impl_basics_2 {
     ($share:ident, $bound:ident, $wrap:ident) => {
         impl<T: $bound, S: $share<T>> $wrap<T, S> {
@@ -72,57 +65,76 @@ macro_rules! impl_basics_2 {
                 }
             }
         }
+        /// The plain encoding of a public value, as used in Fiat-Shamir transcripts. Shared values
+        /// have none: use [CanonicalSerialize] instead.
         impl<T: $bound, S: $share<T>> ToBytes for $wrap<T, S> {
             fn write<W: Write>(&self, writer: W) -> io::Result<()> {
                 match self {
                     Self::Public(v) => v.write(writer),
-                    Self::Shared(_) => unimplemented!("write share: {}", self),
+                    Self::Shared(_) => Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "ToBytes of a shared value",
+                    )),
                 }
             }
         }
+        /// Reads a public value; see [ToBytes].
         impl<T: $bound, S: $share<T>> FromBytes for $wrap<T, S> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                T::read(reader).map(Self::Public)
             }
         }
+        /// A tag byte (public or shared), followed by the value or this party's share of it.
         impl<T: $bound, S: $share<T>> CanonicalSerialize for $wrap<T, S> {
-            fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+            fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
                 match self {
-                    Self::Public(v) => v.serialize(writer),
-                    Self::Shared(_) => unimplemented!("serialize share: {}", self),
+                    Self::Public(v) => {
+                        $crate::wire::macros::PUBLIC_TAG.serialize(&mut writer)?;
+                        v.serialize(writer)
+                    }
+                    Self::Shared(s) => {
+                        $crate::wire::macros::SHARED_TAG.serialize(&mut writer)?;
+                        s.serialize(writer)
+                    }
                 }
             }
             fn serialized_size(&self) -> usize {
-                match self {
+                1 + match self {
                     Self::Public(v) => v.serialized_size(),
-                    Self::Shared(_) => unimplemented!("serialized_size share: {}", self),
+                    Self::Shared(s) => s.serialized_size(),
                 }
             }
         }
-        // NB: CanonicalSerializeWithFlags is unimplemented for Group.
+        // NB: CanonicalSerializeWithFlags is unimplemented for Group, so we take no flags.
         impl<T: $bound, S: $share<T>> CanonicalSerializeWithFlags for $wrap<T, S> {
             fn serialize_with_flags<W: Write, F: Flags>(
                 &self,
-                _writer: W,
+                writer: W,
                 _flags: F,
             ) -> Result<(), SerializationError> {
-                unimplemented!("serialize_with_flags")
+                $crate::share::group::no_flags::<F>()?;
+                self.serialize(writer)
             }
 
             fn serialized_size_with_flags<F: Flags>(&self) -> usize {
-                unimplemented!("serialized_size_with_flags")
+                self.serialized_size()
             }
         }
         impl<T: $bound, S: $share<T>> CanonicalDeserialize for $wrap<T, S> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+                match u8::deserialize(&mut reader)? {
+                    $crate::wire::macros::PUBLIC_TAG => Ok(Self::Public(T::deserialize(reader)?)),
+                    $crate::wire::macros::SHARED_TAG => Ok(Self::Shared(S::deserialize(reader)?)),
+                    _ => Err(SerializationError::InvalidData),
+                }
             }
         }
         impl<T: $bound, S: $share<T>> CanonicalDeserializeWithFlags for $wrap<T, S> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                $crate::share::group::no_flags::<F>()?;
+                Ok((Self::deserialize(reader)?, F::default()))
             }
         }
         impl<T: $bound, S: $share<T>> UniformRand for $wrap<T, S> {
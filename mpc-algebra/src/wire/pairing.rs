@@ -25,42 +25,10 @@ use mpc_trait::MpcWire;
 use super::super::share::field::ExtFieldShare;
 use super::super::share::group::GroupShare;
 use super::super::share::pairing::{AffProjShare, PairingShare};
-use super::super::share::BeaverSource;
 use super::field::MpcField;
 use super::group::MpcGroup;
 use crate::Reveal;
-
-#[derive(Derivative)]
-#[derivative(Default(bound = ""), Clone(bound = ""), Copy(bound = ""))]
-pub struct DummyPairingTripleSource<E, S> {
-    _phants: PhantomData<(E, S)>,
-}
-
-impl<E: PairingEngine, S: PairingShare<E>>
-    BeaverSource<MpcG1Projective<E, S>, MpcG2Projective<E, S>, MpcExtField<E::Fqk, S::FqkShare>>
-    for DummyPairingTripleSource<E, S>
-{
-    #[inline]
-    fn triple(
-        &mut self,
-    ) -> (
-        MpcG1Projective<E, S>,
-        MpcG2Projective<E, S>,
-        MpcExtField<E::Fqk, S::FqkShare>,
-    ) {
-        let g1 = E::G1Projective::zero();
-        let g2 = E::G2Projective::zero();
-        (
-            MpcG1Projective::from_add_shared(g1.clone()),
-            MpcG2Projective::from_add_shared(g2.clone()),
-            MpcExtField::from_add_shared(E::pairing(g1, g2)),
-        )
-    }
-    #[inline]
-    fn inv_pair(&mut self) -> (MpcG2Projective<E, S>, MpcG2Projective<E, S>) {
-        unimplemented!("No inverses from Pairing triple source")
-    }
-}
+use mpc_net::{ActiveNet as Net, MpcNet};
 
 #[derive(Debug, Derivative)]
 #[derivative(
@@ -89,7 +57,7 @@ pub struct MpcG1Projective<E: PairingEngine, PS: PairingShare<E>> {
 #[derive(Debug, Derivative)]
 #[derivative(Clone(bound = ""), Default(bound = "E::G1Prepared: Default"))]
 pub struct MpcG1Prep<E: PairingEngine, PS: PairingShare<E>> {
-    pub val: E::G1Prepared,
+    pub val: MpcPrepared<E::G1Prepared, PS::G1AffineShare>,
     pub _phants: PhantomData<(E, PS)>,
 }
 
@@ -120,10 +88,24 @@ pub struct MpcG2Projective<E: PairingEngine, PS: PairingShare<E>> {
 #[derive(Debug, Derivative)]
 #[derivative(Clone(bound = ""), Default(bound = "E::G1Prepared: Default"))]
 pub struct MpcG2Prep<E: PairingEngine, PS: PairingShare<E>> {
-    pub val: E::G2Prepared,
+    pub val: MpcPrepared<E::G2Prepared, PS::G2AffineShare>,
     pub _phants: PhantomData<(E, PS)>,
 }
 
+/// A prepared point. Public points are prepared as usual, but shared ones are kept as they are:
+/// each party prepares its share when it is paired.
+#[derive(Debug, Clone)]
+pub enum MpcPrepared<P, S> {
+    Public(P),
+    Shared(S),
+}
+
+impl<P: Default, S> Default for MpcPrepared<P, S> {
+    fn default() -> Self {
+        MpcPrepared::Public(P::default())
+    }
+}
+
 #[derive(Derivative)]
 #[derivative(
     Clone(bound = ""),
@@ -165,31 +147,71 @@ impl<E: PairingEngine, PS: PairingShare<E>> PairingEngine for MpcPairingEngine<E
     type G2Prepared = MpcG2Prep<E, PS>;
     type Fqk = MpcExtField<E::Fqk, PS::FqkShare>;
 
-    fn miller_loop<'a, I>(_i: I) -> Self::Fqk
+    /// When a shared point is paired with a public one, each party runs the Miller loop on its
+    /// own share. Two shared points are first masked (see [Self::mask_pair]). Either way, the
+    /// result is only correct after the final exponentiation, so it must not be revealed before
+    /// then.
+    fn miller_loop<'a, I>(i: I) -> Self::Fqk
     where
         I: IntoIterator<Item = &'a (Self::G1Prepared, Self::G2Prepared)>,
     {
-        unimplemented!("miller_loop")
-        // <Bls12_377 as PairingEngine>::miller_loop(i)
+        let mut public = Vec::new();
+        let mut shared = Vec::new();
+        let mut both = Vec::new();
+        for (p, q) in i {
+            match (&p.val, &q.val) {
+                (MpcPrepared::Public(p), MpcPrepared::Public(q)) => {
+                    public.push((p.clone(), q.clone()))
+                }
+                (MpcPrepared::Shared(p), MpcPrepared::Public(q)) => {
+                    shared.push(PS::g1_map_to_fqk(*p, |p| {
+                        E::miller_loop(&[(p.into(), q.clone())])
+                    }))
+                }
+                (MpcPrepared::Public(p), MpcPrepared::Shared(q)) => {
+                    shared.push(PS::g2_map_to_fqk(*q, |q| {
+                        E::miller_loop(&[(p.clone(), q.into())])
+                    }))
+                }
+                (MpcPrepared::Shared(p), MpcPrepared::Shared(q)) => both.push((*p, *q)),
+            }
+        }
+        let g1 = E::G1Affine::prime_subgroup_generator();
+        for (p, q) in both {
+            let (d, f, x, y, z) = Self::mask_pair(p, q);
+            public.push((d.into(), f.into()));
+            shared.push(PS::g2_map_to_fqk(y, |y| E::miller_loop(&[(d.into(), y.into())])));
+            shared.push(PS::g1_map_to_fqk(x, |x| E::miller_loop(&[(x.into(), f.into())])));
+            shared.push(PS::g2_map_to_fqk(z, |z| E::miller_loop(&[(g1.into(), z.into())])));
+        }
+        let mut f = MpcField::Public(E::miller_loop(&public));
+        for s in shared {
+            f *= MpcField::Shared(s);
+        }
+        MpcExtField::wrap(f)
     }
 
-    fn final_exponentiation(_f: &Self::Fqk) -> Option<Self::Fqk> {
-        unimplemented!("final_exponentiation")
-        // <Bls12_377 as PairingEngine>::final_exponentiation(f)
+    fn final_exponentiation(f: &Self::Fqk) -> Option<Self::Fqk> {
+        match f.val {
+            MpcField::Public(f) => E::final_exponentiation(&f).map(Self::Fqk::from_public),
+            // The final exponentiation is a homomorphism, and it only fails on zero, which a
+            // multiplicative share never is.
+            MpcField::Shared(f) => Some(MpcExtField::wrap(MpcField::Shared(PS::fqk_map(f, |f| {
+                E::final_exponentiation(&f).unwrap()
+            })))),
+        }
     }
 
     /// Computes a product of pairings.
     #[must_use]
-    fn product_of_pairings<'a, I>(_i: I) -> Self::Fqk
+    fn product_of_pairings<'a, I>(i: I) -> Self::Fqk
     where
         I: IntoIterator<Item = &'a (Self::G1Prepared, Self::G2Prepared)>,
     {
-        // TODO: MPC!
-        // <Bls12_377 as PairingEngine>::product_of_pairings(i)
-        unimplemented!("pairing product")
+        Self::final_exponentiation(&Self::miller_loop(i)).unwrap()
     }
 
-    /// Performs multiple pairing operations
+    /// Performs a pairing operation. Either point, or both, may be shared.
     #[must_use]
     fn pairing<G1, G2>(p: G1, q: G2) -> Self::Fqk
     where
@@ -198,33 +220,45 @@ impl<E: PairingEngine, PS: PairingShare<E>> PairingEngine for MpcPairingEngine<E
     {
         let a: Self::G1Affine = p.into();
         let b: Self::G2Affine = q.into();
-        let a: Self::G1Projective = a.into();
-        let b: Self::G2Projective = b.into();
-        if a.is_shared() && b.is_shared() {
-            let source = &mut DummyPairingTripleSource::default();
-            // x * y = z
-            let (x, y, z) = source.triple();
-            // x + a
-            let xa = (a + x).reveal();
-            // y + b
-            let yb = (b + y).reveal();
-            let xayb: MpcExtField<E::Fqk, PS::FqkShare> =
-                MpcExtField::wrap(MpcField::Public(E::pairing(xa, yb)));
-            let xay: MpcExtField<E::Fqk, PS::FqkShare> = MpcExtField::wrap(MpcField::Shared(
-                <PS::FqkShare as ExtFieldShare<E::Fqk>>::Ext::from_add_shared(E::pairing(
-                    xa,
-                    y.unwrap_as_public(),
-                )),
-            ));
-            let xyb: MpcExtField<E::Fqk, PS::FqkShare> = MpcExtField::wrap(MpcField::Shared(
-                <PS::FqkShare as ExtFieldShare<E::Fqk>>::Ext::from_add_shared(E::pairing(
-                    x.unwrap_as_public(),
-                    yb,
-                )),
-            ));
-            z / xay / xyb * xayb
-        } else {
-            MpcExtField::wrap(MpcField::Public(E::pairing(a.reveal(), b.reveal())))
+        Self::product_of_pairings(&[(a.into(), b.into())])
+    }
+}
+
+/// The opened `d` and `f`, and the shares `x`, `y` and `rs * g2`, from
+/// [MpcPairingEngine::mask_pair].
+type MaskedPair<E, PS> = (
+    <E as PairingEngine>::G1Affine,
+    <E as PairingEngine>::G2Affine,
+    <PS as PairingShare<E>>::G1AffineShare,
+    <PS as PairingShare<E>>::G2AffineShare,
+    <PS as PairingShare<E>>::G2AffineShare,
+);
+
+impl<E: PairingEngine, PS: PairingShare<E>> MpcPairingEngine<E, PS> {
+    /// Mask shared `p` and `q` with `x = r * g1` and `y = s * g2`, for fresh shared `r` and `s`,
+    /// and open `d = p - x` and `f = q - y`. Returns `(d, f, x, y, rs * g2)`, where `rs` is a
+    /// product of shares, which takes a triple from the preprocessing pool. Then
+    /// `e(p, q) = e(d, f) e(d, y) e(x, f) e(g1, rs * g2)`, and every pairing on the right has at
+    /// most one shared point.
+    fn mask_pair(
+        p: PS::G1AffineShare,
+        q: PS::G2AffineShare,
+    ) -> MaskedPair<E, PS> {
+        let rng = &mut rand::thread_rng();
+        let r = MpcField::<E::Fr, PS::FrShare>::rand(rng);
+        let s = MpcField::<E::Fr, PS::FrShare>::rand(rng);
+        let g1 = MpcGroup::<E::G1Affine, PS::G1AffineShare>::from_public(
+            E::G1Affine::prime_subgroup_generator(),
+        );
+        let g2 = MpcGroup::<E::G2Affine, PS::G2AffineShare>::from_public(
+            E::G2Affine::prime_subgroup_generator(),
+        );
+        let (x, y, z) = (g1 * r, g2 * s, g2 * (r * s));
+        let d = (MpcGroup::Shared(p) - x).reveal();
+        let f = (MpcGroup::Shared(q) - y).reveal();
+        match (x, y, z) {
+            (MpcGroup::Shared(x), MpcGroup::Shared(y), MpcGroup::Shared(z)) => (d, f, x, y, z),
+            _ => unreachable!("a public point times a shared scalar is shared"),
         }
     }
 }
@@ -242,8 +276,10 @@ macro_rules! impl_pairing_mpc_wrapper {
             }
         }
         impl<E: $bound1, PS: $bound2<E>> FromBytes for $wrap<E, PS> {
-            fn read<R: Read>(_reader: R) -> io::Result<Self> {
-                unimplemented!("read")
+            fn read<R: Read>(reader: R) -> io::Result<Self> {
+                Ok(Self {
+                    val: $wrapped::read(reader)?,
+                })
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalSerialize for $wrap<E, PS> {
@@ -268,15 +304,18 @@ macro_rules! impl_pairing_mpc_wrapper {
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalDeserialize for $wrap<E, PS> {
-            fn deserialize<R: Read>(_reader: R) -> Result<Self, SerializationError> {
-                unimplemented!("deserialize")
+            fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+                Ok(Self {
+                    val: $wrapped::deserialize(reader)?,
+                })
             }
         }
         impl<E: $bound1, PS: $bound2<E>> CanonicalDeserializeWithFlags for $wrap<E, PS> {
             fn deserialize_with_flags<R: Read, F: Flags>(
-                _reader: R,
+                reader: R,
             ) -> Result<(Self, F), SerializationError> {
-                unimplemented!("deserialize_with_flags")
+                let (val, flags) = $wrapped::deserialize_with_flags(reader)?;
+                Ok((Self { val }, flags))
             }
         }
         impl<E: $bound1, PS: $bound2<E>> UniformRand for $wrap<E, PS> {
@@ -424,6 +463,10 @@ macro_rules! impl_ext_field_wrapper {
                 self.val.reveal()
             }
             #[inline]
+            fn reveal_to(self, party: usize) -> Option<E> {
+                self.val.reveal_to(party)
+            }
+            #[inline]
             fn from_public(t: E) -> Self {
                 Self::wrap($wrapped::from_public(t))
             }
@@ -446,6 +489,13 @@ macro_rules! impl_ext_field_wrapper {
                     .map(Self::wrap)
                     .collect()
             }
+            #[inline]
+            fn input_from_batch(owner: usize, fs: Vec<Option<Self::Base>>) -> Vec<Self> {
+                $wrapped::input_from_batch(owner, fs)
+                    .into_iter()
+                    .map(Self::wrap)
+                    .collect()
+            }
         }
         from_prim!(bool, Field, ExtFieldShare, $wrap);
         from_prim!(u8, Field, ExtFieldShare, $wrap);
@@ -564,6 +614,10 @@ macro_rules! impl_pairing_curve_wrapper {
                 self.val.reveal()
             }
             #[inline]
+            fn reveal_to(self, party: usize) -> Option<Self::Base> {
+                self.val.reveal_to(party)
+            }
+            #[inline]
             fn from_public(t: Self::Base) -> Self {
                 Self {
                     val: $wrapped::from_public(t),
@@ -592,6 +646,13 @@ macro_rules! impl_pairing_curve_wrapper {
                     .map(|val| Self { val })
                     .collect()
             }
+            #[inline]
+            fn input_from_batch(owner: usize, fs: Vec<Option<Self::Base>>) -> Vec<Self> {
+                $wrapped::input_from_batch(owner, fs)
+                    .into_iter()
+                    .map(|val| Self { val })
+                    .collect()
+            }
         }
         impl_pairing_mpc_wrapper!($wrapped, $bound1, $bound2, $base, $share, $wrap);
         impl<E: $bound1, PS: $bound2<E>> Mul<MpcField<E::Fr, PS::FrShare>> for $wrap<E, PS> {
@@ -688,14 +749,26 @@ macro_rules! impl_aff_proj {
         }
 
         impl<E: PairingEngine, PS: PairingShare<E>> From<$w_aff<E, PS>> for $w_prep<E, PS> {
-            fn from(_o: $w_aff<E, PS>) -> Self {
-                unimplemented!("Prepared curves")
+            fn from(o: $w_aff<E, PS>) -> Self {
+                Self {
+                    val: match o.val {
+                        MpcGroup::Public(g) => MpcPrepared::Public(g.into()),
+                        MpcGroup::Shared(g) => MpcPrepared::Shared(g),
+                    },
+                    _phants: PhantomData::default(),
+                }
             }
         }
 
         impl<E: PairingEngine, PS: PairingShare<E>> ToBytes for $w_prep<E, PS> {
-            fn write<W: Write>(&self, _writer: W) -> io::Result<()> {
-                unimplemented!("Prepared curves")
+            fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+                match &self.val {
+                    MpcPrepared::Public(g) => g.write(writer),
+                    MpcPrepared::Shared(_) => Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "ToBytes of a shared value",
+                    )),
+                }
             }
         }
 
@@ -703,12 +776,22 @@ macro_rules! impl_aff_proj {
             type Base = E::$prep;
             #[inline]
             fn reveal(self) -> E::$prep {
-                self.val
+                match self.val {
+                    MpcPrepared::Public(g) => g,
+                    MpcPrepared::Shared(g) => g.reveal().into(),
+                }
+            }
+            #[inline]
+            fn reveal_to(self, party: usize) -> Option<E::$prep> {
+                match self.val {
+                    MpcPrepared::Public(g) => (Net::party_id() == party).then_some(g),
+                    MpcPrepared::Shared(g) => g.reveal_to(party).map(Into::into),
+                }
             }
             #[inline]
             fn from_public(g: E::$prep) -> Self {
                 Self {
-                    val: g,
+                    val: MpcPrepared::Public(g),
                     _phants: PhantomData::default(),
                 }
             }
@@ -716,6 +799,10 @@ macro_rules! impl_aff_proj {
             fn from_add_shared(_g: E::$prep) -> Self {
                 panic!("Cannot add share a prepared curve")
             }
+            /// Prepared points cannot be shared: input the point, and prepare that.
+            fn input_from_batch(_owner: usize, _gs: Vec<Option<E::$prep>>) -> Vec<Self> {
+                panic!("Cannot input a prepared curve")
+            }
         }
 
         impl<E: PairingEngine, PS: PairingShare<E>> AffineCurve for $w_aff<E, PS> {
@@ -0,0 +1,23 @@
+//! Generate a keypair for secure channels.
+//!
+//! Writes the secret key to the given file, and prints the public key, which goes next to this
+//! party's address in the hosts file.
+use mpc_net::secure::SecretKey;
+
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "keygen", about = "Generate a keypair for secure channels")]
+struct Opt {
+    /// Where to write the secret key
+    #[structopt(parse(from_os_str))]
+    secret_key_file: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let key = SecretKey::generate();
+    std::fs::write(&opt.secret_key_file, key.to_hex()).expect("could not write secret key");
+    println!("{}", key.public_key());
+}
@@ -0,0 +1,744 @@
+//! An asynchronous TCP transport.
+//!
+//! [Connections](crate::multi::Connections) runs one operation at a time: it writes to each peer
+//! and then blocks reading from each, tying up rayon threads while it waits. [AsyncConnections]
+//! instead gives every peer a writer thread and a reader thread. An operation queues its messages
+//! as soon as it is called, and returns a future that resolves once the peers' messages are in.
+//! A party can therefore start several operations before waiting on any of them, and waiting
+//! costs no CPU.
+//!
+//! The futures only need a [Waker], not a particular runtime: they can be awaited under tokio, or
+//! any other executor, or driven with [block_on]. [MpcAsyncNet] runs the transport behind a
+//! blocking adapter, so that code written against [MpcNet] (and `MpcSerNet`) works unchanged.
+//!
+//! Only [Topology::Mesh] networks are supported.
+use log::debug;
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, JoinHandle, Thread};
+
+use super::config::HostConfig;
+use super::frame::{self, Rounds, Tag};
+use super::multi::Connections;
+use super::secure::{SecretKey, SecureChannel};
+use super::{MpcNet, MpcNetError, MpcTransport, NetOp, Session, Stats, Topology, ABORT_MARKER};
+
+/// The result of a network operation, once the other parties' messages arrive.
+pub type NetFuture<T> = Pin<Box<dyn Future<Output = Result<T, MpcNetError>> + Send>>;
+
+/// Like [MpcTransport], but each operation sends its messages right away, and returns a future
+/// for the messages it receives.
+///
+/// Operations are numbered in the order they are called, not the order their futures are
+/// awaited, so every party must call them in the same order. On an error, the party aborts, as
+/// with [MpcTransport].
+pub trait AsyncMpcNet: Send + Sync {
+    /// What is my party number (0 to n-1)?
+    fn party_id(&self) -> usize;
+    /// How many parties are there?
+    fn n_parties(&self) -> usize;
+    /// Which party is the king?
+    fn king(&self) -> usize;
+    /// Abort the computation: tell all peers, and close all connections.
+    fn abort(&self);
+    /// Set statistics to zero.
+    fn reset_stats(&self);
+    /// Get statistics.
+    fn stats(&self) -> Stats;
+    /// All parties send bytes to each other.
+    fn broadcast_bytes(&self, bytes: &[u8]) -> NetFuture<Vec<Vec<u8>>>;
+    /// Each party sends `bytes[j]` to party `j` (and no one else).
+    fn all_to_all_bytes(&self, bytes: &[Vec<u8>]) -> NetFuture<Vec<Vec<u8>>>;
+    /// All parties send bytes to the king.
+    fn send_bytes_to_king(&self, bytes: &[u8]) -> NetFuture<Option<Vec<Vec<u8>>>>;
+    /// All parties recv bytes from the king.
+    /// Provide bytes iff you're the king!
+    fn recv_bytes_from_king(&self, bytes: Option<Vec<Vec<u8>>>) -> NetFuture<Vec<u8>>;
+}
+
+/// Run `future` to completion on this thread.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+            return out;
+        }
+        thread::park();
+    }
+}
+
+/// What a writer thread should do next.
+enum Out {
+    /// Write these bytes.
+    Wire(Vec<u8>),
+    /// Tell the peer we are aborting, and stop.
+    Abort,
+    /// Close the connection, once everything before has been written.
+    Close,
+}
+
+/// Why no more messages will arrive from a peer.
+#[derive(Clone, Debug)]
+enum Closed {
+    Aborted,
+    Auth,
+    Io(io::ErrorKind, String),
+}
+
+impl Closed {
+    fn error(&self, peer: usize, op: NetOp) -> MpcNetError {
+        match self {
+            Closed::Aborted => MpcNetError::Aborted { peer, op },
+            Closed::Auth => MpcNetError::Auth {
+                peer,
+                op,
+                reason: "message failed authentication",
+            },
+            Closed::Io(kind, msg) => {
+                MpcNetError::io(peer, op, 0)(io::Error::new(*kind, msg.clone()))
+            }
+        }
+    }
+}
+
+/// Messages from one peer that no operation has taken yet.
+#[derive(Default)]
+struct Inbox {
+    /// By arrival: the first message from the peer is number 0.
+    msgs: BTreeMap<u64, Vec<u8>>,
+    arrived: u64,
+    /// How many messages operations have asked for.
+    wanted: u64,
+    closed: Option<Closed>,
+    /// Who to wake when a message arrives, by the number of the message they wait for.
+    wakers: BTreeMap<u64, Waker>,
+    bytes_recv: usize,
+}
+
+impl Inbox {
+    fn wake(inbox: &Mutex<Inbox>, update: impl FnOnce(&mut Inbox)) {
+        let wakers = {
+            let mut inbox = inbox.lock().unwrap();
+            update(&mut inbox);
+            std::mem::take(&mut inbox.wakers)
+        };
+        for w in wakers.into_values() {
+            w.wake();
+        }
+    }
+}
+
+/// Our connection to one peer.
+struct Link {
+    out: Sender<Out>,
+    inbox: Arc<Mutex<Inbox>>,
+    /// The reader thread opens messages, and operations seal them.
+    channel: Option<Arc<Mutex<SecureChannel>>>,
+}
+
+/// What operations update as they start. One lock covers it, so that operations reach every peer
+/// in the order they were started.
+struct Started {
+    rounds: Rounds,
+    stats: Stats,
+}
+
+struct Shared {
+    id: usize,
+    king: usize,
+    /// `None` for ourselves.
+    links: Vec<Option<Link>>,
+    started: Mutex<Started>,
+    aborted: AtomicBool,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// TCP connections to every other party, which run operations concurrently.
+///
+/// Clones share the connections, which close when the last clone is dropped.
+#[derive(Clone)]
+pub struct AsyncConnections(Arc<Shared>);
+
+impl AsyncConnections {
+    /// Connect to the parties listed in `config`, as party `party_id`; see
+    /// [Connections::connect].
+    pub fn connect(config: &HostConfig, party_id: usize) -> Result<Self, MpcNetError> {
+        check_topology(config)?;
+        Self::start(Connections::connect(config, party_id)?)
+    }
+
+    /// Like [AsyncConnections::connect], but wait for peers on `listener`; see
+    /// [Connections::connect_on].
+    pub fn connect_on(
+        config: &HostConfig,
+        party_id: usize,
+        listener: TcpListener,
+    ) -> Result<Self, MpcNetError> {
+        check_topology(config)?;
+        Self::start(Connections::connect_on(config, party_id, listener)?)
+    }
+
+    /// Like [AsyncConnections::connect], but over secure channels; see
+    /// [Connections::connect_secure].
+    pub fn connect_secure(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: SecretKey,
+    ) -> Result<Self, MpcNetError> {
+        check_topology(config)?;
+        Self::start(Connections::connect_secure(config, party_id, secret_key)?)
+    }
+
+    /// Hand each of `ch`'s connections to a reader and a writer thread.
+    fn start(ch: Connections) -> Result<Self, MpcNetError> {
+        let (id, options, rounds, streams) = ch.into_parts();
+        let n = streams.len();
+        let mut links = Vec::with_capacity(n);
+        let mut threads = Vec::with_capacity(2 * n);
+        for (peer, link) in streams.into_iter().enumerate() {
+            let (stream, channel) = match link {
+                Some(link) => link,
+                None => {
+                    links.push(None);
+                    continue;
+                }
+            };
+            let spawn_err = MpcNetError::io(peer, NetOp::Connect, 0);
+            let channel = channel.map(|c| Arc::new(Mutex::new(c)));
+            let inbox = Arc::new(Mutex::new(Inbox::default()));
+            let (out, out_rx) = mpsc::channel();
+            let writer = stream.try_clone().map_err(&spawn_err)?;
+            threads.push(
+                thread::Builder::new()
+                    .name(format!("mpc-net writer {}", peer))
+                    .spawn(move || write_loop(writer, out_rx))
+                    .map_err(&spawn_err)?,
+            );
+            let (r_inbox, r_channel) = (inbox.clone(), channel.clone());
+            let max_len = options.max_message_len;
+            threads.push(
+                thread::Builder::new()
+                    .name(format!("mpc-net reader {}", peer))
+                    .spawn(move || read_loop(stream, r_inbox, r_channel, max_len))
+                    .map_err(&spawn_err)?,
+            );
+            links.push(Some(Link {
+                out,
+                inbox,
+                channel,
+            }));
+        }
+        let stats = Stats {
+            bytes_sent_to: vec![0; n],
+            ..Stats::default()
+        };
+        Ok(Self(Arc::new(Shared {
+            id,
+            king: options.king,
+            links,
+            started: Mutex::new(Started { rounds, stats }),
+            aborted: AtomicBool::new(false),
+            threads,
+        })))
+    }
+
+    /// Start an operation: send `out(j)` to each peer `j` it gives bytes for, and wait for a
+    /// message from each peer `j` for which `from(j)`.
+    fn begin<'b>(
+        &self,
+        op: NetOp,
+        out: impl Fn(usize) -> Option<&'b [u8]>,
+        from: impl Fn(usize) -> bool,
+    ) -> Result<Pending, MpcNetError> {
+        let shared = &self.0;
+        if shared.aborted.load(Ordering::SeqCst) {
+            return Err(MpcNetError::Aborted {
+                peer: shared.id,
+                op,
+            });
+        }
+        let mut started = shared.started.lock().unwrap();
+        let tag = started.rounds.next(op);
+        let mut waiting = Vec::new();
+        for (j, link) in shared.links.iter().enumerate() {
+            let link = match link {
+                Some(link) => link,
+                None => continue,
+            };
+            if let Some(bytes) = out(j) {
+                let mut bytes = frame::encode(tag, bytes);
+                if let Some(channel) = &link.channel {
+                    bytes = channel.lock().unwrap().seal(&bytes);
+                }
+                let mut wire = Vec::with_capacity(8 + bytes.len());
+                wire.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                wire.extend_from_slice(&bytes);
+                started.stats.bytes_sent_to[j] += wire.len();
+                // If the writer is gone, the reader reports why.
+                let _ = link.out.send(Out::Wire(wire));
+            }
+            if from(j) {
+                let mut inbox = link.inbox.lock().unwrap();
+                waiting.push((j, inbox.wanted));
+                inbox.wanted += 1;
+            }
+        }
+        match op {
+            NetOp::Broadcast | NetOp::AllToAll => started.stats.broadcasts += 1,
+            NetOp::SendToKing => started.stats.to_king += 1,
+            NetOp::RecvFromKing => started.stats.from_king += 1,
+            _ => {}
+        }
+        Ok(Pending {
+            shared: shared.clone(),
+            tag,
+            waiting,
+            msgs: vec![Vec::new(); shared.links.len()],
+        })
+    }
+}
+
+fn check_topology(config: &HostConfig) -> Result<(), MpcNetError> {
+    if config.options.topology != Topology::Mesh {
+        return Err(MpcNetError::Config(format!(
+            "the async transport does not support the {} topology",
+            config.options.topology
+        )));
+    }
+    Ok(())
+}
+
+impl Shared {
+    fn abort(&self) {
+        if self.aborted.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        debug!("Aborting");
+        for link in self.links.iter().flatten() {
+            let _ = link.out.send(Out::Abort);
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        for link in self.links.iter().flatten() {
+            let _ = link.out.send(Out::Close);
+        }
+        // A waker run by a reader thread may drop the last handle, so that thread cannot wait for
+        // itself.
+        let me = thread::current().id();
+        for t in self.threads.drain(..) {
+            if t.thread().id() != me {
+                let _ = t.join();
+            }
+        }
+    }
+}
+
+fn write_loop(mut stream: TcpStream, out: Receiver<Out>) {
+    for msg in out {
+        match msg {
+            Out::Wire(wire) => {
+                if let Err(e) = stream.write_all(&wire) {
+                    debug!("Write failed: {}", e);
+                    break;
+                }
+            }
+            Out::Abort => {
+                // The peer may already be gone, so errors are expected.
+                let _ = stream.write_all(&ABORT_MARKER.to_le_bytes());
+                let _ = stream.shutdown(Shutdown::Write);
+                break;
+            }
+            Out::Close => break,
+        }
+    }
+    // Stop the reader too.
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn read_loop(
+    mut stream: TcpStream,
+    inbox: Arc<Mutex<Inbox>>,
+    channel: Option<Arc<Mutex<SecureChannel>>>,
+    max_len: usize,
+) {
+    let closed = loop {
+        let msg = match read_msg(&mut stream, &inbox, max_len) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => break Closed::Aborted,
+            Err(e) => break Closed::Io(e.kind(), e.to_string()),
+        };
+        let len = msg.len();
+        let msg = match &channel {
+            Some(channel) => match channel.lock().unwrap().open(msg) {
+                Some(msg) => msg,
+                None => break Closed::Auth,
+            },
+            None => msg,
+        };
+        Inbox::wake(&inbox, |inbox| {
+            inbox.msgs.insert(inbox.arrived, msg);
+            inbox.arrived += 1;
+            inbox.bytes_recv += 8 + len;
+        });
+    };
+    Inbox::wake(&inbox, |inbox| inbox.closed = Some(closed));
+}
+
+/// The next message on `stream`, or `None` if the peer aborted. Messages longer than `max_len`
+/// fail, before we set aside room for them.
+fn read_msg(
+    stream: &mut TcpStream,
+    inbox: &Mutex<Inbox>,
+    max_len: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 8];
+    read_full(stream, inbox, &mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len == ABORT_MARKER {
+        return Ok(None);
+    }
+    if len > max_len as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes (at most {} allowed)", len, max_len),
+        ));
+    }
+    let mut msg = vec![0u8; len as usize];
+    read_full(stream, inbox, &mut msg)?;
+    Ok(Some(msg))
+}
+
+/// Fill `buf` from `stream`.
+///
+/// The read timeout only counts while some operation is waiting for a message. Otherwise we keep
+/// reading where we left off, even in the middle of a message.
+fn read_full(stream: &mut TcpStream, inbox: &Mutex<Inbox>, buf: &mut [u8]) -> io::Result<()> {
+    let mut got = 0;
+    while got < buf.len() {
+        match stream.read(&mut buf[got..]) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(k) => got += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                let inbox = inbox.lock().unwrap();
+                if inbox.wanted > inbox.arrived {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Waits for the messages of one operation.
+struct Pending {
+    shared: Arc<Shared>,
+    tag: Tag,
+    /// (peer, number of the message we want from it)
+    waiting: Vec<(usize, u64)>,
+    /// By peer; empty for peers we are not waiting for.
+    msgs: Vec<Vec<u8>>,
+}
+
+impl Future for Pending {
+    type Output = Result<Vec<Vec<u8>>, MpcNetError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        while let Some(&(peer, n)) = this.waiting.last() {
+            let link = this.shared.links[peer].as_ref().unwrap();
+            let mut inbox = link.inbox.lock().unwrap();
+            let r = match (inbox.msgs.remove(&n), &inbox.closed) {
+                (Some(msg), _) => frame::decode(peer, this.tag, msg),
+                (None, Some(closed)) => Err(closed.error(peer, this.tag.op)),
+                (None, None) => {
+                    // Each poll replaces the waker we left last time.
+                    inbox.wakers.insert(n, cx.waker().clone());
+                    return Poll::Pending;
+                }
+            };
+            drop(inbox);
+            match r {
+                Ok(msg) => {
+                    this.msgs[peer] = msg;
+                    this.waiting.pop();
+                }
+                Err(e) => {
+                    debug!("Network error: {}", e);
+                    this.shared.abort();
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+        Poll::Ready(Ok(std::mem::take(&mut this.msgs)))
+    }
+}
+
+impl AsyncMpcNet for AsyncConnections {
+    fn party_id(&self) -> usize {
+        self.0.id
+    }
+
+    fn n_parties(&self) -> usize {
+        self.0.links.len()
+    }
+
+    fn king(&self) -> usize {
+        self.0.king
+    }
+
+    fn abort(&self) {
+        self.0.abort()
+    }
+
+    fn reset_stats(&self) {
+        let mut started = self.0.started.lock().unwrap();
+        started.stats = Stats {
+            bytes_sent_to: vec![0; self.n_parties()],
+            ..Stats::default()
+        };
+        for link in self.0.links.iter().flatten() {
+            link.inbox.lock().unwrap().bytes_recv = 0;
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        let mut stats = self.0.started.lock().unwrap().stats.clone();
+        stats.bytes_recv_from = self
+            .0
+            .links
+            .iter()
+            .map(|l| l.as_ref().map_or(0, |l| l.inbox.lock().unwrap().bytes_recv))
+            .collect();
+        stats.bytes_sent = stats.bytes_sent_to.iter().sum();
+        stats.bytes_recv = stats.bytes_recv_from.iter().sum();
+        stats
+    }
+
+    fn broadcast_bytes(&self, bytes: &[u8]) -> NetFuture<Vec<Vec<u8>>> {
+        let pending = self.begin(NetOp::Broadcast, |_| Some(bytes), |_| true);
+        let (id, own) = (self.party_id(), bytes.to_vec());
+        Box::pin(async move {
+            let mut msgs = pending?.await?;
+            msgs[id] = own;
+            Ok(msgs)
+        })
+    }
+
+    fn all_to_all_bytes(&self, bytes: &[Vec<u8>]) -> NetFuture<Vec<Vec<u8>>> {
+        assert_eq!(bytes.len(), self.n_parties());
+        let pending = self.begin(NetOp::AllToAll, |j| Some(&bytes[j][..]), |_| true);
+        let id = self.party_id();
+        let own = bytes[id].clone();
+        Box::pin(async move {
+            let mut msgs = pending?.await?;
+            msgs[id] = own;
+            Ok(msgs)
+        })
+    }
+
+    fn send_bytes_to_king(&self, bytes: &[u8]) -> NetFuture<Option<Vec<Vec<u8>>>> {
+        let (id, king) = (self.party_id(), self.king());
+        let pending = self.begin(
+            NetOp::SendToKing,
+            |j| if j == king { Some(bytes) } else { None },
+            |_| id == king,
+        );
+        let own = bytes.to_vec();
+        Box::pin(async move {
+            let mut msgs = pending?.await?;
+            Ok(if id == king {
+                msgs[id] = own;
+                Some(msgs)
+            } else {
+                None
+            })
+        })
+    }
+
+    fn recv_bytes_from_king(&self, bytes: Option<Vec<Vec<u8>>>) -> NetFuture<Vec<u8>> {
+        let (id, king) = (self.party_id(), self.king());
+        let bytes = if id == king {
+            let bytes = bytes.expect("king needs bytes");
+            assert_eq!(bytes.len(), self.n_parties());
+            Some(bytes)
+        } else {
+            None
+        };
+        let pending = self.begin(
+            NetOp::RecvFromKing,
+            |j| bytes.as_ref().map(|b| &b[j][..]),
+            |j| j == king,
+        );
+        let own = bytes.map(|mut b| b.swap_remove(id));
+        Box::pin(async move {
+            let mut msgs = pending?.await?;
+            Ok(own.unwrap_or_else(|| std::mem::take(&mut msgs[king])))
+        })
+    }
+}
+
+/// Runs an [AsyncMpcNet] as a blocking [MpcTransport], waiting on each operation in turn.
+pub struct Blocking<N>(pub N);
+
+impl<N: AsyncMpcNet> MpcTransport for Blocking<N> {
+    fn party_id(&self) -> usize {
+        self.0.party_id()
+    }
+
+    fn n_parties(&self) -> usize {
+        self.0.n_parties()
+    }
+
+    fn king(&self) -> usize {
+        self.0.king()
+    }
+
+    fn abort(&mut self) {
+        self.0.abort()
+    }
+
+    fn reset_stats(&mut self) {
+        self.0.reset_stats()
+    }
+
+    fn stats(&self) -> Stats {
+        self.0.stats()
+    }
+
+    fn broadcast_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        block_on(self.0.broadcast_bytes(bytes))
+    }
+
+    fn all_to_all_bytes(&mut self, bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        block_on(self.0.all_to_all_bytes(bytes))
+    }
+
+    fn send_bytes_to_king(&mut self, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        block_on(self.0.send_bytes_to_king(bytes))
+    }
+
+    fn recv_bytes_from_king(
+        &mut self,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
+        block_on(self.0.recv_bytes_from_king(bytes))
+    }
+}
+
+/// Connects all parties over TCP, as [crate::MpcMultiNet] does, but through [AsyncConnections].
+pub struct MpcAsyncNet;
+
+impl MpcAsyncNet {
+    /// Like [MpcNet::init_from_config], but over secure channels.
+    pub fn init_secure_from_config(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: SecretKey,
+    ) -> Result<(), MpcNetError> {
+        let ch = AsyncConnections::connect_secure(config, party_id, secret_key)?;
+        Session::set_default(Some(Session::new(Blocking(ch))));
+        Ok(())
+    }
+}
+
+impl MpcNet for MpcAsyncNet {
+    #[inline]
+    fn init_from_config(config: &HostConfig, party_id: usize) -> Result<(), MpcNetError> {
+        let ch = AsyncConnections::connect(config, party_id)?;
+        Session::set_default(Some(Session::new(Blocking(ch))));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::on_localhost;
+    use crate::{ActiveNet, NetOptions};
+    use std::time::Duration;
+
+    /// Several operations can be in flight at once, and behind the blocking adapter, the usual
+    /// network calls work.
+    #[test]
+    fn in_flight() {
+        on_localhost(3, NetOptions::default(), &[], |config, id, listener| {
+            let net = AsyncConnections::connect_on(config, id, listener).unwrap();
+            let first = net.broadcast_bytes(&[id as u8]);
+            let second = net.broadcast_bytes(&[id as u8 + 3]);
+            assert_eq!(block_on(second).unwrap(), vec![vec![3], vec![4], vec![5]]);
+            assert_eq!(block_on(first).unwrap(), vec![vec![0], vec![1], vec![2]]);
+            Session::new(Blocking(net)).enter(|| {
+                let back = ActiveNet::king_compute(&[id as u8], |all| all).unwrap();
+                assert_eq!(back, vec![id as u8]);
+            });
+        });
+    }
+
+    /// A read timeout while no operation is waiting does not lose the part of a message already
+    /// read.
+    #[test]
+    fn resume_partial_read() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut writer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut reader, _) = listener.accept().unwrap();
+        reader
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let inbox = Mutex::new(Inbox::default());
+        let msg = vec![7u8; 100];
+        writer.write_all(&(msg.len() as u64).to_le_bytes()).unwrap();
+        writer.write_all(&msg[..50]).unwrap();
+        thread::scope(|scope| {
+            let read = scope.spawn(|| read_msg(&mut reader, &inbox, 1 << 20));
+            thread::sleep(Duration::from_millis(100));
+            writer.write_all(&msg[50..]).unwrap();
+            assert_eq!(read.join().unwrap().unwrap(), Some(msg.clone()));
+        });
+    }
+
+    /// Polling a waiting operation again replaces its waker rather than adding another.
+    #[test]
+    fn one_waker_per_wait() {
+        let (go, wait) = mpsc::channel::<()>();
+        let wait = Mutex::new(wait);
+        on_localhost(2, NetOptions::default(), &[], |config, id, listener| {
+            let net = AsyncConnections::connect_on(config, id, listener).unwrap();
+            if id == 1 {
+                wait.lock().unwrap().recv().unwrap();
+            } else {
+                let mut pending = net.broadcast_bytes(&[0]);
+                let mut cx = Context::from_waker(Waker::noop());
+                for _ in 0..3 {
+                    assert!(pending.as_mut().poll(&mut cx).is_pending());
+                }
+                let inbox = net.0.links[1].as_ref().unwrap().inbox.clone();
+                assert_eq!(inbox.lock().unwrap().wakers.len(), 1);
+                go.send(()).unwrap();
+                assert_eq!(block_on(pending).unwrap(), vec![vec![0], vec![1]]);
+                return;
+            }
+            assert_eq!(block_on(net.broadcast_bytes(&[1])).unwrap(), vec![vec![0], vec![1]]);
+        });
+    }
+}
@@ -0,0 +1,493 @@
+//! Host files: who the parties are, and how to reach them.
+//!
+//! There are two formats. The plain one lists one party per line, in order of party id: its
+//! `HOST:PORT`, optionally followed by its public key (see [crate::secure]).
+//!
+//! The structured one is a small subset of TOML:
+//!
+//! ```toml
+//! # Optional. Parties only connect to peers with the same session id.
+//! session_id = "auction-7"
+//! king = 1
+//! topology = "star"
+//! max_message_len = 1073741824
+//!
+//! [timeouts]
+//! connect_ms = 30000
+//! read_ms = 60000
+//!
+//! [[party]]
+//! id = 0
+//! address = "10.0.0.1:8000"
+//! # Where to listen, if not at `address` (e.g. behind NAT).
+//! bind = "0.0.0.0:8000"
+//! public_key = "..."
+//!
+//! [[party]]
+//! id = 1
+//! address = "10.0.0.2:8000"
+//! ```
+//!
+//! Only strings and non-negative integers are supported. Any key may be left out except a party's
+//! `id` and `address`; the rest default as in [NetOptions].
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::secure::PublicKey;
+use super::{MpcNetError, NetOptions};
+
+/// How to reach one party.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartyConfig {
+    /// Where the other parties contact this one.
+    pub address: SocketAddr,
+    /// Where this party listens. Usually the same as `address`.
+    pub bind: SocketAddr,
+    pub public_key: Option<PublicKey>,
+}
+
+/// The contents of a host file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostConfig {
+    /// By party id.
+    pub parties: Vec<PartyConfig>,
+    pub options: NetOptions,
+    /// Names this run of the computation, so that parties from another run cannot join it.
+    pub session_id: Option<String>,
+}
+
+impl HostConfig {
+    /// Read the host file at `path`, in either format.
+    pub fn from_file(path: &str) -> Result<Self, MpcNetError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            MpcNetError::Config(format!("could not open host file {}: {}", path, e))
+        })?;
+        Self::parse(&text).map_err(|e| match e {
+            MpcNetError::Config(msg) => MpcNetError::Config(format!("{}: {}", path, msg)),
+            e => e,
+        })
+    }
+
+    /// The number of parties.
+    pub fn n_parties(&self) -> usize {
+        self.parties.len()
+    }
+
+    /// Check that `party_id` is one of the parties, and that the options make sense for them.
+    pub fn check(&self, party_id: usize) -> Result<(), MpcNetError> {
+        let n = self.n_parties();
+        if party_id >= n {
+            return Err(MpcNetError::Config(format!(
+                "party {} does not exist; there are {} parties",
+                party_id, n
+            )));
+        }
+        if self.options.king >= n {
+            return Err(MpcNetError::Config(format!(
+                "the king, party {}, does not exist; there are {} parties",
+                self.options.king, n
+            )));
+        }
+        Ok(())
+    }
+
+    /// A digest of the session id, which parties compare when they connect.
+    pub(crate) fn session_digest(&self) -> [u8; 32] {
+        let mut h = Sha256::new();
+        match &self.session_id {
+            Some(id) => {
+                h.update(b"session ");
+                h.update(id.as_bytes());
+            }
+            None => h.update(b"no session"),
+        }
+        h.finalize().into()
+    }
+
+    fn parse(text: &str) -> Result<Self, MpcNetError> {
+        let structured = text
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .is_some_and(|l| l.starts_with('[') || l.contains('='));
+        let config = if structured {
+            Self::parse_toml(text)?
+        } else {
+            Self::parse_plain(text)?
+        };
+        if config.parties.is_empty() {
+            return Err(MpcNetError::Config("no parties listed".into()));
+        }
+        if config.options.king >= config.n_parties() {
+            return Err(MpcNetError::Config(format!(
+                "the king, party {}, is not listed; there are {} parties",
+                config.options.king,
+                config.n_parties()
+            )));
+        }
+        let n_keys = config
+            .parties
+            .iter()
+            .filter(|p| p.public_key.is_some())
+            .count();
+        if n_keys != 0 && n_keys != config.n_parties() {
+            return Err(MpcNetError::Config(format!(
+                "public keys listed for only {} of {} parties",
+                n_keys,
+                config.n_parties()
+            )));
+        }
+        Ok(config)
+    }
+
+    fn parse_plain(text: &str) -> Result<Self, MpcNetError> {
+        let mut parties = Vec::new();
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            if let Some(addr) = fields.next().filter(|f| !f.starts_with('#')) {
+                let address = parse_addr(addr)?;
+                let public_key = fields.next().map(str::parse).transpose()?;
+                parties.push(PartyConfig {
+                    address,
+                    bind: address,
+                    public_key,
+                });
+            }
+        }
+        Ok(Self {
+            parties,
+            options: NetOptions::default(),
+            session_id: None,
+        })
+    }
+
+    fn parse_toml(text: &str) -> Result<Self, MpcNetError> {
+        let tables = parse_tables(text)?;
+        let mut options = NetOptions::default();
+        let mut session_id = None;
+        let mut parties = Vec::new();
+        for (name, mut table) in tables {
+            match name.as_str() {
+                "" => {
+                    session_id = table.take_str("session_id")?;
+                    if let Some(king) = table.take_int("king")? {
+                        options.king = king as usize;
+                    }
+                    if let Some(topology) = table.take_str("topology")? {
+                        options.topology = topology.parse()?;
+                    }
+                    if let Some(len) = table.take_int("max_message_len")? {
+                        options.max_message_len = len as usize;
+                    }
+                }
+                "timeouts" => {
+                    if let Some(ms) = table.take_int("connect_ms")? {
+                        options.timeouts.connect = Duration::from_millis(ms);
+                    }
+                    options.timeouts.read = table.take_int("read_ms")?.map(Duration::from_millis);
+                }
+                "party" => {
+                    let id = table.require(Table::take_int, "id")? as usize;
+                    let address = parse_addr(&table.require(Table::take_str, "address")?)?;
+                    let bind = table
+                        .take_str("bind")?
+                        .map_or(Ok(address), |b| parse_addr(&b))?;
+                    let public_key = table
+                        .take_str("public_key")?
+                        .map(|k| k.parse())
+                        .transpose()?;
+                    let party = PartyConfig {
+                        address,
+                        bind,
+                        public_key,
+                    };
+                    parties.push((id, party, table.line));
+                }
+                _ => {
+                    return Err(MpcNetError::Config(format!(
+                        "line {}: unknown table [{}]",
+                        table.line, name
+                    )))
+                }
+            }
+            table.finish()?;
+        }
+        parties.sort_by_key(|(id, _, _)| *id);
+        for (i, (id, _, line)) in parties.iter().enumerate() {
+            if *id != i {
+                return Err(MpcNetError::Config(format!(
+                    "line {}: party ids must be 0, 1, ..., each listed once, but found {} where {} \
+                     was expected",
+                    line, id, i
+                )));
+            }
+        }
+        Ok(Self {
+            parties: parties.into_iter().map(|(_, p, _)| p).collect(),
+            options,
+            session_id,
+        })
+    }
+}
+
+impl FromStr for HostConfig {
+    type Err = MpcNetError;
+    fn from_str(s: &str) -> Result<Self, MpcNetError> {
+        Self::parse(s)
+    }
+}
+
+fn parse_addr(s: &str) -> Result<SocketAddr, MpcNetError> {
+    s.parse()
+        .map_err(|e| MpcNetError::Config(format!("bad socket address: {}:\n{}", s, e)))
+}
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Int(u64),
+}
+
+/// The keys of one table, which are removed as they are read.
+struct Table {
+    /// Where the table starts.
+    line: usize,
+    values: HashMap<String, (usize, Value)>,
+}
+
+impl Table {
+    fn take_str(&mut self, key: &str) -> Result<Option<String>, MpcNetError> {
+        match self.values.remove(key) {
+            None => Ok(None),
+            Some((_, Value::Str(s))) => Ok(Some(s)),
+            Some((line, v)) => Err(MpcNetError::Config(format!(
+                "line {}: {} should be a string, not {:?}",
+                line, key, v
+            ))),
+        }
+    }
+
+    fn take_int(&mut self, key: &str) -> Result<Option<u64>, MpcNetError> {
+        match self.values.remove(key) {
+            None => Ok(None),
+            Some((_, Value::Int(i))) => Ok(Some(i)),
+            Some((line, v)) => Err(MpcNetError::Config(format!(
+                "line {}: {} should be an integer, not {:?}",
+                line, key, v
+            ))),
+        }
+    }
+
+    fn require<T>(
+        &mut self,
+        take: impl FnOnce(&mut Self, &str) -> Result<Option<T>, MpcNetError>,
+        key: &str,
+    ) -> Result<T, MpcNetError> {
+        let line = self.line;
+        take(self, key)?
+            .ok_or_else(|| MpcNetError::Config(format!("line {}: missing {}", line, key)))
+    }
+
+    /// Fail if any keys were not read: they are probably misspelled.
+    fn finish(self) -> Result<(), MpcNetError> {
+        match self.values.into_iter().min_by_key(|(_, (line, _))| *line) {
+            None => Ok(()),
+            Some((key, (line, _))) => Err(MpcNetError::Config(format!(
+                "line {}: unknown key {}",
+                line, key
+            ))),
+        }
+    }
+}
+
+/// Split `text` into its tables, in order. The top-level keys are the table named "".
+fn parse_tables(text: &str) -> Result<Vec<(String, Table)>, MpcNetError> {
+    let mut tables = vec![(
+        String::new(),
+        Table {
+            line: 1,
+            values: HashMap::new(),
+        },
+    )];
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |msg: &str| MpcNetError::Config(format!("line {}: {}", line_no, msg));
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            let (name, array) = match line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+                Some(name) => (name, true),
+                None => (
+                    line.strip_prefix('[')
+                        .and_then(|l| l.strip_suffix(']'))
+                        .ok_or_else(|| err("bad table header"))?,
+                    false,
+                ),
+            };
+            let name = name.trim();
+            // Only `party` is an array of tables; it is an error to repeat any other table.
+            if array != (name == "party") || (!array && tables.iter().any(|(n, _)| n == name)) {
+                return Err(err(&format!("unexpected table header {}", line)));
+            }
+            let table = Table {
+                line: line_no,
+                values: HashMap::new(),
+            };
+            tables.push((name.to_string(), table));
+            continue;
+        }
+        let mut kv = line.splitn(2, '=');
+        let key = kv.next().unwrap().trim();
+        let value = kv.next().ok_or_else(|| err("expected key = value"))?.trim();
+        let value = parse_value(value).ok_or_else(|| err(&format!("bad value {}", value)))?;
+        let (_, table) = tables.last_mut().unwrap();
+        if table
+            .values
+            .insert(key.to_string(), (line_no, value))
+            .is_some()
+        {
+            return Err(err(&format!("{} is set twice", key)));
+        }
+    }
+    Ok(tables)
+}
+
+/// `line`, up to any `#` outside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    if let Some(body) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        let mut out = String::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next()? {
+                    '\\' => out.push('\\'),
+                    '"' => out.push('"'),
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    _ => return None,
+                },
+                '"' => return None,
+                c => out.push(c),
+            }
+        }
+        Some(Value::Str(out))
+    } else {
+        s.replace('_', "").parse().ok().map(Value::Int)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Topology;
+
+    fn bad(text: &str) -> String {
+        match text.parse::<HostConfig>() {
+            Err(MpcNetError::Config(msg)) => msg,
+            r => panic!("expected a config error for\n{}\ngot {:?}", text, r),
+        }
+    }
+
+    #[test]
+    fn plain() {
+        let config: HostConfig = "127.0.0.1:8000\n\n# a comment\n127.0.0.1:8001\n"
+            .parse()
+            .unwrap();
+        assert_eq!(config.n_parties(), 2);
+        assert_eq!(config.parties[1].bind, config.parties[1].address);
+        assert_eq!(config.options, Default::default());
+        assert!(bad("127.0.0.1\n").contains("bad socket address"));
+        assert!(bad("").contains("no parties"));
+    }
+
+    #[test]
+    fn toml() {
+        let config: HostConfig = r#"
+            session_id = "run # 7" # a comment
+            king = 1
+            topology = "hybrid"
+            max_message_len = 1_000
+
+            [timeouts]
+            read_ms = 1_500
+
+            [[party]]
+            id = 1
+            address = "10.0.0.2:8000"
+            bind = "0.0.0.0:8000"
+
+            [[party]]
+            id = 0
+            address = "10.0.0.1:8000"
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(config.session_id.as_deref(), Some("run # 7"));
+        assert_eq!(config.options.king, 1);
+        assert_eq!(config.options.topology, Topology::Hybrid);
+        assert_eq!(config.options.max_message_len, 1000);
+        assert_eq!(
+            config.options.timeouts.read,
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(config.parties[0].address, "10.0.0.1:8000".parse().unwrap());
+        assert_eq!(config.parties[1].bind, "0.0.0.0:8000".parse().unwrap());
+    }
+
+    #[test]
+    fn malformed_toml() {
+        let party = "[[party]]\nid = 0\naddress = \"10.0.0.1:8000\"\n";
+        assert!(bad(&format!("{}port = 1", party)).contains("unknown key port"));
+        assert!(bad(&format!("[party]\n{}", &party[10..])).contains("unexpected table"));
+        assert!(bad(&format!("[timeouts\n{}", party)).contains("bad table header"));
+        assert!(bad(&format!("[other]\n{}", party)).contains("unknown table"));
+        assert!(bad(&format!("{}king", party)).contains("expected key = value"));
+        assert!(bad(&format!("king = one\n{}", party)).contains("bad value"));
+        assert!(bad(&format!("king = \"1\"\n{}", party)).contains("should be an integer"));
+        assert!(bad(&format!("topology = \"ring\"\n{}", party)).contains("unknown topology"));
+        assert!(bad(&format!("session_id = \"a\nsession_id = \"b\"\n{}", party))
+            .contains("bad value"));
+        assert!(bad("[[party]]\nid = 0").contains("missing address"));
+        assert!(bad("[[party]]\naddress = \"10.0.0.1:8000\"").contains("missing id"));
+    }
+
+    #[test]
+    fn bad_ids() {
+        let party = |id: usize| format!("[[party]]\nid = {}\naddress = \"10.0.0.1:8000\"\n", id);
+        // Ids are 0, 1, ..., each listed once.
+        assert!(bad(&party(1)).contains("found 1 where 0"));
+        assert!(bad(&format!("{}{}", party(0), party(0))).contains("found 0 where 1"));
+        assert!(bad(&format!("{}{}", party(0), party(2))).contains("found 2 where 1"));
+        assert!(bad(&format!("{}id = 1", party(0))).contains("id is set twice"));
+        // The king is one of the parties.
+        let two = format!("{}{}", party(0), party(1));
+        assert!(format!("king = 1\n{}", two).parse::<HostConfig>().is_ok());
+        assert!(bad(&format!("king = 2\n{}", two)).contains("king, party 2"));
+        assert!(bad("king = 1\n127.0.0.1:8000").contains("expected key = value"));
+        // And so are we.
+        let config: HostConfig = two.parse().unwrap();
+        assert!(config.check(1).is_ok());
+        assert!(matches!(config.check(2), Err(MpcNetError::Config(_))));
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use super::frame::Tag;
+
+/// A network operation, for error reporting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetOp {
+    Connect,
+    Accept,
+    Broadcast,
+    AllToAll,
+    SendToKing,
+    RecvFromKing,
+    Exchange,
+    Handshake,
+}
+
+impl Display for NetOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NetOp::Connect => "connect",
+            NetOp::Accept => "accept",
+            NetOp::Broadcast => "broadcast",
+            NetOp::AllToAll => "all-to-all",
+            NetOp::SendToKing => "send to king",
+            NetOp::RecvFromKing => "receive from king",
+            NetOp::Exchange => "exchange",
+            NetOp::Handshake => "handshake",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl NetOp {
+    /// This operation's code in message headers.
+    pub(crate) fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        [
+            NetOp::Connect,
+            NetOp::Accept,
+            NetOp::Broadcast,
+            NetOp::AllToAll,
+            NetOp::SendToKing,
+            NetOp::RecvFromKing,
+            NetOp::Exchange,
+            NetOp::Handshake,
+        ]
+        .iter()
+        .copied()
+        .find(|op| op.code() == code)
+    }
+}
+
+#[derive(Debug)]
+pub enum MpcNetError {
+    /// The host configuration could not be read.
+    Config(String),
+    /// Talking to `peer` failed, or timed out.
+    Io {
+        peer: usize,
+        op: NetOp,
+        /// How many bytes we were sending or waiting for
+        bytes_expected: usize,
+        source: io::Error,
+    },
+    /// `peer` sent a message of the wrong length.
+    BadLength {
+        peer: usize,
+        op: NetOp,
+        bytes_expected: usize,
+        bytes_received: usize,
+    },
+    /// `peer` aborted the computation. If `peer` is us, the network was used after an abort.
+    Aborted { peer: usize, op: NetOp },
+    /// `peer` failed authentication, or a message from it was tampered with.
+    Auth {
+        peer: usize,
+        op: NetOp,
+        reason: &'static str,
+    },
+    /// `peer` sent a message we cannot read: it uses another framing version, or the frame is
+    /// malformed.
+    BadFrame {
+        peer: usize,
+        op: NetOp,
+        reason: String,
+    },
+    /// `peer` sent a message for another operation than the one we are running: the parties have
+    /// fallen out of step.
+    OutOfOrder {
+        peer: usize,
+        expected: Tag,
+        received: Tag,
+    },
+    /// `peer`, the king, relayed us different broadcast messages than it relayed `witness` (or
+    /// `witness` lied about what it got).
+    Equivocation {
+        peer: usize,
+        witness: usize,
+        op: NetOp,
+    },
+}
+
+impl MpcNetError {
+    /// Wraps an I/O error from talking to `peer`.
+    pub(crate) fn io(peer: usize, op: NetOp, bytes_expected: usize) -> impl Fn(io::Error) -> Self {
+        move |source| MpcNetError::Io {
+            peer,
+            op,
+            bytes_expected,
+            // This is how a read timeout shows up on unix.
+            source: if source.kind() == io::ErrorKind::WouldBlock {
+                io::Error::new(io::ErrorKind::TimedOut, "timed out")
+            } else {
+                source
+            },
+        }
+    }
+
+    /// The peer that caused the error, if there is one.
+    pub fn peer(&self) -> Option<usize> {
+        match self {
+            MpcNetError::Config(_) => None,
+            MpcNetError::Io { peer, .. }
+            | MpcNetError::BadLength { peer, .. }
+            | MpcNetError::Aborted { peer, .. }
+            | MpcNetError::Auth { peer, .. }
+            | MpcNetError::BadFrame { peer, .. }
+            | MpcNetError::OutOfOrder { peer, .. }
+            | MpcNetError::Equivocation { peer, .. } => Some(*peer),
+        }
+    }
+
+    /// Did the error come from a timeout?
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            MpcNetError::Io { source, .. } => source.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+}
+
+impl Display for MpcNetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MpcNetError::Config(msg) => write!(f, "bad host configuration: {}", msg),
+            MpcNetError::Io {
+                peer,
+                op,
+                bytes_expected,
+                source,
+            } => write!(
+                f,
+                "{} with party {} failed ({} bytes expected): {}",
+                op, peer, bytes_expected, source
+            ),
+            MpcNetError::BadLength {
+                peer,
+                op,
+                bytes_expected,
+                bytes_received,
+            } => write!(
+                f,
+                "{} with party {}: expected {} bytes, got {}",
+                op, peer, bytes_expected, bytes_received
+            ),
+            MpcNetError::Aborted { peer, op } => {
+                write!(f, "{}: party {} aborted the computation", op, peer)
+            }
+            MpcNetError::Auth { peer, op, reason } => {
+                write!(f, "{} with party {}: authentication failed: {}", op, peer, reason)
+            }
+            MpcNetError::BadFrame { peer, op, reason } => {
+                write!(f, "{} with party {}: bad message: {}", op, peer, reason)
+            }
+            MpcNetError::OutOfOrder {
+                peer,
+                expected,
+                received,
+            } => write!(
+                f,
+                "expected a message for {} from party {}, got one for {}",
+                expected, peer, received
+            ),
+            MpcNetError::Equivocation { peer, witness, op } => write!(
+                f,
+                "{}: the king, party {}, relayed party {} different messages than us",
+                op, peer, witness
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MpcNetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MpcNetError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! How messages between parties are framed.
+//!
+//! On the wire, each message is its length (8 bytes, little-endian) followed by that many bytes:
+//! a header, then the payload. Over a [crate::secure] channel the header is sealed along with the
+//! payload, so it is authenticated too. The header holds
+//!
+//! * the framing [VERSION], so that parties running incompatible builds fail on their first
+//!   message, and
+//! * a [Tag]: the operation the message belongs to, and how many operations the sender ran
+//!   before it.
+//!
+//! Every party runs the same operations in the same order. A message with an unexpected tag means
+//! that the parties have fallen out of step, and we fail with [MpcNetError::OutOfOrder] instead of
+//! reading it as part of the wrong round.
+use std::fmt::{self, Display, Formatter};
+use std::io::Read;
+
+use super::{MpcNetError, NetOp, ABORT_MARKER};
+
+/// The version of this framing.
+pub const VERSION: u8 = 1;
+
+/// Length of the header: the version, the operation, and the round.
+pub const HEADER_LEN: usize = 10;
+
+/// Which message of the computation this is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub op: NetOp,
+    /// How many operations the sender ran before this one.
+    pub round: u64,
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (round {})", self.op, self.round)
+    }
+}
+
+/// Counts a transport's operations, to tag their messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Rounds(u64);
+
+impl Rounds {
+    /// The tag for our next operation, `op`.
+    pub(crate) fn next(&mut self, op: NetOp) -> Tag {
+        let tag = Tag { op, round: self.0 };
+        self.0 += 1;
+        tag
+    }
+}
+
+/// `payload`, with a header carrying `tag`.
+pub(crate) fn encode(tag: Tag, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.push(VERSION);
+    frame.push(tag.op.code());
+    frame.extend_from_slice(&tag.round.to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// The payload of `frame`, which `peer` sent us. It should carry the tag `expected`.
+pub(crate) fn decode(
+    peer: usize,
+    expected: Tag,
+    mut frame: Vec<u8>,
+) -> Result<Vec<u8>, MpcNetError> {
+    let bad = |reason: String| MpcNetError::BadFrame {
+        peer,
+        op: expected.op,
+        reason,
+    };
+    if frame.len() < HEADER_LEN {
+        return Err(bad(format!("{} byte frame has no header", frame.len())));
+    }
+    if frame[0] != VERSION {
+        return Err(bad(format!(
+            "framing version {}, but we run version {}",
+            frame[0], VERSION
+        )));
+    }
+    let op =
+        NetOp::from_code(frame[1]).ok_or_else(|| bad(format!("unknown operation {}", frame[1])))?;
+    let mut round = [0u8; 8];
+    round.copy_from_slice(&frame[2..HEADER_LEN]);
+    let received = Tag {
+        op,
+        round: u64::from_le_bytes(round),
+    };
+    if received != expected {
+        return Err(MpcNetError::OutOfOrder {
+            peer,
+            expected,
+            received,
+        });
+    }
+    Ok(frame.split_off(HEADER_LEN))
+}
+
+/// Read one length-prefixed message from `peer`, during `op`, without opening or decoding it.
+///
+/// Fails with [MpcNetError::Aborted] if the peer announced an abort instead, and with
+/// [MpcNetError::BadLength] if it announced more than `max_len` bytes, before we set aside room
+/// for them.
+pub(crate) fn read_msg(
+    s: &mut impl Read,
+    peer: usize,
+    op: NetOp,
+    max_len: usize,
+) -> Result<Vec<u8>, MpcNetError> {
+    let mut len = [0u8; 8];
+    s.read_exact(&mut len)
+        .map_err(MpcNetError::io(peer, op, len.len()))?;
+    let len = u64::from_le_bytes(len);
+    if len == ABORT_MARKER {
+        return Err(MpcNetError::Aborted { peer, op });
+    }
+    if len > max_len as u64 {
+        return Err(MpcNetError::BadLength {
+            peer,
+            op,
+            bytes_expected: max_len,
+            bytes_received: len as usize,
+        });
+    }
+    let mut msg = vec![0u8; len as usize];
+    s.read_exact(&mut msg)
+        .map_err(MpcNetError::io(peer, op, msg.len()))?;
+    Ok(msg)
+}
+
+/// Several messages as one, each prefixed with its length, for the king to relay.
+pub(crate) fn join(msgs: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(msgs.iter().map(|m| 8 + m.len()).sum());
+    for m in msgs {
+        bytes.extend_from_slice(&(m.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(m);
+    }
+    bytes
+}
+
+/// Undo [join] on `bytes`, which `peer` sent us during `op`.
+pub(crate) fn split(peer: usize, op: NetOp, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+    let mut msgs = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let mut len = [0u8; 8];
+        let m = rest.get(..8).and_then(|l| {
+            len.copy_from_slice(l);
+            let end = (u64::from_le_bytes(len) as usize).checked_add(8)?;
+            rest.get(8..end)
+        });
+        let m = m.ok_or_else(|| MpcNetError::BadFrame {
+            peer,
+            op,
+            reason: "truncated relayed message".into(),
+        })?;
+        rest = &rest[8 + m.len()..];
+        msgs.push(m.to_vec());
+    }
+    Ok(msgs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_parties, ActiveNet as Net, MpcNet};
+
+    #[test]
+    fn round_trip() {
+        let tag = Tag {
+            op: NetOp::Broadcast,
+            round: 7,
+        };
+        let frame = encode(tag, b"payload");
+        assert_eq!(frame.len(), HEADER_LEN + 7);
+        assert_eq!(decode(1, tag, frame).unwrap(), b"payload");
+        let msgs = vec![vec![], vec![1, 2], vec![3]];
+        assert_eq!(split(0, NetOp::Broadcast, &join(&msgs)).unwrap(), msgs);
+    }
+
+    #[test]
+    fn bad_version() {
+        let tag = Tag {
+            op: NetOp::AllToAll,
+            round: 0,
+        };
+        let mut frame = encode(tag, &[1, 2, 3]);
+        frame[0] = VERSION + 1;
+        match decode(2, tag, frame) {
+            Err(MpcNetError::BadFrame { peer: 2, op, .. }) => assert_eq!(op, NetOp::AllToAll),
+            r => panic!("expected a bad frame, got {:?}", r),
+        }
+        assert!(matches!(
+            decode(2, tag, vec![VERSION]),
+            Err(MpcNetError::BadFrame { .. })
+        ));
+    }
+
+    /// A peer that announces a message longer than we allow fails before we read any of it.
+    #[test]
+    fn too_long() {
+        let mut wire = 1000u64.to_le_bytes().to_vec();
+        wire.extend_from_slice(&[0; 1000]);
+        assert_eq!(
+            read_msg(&mut &wire[..], 1, NetOp::Broadcast, 1000).unwrap(),
+            vec![0; 1000]
+        );
+        match read_msg(&mut &wire[..], 1, NetOp::Broadcast, 999) {
+            Err(MpcNetError::BadLength {
+                peer: 1,
+                bytes_expected: 999,
+                bytes_received: 1000,
+                ..
+            }) => {}
+            r => panic!("expected a bad length, got {:?}", r),
+        }
+        let huge = (1u64 << 62).to_le_bytes();
+        assert!(matches!(
+            read_msg(&mut &huge[..], 1, NetOp::Broadcast, 1 << 30),
+            Err(MpcNetError::BadLength { .. })
+        ));
+        let abort = ABORT_MARKER.to_le_bytes();
+        assert!(matches!(
+            read_msg(&mut &abort[..], 1, NetOp::Broadcast, 1 << 30),
+            Err(MpcNetError::Aborted { peer: 1, .. })
+        ));
+    }
+
+    /// Parties can broadcast, and send the king, messages of different lengths.
+    #[test]
+    fn ragged_messages() {
+        run_parties(3, |id| {
+            let mine = vec![id as u8; id];
+            let all = Net::broadcast_bytes(&mine).unwrap();
+            assert_eq!(all, vec![vec![], vec![1], vec![2, 2]]);
+            let back = Net::king_compute(&mine, |all| all.into_iter().rev().collect()).unwrap();
+            assert_eq!(back.len(), 2 - id);
+        });
+    }
+
+    /// A party that runs a different operation from the others is caught on its first message.
+    #[test]
+    fn out_of_order() {
+        let results = run_parties(2, |id| {
+            if id == 0 {
+                Net::all_to_all_bytes(&[vec![1], vec![1]])
+            } else {
+                Net::broadcast_bytes(&[1])
+            }
+        });
+        let ops = [NetOp::AllToAll, NetOp::Broadcast];
+        for (id, r) in results.into_iter().enumerate() {
+            match r {
+                Err(MpcNetError::OutOfOrder {
+                    peer,
+                    expected,
+                    received,
+                }) => {
+                    let tag = |op| Tag { op, round: 0 };
+                    assert_eq!(peer, 1 - id);
+                    assert_eq!(expected, tag(ops[id]));
+                    assert_eq!(received, tag(ops[1 - id]));
+                }
+                r => panic!("expected an out-of-order error, got {:?}", r),
+            }
+        }
+    }
+}
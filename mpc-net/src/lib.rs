@@ -1,61 +1,287 @@
+pub mod async_net;
+pub mod config;
+pub mod error;
+pub mod frame;
+pub mod mem;
 pub mod multi;
+pub mod secure;
+pub mod session;
 pub mod two;
 
-pub use two::MpcTwoNet;
+pub use async_net::{AsyncMpcNet, MpcAsyncNet};
+pub use config::HostConfig;
+pub use error::{MpcNetError, NetOp};
+pub use mem::{run_on, run_parties, MemTransport};
 pub use multi::MpcMultiNet;
+pub use session::Session;
+pub use two::MpcTwoNet;
+
+use std::fmt::{self, Display, Formatter};
+use std::io::Read;
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Stats {
     pub bytes_sent: usize,
     pub bytes_recv: usize,
     pub broadcasts: usize,
     pub to_king: usize,
     pub from_king: usize,
+    /// `bytes_sent_to[j]` is how many bytes we sent party `j` directly. Empty if the transport
+    /// does not count bytes per link.
+    pub bytes_sent_to: Vec<usize>,
+    /// `bytes_recv_from[j]` is how many bytes we got from party `j` directly.
+    pub bytes_recv_from: Vec<usize>,
 }
 
-impl std::default::Default for Stats {
+/// How long to wait for peers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    /// How long to keep trying to reach each peer while connecting.
+    pub connect: Duration,
+    /// How long to wait for a message from a peer. `None` waits forever.
+    pub read: Option<Duration>,
+}
+
+impl std::default::Default for Timeouts {
     fn default() -> Self {
         Self {
-            bytes_sent: 0,
-            bytes_recv: 0,
-            broadcasts: 0,
-            to_king: 0,
-            from_king: 0,
+            connect: Duration::from_secs(30),
+            read: None,
         }
     }
 }
 
+/// Which parties have connections to each other, and how broadcasts travel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Every pair of parties is connected, and each party sends its broadcasts straight to every
+    /// other party.
+    #[default]
+    Mesh,
+    /// Every party is connected to the king only, which relays all traffic. This needs n - 1
+    /// connections, rather than n(n - 1)/2, but the king carries every message. Parties need
+    /// [secure] channels, whose keys sign what they got in each broadcast.
+    Star,
+    /// Every pair of parties is connected, but broadcasts are relayed by the king, so each party
+    /// sends its message once rather than n - 1 times.
+    Hybrid,
+}
+
+impl Display for Topology {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Topology::Mesh => "mesh",
+            Topology::Star => "star",
+            Topology::Hybrid => "hybrid",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Topology {
+    type Err = MpcNetError;
+    fn from_str(s: &str) -> Result<Self, MpcNetError> {
+        match s {
+            "mesh" => Ok(Topology::Mesh),
+            "star" => Ok(Topology::Star),
+            "hybrid" => Ok(Topology::Hybrid),
+            _ => Err(MpcNetError::Config(format!(
+                "unknown topology {} (expected mesh, star or hybrid)",
+                s
+            ))),
+        }
+    }
+}
+
+/// How to connect to the other parties.
+///
+/// The king relays messages in a [Topology::Star] or [Topology::Hybrid] network, so it can drop
+/// them. It cannot change broadcasts unnoticed: after each one, parties compare digests of what
+/// the king relayed them (directly in a hybrid network, and signed in a star one), and fail with
+/// [MpcNetError::Equivocation] if they differ. In a star network the king also relays, and so can
+/// read and change, the messages parties send each other directly; use checks such as MACs that
+/// catch changed values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetOptions {
+    pub timeouts: Timeouts,
+    /// The party that coordinates the others.
+    pub king: usize,
+    pub topology: Topology,
+    /// The longest message, in bytes, that we accept from a peer. A peer that announces a longer
+    /// one fails with [MpcNetError::BadLength], before we set aside room for it.
+    pub max_message_len: usize,
+}
+
+impl std::default::Default for NetOptions {
+    fn default() -> Self {
+        Self {
+            timeouts: Timeouts::default(),
+            king: 0,
+            topology: Topology::default(),
+            max_message_len: 1 << 30,
+        }
+    }
+}
+
+/// Messages are prefixed with their length; this length instead announces an abort.
+pub(crate) const ABORT_MARKER: u64 = u64::MAX;
+
+/// Did the peer on the other end of `s` abort (and hang up)?
+///
+/// An abort is the last thing a peer sends, so we look at the end of whatever it left us.
+pub(crate) fn sent_abort(s: &mut TcpStream) -> bool {
+    let mut left = Vec::new();
+    let _ = s.set_nonblocking(true);
+    let _ = s.read_to_end(&mut left);
+    let _ = s.set_nonblocking(false);
+    left.ends_with(&ABORT_MARKER.to_le_bytes())
+}
+
+/// One party's connections to the others.
+///
+/// Network operations fail with a [MpcNetError]. When an operation fails, the party aborts: it
+/// tells all its peers (who will see [MpcNetError::Aborted] when they next hear from it), and
+/// stops talking to them.
+pub trait MpcTransport: Send {
+    /// What is my party number (0 to n-1)?
+    fn party_id(&self) -> usize;
+    /// How many parties are there?
+    fn n_parties(&self) -> usize;
+    /// Which party is the king?
+    fn king(&self) -> usize {
+        0
+    }
+    /// Abort the computation: tell all peers, and close all connections.
+    fn abort(&mut self);
+    /// Set statistics to zero.
+    fn reset_stats(&mut self);
+    /// Get statistics.
+    fn stats(&self) -> Stats;
+    /// All parties send bytes to each other.
+    fn broadcast_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError>;
+    /// Each party sends `bytes[j]` to party `j` (and no one else).
+    /// Returns the bytes that each party sent to us.
+    fn all_to_all_bytes(&mut self, bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError>;
+    /// All parties send bytes to the king.
+    fn send_bytes_to_king(&mut self, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError>;
+    /// All parties recv bytes from the king.
+    /// Provide bytes iff you're the king!
+    fn recv_bytes_from_king(
+        &mut self,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError>;
+}
+
+/// The network of the current [Session].
+///
+/// Implementors differ only in how they connect; everything else goes through whichever session
+/// is active on the calling thread.
 pub trait MpcNet {
-    /// Am I the first party?
+    /// Am I the king?
     #[inline]
     fn am_king() -> bool {
-        Self::party_id() == 0
+        Self::party_id() == Self::king()
+    }
+    /// Which party is the king: the one that coordinates the others?
+    #[inline]
+    fn king() -> usize {
+        Session::expect_current().king()
     }
     /// How many parties are there?
-    fn n_parties() -> usize;
+    #[inline]
+    fn n_parties() -> usize {
+        Session::expect_current().n_parties()
+    }
     /// What is my party number (0 to n-1)?
-    fn party_id() -> usize;
-    /// Initialize the network layer from a file.
-    /// The file should contain one HOST:PORT setting per line, corresponding to the addresses of
-    /// the parties in increasing order.
+    #[inline]
+    fn party_id() -> usize {
+        Session::expect_current().party_id()
+    }
+    /// Initialize the network layer from a host file (see [config] for its formats).
     ///
     /// Parties are zero-indexed.
-    fn init_from_file(path: &str, party_id: usize);
-    /// Is the network layer initalized?
-    fn is_init() -> bool;
-    /// Uninitialize the network layer, closing all connections.
-    fn deinit();
+    #[inline]
+    fn init_from_file(path: &str, party_id: usize) -> Result<(), MpcNetError> {
+        Self::init_from_config(&HostConfig::from_file(path)?, party_id)
+    }
+    /// Like [MpcNet::init_from_file], but with custom timeouts.
+    #[inline]
+    fn init_from_file_with_timeouts(
+        path: &str,
+        party_id: usize,
+        timeouts: Timeouts,
+    ) -> Result<(), MpcNetError> {
+        let mut config = HostConfig::from_file(path)?;
+        config.options.timeouts = timeouts;
+        Self::init_from_config(&config, party_id)
+    }
+    /// Like [MpcNet::init_from_file], but with `options` in place of those in the file.
+    #[inline]
+    fn init_from_file_with_options(
+        path: &str,
+        party_id: usize,
+        options: NetOptions,
+    ) -> Result<(), MpcNetError> {
+        let mut config = HostConfig::from_file(path)?;
+        config.options = options;
+        Self::init_from_config(&config, party_id)
+    }
+    /// Connect to the parties in `config`, as party `party_id`.
+    ///
+    /// The new connections become the default session (see [Session::set_default]).
+    fn init_from_config(config: &HostConfig, party_id: usize) -> Result<(), MpcNetError>;
+    /// Is there an active session?
+    #[inline]
+    fn is_init() -> bool {
+        Session::current().is_some()
+    }
+    /// Drop the default session. Its connections close once no thread is using it.
+    #[inline]
+    fn deinit() {
+        Session::set_default(None);
+    }
+    /// Abort the computation: tell all peers, and close all connections.
+    #[inline]
+    fn abort() {
+        Session::expect_current().with_transport(|t| t.abort())
+    }
     /// Set statistics to zero.
-    fn reset_stats();
-    /// Get statistics.
-    fn stats() -> Stats;
+    #[inline]
+    fn reset_stats() {
+        if let Some(s) = Session::current() {
+            s.with_transport(|t| t.reset_stats())
+        }
+    }
+    /// Get statistics (all zero if there is no session).
+    #[inline]
+    fn stats() -> Stats {
+        Session::current().map_or_else(Stats::default, |s| s.with_transport(|t| t.stats()))
+    }
     /// All parties send bytes to each other.
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>>;
+    #[inline]
+    fn broadcast_bytes(bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        Session::expect_current().with_transport(|t| t.broadcast_bytes(bytes))
+    }
+    /// Each party sends `bytes[j]` to party `j` (and no one else).
+    /// Returns the bytes that each party sent to us.
+    #[inline]
+    fn all_to_all_bytes(bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        Session::expect_current().with_transport(|t| t.all_to_all_bytes(bytes))
+    }
     /// All parties send bytes to the king.
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>>;
+    #[inline]
+    fn send_bytes_to_king(bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        Session::expect_current().with_transport(|t| t.send_bytes_to_king(bytes))
+    }
     /// All parties recv bytes from the king.
     /// Provide bytes iff you're the king!
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8>;
+    #[inline]
+    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Result<Vec<u8>, MpcNetError> {
+        Session::expect_current().with_transport(|t| t.recv_bytes_from_king(bytes))
+    }
 
     /// Everyone sends bytes to the king, who recieves those bytes, runs a computation on them, and
     /// redistributes the resulting bytes.
@@ -63,8 +289,97 @@ pub trait MpcNet {
     /// The king's computation is given by a function, `f`
     /// proceeds.
     #[inline]
-    fn king_compute(bytes: &[u8], f: impl Fn(Vec<Vec<u8>>) -> Vec<Vec<u8>>) -> Vec<u8> {
-        let king_response = Self::send_bytes_to_king(bytes).map(f);
+    fn king_compute(
+        bytes: &[u8],
+        f: impl Fn(Vec<Vec<u8>>) -> Vec<Vec<u8>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
+        let king_response = Self::send_bytes_to_king(bytes)?.map(f);
         Self::recv_bytes_from_king(king_response)
     }
 }
+
+/// The network of whichever session is active. Protocols should use this, rather than a
+/// particular kind of network.
+///
+/// Initializing it connects over TCP, as [MpcMultiNet] does.
+pub struct ActiveNet;
+
+impl MpcNet for ActiveNet {
+    #[inline]
+    fn init_from_config(config: &HostConfig, party_id: usize) -> Result<(), MpcNetError> {
+        MpcMultiNet::init_from_config(config, party_id)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::panic;
+
+    /// Run `f` as each of `n` parties, on threads connected over TCP on localhost, with
+    /// `options`. Each party gets the host file and a listener on a port the OS picked, so that
+    /// tests running at once do not collide. The host file lists the public keys of `keys`, if
+    /// there are any.
+    ///
+    /// Each party gets its own rayon pool, as it would in its own process: parties block in
+    /// rayon tasks while they wait for each other, so sharing one pool could deadlock.
+    pub(crate) fn on_localhost<T: Send>(
+        n: usize,
+        options: NetOptions,
+        keys: &[secure::SecretKey],
+        f: impl Fn(&HostConfig, usize, TcpListener) -> T + Sync,
+    ) -> Vec<T> {
+        let listeners: Vec<TcpListener> = (0..n)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let config = HostConfig {
+            parties: listeners
+                .iter()
+                .enumerate()
+                .map(|(id, l)| {
+                    let address = l.local_addr().unwrap();
+                    config::PartyConfig {
+                        address,
+                        bind: address,
+                        public_key: keys.get(id).map(|k| k.public_key()),
+                    }
+                })
+                .collect(),
+            options,
+            session_id: None,
+        };
+        let (config, f) = (&config, &f);
+        std::thread::scope(|scope| {
+            let parties: Vec<_> = listeners
+                .into_iter()
+                .enumerate()
+                .map(|(id, l)| {
+                    scope.spawn(move || {
+                        let pool = rayon::ThreadPoolBuilder::new()
+                            .num_threads(n)
+                            .build()
+                            .unwrap();
+                        pool.install(|| f(config, id, l))
+                    })
+                })
+                .collect();
+            parties.into_iter().map(|p| p.join().unwrap()).collect()
+        })
+    }
+
+    #[test]
+    fn state_survives_panic() {
+        run_parties(2, |_| {
+            session::with_state(|s: &mut Vec<u8>| s.push(1));
+            let r = panic::catch_unwind(|| {
+                session::with_state(|s: &mut Vec<u8>| {
+                    s.push(2);
+                    panic!("oops")
+                })
+            });
+            assert!(r.is_err());
+            assert_eq!(session::with_state(|s: &mut Vec<u8>| s.clone()), vec![1, 2]);
+        });
+    }
+}
@@ -0,0 +1,296 @@
+//! An in-memory network, for running every party inside one process.
+use log::debug;
+
+use std::io;
+use std::panic;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use super::frame::{Rounds, Tag};
+use super::{MpcNetError, MpcTransport, NetOp, Session, Stats};
+
+enum Msg {
+    Data(Tag, Vec<u8>),
+    Abort,
+}
+
+/// One party's end of an in-memory network.
+///
+/// Messages go through unbounded channels, so sending never blocks. They are not serialized, but
+/// carry the same [Tag]s as over TCP, so that parties falling out of step are caught the same way.
+pub struct MemTransport {
+    id: usize,
+    /// `to[j]` carries our messages to party `j`. We have no channel to ourselves.
+    to: Vec<Option<Sender<Msg>>>,
+    /// `from[j]` carries party `j`'s messages to us.
+    from: Vec<Option<Receiver<Msg>>>,
+    read_timeout: Option<Duration>,
+    king: usize,
+    stats: Stats,
+    /// Set once we have aborted; all further operations fail.
+    aborted: bool,
+    rounds: Rounds,
+}
+
+impl MemTransport {
+    /// Connect `n` parties; party `i` gets the `i`th transport.
+    pub fn network(n: usize) -> Vec<Self> {
+        let mut parties: Vec<Self> = (0..n)
+            .map(|id| Self {
+                id,
+                to: (0..n).map(|_| None).collect(),
+                from: (0..n).map(|_| None).collect(),
+                read_timeout: None,
+                king: 0,
+                stats: Stats {
+                    bytes_sent_to: vec![0; n],
+                    bytes_recv_from: vec![0; n],
+                    ..Stats::default()
+                },
+                aborted: false,
+                rounds: Rounds::default(),
+            })
+            .collect();
+        for i in 0..n {
+            for j in 0..n {
+                if i != j {
+                    let (send, recv) = channel();
+                    parties[i].to[j] = Some(send);
+                    parties[j].from[i] = Some(recv);
+                }
+            }
+        }
+        parties
+    }
+
+    /// Fail if a message takes longer than `timeout` to arrive. `None` waits forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Make `king` the king. Every party must agree on it.
+    pub fn set_king(&mut self, king: usize) {
+        assert!(king < self.n_parties(), "no party {}", king);
+        self.king = king;
+    }
+
+    fn send(&mut self, to: usize, tag: Tag, bytes: &[u8]) -> Result<(), MpcNetError> {
+        self.stats.bytes_sent += bytes.len();
+        self.stats.bytes_sent_to[to] += bytes.len();
+        self.to[to]
+            .as_ref()
+            .unwrap()
+            .send(Msg::Data(tag, bytes.to_vec()))
+            .map_err(|_| MpcNetError::Aborted {
+                peer: to,
+                op: tag.op,
+            })
+    }
+
+    /// Receive a message from `from`, which should be tagged `tag`.
+    fn recv(&mut self, from: usize, tag: Tag) -> Result<Vec<u8>, MpcNetError> {
+        let op = tag.op;
+        let r = self.from[from].as_ref().unwrap();
+        let msg = match self.read_timeout {
+            Some(t) => r.recv_timeout(t).map_err(|e| match e {
+                RecvTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, "timed out"),
+                RecvTimeoutError::Disconnected => {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "peer hung up")
+                }
+            }),
+            None => r
+                .recv()
+                .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "peer hung up")),
+        }
+        .map_err(MpcNetError::io(from, op, 0))?;
+        let bytes = match msg {
+            Msg::Data(received, bytes) if received == tag => bytes,
+            Msg::Data(received, _) => {
+                return Err(MpcNetError::OutOfOrder {
+                    peer: from,
+                    expected: tag,
+                    received,
+                })
+            }
+            Msg::Abort => return Err(MpcNetError::Aborted { peer: from, op }),
+        };
+        self.stats.bytes_recv += bytes.len();
+        self.stats.bytes_recv_from[from] += bytes.len();
+        Ok(bytes)
+    }
+
+    fn peers(&self) -> impl Iterator<Item = usize> {
+        let id = self.id;
+        (0..self.to.len()).filter(move |j| *j != id)
+    }
+
+    /// Run `f`, aborting if it fails.
+    fn run<T>(
+        &mut self,
+        op: NetOp,
+        f: impl FnOnce(&mut Self) -> Result<T, MpcNetError>,
+    ) -> Result<T, MpcNetError> {
+        if self.aborted {
+            return Err(MpcNetError::Aborted { peer: self.id, op });
+        }
+        let r = f(self);
+        if let Err(e) = &r {
+            debug!("Network error: {}", e);
+            self.abort();
+        }
+        r
+    }
+}
+
+impl MpcTransport for MemTransport {
+    fn party_id(&self) -> usize {
+        self.id
+    }
+
+    fn n_parties(&self) -> usize {
+        self.to.len()
+    }
+
+    fn king(&self) -> usize {
+        self.king
+    }
+
+    fn abort(&mut self) {
+        if self.aborted {
+            return;
+        }
+        debug!("Aborting");
+        self.aborted = true;
+        for to in &mut self.to {
+            if let Some(s) = to.take() {
+                // The peer may already be gone.
+                let _ = s.send(Msg::Abort);
+            }
+        }
+    }
+
+    fn reset_stats(&mut self) {
+        let n = self.n_parties();
+        self.stats = Stats {
+            bytes_sent_to: vec![0; n],
+            bytes_recv_from: vec![0; n],
+            ..Stats::default()
+        };
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+
+    fn broadcast_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        self.run(NetOp::Broadcast, |t| {
+            let tag = t.rounds.next(NetOp::Broadcast);
+            t.stats.broadcasts += 1;
+            for j in t.peers().collect::<Vec<_>>() {
+                t.send(j, tag, bytes)?;
+            }
+            (0..t.n_parties())
+                .map(|j| {
+                    if j == t.id {
+                        Ok(bytes.to_vec())
+                    } else {
+                        t.recv(j, tag)
+                    }
+                })
+                .collect()
+        })
+    }
+
+    fn all_to_all_bytes(&mut self, bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        assert_eq!(bytes.len(), self.n_parties());
+        self.run(NetOp::AllToAll, |t| {
+            let tag = t.rounds.next(NetOp::AllToAll);
+            for j in t.peers().collect::<Vec<_>>() {
+                t.send(j, tag, &bytes[j])?;
+            }
+            (0..t.n_parties())
+                .map(|j| {
+                    if j == t.id {
+                        Ok(bytes[j].clone())
+                    } else {
+                        t.recv(j, tag)
+                    }
+                })
+                .collect()
+        })
+    }
+
+    fn send_bytes_to_king(&mut self, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        self.run(NetOp::SendToKing, |t| {
+            let tag = t.rounds.next(NetOp::SendToKing);
+            t.stats.to_king += 1;
+            if t.id == t.king {
+                (0..t.n_parties())
+                    .map(|j| {
+                        if j == t.id {
+                            Ok(bytes.to_vec())
+                        } else {
+                            t.recv(j, tag)
+                        }
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Some)
+            } else {
+                t.send(t.king, tag, bytes)?;
+                Ok(None)
+            }
+        })
+    }
+
+    fn recv_bytes_from_king(
+        &mut self,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
+        self.run(NetOp::RecvFromKing, |t| {
+            let tag = t.rounds.next(NetOp::RecvFromKing);
+            t.stats.from_king += 1;
+            if t.id == t.king {
+                let mut bytes = bytes.expect("king needs bytes");
+                assert_eq!(bytes.len(), t.n_parties());
+                for j in t.peers().collect::<Vec<_>>() {
+                    t.send(j, tag, &bytes[j])?;
+                }
+                Ok(bytes.swap_remove(t.id))
+            } else {
+                t.recv(t.king, tag)
+            }
+        })
+    }
+}
+
+/// Run `f(party_id)` as each of `n` parties, each on its own thread, in its own session over a
+/// [MemTransport]. Returns the parties' outputs, in order.
+///
+/// If any party panics, so does this (once every party has stopped).
+pub fn run_parties<T: Send>(n: usize, f: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    run_on(MemTransport::network(n), f)
+}
+
+/// Like [run_parties], but over the given transports (from [MemTransport::network]), which may
+/// have been configured first.
+pub fn run_on<T: Send>(transports: Vec<MemTransport>, f: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    let f = &f;
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = transports
+            .into_iter()
+            .enumerate()
+            .map(|(id, transport)| {
+                let session = Session::new(transport);
+                std::thread::Builder::new()
+                    .name(format!("party {}", id))
+                    .spawn_scoped(scope, move || session.enter(|| f(id)))
+                    .expect("could not spawn party thread")
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join()).collect()
+    });
+    results
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|e| panic::resume_unwind(e)))
+        .collect()
+}
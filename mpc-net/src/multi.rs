@@ -1,39 +1,53 @@
-use lazy_static::lazy_static;
 use log::debug;
 use rayon::prelude::*;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::Mutex;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
 
 use ark_std::{end_timer, start_timer};
+use sha2::{Digest, Sha256};
 
-use super::{MpcNet, Stats};
-
-#[macro_use]
-lazy_static! {
-    static ref CONNECTIONS: Mutex<Connections> = Mutex::new(Connections::default());
-}
-
-/// Macro for locking the FieldChannel singleton in the current scope.
-macro_rules! get_ch {
-    () => {
-        CONNECTIONS.lock().expect("Poisoned FieldChannel")
-    };
-}
+use super::config::HostConfig;
+use super::frame::{self, Rounds, Tag};
+use super::secure::{PublicKey, SecretKey, SecureChannel};
+use super::{
+    sent_abort, MpcNet, MpcNetError, MpcTransport, NetOp, NetOptions, Session, Stats, Topology,
+    ABORT_MARKER,
+};
 
 #[derive(Debug)]
 struct Peer {
     id: usize,
     addr: SocketAddr,
+    /// Where the peer listens; we only use our own.
+    bind: SocketAddr,
     stream: Option<TcpStream>,
+    /// The peer's public key, if the host file lists one.
+    public_key: Option<PublicKey>,
+    /// Set once the handshake with this peer is done.
+    channel: Option<SecureChannel>,
+    /// Bytes sent to and received from this peer, including framing.
+    bytes_sent: usize,
+    bytes_recv: usize,
+    /// The longest message we accept from this peer; see [NetOptions::max_message_len].
+    max_message_len: usize,
 }
 
+/// TCP connections to the other parties: to all of them, or in a [Topology::Star] network, just
+/// between the king and everyone else.
 #[derive(Default, Debug)]
-struct Connections {
+pub struct Connections {
     id: usize,
     peers: Vec<Peer>,
     stats: Stats,
+    options: NetOptions,
+    /// Digest of the session id; see [HostConfig::session_id].
+    session: [u8; 32],
+    /// Our secret key, when running over secure channels.
+    secret_key: Option<SecretKey>,
+    /// Set once we have aborted; all further operations fail.
+    aborted: bool,
+    rounds: Rounds,
 }
 
 impl std::default::Default for Peer {
@@ -41,268 +55,803 @@ impl std::default::Default for Peer {
         Self {
             id: 0,
             addr: "127.0.0.1:8000".parse().unwrap(),
+            bind: "127.0.0.1:8000".parse().unwrap(),
             stream: None,
+            public_key: None,
+            channel: None,
+            bytes_sent: 0,
+            bytes_recv: 0,
+            max_message_len: NetOptions::default().max_message_len,
         }
     }
 }
 
+impl Peer {
+    fn stream(&mut self) -> &mut TcpStream {
+        self.stream
+            .as_mut()
+            .expect("Unconnected peer. Did you forget init_from_file(..)?")
+    }
+
+    /// Send `bytes` as the message tagged `tag`.
+    fn send(&mut self, tag: Tag, bytes: &[u8]) -> Result<(), MpcNetError> {
+        let id = self.id;
+        let mut bytes = frame::encode(tag, bytes);
+        if let Some(channel) = self.channel.as_mut() {
+            bytes = channel.seal(&bytes);
+        }
+        let mut wire = Vec::with_capacity(8 + bytes.len());
+        wire.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        wire.extend_from_slice(&bytes);
+        let s = self.stream();
+        s.write_all(&wire).map_err(|e| {
+            if sent_abort(s) {
+                MpcNetError::Aborted {
+                    peer: id,
+                    op: tag.op,
+                }
+            } else {
+                MpcNetError::io(id, tag.op, bytes.len())(e)
+            }
+        })?;
+        self.bytes_sent += wire.len();
+        Ok(())
+    }
+
+    /// Receive a message, which should be tagged `tag`.
+    fn recv(&mut self, tag: Tag) -> Result<Vec<u8>, MpcNetError> {
+        let id = self.id;
+        let op = tag.op;
+        let max_len = self.max_message_len;
+        let bytes_in = frame::read_msg(self.stream(), id, op, max_len)?;
+        self.bytes_recv += 8 + bytes_in.len();
+        let bytes_in = match self.channel.as_mut() {
+            Some(channel) => channel.open(bytes_in).ok_or(MpcNetError::Auth {
+                peer: id,
+                op,
+                reason: "message failed authentication",
+            })?,
+            None => bytes_in,
+        };
+        frame::decode(id, tag, bytes_in)
+    }
+}
+
 impl Connections {
-    /// Given a path and the `id` of oneself, initialize the structure
-    fn init_from_path(&mut self, path: &str, id: usize) {
-        let f = BufReader::new(File::open(path).expect("host configuration path"));
-        let mut peer_id = 0;
-        for line in f.lines() {
-            let line = line.unwrap();
-            let trimmed = line.trim();
-            if trimmed.len() > 0 {
-                let addr: SocketAddr = trimmed
-                    .parse()
-                    .unwrap_or_else(|e| panic!("bad socket address: {}:\n{}", trimmed, e));
-                let peer = Peer {
-                    id: peer_id,
-                    addr,
-                    stream: None,
-                };
-                self.peers.push(peer);
-                peer_id += 1;
+    /// Connect to the parties listed in `config`, as party `party_id`.
+    pub fn connect(config: &HostConfig, party_id: usize) -> Result<Self, MpcNetError> {
+        Self::connect_with(config, party_id, None, None)
+    }
+
+    /// Like [Connections::connect], but wait for peers on `listener`, which is already bound. Bind
+    /// it to port 0 to let the OS pick a free port, and list the address it got in `config`.
+    pub fn connect_on(
+        config: &HostConfig,
+        party_id: usize,
+        listener: TcpListener,
+    ) -> Result<Self, MpcNetError> {
+        Self::connect_with(config, party_id, None, Some(listener))
+    }
+
+    /// Like [Connections::connect], but talk to the other parties over authenticated, encrypted
+    /// channels (see [crate::secure]). The host file must list every party's public key, and ours
+    /// must match `secret_key`.
+    pub fn connect_secure(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: SecretKey,
+    ) -> Result<Self, MpcNetError> {
+        Self::connect_with(config, party_id, Some(secret_key), None)
+    }
+
+    /// Like [Connections::connect_secure], but wait for peers on `listener`; see
+    /// [Connections::connect_on].
+    pub fn connect_secure_on(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: SecretKey,
+        listener: TcpListener,
+    ) -> Result<Self, MpcNetError> {
+        Self::connect_with(config, party_id, Some(secret_key), Some(listener))
+    }
+
+    /// Connect, over secure channels if we have a `secret_key`, and listening on `listener` or
+    /// else where `config` says.
+    fn connect_with(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: Option<SecretKey>,
+        listener: Option<TcpListener>,
+    ) -> Result<Self, MpcNetError> {
+        let mut ch = Self::new(config, party_id)?;
+        match &secret_key {
+            Some(key) if ch.peers[party_id].public_key != Some(key.public_key()) => {
+                return Err(MpcNetError::Config(format!(
+                    "the host file does not list our public key ({}) for party {}",
+                    key.public_key(),
+                    party_id
+                )));
+            }
+            Some(_) => {}
+            None if config.parties.iter().any(|p| p.public_key.is_some()) => {
+                return Err(MpcNetError::Config(
+                    "the host file lists public keys; use connect_secure".into(),
+                ));
             }
+            None if config.options.topology == Topology::Star => {
+                return Err(MpcNetError::Config(
+                    "star networks need public keys, to check what the king relays; use \
+                     connect_secure"
+                        .into(),
+                ));
+            }
+            None => {}
         }
-        assert!(id < self.peers.len());
-        self.id = id;
+        ch.secret_key = secret_key;
+        ch.run(NetOp::Connect, |ch| {
+            let listener = match listener {
+                Some(l) => l,
+                None => ch.bind()?,
+            };
+            ch.connect_to_all(listener)
+        })?;
+        Ok(ch)
     }
-    fn connect_to_all(&mut self) {
-        let timer = start_timer!(|| "Connecting");
-        let n = self.peers.len();
-        for from_id in 0..n {
-            for to_id in (from_id + 1)..n {
-                debug!("{} to {}", from_id, to_id);
-                if self.id == from_id {
-                    let to_addr = self.peers[to_id].addr;
-                    debug!("Contacting {}", to_id);
-                    let stream = loop {
-                        let mut ms_waited = 0;
-                        match TcpStream::connect(to_addr) {
-                            Ok(s) => break s,
-                            Err(e) => match e.kind() {
-                                std::io::ErrorKind::ConnectionRefused
-                                | std::io::ErrorKind::ConnectionReset => {
-                                    ms_waited += 10;
-                                    std::thread::sleep(std::time::Duration::from_millis(10));
-                                    if ms_waited % 3_000 == 0 {
-                                        debug!("Still waiting");
-                                    } else if ms_waited > 30_000 {
-                                        panic!("Could not find peer in 30s");
-                                    }
-                                }
-                                _ => {
-                                    panic!("Error during FieldChannel::new: {}", e);
-                                }
-                            },
+
+    /// Unconnected peers, as listed in `config`.
+    fn new(config: &HostConfig, id: usize) -> Result<Self, MpcNetError> {
+        config.check(id)?;
+        let peers = config
+            .parties
+            .iter()
+            .enumerate()
+            .map(|(peer_id, p)| Peer {
+                id: peer_id,
+                addr: p.address,
+                bind: p.bind,
+                public_key: p.public_key,
+                max_message_len: config.options.max_message_len,
+                ..Peer::default()
+            })
+            .collect();
+        Ok(Self {
+            id,
+            peers,
+            options: config.options,
+            session: config.session_digest(),
+            ..Self::default()
+        })
+    }
+    /// Take the connections apart, for [crate::async_net]: our id, our options, how many rounds
+    /// we have run, and the socket and secure channel to each peer (`None` for ourselves, and for
+    /// peers we are not linked to).
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        usize,
+        NetOptions,
+        Rounds,
+        Vec<Option<(TcpStream, Option<SecureChannel>)>>,
+    ) {
+        let links = self
+            .peers
+            .into_iter()
+            .map(|p| {
+                let channel = p.channel;
+                p.stream.map(|s| (s, channel))
+            })
+            .collect();
+        (self.id, self.options, self.rounds, links)
+    }
+    /// Are parties `a` and `b` connected?
+    fn linked(&self, a: usize, b: usize) -> bool {
+        a != b
+            && (self.options.topology != Topology::Star
+                || a == self.options.king
+                || b == self.options.king)
+    }
+    /// Run `f`, aborting if it fails.
+    fn run<T>(
+        &mut self,
+        op: NetOp,
+        f: impl FnOnce(&mut Self) -> Result<T, MpcNetError>,
+    ) -> Result<T, MpcNetError> {
+        if self.aborted {
+            return Err(MpcNetError::Aborted { peer: self.id, op });
+        }
+        let r = f(self);
+        if let Err(e) = &r {
+            debug!("Network error: {}", e);
+            self.abort();
+        }
+        r
+    }
+    /// Keep trying to reach party `to_id` until the connect timeout runs out.
+    fn contact(&self, to_id: usize) -> Result<TcpStream, MpcNetError> {
+        let to_addr = self.peers[to_id].addr;
+        let start = Instant::now();
+        let mut last_note = start;
+        loop {
+            match TcpStream::connect(to_addr) {
+                Ok(s) => break Ok(s),
+                Err(e) => match e.kind() {
+                    io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {
+                        if start.elapsed() > self.options.timeouts.connect {
+                            break Err(MpcNetError::io(to_id, NetOp::Connect, 0)(e));
                         }
-                    };
-                    stream.set_nodelay(true).unwrap();
-                    self.peers[to_id].stream = Some(stream);
-                } else if self.id == to_id {
-                    debug!("Awaiting {}", from_id);
-                    let listener = TcpListener::bind(self.peers[self.id].addr).unwrap();
-                    let (stream, _addr) = listener.accept().unwrap();
-                    stream.set_nodelay(true).unwrap();
-                    self.peers[from_id].stream = Some(stream);
-                }
+                        if last_note.elapsed() > Duration::from_secs(3) {
+                            debug!("Still waiting for {}", to_id);
+                            last_note = Instant::now();
+                        }
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    _ => break Err(MpcNetError::io(to_id, NetOp::Connect, 0)(e)),
+                },
             }
-            // Sender for next round waits for note from this sender to prevent race on receipt.
-            if from_id + 1 < n {
-                if self.id == from_id {
-                    self.peers[self.id + 1]
-                        .stream
-                        .as_mut()
-                        .unwrap()
-                        .write_all(&[0u8])
-                        .unwrap();
-                } else if self.id == from_id + 1 {
-                    self.peers[self.id - 1]
-                        .stream
-                        .as_mut()
-                        .unwrap()
-                        .read_exact(&mut [0u8])
-                        .unwrap();
+        }
+    }
+    /// Wait for every lower-numbered party we are linked to to contact us on `listener`.
+    fn accept_all(&mut self, listener: &TcpListener) -> Result<(), MpcNetError> {
+        let own_id = self.id;
+        let timeout = self.options.timeouts.connect;
+        listener
+            .set_nonblocking(true)
+            .map_err(MpcNetError::io(own_id, NetOp::Accept, 0))?;
+        let start = Instant::now();
+        while let Some(missing) =
+            (0..own_id).find(|i| self.linked(*i, own_id) && self.peers[*i].stream.is_none())
+        {
+            let mut stream = match listener.accept() {
+                Ok((stream, _addr)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if start.elapsed() > timeout {
+                        return Err(MpcNetError::io(missing, NetOp::Accept, 0)(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "peer never connected",
+                        )));
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                    continue;
                 }
+                Err(e) => return Err(MpcNetError::io(missing, NetOp::Accept, 0)(e)),
+            };
+            // The connecting party introduces itself: its id, then its session.
+            let mut intro = [0u8; 40];
+            stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(timeout)))
+                .and_then(|()| stream.read_exact(&mut intro))
+                .map_err(MpcNetError::io(missing, NetOp::Accept, intro.len()))?;
+            let mut from_id = [0u8; 8];
+            from_id.copy_from_slice(&intro[..8]);
+            let from_id = u64::from_le_bytes(from_id) as usize;
+            if intro[8..] != self.session {
+                return Err(MpcNetError::io(missing, NetOp::Accept, 0)(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("party {} is running another session", from_id),
+                )));
+            }
+            if from_id >= own_id
+                || !self.linked(from_id, own_id)
+                || self.peers[from_id].stream.is_some()
+            {
+                return Err(MpcNetError::io(missing, NetOp::Accept, 0)(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected connection from party {}", from_id),
+                )));
             }
+            debug!("Accepted {}", from_id);
+            self.peers[from_id].stream = Some(stream);
         }
-        // Do a round with the king, to be sure everyone is ready
-        let from_all = self.send_to_king(&[self.id as u8]);
-        self.recv_from_king(from_all);
-        for id in 0..n {
-            if id != self.id {
-                assert!(self.peers[id].stream.is_some());
+        Ok(())
+    }
+    /// Run the handshake with every peer, in parallel.
+    fn handshake_all(&mut self) -> Result<(), MpcNetError> {
+        let timer = start_timer!(|| "Handshakes");
+        let own_id = self.id;
+        let own_key = self.secret_key.as_ref().unwrap();
+        let session = &self.session;
+        self.peers
+            .par_iter_mut()
+            .filter(|p| p.stream.is_some())
+            .map(|peer| {
+                let peer_key = peer.public_key.expect("checked when reading the host file");
+                let channel = SecureChannel::handshake(
+                    peer.stream.as_mut().unwrap(),
+                    own_id,
+                    own_key,
+                    peer.id,
+                    &peer_key,
+                    session,
+                )?;
+                peer.channel = Some(channel);
+                Ok(())
+            })
+            .collect::<Result<(), _>>()?;
+        end_timer!(timer);
+        Ok(())
+    }
+    /// Listen where the host file says we should.
+    fn bind(&self) -> Result<TcpListener, MpcNetError> {
+        TcpListener::bind(self.peers[self.id].bind).map_err(MpcNetError::io(
+            self.id,
+            NetOp::Accept,
+            0,
+        ))
+    }
+    /// Connect to every peer we are linked to, accepting the lower-numbered ones on `listener`.
+    fn connect_to_all(&mut self, listener: TcpListener) -> Result<(), MpcNetError> {
+        let timer = start_timer!(|| "Connecting");
+        let n = self.peers.len();
+        let own_id = self.id;
+        // We contact every higher-numbered party, and wait for every lower-numbered one.
+        let higher: Vec<usize> = ((own_id + 1)..n)
+            .filter(|j| self.linked(own_id, *j))
+            .collect();
+        for to_id in higher {
+            debug!("Contacting {}", to_id);
+            let mut stream = self.contact(to_id)?;
+            let mut intro = (own_id as u64).to_le_bytes().to_vec();
+            intro.extend_from_slice(&self.session);
+            stream.write_all(&intro).map_err(MpcNetError::io(
+                to_id,
+                NetOp::Connect,
+                intro.len(),
+            ))?;
+            self.peers[to_id].stream = Some(stream);
+        }
+        self.accept_all(&listener)?;
+        let timeout = self.options.timeouts.read;
+        for peer in &mut self.peers {
+            let id = peer.id;
+            if let Some(stream) = peer.stream.as_mut() {
+                stream
+                    .set_nodelay(true)
+                    .and_then(|()| stream.set_read_timeout(timeout))
+                    .and_then(|()| stream.set_write_timeout(timeout))
+                    .map_err(MpcNetError::io(id, NetOp::Connect, 0))?;
             }
         }
+        if self.secret_key.is_some() {
+            self.handshake_all()?;
+        }
+        // Do a round with the king, to be sure everyone is ready
+        let from_all = self.send_to_king(&[self.id as u8])?;
+        self.recv_from_king(from_all)?;
         end_timer!(timer);
+        Ok(())
     }
     fn am_king(&self) -> bool {
-        self.id == 0
+        self.id == self.options.king
     }
-    fn broadcast(&mut self, bytes_out: &[u8]) -> Vec<Vec<u8>> {
+    fn broadcast(&mut self, bytes_out: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
         let timer = start_timer!(|| format!("Broadcast {}", bytes_out.len()));
-        let m = bytes_out.len();
-        let own_id = self.id;
-        self.stats.bytes_sent += (self.peers.len() - 1) * m;
-        self.stats.bytes_recv += (self.peers.len() - 1) * m;
+        let tag = self.rounds.next(NetOp::Broadcast);
         self.stats.broadcasts += 1;
-        let r = self
-            .peers
+        let r = if self.options.topology == Topology::Mesh {
+            self.exchange(tag, |_| bytes_out)
+        } else {
+            // The king gathers every message, and sends them all to everyone.
+            let n = self.peers.len();
+            let all = self.gather(tag, bytes_out)?;
+            let joined = self.scatter(tag, all.map(|all| vec![frame::join(&all); n]))?;
+            let all = frame::split(self.options.king, tag.op, &joined)?;
+            let bad = |reason: String| MpcNetError::BadFrame {
+                peer: self.options.king,
+                op: tag.op,
+                reason,
+            };
+            if all.len() != n {
+                return Err(bad(format!("relayed {} messages for {} parties", all.len(), n)));
+            }
+            if all[self.id] != bytes_out {
+                return Err(bad("relayed our own message changed".into()));
+            }
+            self.check_relayed(tag, &all)?;
+            Ok(all)
+        };
+        end_timer!(timer);
+        r
+    }
+    /// Check that the king relayed everyone the same messages, `all`, in the broadcast `tag`.
+    ///
+    /// Each party checks that the king relayed its own message unchanged, so it is enough to
+    /// compare digests of what everyone got. In a [Topology::Hybrid] network, parties send each
+    /// other their digests directly. In a [Topology::Star] network, the king relays them too, so
+    /// each party signs its digest, and the others check the signatures.
+    fn check_relayed(&mut self, tag: Tag, all: &[Vec<u8>]) -> Result<(), MpcNetError> {
+        let king = self.options.king;
+        let mut h = Sha256::new();
+        h.update(self.session);
+        h.update(frame::encode(tag, &frame::join(all)));
+        let digest = h.finalize();
+        let tag = self.rounds.next(NetOp::Broadcast);
+        let equivocation = |witness| MpcNetError::Equivocation {
+            peer: king,
+            witness,
+            op: tag.op,
+        };
+        if self.options.topology == Topology::Hybrid {
+            let digests = self.exchange(tag, |_| &digest[..])?;
+            match digests.iter().position(|d| d[..] != digest[..]) {
+                Some(j) => Err(equivocation(j)),
+                None => Ok(()),
+            }
+        } else {
+            let n = self.peers.len();
+            let sig = self
+                .secret_key
+                .as_ref()
+                .expect("star networks run over secure channels")
+                .sign(&digest);
+            let sigs = self.gather(tag, &sig)?;
+            let sigs = self.scatter(tag, sigs.map(|sigs| vec![frame::join(&sigs); n]))?;
+            let sigs = frame::split(king, tag.op, &sigs)?;
+            if sigs.len() != n {
+                return Err(MpcNetError::BadFrame {
+                    peer: king,
+                    op: tag.op,
+                    reason: format!("relayed {} signatures for {} parties", sigs.len(), n),
+                });
+            }
+            match (0..n).find(|j| {
+                let key = self.peers[*j].public_key.expect("checked when connecting");
+                !key.verify(&digest, &sigs[*j])
+            }) {
+                Some(j) => Err(equivocation(j)),
+                None => Ok(()),
+            }
+        }
+    }
+    fn all_to_all(&mut self, bytes_out: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        let timer = start_timer!(|| "All to all");
+        let own_id = self.id;
+        assert_eq!(bytes_out.len(), self.peers.len());
+        let tag = self.rounds.next(NetOp::AllToAll);
+        let r = if self.options.topology == Topology::Star {
+            // The king gathers every party's messages, and sends each party those addressed to
+            // it. Our message to ourselves stays here.
+            let mut row = bytes_out.to_vec();
+            row[own_id].clear();
+            let rows = self.gather(tag, &frame::join(&row))?;
+            let columns = match rows {
+                Some(rows) => {
+                    let rows = rows
+                        .iter()
+                        .enumerate()
+                        .map(|(i, r)| frame::split(i, tag.op, r))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let column = |j: usize| -> Vec<Vec<u8>> {
+                        rows.iter()
+                            .map(|r| r.get(j).cloned().unwrap_or_default())
+                            .collect()
+                    };
+                    Some((0..rows.len()).map(|j| frame::join(&column(j))).collect())
+                }
+                None => None,
+            };
+            let mut column = frame::split(self.options.king, tag.op, &self.scatter(tag, columns)?)?;
+            if column.len() != self.peers.len() {
+                return Err(MpcNetError::BadFrame {
+                    peer: self.options.king,
+                    op: tag.op,
+                    reason: format!(
+                        "relayed {} messages for {} parties",
+                        column.len(),
+                        self.peers.len()
+                    ),
+                });
+            }
+            column[own_id] = bytes_out[own_id].clone();
+            Ok(column)
+        } else {
+            self.exchange(tag, |id| &bytes_out[id])
+        };
+        end_timer!(timer);
+        r
+    }
+    /// Send `bytes_out(j)` to each peer `j`, and receive a message from each, directly.
+    fn exchange<'a>(
+        &mut self,
+        tag: Tag,
+        bytes_out: impl Fn(usize) -> &'a [u8] + Sync,
+    ) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        let own_id = self.id;
+        self.peers
             .par_iter_mut()
             .enumerate()
             .map(|(id, peer)| {
-                let mut bytes_in = vec![0u8; m];
                 if id < own_id {
-                    let stream = peer.stream.as_mut().unwrap();
-                    stream.read_exact(&mut bytes_in[..]).unwrap();
-                    stream.write_all(bytes_out).unwrap();
+                    let bytes_in = peer.recv(tag)?;
+                    peer.send(tag, bytes_out(id))?;
+                    Ok(bytes_in)
                 } else if id == own_id {
-                    bytes_in.copy_from_slice(bytes_out);
+                    Ok(bytes_out(id).to_vec())
                 } else {
-                    let stream = peer.stream.as_mut().unwrap();
-                    stream.write_all(bytes_out).unwrap();
-                    stream.read_exact(&mut bytes_in[..]).unwrap();
-                };
-                bytes_in
+                    peer.send(tag, bytes_out(id))?;
+                    peer.recv(tag)
+                }
             })
-            .collect();
-        end_timer!(timer);
-        r
+            .collect()
     }
-    fn send_to_king(&mut self, bytes_out: &[u8]) -> Option<Vec<Vec<u8>>> {
-        let timer = start_timer!(|| format!("To king {}", bytes_out.len()));
-        let m = bytes_out.len();
+    /// Everyone sends `bytes_out` to the king, which gets them all.
+    fn gather(&mut self, tag: Tag, bytes_out: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
         let own_id = self.id;
-        self.stats.to_king += 1;
-        let r = if self.am_king() {
-            self.stats.bytes_recv += (self.peers.len() - 1) * m;
-            Some(
+        if self.am_king() {
+            Ok(Some(
                 self.peers
                     .par_iter_mut()
                     .enumerate()
                     .map(|(id, peer)| {
-                        let mut bytes_in = vec![0u8; m];
                         if id == own_id {
-                            bytes_in.copy_from_slice(bytes_out);
+                            Ok(bytes_out.to_vec())
                         } else {
-                            let stream = peer.stream.as_mut().unwrap();
-                            stream.read_exact(&mut bytes_in[..]).unwrap();
-                        };
-                        bytes_in
+                            peer.recv(tag)
+                        }
                     })
-                    .collect(),
-            )
+                    .collect::<Result<_, _>>()?,
+            ))
         } else {
-            self.stats.bytes_sent += m;
-            self.peers[0]
-                .stream
-                .as_mut()
-                .unwrap()
-                .write_all(bytes_out)
-                .unwrap();
-            None
-        };
-        end_timer!(timer);
-        r
+            self.peers[self.options.king].send(tag, bytes_out)?;
+            Ok(None)
+        }
     }
-    fn recv_from_king(&mut self, bytes_out: Option<Vec<Vec<u8>>>) -> Vec<u8> {
+    /// The king sends `bytes_out[j]` to each party `j`.
+    fn scatter(
+        &mut self,
+        tag: Tag,
+        bytes_out: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
         let own_id = self.id;
-        self.stats.from_king += 1;
         if self.am_king() {
-            let bytes_out = bytes_out.unwrap();
-            let m = bytes_out[0].len();
-            let timer = start_timer!(|| format!("From king {}", m));
-            let bytes_size = (m as u64).to_le_bytes();
-            self.stats.bytes_sent += (self.peers.len() - 1) * (m + 8);
+            let mut bytes_out = bytes_out.expect("king needs bytes");
+            assert_eq!(bytes_out.len(), self.peers.len());
             self.peers
                 .par_iter_mut()
                 .enumerate()
                 .filter(|p| p.0 != own_id)
-                .for_each(|(id, peer)| {
-                    let stream = peer.stream.as_mut().unwrap();
-                    assert_eq!(bytes_out[id].len(), m);
-                    stream.write_all(&bytes_size).unwrap();
-                    stream.write_all(&bytes_out[id]).unwrap();
-                });
-            end_timer!(timer);
-            bytes_out[own_id].clone()
+                .map(|(id, peer)| peer.send(tag, &bytes_out[id]))
+                .collect::<Result<(), _>>()?;
+            Ok(bytes_out.swap_remove(own_id))
         } else {
-            let stream = self.peers[0].stream.as_mut().unwrap();
-            let mut bytes_size = [0u8; 8];
-            stream.read_exact(&mut bytes_size).unwrap();
-            let m = u64::from_le_bytes(bytes_size) as usize;
-            self.stats.bytes_recv += m;
-            let mut bytes_in = vec![0u8; m];
-            stream.read_exact(&mut bytes_in).unwrap();
-            bytes_in
+            self.peers[self.options.king].recv(tag)
         }
     }
-    fn uninit(&mut self) {
+    fn send_to_king(&mut self, bytes_out: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        let timer = start_timer!(|| format!("To king {}", bytes_out.len()));
+        let tag = self.rounds.next(NetOp::SendToKing);
+        self.stats.to_king += 1;
+        let r = self.gather(tag, bytes_out);
+        end_timer!(timer);
+        r
+    }
+    fn recv_from_king(&mut self, bytes_out: Option<Vec<Vec<u8>>>) -> Result<Vec<u8>, MpcNetError> {
+        let timer = start_timer!(|| "From king");
+        let tag = self.rounds.next(NetOp::RecvFromKing);
+        self.stats.from_king += 1;
+        let r = self.scatter(tag, bytes_out);
+        end_timer!(timer);
+        r
+    }
+    /// Tell every peer that we are aborting, and stop sending.
+    fn abort(&mut self) {
+        if self.aborted {
+            return;
+        }
+        debug!("Aborting");
+        self.aborted = true;
         for p in &mut self.peers {
-            p.stream = None;
+            if let Some(stream) = p.stream.as_mut() {
+                // The peers may already be gone, so errors are expected.
+                let _ = stream.write_all(&ABORT_MARKER.to_le_bytes());
+                let _ = stream.shutdown(Shutdown::Write);
+            }
         }
     }
 }
 
-pub struct MpcMultiNet;
+impl MpcTransport for Connections {
+    #[inline]
+    fn party_id(&self) -> usize {
+        self.id
+    }
 
-impl MpcNet for MpcMultiNet {
     #[inline]
-    fn party_id() -> usize {
-        get_ch!().id
+    fn n_parties(&self) -> usize {
+        self.peers.len()
     }
 
     #[inline]
-    fn n_parties() -> usize {
-        get_ch!().peers.len()
+    fn king(&self) -> usize {
+        self.options.king
     }
 
     #[inline]
-    fn init_from_file(path: &str, party_id: usize) {
-        let mut ch = get_ch!();
-        ch.init_from_path(path, party_id);
-        ch.connect_to_all();
+    fn abort(&mut self) {
+        Connections::abort(self)
     }
 
     #[inline]
-    fn is_init() -> bool {
-        get_ch!()
-            .peers
-            .first()
-            .map(|p| p.stream.is_some())
-            .unwrap_or(false)
+    fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        for p in &mut self.peers {
+            p.bytes_sent = 0;
+            p.bytes_recv = 0;
+        }
     }
 
     #[inline]
-    fn deinit() {
-        get_ch!().uninit()
+    fn stats(&self) -> Stats {
+        let mut stats = self.stats.clone();
+        stats.bytes_sent_to = self.peers.iter().map(|p| p.bytes_sent).collect();
+        stats.bytes_recv_from = self.peers.iter().map(|p| p.bytes_recv).collect();
+        stats.bytes_sent = stats.bytes_sent_to.iter().sum();
+        stats.bytes_recv = stats.bytes_recv_from.iter().sum();
+        stats
     }
 
     #[inline]
-    fn reset_stats() {
-        get_ch!().stats = Stats::default();
+    fn broadcast_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        self.run(NetOp::Broadcast, |ch| ch.broadcast(bytes))
     }
 
     #[inline]
-    fn stats() -> crate::Stats {
-        get_ch!().stats.clone()
+    fn all_to_all_bytes(&mut self, bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        self.run(NetOp::AllToAll, |ch| ch.all_to_all(bytes))
     }
 
     #[inline]
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
-        get_ch!().broadcast(bytes)
+    fn send_bytes_to_king(&mut self, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        self.run(NetOp::SendToKing, |ch| ch.send_to_king(bytes))
     }
 
     #[inline]
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
-        get_ch!().send_to_king(bytes)
+    fn recv_bytes_from_king(
+        &mut self,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
+        self.run(NetOp::RecvFromKing, |ch| ch.recv_from_king(bytes))
     }
+}
 
+/// Connects all parties over TCP.
+pub struct MpcMultiNet;
+
+impl MpcMultiNet {
+    /// Like [MpcNet::init_from_file], but over secure channels; see
+    /// [Connections::connect_secure].
+    pub fn init_secure_from_file(
+        path: &str,
+        party_id: usize,
+        secret_key: SecretKey,
+    ) -> Result<(), MpcNetError> {
+        Self::init_secure_from_config(&HostConfig::from_file(path)?, party_id, secret_key)
+    }
+    /// Like [MpcNet::init_from_config], but over secure channels.
+    pub fn init_secure_from_config(
+        config: &HostConfig,
+        party_id: usize,
+        secret_key: SecretKey,
+    ) -> Result<(), MpcNetError> {
+        let ch = Connections::connect_secure(config, party_id, secret_key)?;
+        Session::set_default(Some(Session::new(ch)));
+        Ok(())
+    }
+}
+
+impl MpcNet for MpcMultiNet {
     #[inline]
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
-        get_ch!().recv_from_king(bytes)
+    fn init_from_config(config: &HostConfig, party_id: usize) -> Result<(), MpcNetError> {
+        let ch = Connections::connect(config, party_id)?;
+        Session::set_default(Some(Session::new(ch)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::on_localhost;
+
+    /// Connect as party `id`, over secure channels if there are `keys`.
+    fn connect(
+        config: &HostConfig,
+        id: usize,
+        listener: TcpListener,
+        keys: &[SecretKey],
+    ) -> Connections {
+        match keys.get(id) {
+            Some(key) => Connections::connect_secure_on(config, id, key.clone(), listener),
+            None => Connections::connect_on(config, id, listener),
+        }
+        .unwrap()
+    }
+
+    fn relayed(topology: Topology) -> NetOptions {
+        NetOptions {
+            king: 1,
+            topology,
+            ..NetOptions::default()
+        }
+    }
+
+    /// Every operation works over TCP in each topology, with a king other than party 0.
+    #[test]
+    fn topologies() {
+        let keys: Vec<SecretKey> = (0..4).map(|_| SecretKey::generate()).collect();
+        for topology in [Topology::Mesh, Topology::Star, Topology::Hybrid] {
+            let keys = if topology == Topology::Star {
+                &keys[..]
+            } else {
+                &[]
+            };
+            on_localhost(4, relayed(topology), keys, |config, id, listener| {
+                let mut ch = connect(config, id, listener, keys);
+                ch.reset_stats();
+                let all = ch.broadcast_bytes(&vec![id as u8; id]).unwrap();
+                assert_eq!(all, vec![vec![], vec![1], vec![2, 2], vec![3, 3, 3]]);
+                let row: Vec<Vec<u8>> = (0..4).map(|j| vec![id as u8, j as u8]).collect();
+                let column = ch.all_to_all_bytes(&row).unwrap();
+                assert_eq!(column, (0..4).map(|i| vec![i as u8, id as u8]).collect::<Vec<_>>());
+                let gathered = ch.send_bytes_to_king(&[id as u8]).unwrap();
+                assert_eq!(gathered.is_some(), id == 1);
+                let back = ch.recv_bytes_from_king(gathered).unwrap();
+                assert_eq!(back, vec![id as u8]);
+                let stats = ch.stats();
+                for (j, sent) in stats.bytes_sent_to.iter().enumerate() {
+                    let linked = j != id && (topology != Topology::Star || j == 1 || id == 1);
+                    assert_eq!(*sent > 0, linked, "party {} to party {}", id, j);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn star_needs_keys() {
+        let config: HostConfig = "127.0.0.1:0\n127.0.0.1:0\n127.0.0.1:0\n".parse().unwrap();
+        let config = HostConfig {
+            options: relayed(Topology::Star),
+            ..config
+        };
+        assert!(matches!(
+            Connections::connect(&config, 0),
+            Err(MpcNetError::Config(_))
+        ));
+    }
+
+    /// A king that relays one party a changed broadcast is caught by the others.
+    #[test]
+    fn equivocating_king() {
+        let keys: Vec<SecretKey> = (0..3).map(|_| SecretKey::generate()).collect();
+        for topology in [Topology::Star, Topology::Hybrid] {
+            let keys = if topology == Topology::Star {
+                &keys[..]
+            } else {
+                &[]
+            };
+            let results = on_localhost(3, relayed(topology), keys, |config, id, listener| {
+                let mut ch = connect(config, id, listener, keys);
+                if id != 1 {
+                    return ch.broadcast_bytes(&[id as u8]);
+                }
+                // Relay party 2 another message from party 0.
+                let tag = ch.rounds.next(NetOp::Broadcast);
+                let all = ch.gather(tag, &[1]).unwrap().unwrap();
+                let mut changed = all.clone();
+                changed[0] = vec![9];
+                let joined = vec![frame::join(&all), frame::join(&all), frame::join(&changed)];
+                ch.scatter(tag, Some(joined)).unwrap();
+                ch.check_relayed(tag, &all).map(|()| all)
+            });
+            for id in [0, 2] {
+                match &results[id] {
+                    Err(MpcNetError::Equivocation { peer: 1, witness, .. }) => {
+                        assert_eq!(*witness, 2 - id, "{}", topology)
+                    }
+                    r => panic!("{}: expected an equivocation, got {:?}", topology, r),
+                }
+            }
+        }
     }
 }
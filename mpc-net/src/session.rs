@@ -0,0 +1,166 @@
+//! Sessions: which network a party is using, along with the state it keeps for that network.
+//!
+//! MPC code reaches the network through [crate::MpcNet], which uses the *current* session: the
+//! one [entered](Session::enter) on this thread, or failing that, the [default](Session::set_default)
+//! one. A process can therefore run several sessions at once, e.g. one thread per simulated
+//! party, each over a [crate::MemTransport].
+use lazy_static::lazy_static;
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::MpcTransport;
+
+/// A handle to one party's view of an MPC: its transport, and per-session state (preprocessing,
+/// keys, ...). Cloning the handle does not copy the session.
+#[derive(Clone)]
+pub struct Session(Arc<Inner>);
+
+struct Inner {
+    party_id: usize,
+    n_parties: usize,
+    king: usize,
+    transport: Mutex<Box<dyn MpcTransport>>,
+    /// State, by type. An entry is `None` while its state is checked out.
+    state: Mutex<HashMap<TypeId, Option<Box<dyn Any + Send>>>>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+lazy_static! {
+    static ref DEFAULT: Mutex<Option<Session>> = Mutex::new(None);
+}
+
+/// Restores the previously entered session when dropped, even if we are unwinding.
+struct Restore(Option<Session>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let prev = self.0.take();
+        CURRENT.with(|c| *c.borrow_mut() = prev);
+    }
+}
+
+/// Checks state back in to its session when dropped, even if we are unwinding.
+struct CheckIn<'a, S: Any + Send> {
+    session: &'a Session,
+    state: Option<Box<S>>,
+}
+
+impl<'a, S: Any + Send> Drop for CheckIn<'a, S> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            self.session
+                .0
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(TypeId::of::<S>(), Some(state));
+        }
+    }
+}
+
+impl Session {
+    pub fn new(transport: impl MpcTransport + 'static) -> Self {
+        Self(Arc::new(Inner {
+            party_id: transport.party_id(),
+            n_parties: transport.n_parties(),
+            king: transport.king(),
+            transport: Mutex::new(Box::new(transport)),
+            state: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Run `f` with this session current on this thread.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        let prev = CURRENT.with(|c| c.borrow_mut().replace(self.clone()));
+        let _restore = Restore(prev);
+        f()
+    }
+
+    /// Set the session used by threads that have not entered one, returning the old one.
+    pub fn set_default(session: Option<Session>) -> Option<Session> {
+        std::mem::replace(&mut *DEFAULT.lock().unwrap(), session)
+    }
+
+    /// The current session, if there is one.
+    pub fn current() -> Option<Session> {
+        CURRENT
+            .with(|c| c.borrow().clone())
+            .or_else(|| DEFAULT.lock().unwrap().clone())
+    }
+
+    /// The current session.
+    ///
+    /// Panics if there is none.
+    pub fn expect_current() -> Session {
+        Self::current().expect("No MPC session. Did you forget init_from_file(..)?")
+    }
+
+    #[inline]
+    pub fn party_id(&self) -> usize {
+        self.0.party_id
+    }
+
+    #[inline]
+    pub fn n_parties(&self) -> usize {
+        self.0.n_parties
+    }
+
+    #[inline]
+    pub fn king(&self) -> usize {
+        self.0.king
+    }
+
+    /// Run `f` on the transport.
+    pub fn with_transport<R>(&self, f: impl FnOnce(&mut dyn MpcTransport) -> R) -> R {
+        let mut t = self.0.transport.lock().expect("Poisoned transport");
+        f(&mut **t)
+    }
+
+    /// Run `f` on this session's state of type `S`, which starts out as `S::default()`.
+    ///
+    /// The state is checked out while `f` runs, so `f` may use the network, or other state, but
+    /// not the same state again. It is checked back in even if `f` panics.
+    pub fn with_state<S: Any + Send + Default, R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        let id = TypeId::of::<S>();
+        let checked_out = self
+            .0
+            .state
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| Some(Box::new(S::default())))
+            .take();
+        let state: Box<S> = match checked_out {
+            Some(s) => s.downcast().unwrap(),
+            None => panic!("{} is already in use", std::any::type_name::<S>()),
+        };
+        let mut check_in = CheckIn {
+            session: self,
+            state: Some(state),
+        };
+        f(check_in.state.as_mut().unwrap())
+    }
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Session(party {} of {})",
+            self.party_id(),
+            self.n_parties()
+        )
+    }
+}
+
+/// Run `f` on the current session's state of type `S`. See [Session::with_state].
+#[inline]
+pub fn with_state<S: Any + Send + Default, R>(f: impl FnOnce(&mut S) -> R) -> R {
+    Session::expect_current().with_state(f)
+}
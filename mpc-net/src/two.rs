@@ -1,33 +1,37 @@
-use lazy_static::lazy_static;
 use log::debug;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::Mutex;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
 
 use ark_std::{end_timer, start_timer};
 
-use super::{MpcNet, Stats};
-
-#[macro_use]
-lazy_static! {
-    pub static ref CH: Mutex<FieldChannel> = Mutex::new(FieldChannel::default());
-}
-
-/// Macro for locking the FieldChannel singleton in the current scope.
-macro_rules! get_ch {
-    () => {
-        CH.lock().expect("Poisoned FieldChannel")
-    };
-}
+use super::config::HostConfig;
+use super::frame::{self, Rounds, Tag};
+use super::{
+    sent_abort, MpcNet, MpcNetError, MpcTransport, NetOp, NetOptions, Session, Stats, Timeouts,
+    ABORT_MARKER,
+};
 
+/// A TCP connection between two parties.
 pub struct FieldChannel {
     /// Empty if unitialized
     pub stream: Option<TcpStream>,
+    /// Where we listen, if we are the party that waits to be contacted.
     pub self_addr: SocketAddr,
     pub other_addr: SocketAddr,
     pub stats: Stats,
     pub talk_first: bool,
+    pub timeouts: Timeouts,
+    /// The party that coordinates: 0 or 1. Two parties need no relay, so the choice of topology
+    /// makes no difference.
+    pub king: usize,
+    /// Set once we have aborted; all further operations fail.
+    pub aborted: bool,
+    /// The longest message we accept; see [NetOptions::max_message_len].
+    pub max_message_len: usize,
+    /// Digest of the session id; see [HostConfig::session_id].
+    session: [u8; 32],
+    rounds: Rounds,
 }
 
 impl std::default::Default for FieldChannel {
@@ -39,64 +43,158 @@ impl std::default::Default for FieldChannel {
             other_addr: "127.0.0.1:8000".parse().unwrap(),
             stats: Stats::default(),
             talk_first: false,
+            timeouts: Timeouts::default(),
+            king: 0,
+            aborted: false,
+            max_message_len: NetOptions::default().max_message_len,
+            session: [0; 32],
+            rounds: Rounds::default(),
         }
     }
 }
 
 impl FieldChannel {
-    fn init_from_path(&mut self, path: &str, id: usize) {
-        let f = BufReader::new(File::open(path).expect("host configuration path"));
-        let mut addrs = Vec::new();
-        for line in f.lines() {
-            let line = line.unwrap();
-            let trimmed = line.trim();
-            if trimmed.len() > 0 {
-                let addr: SocketAddr = trimmed
-                    .parse()
-                    .unwrap_or_else(|e| panic!("bad socket address: {}:\n{}", trimmed, e));
-                addrs.push(addr);
-            }
+    /// Connect to the other party listed in `config`, as party `party_id`.
+    pub fn connect_from_config(config: &HostConfig, party_id: usize) -> Result<Self, MpcNetError> {
+        let mut ch = Self::default();
+        ch.init_from_config(config, party_id)?;
+        ch.run(NetOp::Connect, |ch| ch.connect())?;
+        debug!("Connected");
+        Ok(ch)
+    }
+
+    /// Like [FieldChannel::connect_from_config], but if we wait to be contacted (as party 1
+    /// does), wait on `listener`, which is already bound. Bind it to port 0 to let the OS pick a
+    /// free port, and list the address it got in `config`.
+    pub fn connect_on(
+        config: &HostConfig,
+        party_id: usize,
+        listener: TcpListener,
+    ) -> Result<Self, MpcNetError> {
+        let mut ch = Self::default();
+        ch.init_from_config(config, party_id)?;
+        ch.run(NetOp::Connect, |ch| ch.connect_with(Some(listener)))?;
+        debug!("Connected");
+        Ok(ch)
+    }
+
+    fn init_from_config(&mut self, config: &HostConfig, id: usize) -> Result<(), MpcNetError> {
+        if config.n_parties() != 2 {
+            return Err(MpcNetError::Config(format!(
+                "the host file lists {} parties (need 2)",
+                config.n_parties()
+            )));
+        }
+        config.check(id)?;
+        if config.parties.iter().any(|p| p.public_key.is_some()) {
+            return Err(MpcNetError::Config(
+                "the host file lists public keys, but two-party connections are not secure; use \
+                 MpcMultiNet"
+                    .into(),
+            ));
         }
-        assert_eq!(addrs.len(), 2);
-        assert!(id < addrs.len());
-        self.self_addr = addrs[id];
-        self.other_addr = addrs[1 - id];
+        self.self_addr = config.parties[id].bind;
+        self.other_addr = config.parties[1 - id].address;
         self.talk_first = id == 0;
+        self.timeouts = config.options.timeouts;
+        self.king = config.options.king;
+        self.max_message_len = config.options.max_message_len;
+        self.session = config.session_digest();
+        self.aborted = false;
+        Ok(())
     }
 
+    /// The other party's id
     #[inline]
-    pub fn connect(&mut self) {
+    fn other_id(&self) -> usize {
+        if self.talk_first {
+            1
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    pub fn connect(&mut self) -> Result<(), MpcNetError> {
+        self.connect_with(None)
+    }
+
+    /// Connect, listening on `listener` (if we listen) or else at our bind address.
+    fn connect_with(&mut self, listener: Option<TcpListener>) -> Result<(), MpcNetError> {
         debug!("I am {}, connecting to {}", self.self_addr, self.other_addr);
-        self.stream = Some(if self.talk_first {
+        let other = self.other_id();
+        let start = Instant::now();
+        let mut stream = if self.talk_first {
             debug!("Attempting to contact peer");
             loop {
-                let mut ms_waited = 0;
                 match TcpStream::connect(self.other_addr) {
                     Ok(s) => break s,
                     Err(e) => {
-                        if e.kind() == std::io::ErrorKind::ConnectionRefused {
-                            ms_waited += 100;
-                            std::thread::sleep(std::time::Duration::from_millis(100));
-                            if ms_waited % 3_000 == 0 {
-                                debug!("Still waiting");
-                            } else if ms_waited > 30_000 {
-                                panic!("Could not find peer in 30s");
-                            }
+                        if e.kind() == io::ErrorKind::ConnectionRefused
+                            && start.elapsed() < self.timeouts.connect
+                        {
+                            std::thread::sleep(Duration::from_millis(100));
                         } else {
-                            panic!("Error during FieldChannel::new: {}", e);
+                            return Err(MpcNetError::io(other, NetOp::Connect, 0)(e));
                         }
                     }
                 }
             }
         } else {
-            let listener = TcpListener::bind(self.self_addr).unwrap();
+            let listener = match listener {
+                Some(l) => l,
+                None => TcpListener::bind(self.self_addr).map_err(MpcNetError::io(
+                    other,
+                    NetOp::Accept,
+                    0,
+                ))?,
+            };
+            listener
+                .set_nonblocking(true)
+                .map_err(MpcNetError::io(other, NetOp::Accept, 0))?;
             debug!("Waiting for peer to contact us");
-            let (stream, _addr) = listener.accept().unwrap();
+            loop {
+                match listener.accept() {
+                    Ok((stream, _addr)) => break stream,
+                    Err(e)
+                        if e.kind() == io::ErrorKind::WouldBlock
+                            && start.elapsed() < self.timeouts.connect =>
+                    {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    Err(e) => return Err(MpcNetError::io(other, NetOp::Accept, 0)(e)),
+                }
+            }
+        };
+        // The contacting party names its session, and the other checks it.
+        if self.talk_first {
+            stream.write_all(&self.session).map_err(MpcNetError::io(
+                other,
+                NetOp::Connect,
+                self.session.len(),
+            ))?;
+        } else {
+            let mut session = [0u8; 32];
             stream
-        });
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(self.timeouts.connect)))
+                .and_then(|()| stream.read_exact(&mut session))
+                .map_err(MpcNetError::io(other, NetOp::Accept, session.len()))?;
+            if session != self.session {
+                return Err(MpcNetError::io(other, NetOp::Accept, 0)(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("party {} is running another session", other),
+                )));
+            }
+        }
         // disable nagle's alg
-        self.stream.as_mut().unwrap().set_nodelay(true).unwrap();
-        self.stream.as_mut().unwrap().set_nonblocking(true).unwrap();
+        stream
+            .set_nodelay(true)
+            .and_then(|()| stream.set_read_timeout(self.timeouts.read))
+            .and_then(|()| stream.set_write_timeout(self.timeouts.read))
+            .map_err(MpcNetError::io(other, NetOp::Connect, 0))?;
+        self.stream = Some(stream);
+        Ok(())
     }
     #[inline]
     pub fn stream(&mut self) -> &mut TcpStream {
@@ -105,79 +203,96 @@ impl FieldChannel {
             .expect("Unitialized FieldChannel. Did you forget init(..)?")
     }
 
+    /// Run `f`, aborting if it fails.
+    fn run<T>(
+        &mut self,
+        op: NetOp,
+        f: impl FnOnce(&mut Self) -> Result<T, MpcNetError>,
+    ) -> Result<T, MpcNetError> {
+        if self.aborted {
+            return Err(MpcNetError::Aborted {
+                peer: 1 - self.other_id(),
+                op,
+            });
+        }
+        let r = f(self);
+        if let Err(e) = &r {
+            debug!("Network error: {}", e);
+            self.abort();
+        }
+        r
+    }
+
+    /// Send `v` as the message tagged `tag`.
     #[inline]
-    pub fn send_slice(&mut self, v: &[u8]) {
-        let s = self.stream();
-        s.set_nonblocking(false).unwrap();
-        let bytes = (v.len() as u64).to_ne_bytes();
-        s.write_all(&bytes[..]).unwrap();
-        s.write_all(v).unwrap();
-        s.set_nonblocking(true).unwrap();
-        self.stats.bytes_sent += bytes.len() + v.len();
+    pub fn send_slice(&mut self, tag: Tag, v: &[u8]) -> Result<(), MpcNetError> {
+        let other = self.other_id();
+        let sent = send_on(self.stream(), other, tag, v)?;
+        self.stats.bytes_sent += sent;
+        Ok(())
     }
 
+    /// Receive a message, which should be tagged `tag`.
     #[inline]
-    pub fn recv_vec(&mut self) -> Vec<u8> {
-        let s = self.stream();
-        let mut len = [0u8; 8];
-        s.set_nonblocking(false).unwrap();
-        s.read_exact(&mut len[..]).unwrap();
-        let mut bytes = vec![0u8; u64::from_ne_bytes(len) as usize];
-        s.read_exact(&mut bytes[..]).unwrap();
-        s.set_nonblocking(true).unwrap();
-        self.stats.bytes_recv += bytes.len() + len.len();
-        bytes
+    pub fn recv_vec(&mut self, tag: Tag) -> Result<Vec<u8>, MpcNetError> {
+        let other = self.other_id();
+        let op = tag.op;
+        let max_len = self.max_message_len;
+        let bytes = frame::read_msg(self.stream(), other, op, max_len)?;
+        self.stats.bytes_recv += 8 + bytes.len();
+        frame::decode(other, tag, bytes)
     }
 
+    /// Simultaneously send and receive messages (which may differ in length).
+    ///
+    /// We send from another thread, on a clone of the socket, so that neither party has to finish
+    /// sending before it starts to receive. Both threads block on the socket until it is ready.
     #[inline]
-    pub fn exchange_bytes(&mut self, bytes_out: &[u8]) -> std::io::Result<Vec<u8>> {
+    pub fn exchange_bytes(&mut self, bytes_out: &[u8]) -> Result<Vec<u8>, MpcNetError> {
         let timer = start_timer!(|| format!("Exchanging {}", bytes_out.len()));
-        let s = self.stream();
-        let n = bytes_out.len();
-        let mut bytes_in = vec![0u8; n];
-        let mut bytes_in_offset = 0;
-        let mut bytes_out_offset = 0;
-        while bytes_out_offset < n || bytes_in_offset < n {
-            if bytes_out_offset < n {
-                match s.write(&bytes_out[bytes_out_offset..]) {
-                    Ok(written) => {
-                        bytes_out_offset += written;
-                        let _e = s.flush();
-                    }
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::WouldBlock {
-                        } else if e.kind() == std::io::ErrorKind::Interrupted {
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-            if bytes_in_offset < n {
-                match s.read(&mut bytes_in[bytes_in_offset..]) {
-                    Ok(read) => {
-                        bytes_in_offset += read;
-                    }
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::WouldBlock {
-                        } else if e.kind() == std::io::ErrorKind::Interrupted {
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-        }
+        let other = self.other_id();
+        let tag = self.rounds.next(NetOp::Exchange);
+        let mut writer = self
+            .stream()
+            .try_clone()
+            .map_err(MpcNetError::io(other, tag.op, bytes_out.len()))?;
+        let (sent, bytes_in) = std::thread::scope(|scope| {
+            let sending = scope.spawn(move || send_on(&mut writer, other, tag, bytes_out));
+            let bytes_in = self.recv_vec(tag);
+            (sending.join().unwrap(), bytes_in)
+        });
+        let bytes_in = bytes_in?;
+        self.stats.bytes_sent += sent?;
         self.stats.broadcasts += 1;
-        self.stats.bytes_sent += n;
-        self.stats.bytes_recv += n;
         end_timer!(timer);
         Ok(bytes_in)
     }
 
+    /// Tell the other party that we are aborting, and stop sending.
+    pub fn abort(&mut self) {
+        if self.aborted {
+            return;
+        }
+        debug!("Aborting");
+        self.aborted = true;
+        if let Some(s) = self.stream.as_mut() {
+            // The peer may already be gone, so errors are expected.
+            let _ = s.write_all(&ABORT_MARKER.to_le_bytes());
+            let _ = s.shutdown(Shutdown::Write);
+        }
+    }
+
     #[inline]
     pub fn stats(&self) -> Stats {
-        self.stats.clone()
+        let mut stats = self.stats.clone();
+        let link = |bytes: usize| {
+            let mut v = vec![0, 0];
+            v[self.other_id()] = bytes;
+            v
+        };
+        stats.bytes_sent_to = link(stats.bytes_sent);
+        stats.bytes_recv_from = link(stats.bytes_recv);
+        stats
     }
 
     #[inline]
@@ -186,134 +301,166 @@ impl FieldChannel {
     }
 }
 
-#[inline]
-/// Initialize the MPC
-pub fn init_from_path(path: &str, id: usize) {
-    let mut ch = get_ch!();
-    assert!(
-        ch.stream.is_none(),
-        "FieldChannel should no be re-intialized. Did you call init(..) twice?"
-    );
-    ch.init_from_path(path, id);
-    ch.connect();
-    debug!("Connected");
-}
-
-#[inline]
-pub fn deinit() {
-    CH.lock().expect("Poisoned FieldChannel").stream = None;
-}
-
-#[inline]
-pub fn exchange_bytes(bytes_out: &[u8]) -> std::io::Result<Vec<u8>> {
-    CH.lock()
-        .expect("Poisoned FieldChannel")
-        .exchange_bytes(bytes_out)
-}
-
-#[inline]
-pub fn is_init() -> bool {
-    get_ch!().stream.is_some()
-}
-
-#[inline]
-pub fn stats() -> Stats {
-    let ch = get_ch!();
-    ch.stats()
+/// Send `v`, as the message tagged `tag`, to party `other` on `s`. Returns how many bytes went
+/// out, framing included.
+fn send_on(s: &mut TcpStream, other: usize, tag: Tag, v: &[u8]) -> Result<usize, MpcNetError> {
+    let op = tag.op;
+    let v = frame::encode(tag, v);
+    let bytes = (v.len() as u64).to_le_bytes();
+    s.write_all(&bytes[..])
+        .and_then(|()| s.write_all(&v))
+        .map_err(|e| {
+            if sent_abort(s) {
+                MpcNetError::Aborted { peer: other, op }
+            } else {
+                MpcNetError::io(other, op, v.len())(e)
+            }
+        })?;
+    Ok(bytes.len() + v.len())
 }
 
+/// Simultaneously send and receive messages, in a two-party session.
 #[inline]
-pub fn reset_stats() {
-    let mut ch = get_ch!();
-    ch.reset_stats();
+pub fn exchange_bytes(bytes_out: &[u8]) -> Result<Vec<u8>, MpcNetError> {
+    let session = Session::expect_current();
+    assert_eq!(session.n_parties(), 2, "exchange needs two parties");
+    let mut all = session.with_transport(|t| t.broadcast_bytes(bytes_out))?;
+    Ok(all.swap_remove(1 - session.party_id()))
 }
 
 /// Are you the first party in the MPC?
 #[inline]
 pub fn am_first() -> bool {
-    let ch = get_ch!();
-    assert!(ch.stream.is_some(), "uninit channel");
-    ch.talk_first
+    Session::expect_current().party_id() == 0
 }
 
-pub struct MpcTwoNet;
-
-impl MpcNet for MpcTwoNet {
+impl MpcTransport for FieldChannel {
     #[inline]
-    fn party_id() -> usize {
-        let first = get_ch!().talk_first;
-        if first {
-            0
-        } else {
-            1
-        }
+    fn party_id(&self) -> usize {
+        1 - self.other_id()
     }
 
     #[inline]
-    fn n_parties() -> usize {
+    fn n_parties(&self) -> usize {
         2
     }
 
     #[inline]
-    fn init_from_file(path: &str, party_id: usize) {
-        get_ch!().init_from_path(path, party_id);
-    }
-
-    #[inline]
-    fn is_init() -> bool {
-        get_ch!().stream.is_some()
+    fn king(&self) -> usize {
+        self.king
     }
 
     #[inline]
-    fn deinit() {
-        get_ch!().stream = None;
+    fn abort(&mut self) {
+        FieldChannel::abort(self)
     }
 
     #[inline]
-    fn reset_stats() {
-        get_ch!().stats = Stats::default();
+    fn reset_stats(&mut self) {
+        FieldChannel::reset_stats(self)
     }
 
     #[inline]
-    fn stats() -> crate::Stats {
-        get_ch!().stats.clone()
+    fn stats(&self) -> Stats {
+        FieldChannel::stats(self)
     }
 
     #[inline]
-    fn broadcast_bytes(bytes: &[u8]) -> Vec<Vec<u8>> {
-        let other = get_ch!().exchange_bytes(bytes).unwrap();
-        if Self::am_king() {
+    fn broadcast_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        let other = self.run(NetOp::Broadcast, |ch| ch.exchange_bytes(bytes))?;
+        Ok(if self.talk_first {
             vec![bytes.to_vec(), other]
         } else {
             vec![other, bytes.to_vec()]
-        }
+        })
     }
 
     #[inline]
-    fn send_bytes_to_king(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
-        let mut ch = get_ch!();
-        ch.stats.to_king += 1;
-        if ch.talk_first {
-            let other = ch.recv_vec();
-            debug_assert_eq!(bytes.len(), other.len());
-            Some(vec![bytes.to_vec(), other])
-        } else {
-            ch.send_slice(bytes);
-            None
-        }
+    fn all_to_all_bytes(&mut self, bytes: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, MpcNetError> {
+        assert_eq!(bytes.len(), 2);
+        self.run(NetOp::AllToAll, |ch| {
+            let me = 1 - ch.other_id();
+            let tag = ch.rounds.next(NetOp::AllToAll);
+            let other = if ch.talk_first {
+                ch.send_slice(tag, &bytes[1 - me])?;
+                ch.recv_vec(tag)?
+            } else {
+                let other = ch.recv_vec(tag)?;
+                ch.send_slice(tag, &bytes[1 - me])?;
+                other
+            };
+            let mut r = vec![bytes[me].clone(), other];
+            if me == 1 {
+                r.reverse();
+            }
+            Ok(r)
+        })
     }
 
     #[inline]
-    fn recv_bytes_from_king(bytes: Option<Vec<Vec<u8>>>) -> Vec<u8> {
-        let mut ch = get_ch!();
-        ch.stats.from_king += 1;
-        if ch.talk_first {
-            let mut bytes = bytes.expect("king needs bytes");
-            assert_eq!(bytes.len(), 2);
-            ch.send_slice(&bytes.pop().unwrap());
-            bytes.pop().unwrap()
-        } else {
-            ch.recv_vec()
-        }
+    fn send_bytes_to_king(&mut self, bytes: &[u8]) -> Result<Option<Vec<Vec<u8>>>, MpcNetError> {
+        self.run(NetOp::SendToKing, |ch| {
+            let tag = ch.rounds.next(NetOp::SendToKing);
+            ch.stats.to_king += 1;
+            if ch.party_id() == ch.king {
+                let other = ch.recv_vec(tag)?;
+                let mut r = vec![bytes.to_vec(), other];
+                if ch.king == 1 {
+                    r.reverse();
+                }
+                Ok(Some(r))
+            } else {
+                ch.send_slice(tag, bytes)?;
+                Ok(None)
+            }
+        })
+    }
+
+    #[inline]
+    fn recv_bytes_from_king(
+        &mut self,
+        bytes: Option<Vec<Vec<u8>>>,
+    ) -> Result<Vec<u8>, MpcNetError> {
+        self.run(NetOp::RecvFromKing, |ch| {
+            let tag = ch.rounds.next(NetOp::RecvFromKing);
+            ch.stats.from_king += 1;
+            if ch.party_id() == ch.king {
+                let mut bytes = bytes.expect("king needs bytes");
+                assert_eq!(bytes.len(), 2);
+                ch.send_slice(tag, &bytes[1 - ch.king])?;
+                Ok(bytes.swap_remove(ch.king))
+            } else {
+                ch.recv_vec(tag)
+            }
+        })
+    }
+}
+
+/// Connects two parties over TCP.
+pub struct MpcTwoNet;
+
+impl MpcNet for MpcTwoNet {
+    #[inline]
+    fn init_from_config(config: &HostConfig, party_id: usize) -> Result<(), MpcNetError> {
+        let ch = FieldChannel::connect_from_config(config, party_id)?;
+        Session::set_default(Some(Session::new(ch)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::on_localhost;
+
+    /// Two parties can exchange messages too long for the socket buffers at once.
+    #[test]
+    fn long_exchange() {
+        on_localhost(2, NetOptions::default(), &[], |config, id, listener| {
+            let mut ch = FieldChannel::connect_on(config, id, listener).unwrap();
+            let mine = vec![id as u8; 1 << 23];
+            let all = ch.broadcast_bytes(&mine).unwrap();
+            assert_eq!(all, vec![vec![0; 1 << 23], vec![1; 1 << 23]]);
+        });
     }
 }
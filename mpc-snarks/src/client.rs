@@ -81,7 +81,7 @@ struct Opt {
     #[structopt(short, long)]
     debug: bool,
 
-    /// File with list of hosts
+    /// Host file: a list of hosts, or a TOML host config
     #[structopt(long, parse(from_os_str))]
     hosts: PathBuf,
 
@@ -872,7 +872,8 @@ fn main() -> () {
         env_logger::init();
     }
     let domain = opt.domain();
-    MpcMultiNet::init_from_file(opt.hosts.to_str().unwrap(), opt.party as usize);
+    MpcMultiNet::init_from_file(opt.hosts.to_str().unwrap(), opt.party as usize)
+        .unwrap_or_else(|e| panic!("{}", e));
     debug!("Start");
     if opt.spdz {
         let inputs = opt
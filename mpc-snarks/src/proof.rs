@@ -13,7 +13,9 @@ use blake2::Blake2s;
 use clap::arg_enum;
 use log::debug;
 use mpc_algebra::{channel, MpcPairingEngine, PairingShare, Reveal};
-use mpc_net::{MpcMultiNet, MpcNet, MpcTwoNet};
+use mpc_net::{
+    secure::SecretKey, HostConfig, MpcAsyncNet, MpcMultiNet, MpcNet, MpcTwoNet, Topology,
+};
 use structopt::StructOpt;
 
 use std::path::PathBuf;
@@ -30,6 +32,9 @@ trait SnarkBench {
         unimplemented!("ark benchmark for {}", std::any::type_name::<Self>())
     }
     fn mpc<E: PairingEngine, S: PairingShare<E>>(n: usize, timer_label: &str);
+    /// Whether the prover can take packed FFTs. Their outputs are fresh sharings, so a prover
+    /// that reads polynomial degrees off the local shares (as GSZ division does) cannot.
+    const PACKED_FFTS: bool = false;
 }
 
 mod squarings {
@@ -72,6 +77,8 @@ mod squarings {
         pub struct Groth16Bench;
 
         impl SnarkBench for Groth16Bench {
+            const PACKED_FFTS: bool = true;
+
             fn local<E: PairingEngine>(n: usize, timer_label: &str) {
                 let rng = &mut test_rng();
                 let circ_no_data = RepeatedSquaringCircuit::without_data(n);
@@ -131,14 +138,17 @@ mod squarings {
                 let proof = channel::without_cheating(|| {
                     let pf = create_random_proof::<MpcPairingEngine<E, S>, _, _>(circ_data, &mpc_params, rng)
                         .unwrap();
+                    // Only the king learns the proof, as a delegating client would.
                     let reveal_timer = start_timer!(|| "reveal");
-                    let pf = pf.reveal();
+                    let pf = pf.reveal_to(MpcMultiNet::king());
                     end_timer!(reveal_timer);
                     pf
                 });
                 end_timer!(timer);
 
-                assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+                if let Some(proof) = proof {
+                    assert!(verify_proof(&pvk, &proof, &public_inputs).unwrap());
+                }
             }
         }
     }
@@ -200,10 +210,12 @@ mod squarings {
                         MpcPairingEngine<E, S>,
                     >::prove(&mpc_pk, circ_data, zk_rng)
                     .unwrap()
-                    .reveal()
+                    .reveal_to(MpcMultiNet::king())
                 });
                 end_timer!(timer);
-                assert!(KzgMarlin::<E::Fr, E>::verify(&vk, &public_inputs, &proof, rng).unwrap());
+                if let Some(proof) = proof {
+                    assert!(KzgMarlin::<E::Fr, E>::verify(&vk, &public_inputs, &proof, rng).unwrap());
+                }
             }
         }
     }
@@ -291,12 +303,14 @@ mod squarings {
                     >::prove(&mpc_pk, &plonk_circ_data, zk_rng);
 
                     let reveal_timer = start_timer!(|| "reveal");
-                    let pf = pf.reveal();
+                    let pf = pf.reveal_to(MpcMultiNet::king());
                     end_timer!(reveal_timer);
                     pf
                 });
                 end_timer!(t);
-                MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs);
+                if let Some(pf) = pf {
+                    MarlinPcPlonk::<E::Fr, E>::verify(&vk, &circ_no_data, pf, &public_inputs);
+                }
             }
         }
     }
@@ -346,7 +360,7 @@ mod squarings {
 
 #[derive(Debug, StructOpt)]
 struct ShareInfo {
-    /// File with list of hosts
+    /// Host file: a list of hosts, or a TOML host config
     #[structopt(long, parse(from_os_str))]
     hosts: PathBuf,
 
@@ -357,11 +371,62 @@ struct ShareInfo {
     /// Use spdz?
     #[structopt(long)]
     alg: MpcAlg,
+
+    /// File with our secret key (hex), for secure channels. The hosts file must then list every
+    /// party's public key.
+    #[structopt(long, parse(from_os_str))]
+    key: Option<PathBuf>,
+
+    /// Which party coordinates the others? Overrides the host file (default 0)
+    #[structopt(long)]
+    king: Option<usize>,
+
+    /// How the parties are connected: mesh, star (through the king) or hybrid (broadcasts
+    /// through the king). Overrides the host file (default mesh)
+    #[structopt(long)]
+    topology: Option<Topology>,
+
+    /// Names this run; parties only connect to peers with the same session id. Overrides the
+    /// host file
+    #[structopt(long)]
+    session_id: Option<String>,
+
+    /// Use the asynchronous transport, which talks to all peers at once (mesh topology only)
+    #[structopt(long)]
+    async_net: bool,
+
+    /// Have the king deal Beaver triples, instead of generating them with OT. Much faster, but
+    /// the king learns every triple
+    #[structopt(long)]
+    trust_dealer: bool,
 }
 
 impl ShareInfo {
     fn setup(&self) {
-        MpcMultiNet::init_from_file(self.hosts.to_str().unwrap(), self.party as usize)
+        let mut config = HostConfig::from_file(self.hosts.to_str().unwrap())
+            .unwrap_or_else(|e| panic!("{}", e));
+        if let Some(king) = self.king {
+            config.options.king = king;
+        }
+        if let Some(topology) = self.topology {
+            config.options.topology = topology;
+        }
+        if let Some(session_id) = &self.session_id {
+            config.session_id = Some(session_id.clone());
+        }
+        let party = self.party as usize;
+        let key = self.key.as_ref().map(|key| {
+            let key = std::fs::read_to_string(key).expect("could not read key file");
+            SecretKey::from_hex(key.trim()).expect("bad secret key")
+        });
+        match (key, self.async_net) {
+            (Some(key), false) => MpcMultiNet::init_secure_from_config(&config, party, key),
+            (None, false) => MpcMultiNet::init_from_config(&config, party),
+            (Some(key), true) => MpcAsyncNet::init_secure_from_config(&config, party, key),
+            (None, true) => MpcAsyncNet::init_from_config(&config, party),
+        }
+        .unwrap_or_else(|e| panic!("{}", e));
+        mpc_algebra::share::beaver::trust_dealer(self.trust_dealer);
     }
     fn teardown(&self) {
         debug!("Stats: {:#?}", MpcMultiNet::stats());
@@ -384,10 +449,28 @@ impl ShareInfo {
                     computation_size,
                     timed_label,
                 ),
-                MpcAlg::Gsz => B::mpc::<E, mpc_algebra::share::gsz20::GszPairingShare<E>>(
+                MpcAlg::Gsz => {
+                    // Check the king's shares of the witness.
+                    mpc_algebra::share::gsz20::vss::enable::<
+                        E::G1Projective,
+                        mpc_algebra::share::msm::NaiveMsm<E::G1Projective>,
+                    >();
+                    B::mpc::<E, mpc_algebra::share::gsz20::GszPairingShare<E>>(
+                        computation_size,
+                        timed_label,
+                    )
+                }
+                MpcAlg::Rss => B::mpc::<E, mpc_algebra::share::rss::RssPairingShare<E>>(
                     computation_size,
                     timed_label,
                 ),
+                MpcAlg::Packed => {
+                    mpc_algebra::share::gsz20::packed::set_packed_ffts(B::PACKED_FFTS);
+                    B::mpc::<E, mpc_algebra::share::gsz20::packed::PackedPairingShare<E>>(
+                        computation_size,
+                        timed_label,
+                    )
+                }
             },
         }
     }
@@ -399,6 +482,8 @@ arg_enum! {
         Spdz,
         Hbc,
         Gsz,
+        Rss,
+        Packed,
     }
 }
 
@@ -506,3 +591,123 @@ fn main() {
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpc_algebra::share::{
+        add::AdditivePairingShare,
+        gsz20::{
+            self,
+            packed::{self, PackedPairingShare},
+            vss, GszPairingShare,
+        },
+        msm::NaiveMsm,
+        rss::RssPairingShare,
+        spdz::SpdzPairingShare,
+    };
+    use mpc_net::run_parties;
+    use squarings::{groth::Groth16Bench, marlin::MarlinBench, plonk::PlonkBench};
+
+    type E = ark_bls12_377::Bls12_377;
+
+    /// Prove (and check) a few squarings with `B` and `S`, as each of `n` in-process parties.
+    fn round_trip<B: SnarkBench, S: PairingShare<E>>(n: usize) {
+        run_parties(n, |_| B::mpc::<E, S>(4, TIMED_SECTION_LABEL));
+    }
+
+    /// [round_trip] with [GszPairingShare], and the king's shares dealt with VSS, as the binary
+    /// does.
+    fn gsz_round_trip<B: SnarkBench>(n: usize) {
+        run_parties(n, |_| {
+            vss::enable::<<E as PairingEngine>::G1Projective, NaiveMsm<_>>();
+            B::mpc::<E, GszPairingShare<E>>(4, TIMED_SECTION_LABEL)
+        });
+    }
+
+    /// [round_trip] with [PackedPairingShare], two secrets to a share, and its FFTs packed too if
+    /// `B` can take that.
+    fn packed_round_trip<B: SnarkBench>(n: usize) {
+        run_parties(n, |_| {
+            gsz20::set_threshold(1);
+            packed::set_pack_size(2);
+            packed::set_packed_ffts(B::PACKED_FFTS);
+            B::mpc::<E, PackedPairingShare<E>>(4, TIMED_SECTION_LABEL)
+        });
+    }
+
+    #[test]
+    fn groth16_hbc() {
+        round_trip::<Groth16Bench, AdditivePairingShare<E>>(3);
+    }
+
+    #[test]
+    fn groth16_spdz() {
+        round_trip::<Groth16Bench, SpdzPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn groth16_gsz() {
+        gsz_round_trip::<Groth16Bench>(3);
+    }
+
+    #[test]
+    fn groth16_rss() {
+        round_trip::<Groth16Bench, RssPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn groth16_packed() {
+        packed_round_trip::<Groth16Bench>(8);
+    }
+
+    #[test]
+    fn marlin_hbc() {
+        round_trip::<MarlinBench, AdditivePairingShare<E>>(3);
+    }
+
+    #[test]
+    fn marlin_spdz() {
+        round_trip::<MarlinBench, SpdzPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn marlin_gsz() {
+        gsz_round_trip::<MarlinBench>(3);
+    }
+
+    #[test]
+    fn marlin_rss() {
+        round_trip::<MarlinBench, RssPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn marlin_packed() {
+        packed_round_trip::<MarlinBench>(8);
+    }
+
+    #[test]
+    fn plonk_hbc() {
+        round_trip::<PlonkBench, AdditivePairingShare<E>>(3);
+    }
+
+    #[test]
+    fn plonk_spdz() {
+        round_trip::<PlonkBench, SpdzPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn plonk_gsz() {
+        gsz_round_trip::<PlonkBench>(3);
+    }
+
+    #[test]
+    fn plonk_rss() {
+        round_trip::<PlonkBench, RssPairingShare<E>>(3);
+    }
+
+    #[test]
+    fn plonk_packed() {
+        packed_round_trip::<PlonkBench>(8);
+    }
+}
@@ -36,6 +36,10 @@ impl<E: PairingEngine, S: PairingShare<E>> Reveal for kzg10::Commitment<MpcPairi
         kzg10::Commitment(self.0.reveal())
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        self.0.reveal_to(party).map(kzg10::Commitment)
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         kzg10::Commitment(<MpcPairingEngine<E, S> as PairingEngine>::G1Affine::from_add_shared(b.0))
     }
@@ -43,6 +47,14 @@ impl<E: PairingEngine, S: PairingShare<E>> Reveal for kzg10::Commitment<MpcPairi
     fn from_public(b: Self::Base) -> Self {
         kzg10::Commitment(<MpcPairingEngine<E, S> as PairingEngine>::G1Affine::from_public(b.0))
     }
+
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let gs = bs.into_iter().map(|b| b.map(|b| b.0)).collect();
+        <MpcPairingEngine<E, S> as PairingEngine>::G1Affine::input_from_batch(owner, gs)
+            .into_iter()
+            .map(kzg10::Commitment)
+            .collect()
+    }
 }
 
 impl<E: PairingEngine, S: PairingShare<E>> Reveal for kzg10::Proof<MpcPairingEngine<E, S>> {
@@ -118,6 +130,13 @@ where
         )
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        let (label, degree_bound) = (self.label().clone(), self.degree_bound());
+        self.commitment
+            .reveal_to(party)
+            .map(|c| LabeledCommitment::new(label, c, degree_bound))
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         LabeledCommitment::new(
             b.label().clone(),
@@ -133,6 +152,19 @@ where
             b.degree_bound(),
         )
     }
+
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let meta = bs
+            .iter()
+            .map(|b| b.as_ref().map(|b| (b.label().clone(), b.degree_bound())))
+            .collect::<Option<Vec<_>>>();
+        let meta = from_owner(owner, meta);
+        let cs = bs.into_iter().map(|b| b.map(|b| b.commitment)).collect();
+        meta.into_iter()
+            .zip(C::input_from_batch(owner, cs))
+            .map(|((label, degree_bound), c)| LabeledCommitment::new(label, c, degree_bound))
+            .collect()
+    }
 }
 
 impl<F: PrimeField, S: FieldShare<F>> Reveal
@@ -148,6 +180,15 @@ impl<F: PrimeField, S: FieldShare<F>> Reveal
         )
     }
 
+    fn reveal_to(self, party: usize) -> Option<Self::Base> {
+        let (label, degree_bound, hiding_bound) =
+            (self.label().clone(), self.degree_bound(), self.hiding_bound());
+        self.polynomial()
+            .clone()
+            .reveal_to(party)
+            .map(|p| LabeledPolynomial::new(label, p, degree_bound, hiding_bound))
+    }
+
     fn from_add_shared(b: Self::Base) -> Self {
         LabeledPolynomial::new(
             b.label().clone(),
@@ -165,6 +206,27 @@ impl<F: PrimeField, S: FieldShare<F>> Reveal
             b.hiding_bound(),
         )
     }
+
+    fn input_from_batch(owner: usize, bs: Vec<Option<Self::Base>>) -> Vec<Self> {
+        let meta = bs
+            .iter()
+            .map(|b| {
+                b.as_ref()
+                    .map(|b| (b.label().clone(), b.degree_bound(), b.hiding_bound()))
+            })
+            .collect::<Option<Vec<_>>>();
+        let meta = from_owner(owner, meta);
+        let ps = bs
+            .into_iter()
+            .map(|b| b.map(|b| b.polynomial().clone()))
+            .collect();
+        meta.into_iter()
+            .zip(DensePolynomial::input_from_batch(owner, ps))
+            .map(|((label, degree_bound, hiding_bound), p)| {
+                LabeledPolynomial::new(label, p, degree_bound, hiding_bound)
+            })
+            .collect()
+    }
 }
 
 impl<E: Field> MpcWire for LabeledPolynomial<E, DensePolynomial<E>> {
@@ -315,8 +315,6 @@ impl<P: MNT6Parameters> G2PreparedVar<P> {
                     g2p.addition_coefficients.push(coeff);
                     r = r2;
                 }
-
-                tmp >>= 1;
             }
         }
 
@@ -90,6 +90,7 @@ where
     /// less than the 7 required when computing via `self.double() + other`.
     ///
     /// This follows the formulae from [\[ELM03\]](https://arxiv.org/abs/math/0208038).
+    #[allow(dead_code)]
     #[tracing::instrument(target = "r1cs", skip(self))]
     pub(crate) fn double_and_add(&self, other: &Self) -> Result<Self, SynthesisError> {
         if [self].is_constant() || other.is_constant() {